@@ -0,0 +1,92 @@
+//! Throughput of reading, sorting, filtering and writing a generated file.
+//! Run with `cargo bench`; `BENCH_ROWS` sets the number of rows (200000 by default).
+
+use std::{hint::black_box, path::PathBuf};
+
+use bootle_geditor_3000::{expr::Expr, CSVData, ReadBackend, ReadOptions, WriteOptions};
+use criterion::{criterion_group, criterion_main, BatchSize, Criterion, Throughput};
+
+/// Writes a file of `rows` rows to the temporary directory and returns its path.
+fn generate(rows: usize) -> PathBuf {
+    let path = std::env::temp_dir().join(format!("bootleg-editor3000-bench-{rows}.csv"));
+    if path.exists() {
+        return path;
+    }
+    let mut text = String::from("id,name,city,price,qty,note\n");
+    for i in 0..rows {
+        let city = ["Hanoi", "Lyon", "Austin", "Porto"][i % 4];
+        let price = (i * 7919 % 100_000) as f64 / 100.0;
+        text.push_str(&format!(
+            "{i},customer {},{city},{price},{},\"note, with comma {i}\"\n",
+            i % 1000,
+            i % 17
+        ));
+    }
+    std::fs::write(&path, text).expect("Failed to write the bench file");
+    path
+}
+
+fn throughput(c: &mut Criterion) {
+    let rows = std::env::var("BENCH_ROWS")
+        .ok()
+        .and_then(|rows| rows.parse().ok())
+        .unwrap_or(200_000);
+    let path = generate(rows);
+    let file_name = path.to_str().expect("temp path is UTF-8");
+    let size = std::fs::metadata(&path).expect("bench file exists").len();
+    let read = |backend| {
+        let options = ReadOptions {
+            backend,
+            ..ReadOptions::default()
+        };
+        CSVData::read_with_options(file_name, options).expect("Failed to read")
+    };
+
+    let mut group = c.benchmark_group("read");
+    group.throughput(Throughput::Bytes(size));
+    group.sample_size(10);
+    for (name, backend) in [("read", ReadBackend::Read), ("mmap", ReadBackend::Mmap)] {
+        group.bench_function(name, |b| b.iter(|| black_box(read(backend))));
+    }
+    group.finish();
+
+    let csv_data = read(ReadBackend::Read);
+    let mut group = c.benchmark_group("edit");
+    group.throughput(Throughput::Elements(rows as u64));
+    group.sample_size(10);
+    group.bench_function("sort", |b| {
+        b.iter_batched(
+            || csv_data.copy(),
+            |mut copy| copy.sort_by_column(3, false, true, &mut |_| {}),
+            BatchSize::LargeInput,
+        )
+    });
+    let filter = Expr::parse(
+        "price > 500 and city == 'Lyon'",
+        &csv_data.headers,
+        csv_data.fields,
+    )
+    .expect("Failed to parse the filter");
+    group.bench_function("filter", |b| {
+        b.iter_batched(
+            || csv_data.copy(),
+            |mut copy| copy.filter_rows(&filter),
+            BatchSize::LargeInput,
+        )
+    });
+    group.finish();
+
+    let output = std::env::temp_dir().join("bootleg-editor3000-bench-output.csv");
+    let output = output.to_str().expect("temp path is UTF-8");
+    let mut group = c.benchmark_group("write");
+    group.throughput(Throughput::Bytes(size));
+    group.sample_size(10);
+    group.bench_function("write", |b| {
+        b.iter(|| csv_data.write_with_options(output, WriteOptions::default()))
+    });
+    group.finish();
+    std::fs::remove_file(output).ok();
+}
+
+criterion_group!(benches, throughput);
+criterion_main!(benches);
@@ -0,0 +1,320 @@
+use std::{cmp::Ordering, collections::HashSet, fmt, str::FromStr};
+
+use csv::StringRecord;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    columns,
+    expr::Value,
+    schema::{ColumnType, Locale},
+    EditorError,
+};
+
+/// A function summarizing the values of a column within a group.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AggFn {
+    /// Rows in the group, or non-empty values when given a column.
+    Count,
+    Sum,
+    Min,
+    Max,
+    /// Mean of the non-empty values.
+    Avg,
+    /// Number of different non-empty values.
+    Distinct,
+}
+
+impl AggFn {
+    /// Parses a function name as used in `--agg`, e.g. `sum` or `avg`.
+    pub fn from_name(name: &str) -> Option<AggFn> {
+        match name.trim().to_ascii_lowercase().as_str() {
+            "count" => Some(AggFn::Count),
+            "sum" => Some(AggFn::Sum),
+            "min" => Some(AggFn::Min),
+            "max" => Some(AggFn::Max),
+            "avg" | "mean" => Some(AggFn::Avg),
+            "distinct" => Some(AggFn::Distinct),
+            _ => None,
+        }
+    }
+
+    /// Returns the type of the summarized values, given the type of the column.
+    pub fn output_type(self, column: ColumnType) -> ColumnType {
+        match self {
+            AggFn::Count | AggFn::Distinct => ColumnType::Integer,
+            AggFn::Sum | AggFn::Avg => ColumnType::Float,
+            AggFn::Min | AggFn::Max => column,
+        }
+    }
+}
+
+impl FromStr for AggFn {
+    type Err = EditorError;
+
+    fn from_str(name: &str) -> Result<AggFn, EditorError> {
+        AggFn::from_name(name).ok_or_else(|| invalid(format!("unknown function '{name}'")))
+    }
+}
+
+impl fmt::Display for AggFn {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            AggFn::Count => "count",
+            AggFn::Sum => "sum",
+            AggFn::Min => "min",
+            AggFn::Max => "max",
+            AggFn::Avg => "avg",
+            AggFn::Distinct => "distinct",
+        })
+    }
+}
+
+/// One summary column of a group-by, such as `sum(amount)`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Aggregation {
+    pub func: AggFn,
+    /// The summarized column (0-based); only `count()` has none.
+    pub column: Option<usize>,
+    /// Header of the summary column, e.g. `sum(amount)`.
+    pub name: String,
+}
+
+impl Aggregation {
+    /// Parses a comma-separated list such as `sum(amount),count(),avg(price)`.
+    /// Columns are named as in `--columns`.
+    pub fn parse_list(
+        spec: &str,
+        headers: &StringRecord,
+        fields: usize,
+    ) -> Result<Vec<Aggregation>, EditorError> {
+        let mut aggregations = Vec::new();
+        let mut rest = spec.trim();
+        while !rest.is_empty() {
+            let (call, after) = match rest.find(')') {
+                Some(end) => rest.split_at(end + 1),
+                None => return Err(invalid(format!("missing ')' in '{spec}'"))),
+            };
+            aggregations.push(Aggregation::parse(call, headers, fields)?);
+            rest = after.trim_start();
+            if let Some(after) = rest.strip_prefix(',') {
+                rest = after.trim_start();
+            } else if !rest.is_empty() {
+                return Err(invalid(format!("expected ',' before '{rest}'")));
+            }
+        }
+        if aggregations.is_empty() {
+            return Err(invalid("no aggregations given".to_string()));
+        }
+        Ok(aggregations)
+    }
+
+    /// Parses one aggregation such as `sum(amount)` or `count()`.
+    pub fn parse(
+        spec: &str,
+        headers: &StringRecord,
+        fields: usize,
+    ) -> Result<Aggregation, EditorError> {
+        let parsed = spec
+            .trim()
+            .strip_suffix(')')
+            .and_then(|call| call.split_once('('));
+        let Some((name, argument)) = parsed else {
+            return Err(invalid(format!("expected FUNCTION(COLUMN), got '{spec}'")));
+        };
+        let func = AggFn::from_name(name)
+            .ok_or_else(|| invalid(format!("unknown function '{}'", name.trim())))?;
+        let argument = argument.trim();
+        let column = if argument.is_empty() {
+            if func != AggFn::Count {
+                return Err(invalid(format!("{func}() needs a column")));
+            }
+            None
+        } else {
+            Some(columns::parse_column(argument, headers, fields)?)
+        };
+        Ok(Aggregation {
+            func,
+            column,
+            name: format!("{func}({argument})"),
+        })
+    }
+}
+
+fn invalid(message: String) -> EditorError {
+    EditorError::InvalidColumns(message)
+}
+
+/// The running summary of one aggregation within one group.
+#[derive(Debug, Default)]
+pub(crate) struct Accumulator {
+    count: usize,
+    sum: f64,
+    min: Option<String>,
+    max: Option<String>,
+    distinct: HashSet<String>,
+    /// Locale the numbers of the column are written in, if any.
+    locale: Option<Locale>,
+}
+
+impl Accumulator {
+    /// Returns an empty summary of a column whose numbers are written in `locale`.
+    pub(crate) fn new(locale: Option<Locale>) -> Accumulator {
+        Accumulator {
+            locale,
+            ..Accumulator::default()
+        }
+    }
+
+    /// Adds a row of the group. Empty values are skipped, except by `count()`.
+    pub(crate) fn add(
+        &mut self,
+        aggregation: &Aggregation,
+        record: &StringRecord,
+    ) -> Result<(), EditorError> {
+        let Some(field) = aggregation.column else {
+            self.count += 1;
+            return Ok(());
+        };
+        let value = record.get(field).unwrap_or("").trim();
+        if value.is_empty() {
+            return Ok(());
+        }
+        self.count += 1;
+        match aggregation.func {
+            AggFn::Count => {}
+            AggFn::Sum | AggFn::Avg => {
+                let number = parse(value, self.locale)
+                    .filter(|n| n.is_finite())
+                    .ok_or_else(|| EditorError::TypeMismatch {
+                        field,
+                        value: value.to_string(),
+                        expected: ColumnType::Float,
+                    })?;
+                self.sum += number;
+            }
+            AggFn::Min => {
+                if self
+                    .min
+                    .as_deref()
+                    .is_none_or(|min| compare(value, min, self.locale).is_lt())
+                {
+                    self.min = Some(value.to_string());
+                }
+            }
+            AggFn::Max => {
+                if self
+                    .max
+                    .as_deref()
+                    .is_none_or(|max| compare(value, max, self.locale).is_gt())
+                {
+                    self.max = Some(value.to_string());
+                }
+            }
+            AggFn::Distinct => {
+                self.distinct.insert(value.to_string());
+            }
+        }
+        Ok(())
+    }
+
+    /// Adds the rows summarized by `other`, e.g. from another chunk of the same group.
+    pub(crate) fn merge(&mut self, other: Accumulator) {
+        self.count += other.count;
+        self.sum += other.sum;
+        if let Some(min) = other.min {
+            if self
+                .min
+                .as_deref()
+                .is_none_or(|old| compare(&min, old, self.locale).is_lt())
+            {
+                self.min = Some(min);
+            }
+        }
+        if let Some(max) = other.max {
+            if self
+                .max
+                .as_deref()
+                .is_none_or(|old| compare(&max, old, self.locale).is_gt())
+            {
+                self.max = Some(max);
+            }
+        }
+        self.distinct.extend(other.distinct);
+    }
+
+    /// Returns the summary as written in the output.
+    pub(crate) fn finish(self, func: AggFn) -> String {
+        let number = |number: f64| Value::Number(number).to_string();
+        match func {
+            AggFn::Count => self.count.to_string(),
+            AggFn::Sum => number(self.sum),
+            AggFn::Avg if self.count == 0 => String::new(),
+            AggFn::Avg => number(self.sum / self.count as f64),
+            AggFn::Min => self.min.unwrap_or_default(),
+            AggFn::Max => self.max.unwrap_or_default(),
+            AggFn::Distinct => self.distinct.len().to_string(),
+        }
+    }
+}
+
+fn parse(value: &str, locale: Option<Locale>) -> Option<f64> {
+    match locale {
+        Some(locale) => locale.parse_number(value),
+        None => value.parse().ok(),
+    }
+}
+
+/// Compares as numbers, written in `locale` if given, when both values are numbers, and
+/// as text otherwise.
+fn compare(a: &str, b: &str, locale: Option<Locale>) -> Ordering {
+    match (parse(a, locale), parse(b, locale)) {
+        (Some(a), Some(b)) => a.total_cmp(&b),
+        _ => a.cmp(b),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_list() {
+        let headers = StringRecord::from(vec!["region", "amount", "price"]);
+        let aggregations =
+            Aggregation::parse_list("sum(amount), count(),AVG(3)", &headers, 3).unwrap();
+        assert_eq!(
+            aggregations,
+            vec![
+                Aggregation {
+                    func: AggFn::Sum,
+                    column: Some(1),
+                    name: "sum(amount)".to_string(),
+                },
+                Aggregation {
+                    func: AggFn::Count,
+                    column: None,
+                    name: "count()".to_string(),
+                },
+                Aggregation {
+                    func: AggFn::Avg,
+                    column: Some(2),
+                    name: "avg(3)".to_string(),
+                },
+            ]
+        );
+        for spec in [
+            "",
+            "sum(amount",
+            "sum()",
+            "median(price)",
+            "sum(cost)",
+            "count() x",
+        ] {
+            assert!(
+                Aggregation::parse_list(spec, &headers, 3).is_err(),
+                "'{spec}' should not parse"
+            );
+        }
+    }
+}
@@ -0,0 +1,145 @@
+use std::str::FromStr;
+
+use sha2::{Digest, Sha256};
+
+use crate::{is_na, EditorError};
+
+/// Written in place of redacted values.
+const REDACTED: &str = "[REDACTED]";
+/// Number of hex digits kept of a hash.
+const HASH_DIGITS: usize = 16;
+
+const FIRST_NAMES: [&str; 24] = [
+    "Alex", "Bao", "Carmen", "Dmitri", "Elena", "Farah", "Gabriel", "Hana", "Ivan", "Julia",
+    "Kenji", "Linh", "Marco", "Nadia", "Omar", "Priya", "Quentin", "Rosa", "Samuel", "Tuan",
+    "Ursula", "Victor", "Wen", "Yara",
+];
+const LAST_NAMES: [&str; 24] = [
+    "Almeida", "Becker", "Chen", "Dubois", "Eriksson", "Fischer", "Garcia", "Hoang", "Ivanova",
+    "Jensen", "Kowalski", "Larsen", "Moreau", "Nguyen", "Okafor", "Petrov", "Quinn", "Rossi",
+    "Sato", "Tran", "Ueda", "Varga", "Weber", "Yilmaz",
+];
+
+/// How `anonymize` replaces the values of a column. Empty values are left empty.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Strategy {
+    /// The first hex digits of the SHA-256 of the salt and the value, so equal values stay
+    /// equal and can still be joined on.
+    Hash,
+    /// `[REDACTED]`.
+    Redact,
+    /// A made-up full name, the same for equal values.
+    FakeName,
+    /// A made-up address at example.com, the same for equal values.
+    FakeEmail,
+    /// Stars in place of every character but the given number of last ones, e.g. `****1234`.
+    Mask(usize),
+}
+
+impl FromStr for Strategy {
+    type Err = EditorError;
+
+    /// Parses `hash`, `redact`, `fake-name`, `fake-email`, or `mask[:N]` (4 last characters
+    /// kept by default).
+    fn from_str(spec: &str) -> Result<Strategy, EditorError> {
+        let strategy = match spec.trim() {
+            "hash" => Strategy::Hash,
+            "redact" => Strategy::Redact,
+            "fake-name" => Strategy::FakeName,
+            "fake-email" => Strategy::FakeEmail,
+            "mask" => Strategy::Mask(4),
+            spec => match spec.strip_prefix("mask:").map(str::parse) {
+                Some(Ok(kept)) => Strategy::Mask(kept),
+                _ => {
+                    return Err(EditorError::InvalidFormat(format!(
+                        "unknown strategy '{spec}', expected hash, redact, fake-name, \
+                         fake-email or mask[:N]"
+                    )))
+                }
+            },
+        };
+        Ok(strategy)
+    }
+}
+
+impl Strategy {
+    /// Returns the anonymized `value`. `salt` keeps hashes and fake data from being
+    /// recomputed from guessed values; the same salt gives the same results.
+    pub fn apply(self, value: &str, salt: &str) -> String {
+        if is_na(value) {
+            return value.to_string();
+        }
+        let digest = || {
+            let mut hasher = Sha256::new();
+            hasher.update(salt.as_bytes());
+            hasher.update([0]);
+            hasher.update(value.as_bytes());
+            hasher.finalize()
+        };
+        match self {
+            Strategy::Hash => digest()
+                .iter()
+                .map(|byte| format!("{byte:02x}"))
+                .collect::<String>()[..HASH_DIGITS]
+                .to_string(),
+            Strategy::Redact => REDACTED.to_string(),
+            Strategy::FakeName | Strategy::FakeEmail => {
+                let digest = digest();
+                let first = FIRST_NAMES[digest[0] as usize % FIRST_NAMES.len()];
+                let last = LAST_NAMES[digest[1] as usize % LAST_NAMES.len()];
+                match self {
+                    Strategy::FakeName => format!("{first} {last}"),
+                    _ => {
+                        let number = u16::from_be_bytes([digest[2], digest[3]]) % 10_000;
+                        format!(
+                            "{}.{}{number}@example.com",
+                            first.to_lowercase(),
+                            last.to_lowercase()
+                        )
+                    }
+                }
+            }
+            Strategy::Mask(kept) => {
+                let length = value.chars().count();
+                // Values no longer than the kept part are masked whole.
+                let kept = if length > kept { kept } else { 0 };
+                value
+                    .chars()
+                    .enumerate()
+                    .map(|(i, c)| if i < length - kept { '*' } else { c })
+                    .collect()
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_strategies() {
+        let hash = Strategy::Hash.apply("ann@mail.com", "pepper");
+        assert_eq!(hash.len(), HASH_DIGITS);
+        assert_eq!(hash, Strategy::Hash.apply("ann@mail.com", "pepper"));
+        assert_ne!(hash, Strategy::Hash.apply("ann@mail.com", "salt"));
+        assert_eq!(Strategy::Redact.apply("Ann", ""), "[REDACTED]");
+        let email = Strategy::FakeEmail.apply("ann@mail.com", "pepper");
+        assert!(email.ends_with("@example.com"));
+        assert_eq!(email, Strategy::FakeEmail.apply("ann@mail.com", "pepper"));
+        assert_eq!(
+            Strategy::FakeName.apply("Ann Lee", "").split(' ').count(),
+            2
+        );
+        let mask: Strategy = "mask".parse().unwrap();
+        assert_eq!(mask.apply("4111111111111234", ""), "************1234");
+        assert_eq!(mask.apply("123", ""), "***");
+        assert_eq!(
+            "mask:2".parse::<Strategy>().unwrap().apply("abcd", ""),
+            "**cd"
+        );
+        assert_eq!(Strategy::Hash.apply(" ", "pepper"), " ");
+        assert!("scramble".parse::<Strategy>().is_err());
+        assert!("mask:x".parse::<Strategy>().is_err());
+    }
+}
@@ -0,0 +1,353 @@
+use std::{
+    fs::{File, OpenOptions},
+    io::{BufRead, BufReader, Write},
+    time::SystemTime,
+};
+
+use chrono::{DateTime, SecondsFormat, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::{aggregate::AggFn, CSVData, EditorError};
+
+/// A change applied to the data, as recorded in an audit journal.
+/// Rows and columns are 0-based indices, as in the library API.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "operation", rename_all = "kebab-case")]
+pub enum Change {
+    /// One cell was set.
+    Set {
+        row: usize,
+        column: usize,
+        old: String,
+        new: String,
+    },
+    /// A row was removed by [`CSVData::delete_row`].
+    DeleteRow { row: usize, old: Vec<String> },
+    /// Rows were added at the end.
+    AppendRows { rows: Vec<Vec<String>> },
+    /// Rows were removed; `rows` are their indices before the removal, in order.
+    RemoveRows {
+        rows: Vec<usize>,
+        old: Vec<Vec<String>>,
+    },
+    /// Cells were cut: whole rows are removed, other cells emptied.
+    Cut {
+        first: usize,
+        last: usize,
+        columns: Vec<usize>,
+        old: Vec<Vec<String>>,
+    },
+    /// A block of cells was pasted with its top-left cell at `row`, `column`.
+    Paste {
+        row: usize,
+        column: usize,
+        values: Vec<Vec<String>>,
+    },
+    /// Some cells of one column were rewritten.
+    SetColumn {
+        column: usize,
+        cells: Vec<CellChange>,
+    },
+    /// The rows were sorted.
+    Sort {
+        column: usize,
+        descending: bool,
+        numeric: bool,
+    },
+    /// Columns were removed and added, as by splitting, merging or computing columns.
+    /// `values` holds the added columns of each row.
+    Reshape {
+        remove: Vec<usize>,
+        at: usize,
+        names: Vec<String>,
+        values: Vec<Vec<String>>,
+    },
+    /// The data was replaced by [`CSVData::pivot`].
+    Pivot {
+        index: Vec<usize>,
+        names: usize,
+        values: usize,
+        agg: Option<AggFn>,
+    },
+    /// The data was replaced by [`CSVData::melt`]; no `values` stands for every other column.
+    Melt {
+        ids: Vec<usize>,
+        values: Option<Vec<usize>>,
+        variable: String,
+        value: String,
+    },
+    /// The rows were replaced by the result of [`merge::merge`](crate::merge::merge), which
+    /// merged in the changes the file `theirs` made to the file `base`.
+    Merge {
+        base: String,
+        theirs: String,
+        rows: Vec<Vec<String>>,
+    },
+}
+
+/// The old and new value of a cell in [`Change::SetColumn`].
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CellChange {
+    pub row: usize,
+    pub old: String,
+    pub new: String,
+}
+
+impl Change {
+    /// Returns the name of the operation, as written in the journal.
+    pub fn operation(&self) -> &'static str {
+        match self {
+            Change::Set { .. } => "set",
+            Change::DeleteRow { .. } => "delete-row",
+            Change::AppendRows { .. } => "append-rows",
+            Change::RemoveRows { .. } => "remove-rows",
+            Change::Cut { .. } => "cut",
+            Change::Paste { .. } => "paste",
+            Change::SetColumn { .. } => "set-column",
+            Change::Sort { .. } => "sort",
+            Change::Reshape { .. } => "reshape",
+            Change::Pivot { .. } => "pivot",
+            Change::Melt { .. } => "melt",
+            Change::Merge { .. } => "merge",
+        }
+    }
+}
+
+/// One line of an audit journal.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Entry {
+    /// When the change was applied, in RFC 3339 format (UTC).
+    pub timestamp: String,
+    /// Who applied it, from the `USER` or `USERNAME` environment variable.
+    pub user: String,
+    /// The file the data was read from.
+    pub file: String,
+    #[serde(flatten)]
+    pub change: Change,
+    /// Checksum of the data before the change (see [`CSVData::checksum`]).
+    pub before: String,
+    /// Checksum of the data after the change.
+    pub after: String,
+}
+
+/// An audit journal: a JSON Lines file to which every change to the data is appended.
+/// Attach it with [`CSVData::set_journal`].
+#[derive(Debug)]
+pub struct Journal {
+    file: File,
+    path: String,
+    user: String,
+    /// Checksum of the data as of the last recorded change.
+    pub(crate) checksum: String,
+    /// Entries of changes made in a transaction, written once it is committed.
+    held: Option<Vec<String>>,
+}
+
+impl Journal {
+    /// Opens `path` for appending, creating it if needed.
+    pub fn open(path: &str) -> Result<Journal, EditorError> {
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        let user = std::env::var("USER")
+            .or_else(|_| std::env::var("USERNAME"))
+            .unwrap_or_else(|_| "unknown".to_string());
+        Ok(Journal {
+            file,
+            path: path.to_string(),
+            user,
+            checksum: String::new(),
+            held: None,
+        })
+    }
+
+    /// Appends an entry for `change`, which turned the data of `file` into data with
+    /// the checksum `after`.
+    pub(crate) fn record(
+        &mut self,
+        file: &str,
+        change: Change,
+        after: String,
+    ) -> Result<(), EditorError> {
+        let entry = Entry {
+            timestamp: DateTime::<Utc>::from(SystemTime::now())
+                .to_rfc3339_opts(SecondsFormat::Millis, true),
+            user: self.user.clone(),
+            file: file.to_string(),
+            change,
+            before: std::mem::replace(&mut self.checksum, after.clone()),
+            after,
+        };
+        let line = serde_json::to_string(&entry)
+            .map_err(|e| EditorError::Journal(format!("{}: {e}", self.path)))?;
+        match &mut self.held {
+            Some(held) => held.push(line),
+            None => writeln!(self.file, "{line}")?,
+        }
+        log::debug!(
+            journal = self.path.as_str(),
+            operation = entry.change.operation();
+            "recorded change"
+        );
+        Ok(())
+    }
+
+    /// Holds back the entries recorded from now on until [`Journal::release`], as the
+    /// changes of a transaction may be rolled back. Returns the number held back so far.
+    pub(crate) fn hold(&mut self) -> usize {
+        self.held.get_or_insert_with(Vec::new).len()
+    }
+
+    /// Writes the entries held back, and writes later ones right away again.
+    pub(crate) fn release(&mut self) -> Result<(), EditorError> {
+        for line in self.held.take().unwrap_or_default() {
+            writeln!(self.file, "{line}")?;
+        }
+        Ok(())
+    }
+
+    /// Forgets the entries held back after the first `kept`, whose changes were rolled
+    /// back to data with the checksum `checksum`. With `last`, later entries are written
+    /// right away again.
+    pub(crate) fn discard(&mut self, kept: usize, checksum: String, last: bool) {
+        if let Some(held) = &mut self.held {
+            held.truncate(kept);
+        }
+        self.checksum = checksum;
+        if last {
+            self.held = None;
+        }
+    }
+}
+
+/// Reads the entries of the journal at `path`.
+pub fn read_journal(path: &str) -> Result<Vec<Entry>, EditorError> {
+    let mut entries = Vec::new();
+    for (i, line) in BufReader::new(File::open(path)?).lines().enumerate() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let entry = serde_json::from_str(&line)
+            .map_err(|e| EditorError::Journal(format!("{path}, line {}: {e}", i + 1)))?;
+        entries.push(entry);
+    }
+    Ok(entries)
+}
+
+/// Applies `entries` in order to `csv_data`, which must be the data the first entry was
+/// recorded on. The checksums of every entry are verified before and after its change.
+/// Returns an error at the first entry that does not match; the entries before it stay applied.
+pub fn replay(csv_data: &mut CSVData, entries: &[Entry]) -> Result<(), EditorError> {
+    for (i, entry) in entries.iter().enumerate() {
+        let mismatch = |when: &str| {
+            EditorError::Journal(format!(
+                "entry {} ({} by {} at {}) does not match the data {when} it",
+                i + 1,
+                entry.change.operation(),
+                entry.user,
+                entry.timestamp
+            ))
+        };
+        if csv_data.checksum()? != entry.before {
+            return Err(mismatch("before"));
+        }
+        csv_data.apply(&entry.change)?;
+        if csv_data.checksum()? != entry.after {
+            return Err(mismatch("after"));
+        }
+    }
+    log::info!(entries = entries.len(); "replayed journal");
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{clipboard::CellRange, expr::Expr};
+
+    fn setup() -> CSVData {
+        CSVData::read_from_file("testdata.csv").expect("Failed to read test CSV file")
+    }
+
+    #[test]
+    fn test_record_and_replay() {
+        let journal_file = "test_audit.jsonl";
+        let _ = std::fs::remove_file(journal_file);
+        let mut csv_data = setup();
+        csv_data
+            .set_journal(Journal::open(journal_file).unwrap())
+            .unwrap();
+        csv_data.modify_field(1, 2, "edited").unwrap();
+        csv_data
+            .sort_by_column(0, true, false, &mut |_| {})
+            .unwrap();
+        csv_data
+            .split_column(2, "a", &["left".to_string(), "right".to_string()], false)
+            .unwrap();
+        let range = CellRange {
+            rows: 0..=1,
+            columns: vec![0, 3],
+        };
+        csv_data.cut_range(&range).unwrap();
+        let filter = Expr::parse("len(carry) > 4", &csv_data.headers, csv_data.fields).unwrap();
+        csv_data.filter_rows(&filter).unwrap();
+        csv_data
+            .apply(&Change::Melt {
+                ids: vec![0],
+                values: None,
+                variable: "variable".to_string(),
+                value: "value".to_string(),
+            })
+            .unwrap();
+
+        let entries = read_journal(journal_file).unwrap();
+        let operations: Vec<_> = entries.iter().map(|e| e.change.operation()).collect();
+        assert_eq!(
+            operations,
+            vec!["set", "sort", "reshape", "cut", "remove-rows", "melt"]
+        );
+        assert!(matches!(
+            &entries[0].change,
+            Change::Set { row: 1, column: 2, old, new } if old == "breathing" && new == "edited"
+        ));
+
+        let mut copy = setup();
+        replay(&mut copy, &entries).unwrap();
+        assert_eq!(copy.headers, csv_data.headers);
+        assert_eq!(copy.data, csv_data.data);
+
+        let mut copy = setup();
+        copy.modify_field(0, 0, "tampered").unwrap();
+        let error = replay(&mut copy, &entries).unwrap_err();
+        assert!(error.to_string().contains("entry 1 (set by"));
+        std::fs::remove_file(journal_file).expect("Failed to remove file");
+    }
+
+    #[test]
+    fn test_transactions_hold_entries() {
+        let journal_file = "test_audit_transactions.jsonl";
+        let _ = std::fs::remove_file(journal_file);
+        let mut csv_data = setup();
+        csv_data
+            .set_journal(Journal::open(journal_file).unwrap())
+            .unwrap();
+        csv_data.begin();
+        csv_data.modify_field(0, 0, "rolled back").unwrap();
+        assert!(read_journal(journal_file).unwrap().is_empty());
+        csv_data.rollback().unwrap();
+        csv_data.begin();
+        csv_data.modify_field(0, 1, "first").unwrap();
+        csv_data.begin();
+        csv_data.modify_field(0, 2, "dropped").unwrap();
+        csv_data.rollback().unwrap();
+        csv_data.modify_field(0, 3, "second").unwrap();
+        csv_data.commit().unwrap();
+
+        // Only the committed changes are in the journal, and they replay.
+        let entries = read_journal(journal_file).unwrap();
+        assert_eq!(entries.len(), 2);
+        let mut copy = setup();
+        replay(&mut copy, &entries).unwrap();
+        assert_eq!(copy.data, csv_data.data);
+        std::fs::remove_file(journal_file).expect("Failed to remove file");
+    }
+}
@@ -0,0 +1,186 @@
+use std::{collections::BTreeMap, path::PathBuf, str::FromStr};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{lock, CSVData, EditorError};
+
+/// Named rows of a file, kept in a hidden sidecar next to it (`.name.csv.bookmarks`).
+/// Bookmarks point at row numbers, so they do not follow a row that moves.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Bookmarks {
+    path: PathBuf,
+    /// Row (0-based) of each bookmark.
+    rows: BTreeMap<String, usize>,
+}
+
+/// The sidecar's contents. Rows are 1-based, as shown to the user.
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct Sidecar {
+    bookmarks: BTreeMap<String, usize>,
+}
+
+impl Bookmarks {
+    /// Returns an empty set of bookmarks for `file_name`.
+    pub fn new(file_name: &str) -> Bookmarks {
+        Bookmarks {
+            path: lock::sidecar_path(file_name, "bookmarks"),
+            rows: BTreeMap::new(),
+        }
+    }
+
+    /// Returns the bookmarks of `file_name`, none if it has no sidecar.
+    pub fn load(file_name: &str) -> Result<Bookmarks, EditorError> {
+        let Bookmarks { path, .. } = Bookmarks::new(file_name);
+        let sidecar: Sidecar = match std::fs::read_to_string(&path) {
+            Ok(contents) => toml::from_str(&contents).map_err(|e| {
+                EditorError::InvalidBookmark(format!("{}: {}", path.display(), e.message()))
+            })?,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Sidecar::default(),
+            Err(e) => return Err(e.into()),
+        };
+        let rows = sidecar
+            .bookmarks
+            .into_iter()
+            .filter(|&(_, row)| row > 0)
+            .map(|(name, row)| (name, row - 1))
+            .collect();
+        Ok(Bookmarks { path, rows })
+    }
+
+    /// Writes the bookmarks to the sidecar, removing it when there are none left.
+    pub fn save(&self) -> Result<(), EditorError> {
+        if self.rows.is_empty() {
+            return match std::fs::remove_file(&self.path) {
+                Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e.into()),
+                _ => Ok(()),
+            };
+        }
+        let sidecar = Sidecar {
+            bookmarks: self
+                .rows
+                .iter()
+                .map(|(name, row)| (name.clone(), row + 1))
+                .collect(),
+        };
+        let contents =
+            toml::to_string(&sidecar).map_err(|e| EditorError::InvalidBookmark(e.to_string()))?;
+        std::fs::write(&self.path, contents)?;
+        log::debug!(file:? = self.path, bookmarks = self.rows.len(); "saved bookmarks");
+        Ok(())
+    }
+
+    /// Returns the row (0-based) of the bookmark `name`.
+    pub fn get(&self, name: &str) -> Option<usize> {
+        self.rows.get(name).copied()
+    }
+
+    /// Bookmarks `row` (0-based) as `name`, returning the row it marked before, if any.
+    /// Names cannot be empty, be a number, start with `/` or contain whitespace,
+    /// so that they cannot be mistaken for another [`Target`].
+    pub fn add(&mut self, name: &str, row: usize) -> Result<Option<usize>, EditorError> {
+        if name.is_empty()
+            || name.parse::<usize>().is_ok()
+            || name.starts_with('/')
+            || name.contains(char::is_whitespace)
+        {
+            return Err(EditorError::InvalidBookmark(format!(
+                "'{name}' cannot be a bookmark name"
+            )));
+        }
+        Ok(self.rows.insert(name.to_string(), row))
+    }
+
+    /// Removes the bookmark `name`, returning its row.
+    pub fn remove(&mut self, name: &str) -> Option<usize> {
+        self.rows.remove(name)
+    }
+
+    /// Returns the bookmarks and their rows (0-based), by name.
+    pub fn iter(&self) -> impl Iterator<Item = (&str, usize)> {
+        self.rows.iter().map(|(name, &row)| (name.as_str(), row))
+    }
+}
+
+/// Where `goto` jumps to.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Target {
+    /// A row (0-based), written as its 1-based number.
+    Row(usize),
+    /// The first row with a cell containing the text, written `/text`; case is ignored.
+    Search(String),
+    /// A bookmarked row, written as the bookmark's name.
+    Bookmark(String),
+}
+
+impl FromStr for Target {
+    type Err = EditorError;
+
+    fn from_str(value: &str) -> Result<Target, EditorError> {
+        if let Some(text) = value.strip_prefix('/') {
+            return Ok(Target::Search(text.to_string()));
+        }
+        match value.parse::<usize>() {
+            Ok(0) => Err(EditorError::InvalidRange(
+                "rows are numbered from 1".to_string(),
+            )),
+            Ok(row) => Ok(Target::Row(row - 1)),
+            Err(_) => Ok(Target::Bookmark(value.to_string())),
+        }
+    }
+}
+
+impl Target {
+    /// Returns the row (0-based) the target points at in `csv_data`.
+    /// Returns an error if the row is past the end, no row matches, or there is no such bookmark.
+    pub fn resolve(&self, csv_data: &CSVData, bookmarks: &Bookmarks) -> Result<usize, EditorError> {
+        let row = match self {
+            Target::Row(row) => *row,
+            Target::Search(text) => csv_data
+                .find(text, 0)
+                .ok_or_else(|| EditorError::NotFound(format!("no row contains '{text}'")))?,
+            Target::Bookmark(name) => bookmarks
+                .get(name)
+                .ok_or_else(|| EditorError::NotFound(format!("no bookmark named '{name}'")))?,
+        };
+        let rows = csv_data.data.len();
+        if row >= rows {
+            return Err(EditorError::RowOutOfBounds { row, rows });
+        }
+        Ok(row)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bookmarks_and_targets() {
+        let file_name = "test_bookmarks.csv";
+        let mut bookmarks = Bookmarks::load(file_name).unwrap();
+        assert_eq!(bookmarks.iter().count(), 0);
+        assert_eq!(bookmarks.add("invoice-42", 3).unwrap(), None);
+        assert_eq!(bookmarks.add("start", 0).unwrap(), None);
+        assert!(bookmarks.add("12", 0).is_err());
+        assert!(bookmarks.add("/x", 0).is_err());
+        bookmarks.save().unwrap();
+        let sidecar = std::fs::read_to_string(".test_bookmarks.csv.bookmarks").unwrap();
+        assert!(sidecar.contains("invoice-42 = 4"));
+
+        let mut bookmarks = Bookmarks::load(file_name).unwrap();
+        assert_eq!(bookmarks.get("invoice-42"), Some(3));
+        let csv_data = CSVData::read_from_file("testdata.csv").unwrap();
+        let resolve = |target: &str| target.parse::<Target>()?.resolve(&csv_data, &bookmarks);
+        assert_eq!(resolve("invoice-42").unwrap(), 3);
+        assert_eq!(resolve("2").unwrap(), 1);
+        assert_eq!(resolve("/TELEPHONE").unwrap(), 2);
+        assert!(matches!(resolve("/nowhere"), Err(EditorError::NotFound(_))));
+        assert!(matches!(resolve("missing"), Err(EditorError::NotFound(_))));
+        assert!(resolve("7").is_err());
+
+        assert_eq!(bookmarks.remove("invoice-42"), Some(3));
+        bookmarks.remove("start");
+        bookmarks.save().unwrap();
+        assert!(!std::path::Path::new(".test_bookmarks.csv.bookmarks").exists());
+    }
+}
@@ -0,0 +1,209 @@
+use std::ops::RangeInclusive;
+
+use csv::{ReaderBuilder, StringRecord, WriterBuilder};
+
+use crate::{columns, EditorError};
+
+/// A block of cells: a run of rows and, within them, a set of columns.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CellRange {
+    /// 0-based rows.
+    pub rows: RangeInclusive<usize>,
+    /// 0-based columns, in order.
+    pub columns: Vec<usize>,
+}
+
+impl CellRange {
+    /// Parses a range written `ROWS[:COLUMNS]`, e.g. `3-5:name,age` or `7`.
+    /// Rows are 1-based, either one row or an inclusive range; columns are resolved
+    /// with [`columns::parse_columns`] and default to all columns.
+    pub fn parse(
+        spec: &str,
+        headers: &StringRecord,
+        fields: usize,
+    ) -> Result<CellRange, EditorError> {
+        let (rows, columns) = match spec.split_once(':') {
+            Some((rows, columns)) => (rows, Some(columns)),
+            None => (spec, None),
+        };
+        let (start, end) = rows.split_once('-').unwrap_or((rows, rows));
+        let (start, end) = (row_number(start, spec)?, row_number(end, spec)?);
+        if end < start {
+            return Err(EditorError::InvalidRange(format!(
+                "rows end before they start in '{spec}'"
+            )));
+        }
+        let columns = match columns {
+            Some(columns) => columns::parse_columns(columns, headers, fields)?,
+            None => (0..fields).collect(),
+        };
+        Ok(CellRange {
+            rows: start - 1..=end - 1,
+            columns,
+        })
+    }
+
+    /// Returns whether the range covers every column of data with `fields` columns, in order.
+    pub fn whole_rows(&self, fields: usize) -> bool {
+        self.columns.iter().copied().eq(0..fields)
+    }
+}
+
+fn row_number(value: &str, spec: &str) -> Result<usize, EditorError> {
+    match value.trim().parse::<usize>() {
+        Ok(number) if number > 0 => Ok(number),
+        _ => Err(EditorError::InvalidRange(format!(
+            "invalid row '{value}' in '{spec}'"
+        ))),
+    }
+}
+
+/// Cells copied or cut from the data, one record per row.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Clip {
+    pub rows: Vec<StringRecord>,
+}
+
+impl Clip {
+    /// Writes the cells as tab-separated values, the format spreadsheets paste.
+    /// Cells containing tabs, quotes or line breaks are quoted.
+    pub fn to_tsv(&self) -> Result<String, EditorError> {
+        let mut writer = WriterBuilder::new()
+            .delimiter(b'\t')
+            .flexible(true)
+            .from_writer(Vec::new());
+        for row in &self.rows {
+            writer.write_record(row)?;
+        }
+        let bytes = writer.into_inner().map_err(|e| e.into_error())?;
+        Ok(String::from_utf8(bytes).expect("records are valid UTF-8"))
+    }
+
+    /// Reads cells from tab-separated values, such as text copied from a spreadsheet.
+    /// Rows may have different lengths.
+    pub fn from_tsv(text: &str) -> Result<Clip, EditorError> {
+        let mut reader = ReaderBuilder::new()
+            .delimiter(b'\t')
+            .has_headers(false)
+            .flexible(true)
+            .from_reader(text.as_bytes());
+        let rows = reader.records().collect::<Result<_, _>>()?;
+        Ok(Clip { rows })
+    }
+
+    /// Removes the first row and returns it as a header, without the empty cells at its end.
+    pub fn take_header(&mut self) -> Option<StringRecord> {
+        if self.rows.is_empty() {
+            return None;
+        }
+        Some(StringRecord::from(trim_end(&self.rows.remove(0))))
+    }
+
+    /// Removes the first row if it repeats `headers`, as when a block copied from a
+    /// spreadsheet starts with its header. Surrounding spaces and empty cells at the end
+    /// of the row are ignored. Returns whether the row was removed.
+    pub fn strip_header(&mut self, headers: &StringRecord) -> bool {
+        let repeated = self.rows.first().is_some_and(|row| {
+            let row = trim_end(row);
+            !row.is_empty()
+                && row.len() == headers.len()
+                && row.iter().zip(headers).all(|(a, b)| a.trim() == b.trim())
+        });
+        if repeated {
+            self.rows.remove(0);
+        }
+        repeated
+    }
+
+    /// Gives every row `fields` cells: empty cells are added to shorter rows, and the empty
+    /// cells spreadsheets leave at the end of longer ones are dropped. Rows still too long
+    /// are cut if `truncate` is set, otherwise an error is returned and nothing changes.
+    /// Returns how many rows had another number of cells, empty ones at the end left out.
+    pub fn fit(&mut self, fields: usize, truncate: bool) -> Result<usize, EditorError> {
+        if !truncate {
+            if let Some((row, values)) = self
+                .rows
+                .iter()
+                .map(trim_end)
+                .enumerate()
+                .find(|(_, values)| values.len() > fields)
+            {
+                return Err(EditorError::Schema {
+                    row,
+                    message: format!("has {} value(s), expected {fields}", values.len()),
+                });
+            }
+        }
+        let mut fitted = 0;
+        for row in &mut self.rows {
+            let values = trim_end(row);
+            if values.len() != fields {
+                fitted += 1;
+            }
+            let mut values: Vec<&str> = values.into_iter().take(fields).collect();
+            values.resize(fields, "");
+            *row = StringRecord::from(values);
+        }
+        Ok(fitted)
+    }
+}
+
+/// Returns the values of `row` up to its last non-empty one.
+fn trim_end(row: &StringRecord) -> Vec<&str> {
+    let mut values: Vec<&str> = row.iter().collect();
+    while values.last().is_some_and(|value| value.is_empty()) {
+        values.pop();
+    }
+    values
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_range_and_tsv() {
+        let headers = StringRecord::from(vec!["name", "age", "city"]);
+        let range = CellRange::parse("2-3:age,city", &headers, 3).unwrap();
+        assert_eq!(range.rows, 1..=2);
+        assert_eq!(range.columns, vec![1, 2]);
+        assert!(!range.whole_rows(3));
+        assert!(CellRange::parse("4", &headers, 3).unwrap().whole_rows(3));
+        assert!(CellRange::parse("3-2", &headers, 3).is_err());
+        assert!(CellRange::parse("0:name", &headers, 3).is_err());
+
+        let clip = Clip {
+            rows: vec![
+                StringRecord::from(vec!["a b", "tab\there"]),
+                StringRecord::from(vec!["c"]),
+            ],
+        };
+        let tsv = clip.to_tsv().unwrap();
+        assert_eq!(tsv, "a b\t\"tab\there\"\nc\n");
+        assert_eq!(Clip::from_tsv(&tsv).unwrap(), clip);
+    }
+
+    #[test]
+    fn test_fit_and_headers() {
+        let headers = StringRecord::from(vec!["name", "age"]);
+        let mut clip = Clip::from_tsv("name \tage\t\nann\t34\t\t\nbob\nc\t1\tx\n").unwrap();
+        assert!(clip.strip_header(&headers));
+        assert!(!clip.strip_header(&headers));
+        assert!(clip.clone().fit(2, false).is_err());
+        assert_eq!(clip.fit(2, true).unwrap(), 2);
+        assert_eq!(
+            clip.rows,
+            vec![
+                StringRecord::from(vec!["ann", "34"]),
+                StringRecord::from(vec!["bob", ""]),
+                StringRecord::from(vec!["c", "1"]),
+            ]
+        );
+        let mut clip = Clip::from_tsv("id\tname\t\n1\n").unwrap();
+        assert_eq!(
+            clip.take_header(),
+            Some(StringRecord::from(vec!["id", "name"]))
+        );
+        assert_eq!(clip.rows.len(), 1);
+    }
+}
@@ -0,0 +1,253 @@
+use std::{fmt, io::IsTerminal, str::FromStr};
+
+use clap::ValueEnum;
+use regex::Regex;
+use serde::Deserialize;
+
+use crate::EditorError;
+
+/// Whether table output is colored.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, ValueEnum)]
+#[serde(rename_all = "kebab-case")]
+pub enum ColorMode {
+    /// Only when printing to a terminal and `NO_COLOR` is not set
+    #[default]
+    Auto,
+    Always,
+    Never,
+}
+
+impl ColorMode {
+    /// Returns whether to write ANSI colors to stdout.
+    pub fn enabled(self) -> bool {
+        match self {
+            ColorMode::Always => true,
+            ColorMode::Never => false,
+            ColorMode::Auto => {
+                std::env::var_os("NO_COLOR").is_none_or(|value| value.is_empty())
+                    && std::io::stdout().is_terminal()
+            }
+        }
+    }
+}
+
+/// Names of the styles, as written in color rules.
+const STYLES: [(&str, Style); 11] = [
+    ("red", Style::Red),
+    ("green", Style::Green),
+    ("yellow", Style::Yellow),
+    ("blue", Style::Blue),
+    ("magenta", Style::Magenta),
+    ("cyan", Style::Cyan),
+    ("bold", Style::Bold),
+    ("dim", Style::Dim),
+    ("underline", Style::Underline),
+    ("reverse", Style::Reverse),
+    ("on-gray", Style::OnGray),
+];
+
+/// A named text style, as used in color rules.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Style {
+    Red,
+    Green,
+    Yellow,
+    Blue,
+    Magenta,
+    Cyan,
+    Bold,
+    Dim,
+    Underline,
+    Reverse,
+    /// Gray background, used to shade rows.
+    OnGray,
+}
+
+impl Style {
+    /// Returns the ANSI escape sequence starting the style.
+    pub fn code(self) -> &'static str {
+        match self {
+            Style::Red => "\x1b[31m",
+            Style::Green => "\x1b[32m",
+            Style::Yellow => "\x1b[33m",
+            Style::Blue => "\x1b[34m",
+            Style::Magenta => "\x1b[35m",
+            Style::Cyan => "\x1b[36m",
+            Style::Bold => "\x1b[1m",
+            Style::Dim => "\x1b[2m",
+            Style::Underline => "\x1b[4m",
+            Style::Reverse => "\x1b[7m",
+            Style::OnGray => "\x1b[48;5;236m",
+        }
+    }
+}
+
+impl FromStr for Style {
+    type Err = EditorError;
+
+    fn from_str(name: &str) -> Result<Style, EditorError> {
+        let lowercase = name.trim().to_ascii_lowercase();
+        STYLES
+            .iter()
+            .find(|(style_name, _)| *style_name == lowercase)
+            .map(|&(_, style)| style)
+            .ok_or_else(|| {
+                let names: Vec<&str> = STYLES.iter().map(|(name, _)| *name).collect();
+                EditorError::InvalidFormat(format!(
+                    "unknown style '{name}', expected one of {}",
+                    names.join(", ")
+                ))
+            })
+    }
+}
+
+impl fmt::Display for Style {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let (name, _) = STYLES
+            .iter()
+            .find(|(_, style)| style == self)
+            .expect("every style has a name");
+        f.write_str(name)
+    }
+}
+
+/// A rule coloring cells or rows of table output, written as:
+/// - `/REGEX/[=STYLE]`: cells matching the regular expression (yellow by default)
+/// - `negative[=STYLE]`: negative numbers (red by default)
+/// - `stripes[=STYLE]`: every other row (on a gray background by default)
+#[derive(Debug, Clone)]
+pub enum ColorRule {
+    Match { pattern: Regex, style: Style },
+    Negative { style: Style },
+    Stripes { style: Style },
+}
+
+impl ColorRule {
+    /// Returns the style of a cell holding `value`, if the rule colors it.
+    pub fn cell_style(&self, value: &str) -> Option<Style> {
+        match self {
+            ColorRule::Match { pattern, style } if pattern.is_match(value) => Some(*style),
+            ColorRule::Negative { style } if is_negative(value) => Some(*style),
+            _ => None,
+        }
+    }
+
+    /// Returns the style of the row at `index` (0-based) on the page, if the rule colors it.
+    pub fn row_style(&self, index: usize) -> Option<Style> {
+        match self {
+            ColorRule::Stripes { style } if index % 2 == 1 => Some(*style),
+            _ => None,
+        }
+    }
+}
+
+/// Returns whether `value` is a negative number, also when written with
+/// thousands separators or a decimal comma, e.g. `-1.234,5`.
+fn is_negative(value: &str) -> bool {
+    let Some(digits) = value.trim().strip_prefix('-') else {
+        return false;
+    };
+    let mut digits = digits
+        .chars()
+        .filter(|c| !matches!(c, ',' | '.' | ' ' | '\'' | '\u{a0}'))
+        .peekable();
+    digits.peek().is_some() && digits.all(|c| c.is_ascii_digit())
+}
+
+impl FromStr for ColorRule {
+    type Err = EditorError;
+
+    fn from_str(rule: &str) -> Result<ColorRule, EditorError> {
+        let style = |style: Option<&str>, default| match style {
+            Some(style) => style.parse(),
+            None => Ok(default),
+        };
+        if let Some(rest) = rule.strip_prefix('/') {
+            let (pattern, style_name) = match rest.rsplit_once('/') {
+                Some((pattern, "")) => (pattern, None),
+                Some((pattern, style)) => match style.strip_prefix('=') {
+                    Some(style) => (pattern, Some(style)),
+                    None => return Err(invalid(rule)),
+                },
+                None => return Err(invalid(rule)),
+            };
+            let pattern = Regex::new(pattern)
+                .map_err(|e| EditorError::InvalidFormat(format!("color rule '{rule}': {e}")))?;
+            return Ok(ColorRule::Match {
+                pattern,
+                style: style(style_name, Style::Yellow)?,
+            });
+        }
+        let (name, style_name) = match rule.split_once('=') {
+            Some((name, style)) => (name, Some(style)),
+            None => (rule, None),
+        };
+        match name.trim() {
+            "negative" => Ok(ColorRule::Negative {
+                style: style(style_name, Style::Red)?,
+            }),
+            "stripes" => Ok(ColorRule::Stripes {
+                style: style(style_name, Style::OnGray)?,
+            }),
+            _ => Err(invalid(rule)),
+        }
+    }
+}
+
+fn invalid(rule: &str) -> EditorError {
+    EditorError::InvalidFormat(format!(
+        "invalid color rule '{rule}', expected /REGEX/[=STYLE], negative[=STYLE] or stripes[=STYLE]"
+    ))
+}
+
+impl fmt::Display for ColorRule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ColorRule::Match { pattern, style } => write!(f, "/{pattern}/={style}"),
+            ColorRule::Negative { style } => write!(f, "negative={style}"),
+            ColorRule::Stripes { style } => write!(f, "stripes={style}"),
+        }
+    }
+}
+
+impl PartialEq for ColorRule {
+    fn eq(&self, other: &ColorRule) -> bool {
+        self.to_string() == other.to_string()
+    }
+}
+
+impl<'de> Deserialize<'de> for ColorRule {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let rule = String::deserialize(deserializer)?;
+        rule.parse().map_err(serde::de::Error::custom)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_rules() {
+        let rules: Vec<ColorRule> = ["/^ERR/", "/a=b/=bold", "negative", "stripes=dim"]
+            .iter()
+            .map(|rule| rule.parse().unwrap())
+            .collect();
+        assert_eq!(rules[0].cell_style("ERROR 42"), Some(Style::Yellow));
+        assert_eq!(rules[0].cell_style("no ERR"), None);
+        assert_eq!(rules[1].cell_style("xa=by"), Some(Style::Bold));
+        assert_eq!(rules[2].cell_style(" -1.234,5"), Some(Style::Red));
+        assert_eq!(rules[2].cell_style("-"), None);
+        assert_eq!(rules[2].cell_style("-12 apples"), None);
+        assert_eq!(rules[3].row_style(1), Some(Style::Dim));
+        assert_eq!(rules[3].row_style(2), None);
+        assert_eq!(rules[1].to_string(), "/a=b/=bold");
+
+        for rule in ["/unclosed", "/x/bold", "/(/", "stripes=pink", "zebra"] {
+            assert!(
+                rule.parse::<ColorRule>().is_err(),
+                "'{rule}' should not parse"
+            );
+        }
+    }
+}
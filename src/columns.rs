@@ -0,0 +1,121 @@
+use csv::StringRecord;
+
+use crate::EditorError;
+
+/// Resolves a column spec such as `name,age,3-5` into 0-based column indices.
+///
+/// Each comma-separated item is either a header name, a 1-based column number,
+/// or an inclusive range of column numbers. Header names take precedence, so a
+/// column literally called `3-5` can still be selected by name.
+/// Returns an error for unknown names or numbers outside `1..=fields`.
+pub fn parse_columns(
+    spec: &str,
+    headers: &StringRecord,
+    fields: usize,
+) -> Result<Vec<usize>, EditorError> {
+    let mut columns = Vec::new();
+    for item in spec.split(',').map(str::trim) {
+        if item.is_empty() {
+            return Err(invalid(format!("empty column in '{spec}'")));
+        }
+        if let Some(index) = headers.iter().position(|name| name == item) {
+            columns.push(index);
+            continue;
+        }
+        let (start, end) = match item.split_once('-') {
+            Some((start, end)) => (column_number(start, item)?, column_number(end, item)?),
+            None => {
+                let number = column_number(item, item)?;
+                (number, number)
+            }
+        };
+        if start > end {
+            return Err(invalid(format!("invalid column range '{item}'")));
+        }
+        if end > fields {
+            return Err(invalid(format!(
+                "column {end} out of bounds, the file has {fields} column(s)"
+            )));
+        }
+        columns.extend(start - 1..end);
+    }
+    Ok(columns)
+}
+
+/// Resolves a spec that must name exactly one column, returning its 0-based index.
+pub fn parse_column(
+    spec: &str,
+    headers: &StringRecord,
+    fields: usize,
+) -> Result<usize, EditorError> {
+    match parse_columns(spec, headers, fields)?.as_slice() {
+        [column] => Ok(*column),
+        _ => Err(invalid(format!("expected a single column, got '{spec}'"))),
+    }
+}
+
+fn column_number(value: &str, item: &str) -> Result<usize, EditorError> {
+    match value.trim().parse::<usize>() {
+        Ok(number) if number > 0 => Ok(number),
+        _ => Err(invalid(format!("unknown column '{item}'"))),
+    }
+}
+
+fn invalid(message: String) -> EditorError {
+    EditorError::InvalidColumns(message)
+}
+
+/// Returns how the column at `field` (0-based) is named to the user and in sidecars: by its
+/// name in `headers`, or by its 1-based number when it has none.
+pub fn column_name(headers: &StringRecord, field: usize) -> String {
+    match headers.get(field) {
+        Some(name) if !name.is_empty() => name.to_string(),
+        _ => (field + 1).to_string(),
+    }
+}
+
+/// Builds a new record holding only the given columns of `record`, in order.
+/// Missing fields are projected as empty strings.
+pub fn project(record: &StringRecord, columns: &[usize]) -> StringRecord {
+    columns
+        .iter()
+        .map(|&i| record.get(i).unwrap_or(""))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_columns() {
+        let headers = StringRecord::from(vec!["id", "name", "age", "city", "zip"]);
+        assert_eq!(parse_columns("name,age", &headers, 5).unwrap(), vec![1, 2]);
+        assert_eq!(
+            parse_columns("zip,1-3", &headers, 5).unwrap(),
+            vec![4, 0, 1, 2]
+        );
+        assert_eq!(parse_columns("4", &headers, 5).unwrap(), vec![3]);
+        assert!(parse_columns("height", &headers, 5).is_err());
+        assert!(parse_columns("4-6", &headers, 5).is_err());
+        assert!(parse_columns("3-2", &headers, 5).is_err());
+        assert!(parse_columns("0", &headers, 5).is_err());
+    }
+
+    #[test]
+    fn test_project() {
+        let record = StringRecord::from(vec!["a", "b", "c"]);
+        assert_eq!(
+            project(&record, &[2, 0, 5]),
+            StringRecord::from(vec!["c", "a", ""])
+        );
+    }
+
+    #[test]
+    fn test_column_name() {
+        let headers = StringRecord::from(vec!["id", "", "price"]);
+        assert_eq!(column_name(&headers, 2), "price");
+        assert_eq!(column_name(&headers, 1), "2");
+        assert_eq!(column_name(&StringRecord::new(), 0), "1");
+    }
+}
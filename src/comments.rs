@@ -0,0 +1,167 @@
+use std::{collections::BTreeMap, ops::Range, path::PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{lock, EditorError};
+
+/// Notes on rows and cells of a file, kept in a hidden sidecar next to it
+/// (`.name.csv.comments`), so that reviewers can flag values without changing the data.
+/// Like bookmarks, comments point at row numbers and do not follow a row that moves;
+/// cell comments name their column, and follow it when columns are reordered.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Comments {
+    path: PathBuf,
+    /// Text of each comment, by row (0-based) and column, `None` for the whole row.
+    notes: BTreeMap<(usize, Option<String>), String>,
+}
+
+/// The sidecar's contents. Rows are 1-based, as shown to the user.
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct Sidecar {
+    #[serde(default)]
+    comments: Vec<Entry>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct Entry {
+    row: usize,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    column: Option<String>,
+    text: String,
+}
+
+impl Comments {
+    /// Returns an empty set of comments for `file_name`.
+    pub fn new(file_name: &str) -> Comments {
+        Comments {
+            path: lock::sidecar_path(file_name, "comments"),
+            notes: BTreeMap::new(),
+        }
+    }
+
+    /// Returns the comments of `file_name`, none if it has no sidecar.
+    pub fn load(file_name: &str) -> Result<Comments, EditorError> {
+        let Comments { path, .. } = Comments::new(file_name);
+        let sidecar: Sidecar = match std::fs::read_to_string(&path) {
+            Ok(contents) => toml::from_str(&contents).map_err(|e| {
+                EditorError::InvalidComment(format!("{}: {}", path.display(), e.message()))
+            })?,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Sidecar::default(),
+            Err(e) => return Err(e.into()),
+        };
+        let notes = sidecar
+            .comments
+            .into_iter()
+            .filter(|entry| entry.row > 0)
+            .map(|entry| ((entry.row - 1, entry.column), entry.text))
+            .collect();
+        Ok(Comments { path, notes })
+    }
+
+    /// Writes the comments to the sidecar, removing it when there are none left.
+    pub fn save(&self) -> Result<(), EditorError> {
+        if self.notes.is_empty() {
+            return match std::fs::remove_file(&self.path) {
+                Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e.into()),
+                _ => Ok(()),
+            };
+        }
+        let sidecar = Sidecar {
+            comments: self
+                .iter()
+                .map(|(row, column, text)| Entry {
+                    row: row + 1,
+                    column: column.map(str::to_string),
+                    text: text.to_string(),
+                })
+                .collect(),
+        };
+        let contents =
+            toml::to_string(&sidecar).map_err(|e| EditorError::InvalidComment(e.to_string()))?;
+        std::fs::write(&self.path, contents)?;
+        log::debug!(file:? = self.path, comments = self.notes.len(); "saved comments");
+        Ok(())
+    }
+
+    /// Comments `row` (0-based), or its cell in `column` (see [`crate::columns::column_name`]),
+    /// returning the comment it replaces, if any. Surrounding spaces of `text` are dropped,
+    /// and it cannot be empty.
+    pub fn add(
+        &mut self,
+        row: usize,
+        column: Option<String>,
+        text: &str,
+    ) -> Result<Option<String>, EditorError> {
+        let text = text.trim();
+        if text.is_empty() {
+            return Err(EditorError::InvalidComment(
+                "a comment cannot be empty".to_string(),
+            ));
+        }
+        Ok(self.notes.insert((row, column), text.to_string()))
+    }
+
+    /// Removes the comment on `row` (0-based), or on its cell in `column`, returning its text.
+    pub fn remove(&mut self, row: usize, column: Option<String>) -> Option<String> {
+        self.notes.remove(&(row, column))
+    }
+
+    /// Returns the comments, by row (0-based) then column, with the row comment first.
+    pub fn iter(&self) -> impl Iterator<Item = (usize, Option<&str>, &str)> {
+        self.notes
+            .iter()
+            .map(|((row, column), text)| (*row, column.as_deref(), text.as_str()))
+    }
+
+    /// Returns the comments on `rows` (0-based), in the order of [`Comments::iter`].
+    pub fn on_rows(&self, rows: Range<usize>) -> impl Iterator<Item = (usize, Option<&str>, &str)> {
+        self.notes
+            .range((rows.start, None)..(rows.end, None))
+            .map(|((row, column), text)| (*row, column.as_deref(), text.as_str()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_comments() {
+        let file_name = "test_comments.csv";
+        let mut comments = Comments::load(file_name).unwrap();
+        assert_eq!(comments.iter().count(), 0);
+        let price = Some("price".to_string());
+        assert_eq!(comments.add(3, price.clone(), " too high ").unwrap(), None);
+        assert_eq!(comments.add(3, None, "duplicate?").unwrap(), None);
+        assert_eq!(
+            comments.add(0, Some("2".to_string()), "typo").unwrap(),
+            None
+        );
+        assert!(comments.add(1, None, "  ").is_err());
+        comments.save().unwrap();
+        let sidecar = std::fs::read_to_string(".test_comments.csv.comments").unwrap();
+        assert!(sidecar.contains("row = 4\ncolumn = \"price\"\ntext = \"too high\""));
+
+        let mut comments = Comments::load(file_name).unwrap();
+        assert_eq!(
+            comments.iter().collect::<Vec<_>>(),
+            vec![
+                (0, Some("2"), "typo"),
+                (3, None, "duplicate?"),
+                (3, Some("price"), "too high"),
+            ]
+        );
+        assert_eq!(comments.on_rows(1..4).count(), 2);
+        assert_eq!(comments.on_rows(4..9).count(), 0);
+        assert_eq!(
+            comments.add(3, price.clone(), "fine").unwrap().as_deref(),
+            Some("too high")
+        );
+
+        assert_eq!(comments.remove(3, price).as_deref(), Some("fine"));
+        comments.remove(3, None);
+        comments.remove(0, Some("2".to_string()));
+        comments.save().unwrap();
+        assert!(!std::path::Path::new(".test_comments.csv.comments").exists());
+    }
+}
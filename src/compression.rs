@@ -0,0 +1,132 @@
+use std::{
+    fmt,
+    io::{Read, Write},
+};
+
+use clap::ValueEnum;
+
+use crate::EditorError;
+
+/// A compression format of CSV files.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Compression {
+    /// gzip (.gz)
+    Gzip,
+    /// Zstandard (.zst)
+    Zstd,
+    /// xz (.xz)
+    Xz,
+}
+
+/// Bytes starting a file in each format.
+const MAGIC: [(&[u8], Compression); 3] = [
+    (b"\x1f\x8b", Compression::Gzip),
+    (b"\x28\xb5\x2f\xfd", Compression::Zstd),
+    (b"\xfd7zXZ\x00", Compression::Xz),
+];
+
+impl Compression {
+    /// Returns the format of the file whose contents start with `bytes`, if compressed.
+    pub fn detect(bytes: &[u8]) -> Option<Compression> {
+        MAGIC
+            .iter()
+            .find(|(magic, _)| bytes.starts_with(magic))
+            .map(|&(_, compression)| compression)
+    }
+
+    /// Returns the format a file name's extension stands for, e.g. `sales.csv.zst`.
+    pub fn from_extension(file_name: &str) -> Option<Compression> {
+        let (_, extension) = file_name.rsplit_once('.')?;
+        match extension.to_ascii_lowercase().as_str() {
+            "gz" | "gzip" => Some(Compression::Gzip),
+            "zst" | "zstd" => Some(Compression::Zstd),
+            "xz" => Some(Compression::Xz),
+            _ => None,
+        }
+    }
+
+    /// Returns the usual file name extension of the format, without the dot.
+    pub fn extension(self) -> &'static str {
+        match self {
+            Compression::Gzip => "gz",
+            Compression::Zstd => "zst",
+            Compression::Xz => "xz",
+        }
+    }
+
+    /// Returns the uncompressed contents of `bytes`.
+    /// Returns an error if they are not valid in this format.
+    pub fn decompress(self, bytes: &[u8]) -> Result<Vec<u8>, EditorError> {
+        let mut decompressed = Vec::new();
+        let result = match self {
+            Compression::Gzip => {
+                flate2::read::MultiGzDecoder::new(bytes).read_to_end(&mut decompressed)
+            }
+            Compression::Zstd => zstd::stream::read::Decoder::new(bytes)
+                .and_then(|mut decoder| decoder.read_to_end(&mut decompressed)),
+            Compression::Xz => {
+                xz2::read::XzDecoder::new_multi_decoder(bytes).read_to_end(&mut decompressed)
+            }
+        };
+        result.map_err(|e| self.error(e))?;
+        log::debug!(
+            compression:% = self,
+            compressed = bytes.len(),
+            bytes = decompressed.len();
+            "decompressed"
+        );
+        Ok(decompressed)
+    }
+
+    /// Returns `bytes` compressed in this format, at the format's default level.
+    pub fn compress(self, bytes: &[u8]) -> Result<Vec<u8>, EditorError> {
+        let compressed = match self {
+            Compression::Gzip => {
+                let mut encoder =
+                    flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+                encoder.write_all(bytes).and_then(|()| encoder.finish())
+            }
+            Compression::Zstd => zstd::stream::encode_all(bytes, 0),
+            Compression::Xz => {
+                let mut encoder = xz2::write::XzEncoder::new(Vec::new(), 6);
+                encoder.write_all(bytes).and_then(|()| encoder.finish())
+            }
+        };
+        compressed.map_err(|e| self.error(e))
+    }
+
+    fn error(self, e: std::io::Error) -> EditorError {
+        EditorError::Compression(format!("{self}: {e}"))
+    }
+}
+
+impl fmt::Display for Compression {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Compression::Gzip => "gzip",
+            Compression::Zstd => "zstd",
+            Compression::Xz => "xz",
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_round_trip() {
+        let text = b"id,name\n1,ann\n2,bob\n".repeat(100);
+        for compression in [Compression::Gzip, Compression::Zstd, Compression::Xz] {
+            let compressed = compression.compress(&text).unwrap();
+            assert!(compressed.len() < text.len());
+            assert_eq!(Compression::detect(&compressed), Some(compression));
+            assert_eq!(compression.decompress(&compressed).unwrap(), text);
+            let file_name = format!("sales.csv.{}", compression.extension());
+            assert_eq!(Compression::from_extension(&file_name), Some(compression));
+            assert!(compression.decompress(&text).is_err());
+        }
+        assert_eq!(Compression::detect(&text), None);
+        assert_eq!(Compression::from_extension("sales.csv"), None);
+    }
+}
@@ -0,0 +1,352 @@
+use std::{
+    error::Error,
+    path::{Path, PathBuf},
+};
+
+use clap::ValueEnum;
+use serde::Deserialize;
+
+use crate::{
+    color::{ColorMode, ColorRule},
+    schema::Locale,
+};
+
+/// Name of the directory holding the config file, relative to the user's config dir.
+const CONFIG_DIR: &str = "bootleg-editor3000";
+const CONFIG_FILE: &str = "config.toml";
+const DEFAULT_RECORDS_PER_PAGE: usize = 10;
+
+/// How records are rendered to the terminal.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, ValueEnum)]
+#[serde(rename_all = "kebab-case")]
+pub enum OutputFormat {
+    /// One `StringRecord([...])` per line (the historical output)
+    #[default]
+    Debug,
+    /// Aligned columns with the header on top
+    Table,
+    /// Plain CSV using the configured delimiter
+    Csv,
+}
+
+/// Whether subcommands print text for people or JSON for programs (see [`crate::report`]).
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, ValueEnum)]
+#[serde(rename_all = "kebab-case")]
+pub enum OutputMode {
+    /// Human-readable text
+    #[default]
+    Text,
+    /// One JSON document per subcommand
+    Json,
+}
+
+/// Colors used when highlighting the header in table output.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, ValueEnum)]
+#[serde(rename_all = "kebab-case")]
+pub enum ColorScheme {
+    /// No colors at all
+    #[default]
+    Plain,
+    /// Colors readable on a light terminal background
+    Light,
+    /// Colors readable on a dark terminal background
+    Dark,
+}
+
+impl ColorScheme {
+    /// Returns the ANSI escape sequence used for header cells, if any.
+    pub fn header_style(self) -> Option<&'static str> {
+        match self {
+            ColorScheme::Plain => None,
+            ColorScheme::Light => Some("\x1b[1;34m"),
+            ColorScheme::Dark => Some("\x1b[1;36m"),
+        }
+    }
+}
+
+/// Whether the first row of the file is a header.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, ValueEnum)]
+#[serde(rename_all = "kebab-case")]
+pub enum HeaderMode {
+    /// The first row holds the column names
+    #[default]
+    FirstRow,
+    /// Every row is data
+    None,
+}
+
+/// What to do with rows whose field count differs from the header.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, ValueEnum)]
+#[serde(rename_all = "kebab-case")]
+pub enum RaggedPolicy {
+    /// Fail on the first ragged row
+    #[default]
+    Error,
+    /// Pad every row (and the header) with empty fields up to the longest row
+    Pad,
+    /// Cut long rows and pad short ones to the header width
+    Truncate,
+    /// Keep rows as they are and print a warning for each ragged one
+    Report,
+}
+
+/// User defaults, as read from `config.toml` or collected from CLI flags.
+/// Every value is optional so that several sources can be layered.
+#[derive(Debug, Default, Clone, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub records_per_page: Option<usize>,
+    pub delimiter: Option<char>,
+    pub color_scheme: Option<ColorScheme>,
+    pub header: Option<HeaderMode>,
+    pub output_format: Option<OutputFormat>,
+    pub output_mode: Option<OutputMode>,
+    pub ragged: Option<RaggedPolicy>,
+    pub locale: Option<Locale>,
+    pub decimals: Option<usize>,
+    pub freeze_cols: Option<usize>,
+    pub cols_per_page: Option<usize>,
+    pub color: Option<ColorMode>,
+    pub color_rules: Option<Vec<ColorRule>>,
+    pub row_numbers: Option<bool>,
+    pub na_value: Option<String>,
+    pub on_read: Option<Vec<String>>,
+    pub on_write: Option<Vec<String>>,
+    pub threads: Option<usize>,
+    pub cache_dir: Option<PathBuf>,
+    pub date_formats: Option<Vec<String>>,
+}
+
+/// Fully resolved settings used by the rest of the program.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Settings {
+    pub records_per_page: usize,
+    pub delimiter: u8,
+    pub color_scheme: ColorScheme,
+    pub header: HeaderMode,
+    pub output_format: OutputFormat,
+    pub output_mode: OutputMode,
+    pub ragged: RaggedPolicy,
+    /// Locale used to format typed columns; `None` leaves values as stored.
+    pub locale: Option<Locale>,
+    /// Number of decimals shown for float columns; `None` leaves values as stored.
+    pub decimals: Option<usize>,
+    /// Number of leading columns kept in view when scrolling to the right.
+    pub freeze_cols: usize,
+    /// Number of columns shown after the frozen ones; `None` shows them all.
+    pub cols_per_page: Option<usize>,
+    /// Whether table output is colored, resolved from the [`ColorMode`].
+    pub color: bool,
+    /// Rules coloring cells and rows of table output, applied when `color` is set.
+    pub color_rules: Vec<ColorRule>,
+    /// Whether rows are numbered in a leading `#` column.
+    pub row_numbers: bool,
+    /// Shown, and written, in place of empty values; `None` leaves them empty.
+    pub na_value: Option<String>,
+    /// Row processors run, in order, on the data read (see [`crate::plugin::Registry`]).
+    pub on_read: Vec<String>,
+    /// Row processors run, in order, on a copy of the data before writing it.
+    pub on_write: Vec<String>,
+    /// Number of threads sorting, filtering, grouping and inferring types; `None` uses one
+    /// per core.
+    pub threads: Option<usize>,
+    /// Directory keeping downloaded remote files for reuse; `None` downloads them every time.
+    pub cache_dir: Option<PathBuf>,
+    /// Formats `normalize-dates` reads dates in, tried in order; empty tries
+    /// [`crate::dates::DEFAULT_FORMATS`].
+    pub date_formats: Vec<String>,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Settings {
+            records_per_page: DEFAULT_RECORDS_PER_PAGE,
+            delimiter: b',',
+            color_scheme: ColorScheme::default(),
+            header: HeaderMode::default(),
+            output_format: OutputFormat::default(),
+            output_mode: OutputMode::default(),
+            ragged: RaggedPolicy::default(),
+            locale: None,
+            decimals: None,
+            freeze_cols: 0,
+            cols_per_page: None,
+            color: false,
+            color_rules: Vec::new(),
+            row_numbers: false,
+            na_value: None,
+            on_read: Vec::new(),
+            on_write: Vec::new(),
+            threads: None,
+            cache_dir: None,
+            date_formats: Vec::new(),
+        }
+    }
+}
+
+impl Config {
+    /// Returns `$XDG_CONFIG_HOME/bootleg-editor3000/config.toml`,
+    /// falling back to `~/.config/bootleg-editor3000/config.toml`.
+    pub fn default_path() -> Option<PathBuf> {
+        let base = std::env::var_os("XDG_CONFIG_HOME")
+            .filter(|dir| !dir.is_empty())
+            .map(PathBuf::from)
+            .or_else(|| std::env::var_os("HOME").map(|home| Path::new(&home).join(".config")))?;
+        Some(base.join(CONFIG_DIR).join(CONFIG_FILE))
+    }
+
+    /// Parses a config file.
+    /// Returns an error if the file cannot be read or is not valid TOML.
+    pub fn read_from_file(path: &Path) -> Result<Config, Box<dyn Error>> {
+        let contents = std::fs::read_to_string(path)
+            .map_err(|e| format!("cannot read config file {}: {e}", path.display()))?;
+        let config = toml::from_str(&contents)
+            .map_err(|e| format!("invalid config file {}: {e}", path.display()))?;
+        Ok(config)
+    }
+
+    /// Loads the config file given with `--config`, or the default one if it exists.
+    /// An explicitly requested file must exist; a missing default file is not an error.
+    pub fn load(explicit: Option<&Path>) -> Result<Config, Box<dyn Error>> {
+        match explicit {
+            Some(path) => Config::read_from_file(path),
+            None => match Config::default_path() {
+                Some(path) if path.is_file() => Config::read_from_file(&path),
+                _ => Ok(Config::default()),
+            },
+        }
+    }
+
+    /// Layers `self` on top of `fallback`: values set in `self` win.
+    pub fn or(self, fallback: Config) -> Config {
+        Config {
+            records_per_page: self.records_per_page.or(fallback.records_per_page),
+            delimiter: self.delimiter.or(fallback.delimiter),
+            color_scheme: self.color_scheme.or(fallback.color_scheme),
+            header: self.header.or(fallback.header),
+            output_format: self.output_format.or(fallback.output_format),
+            output_mode: self.output_mode.or(fallback.output_mode),
+            ragged: self.ragged.or(fallback.ragged),
+            locale: self.locale.or(fallback.locale),
+            decimals: self.decimals.or(fallback.decimals),
+            freeze_cols: self.freeze_cols.or(fallback.freeze_cols),
+            cols_per_page: self.cols_per_page.or(fallback.cols_per_page),
+            color: self.color.or(fallback.color),
+            color_rules: self.color_rules.or(fallback.color_rules),
+            row_numbers: self.row_numbers.or(fallback.row_numbers),
+            na_value: self.na_value.or(fallback.na_value),
+            on_read: self.on_read.or(fallback.on_read),
+            on_write: self.on_write.or(fallback.on_write),
+            threads: self.threads.or(fallback.threads),
+            cache_dir: self.cache_dir.or(fallback.cache_dir),
+            date_formats: self.date_formats.or(fallback.date_formats),
+        }
+    }
+
+    /// Resolves the config into concrete settings, using built-in defaults for unset values.
+    /// Returns an error if the delimiter is not a single ASCII character.
+    pub fn settings(&self) -> Result<Settings, Box<dyn Error>> {
+        let defaults = Settings::default();
+        let delimiter = match self.delimiter {
+            Some(c) if c.is_ascii() => c as u8,
+            Some(c) => {
+                return Err(format!("delimiter must be an ASCII character, got {c:?}").into())
+            }
+            None => defaults.delimiter,
+        };
+        Ok(Settings {
+            records_per_page: self.records_per_page.unwrap_or(defaults.records_per_page),
+            delimiter,
+            color_scheme: self.color_scheme.unwrap_or(defaults.color_scheme),
+            header: self.header.unwrap_or(defaults.header),
+            output_format: self.output_format.unwrap_or(defaults.output_format),
+            output_mode: self.output_mode.unwrap_or(defaults.output_mode),
+            ragged: self.ragged.unwrap_or(defaults.ragged),
+            locale: self.locale,
+            decimals: self.decimals,
+            freeze_cols: self.freeze_cols.unwrap_or(defaults.freeze_cols),
+            cols_per_page: self.cols_per_page.filter(|&columns| columns > 0),
+            color: self.color.unwrap_or_default().enabled(),
+            color_rules: self.color_rules.clone().unwrap_or_default(),
+            row_numbers: self.row_numbers.unwrap_or(defaults.row_numbers),
+            na_value: self.na_value.clone(),
+            on_read: self.on_read.clone().unwrap_or_default(),
+            on_write: self.on_write.clone().unwrap_or_default(),
+            threads: self.threads.filter(|&threads| threads > 0),
+            cache_dir: self.cache_dir.clone(),
+            date_formats: self.date_formats.clone().unwrap_or_default(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_config() {
+        let config: Config = toml::from_str(
+            r#"
+            records_per_page = 25
+            delimiter = ";"
+            color_scheme = "dark"
+            header = "none"
+            output_format = "table"
+            ragged = "pad"
+            color = "always"
+            color_rules = ["negative", "/^ERR/=bold"]
+            "#,
+        )
+        .expect("Failed to parse config");
+        let settings = config.settings().expect("Failed to resolve settings");
+        assert_eq!(settings.records_per_page, 25);
+        assert_eq!(settings.delimiter, b';');
+        assert_eq!(settings.color_scheme, ColorScheme::Dark);
+        assert_eq!(settings.header, HeaderMode::None);
+        assert_eq!(settings.output_format, OutputFormat::Table);
+        assert_eq!(settings.ragged, RaggedPolicy::Pad);
+        assert!(settings.color);
+        assert_eq!(settings.color_rules.len(), 2);
+    }
+
+    #[test]
+    fn test_cli_overrides_config() {
+        let file = Config {
+            records_per_page: Some(25),
+            delimiter: Some(';'),
+            threads: Some(4),
+            ..Config::default()
+        };
+        let cli = Config {
+            records_per_page: Some(5),
+            ..Config::default()
+        };
+        let settings = cli
+            .or(file.clone())
+            .settings()
+            .expect("Failed to resolve settings");
+        assert_eq!(settings.records_per_page, 5);
+        assert_eq!(settings.delimiter, b';');
+        assert_eq!(settings.output_format, OutputFormat::Debug);
+        assert_eq!(settings.threads, Some(4));
+
+        // `--threads 0` uses one thread per CPU.
+        let cli = Config {
+            threads: Some(0),
+            ..Config::default()
+        };
+        let settings = cli.or(file).settings().expect("Failed to resolve settings");
+        assert_eq!(settings.threads, None);
+    }
+
+    #[test]
+    fn test_rejects_unknown_keys_and_bad_delimiter() {
+        assert!(toml::from_str::<Config>("records_per_pages = 3").is_err());
+        assert!(toml::from_str::<Config>("color_rules = [\"zebra\"]").is_err());
+        let config = Config {
+            delimiter: Some('§'),
+            ..Config::default()
+        };
+        assert!(config.settings().is_err());
+    }
+}
@@ -0,0 +1,3555 @@
+use std::{
+    borrow::Cow,
+    cmp::Ordering,
+    collections::{BTreeMap, HashMap},
+    fmt,
+    ops::Range,
+    time::SystemTime,
+};
+
+use clap::ValueEnum;
+use csv::{ReaderBuilder, StringRecord, Writer, WriterBuilder};
+use encoding_rs::{Encoding, UTF_8};
+use memmap2::Mmap;
+use rand::{rngs::Xoshiro256PlusPlus, SeedableRng};
+use rayon::prelude::*;
+use serde::{de::DeserializeOwned, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::{
+    aggregate::{Accumulator, AggFn, Aggregation},
+    audit::{CellChange, Change, Journal},
+    clipboard::{CellRange, Clip},
+    columns,
+    compression::Compression,
+    config::{HeaderMode, RaggedPolicy, Settings},
+    encoding,
+    expr::Expr,
+    fixed_width,
+    index::{Index, IndexMode, Offset, Stamp, INDEX_EVERY},
+    plugin::{Processed, RowProcessor, Verdict},
+    progress::{Progress, ProgressEvent, Stage, REPORT_EVERY},
+    render::{self, Layout},
+    row::Row,
+    schema::{self, ColumnType, Locale},
+    transform::{self, Piece},
+    EditorError,
+};
+
+#[derive(Debug, Clone)]
+pub struct Page {
+    pub start: usize,
+    pub end: usize,
+}
+
+/// Number of rows each thread summarizes at a time in [`CSVData::group_by`].
+const GROUP_CHUNK: usize = 16_384;
+
+/// Files from this size up are memory-mapped by [`ReadBackend::Auto`].
+pub const MMAP_THRESHOLD: u64 = 64 << 20;
+
+/// How the bytes of a file are loaded before parsing.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum ReadBackend {
+    /// Memory-maps files of 64 MiB or more, reads smaller ones
+    #[default]
+    Auto,
+    /// Reads the whole file into memory
+    Read,
+    /// Memory-maps the file, so that its bytes are not copied before parsing.
+    /// The file must not be truncated while it is read
+    Mmap,
+}
+
+impl ReadBackend {
+    /// Returns whether a file of `size` bytes is memory-mapped.
+    pub fn maps(self, size: u64) -> bool {
+        match self {
+            ReadBackend::Auto => size >= MMAP_THRESHOLD,
+            ReadBackend::Read => false,
+            ReadBackend::Mmap => true,
+        }
+    }
+}
+
+/// Options controlling how a CSV file is parsed.
+#[derive(Debug, Clone, Copy)]
+pub struct ReadOptions {
+    pub delimiter: u8,
+    pub has_headers: bool,
+    pub ragged: RaggedPolicy,
+    /// Encoding of the file, or `None` to detect it.
+    pub encoding: Option<&'static Encoding>,
+    pub backend: ReadBackend,
+    /// Whether the file gets a sidecar index, and is read with it (see [`crate::index`]).
+    pub index: IndexMode,
+}
+
+impl Default for ReadOptions {
+    fn default() -> Self {
+        ReadOptions {
+            delimiter: b',',
+            has_headers: true,
+            ragged: RaggedPolicy::default(),
+            encoding: None,
+            backend: ReadBackend::default(),
+            index: IndexMode::default(),
+        }
+    }
+}
+
+impl From<&Settings> for ReadOptions {
+    fn from(settings: &Settings) -> Self {
+        ReadOptions {
+            delimiter: settings.delimiter,
+            has_headers: settings.header == HeaderMode::FirstRow,
+            ragged: settings.ragged,
+            encoding: None,
+            backend: ReadBackend::default(),
+            index: IndexMode::default(),
+        }
+    }
+}
+
+/// Options controlling how CSV data is written.
+#[derive(Debug, Clone)]
+pub struct WriteOptions {
+    pub delimiter: u8,
+    pub encoding: &'static Encoding,
+    /// Written in place of empty values (see [`is_na`]); `None` leaves them empty.
+    pub na_value: Option<String>,
+    pub compression: Option<Compression>,
+}
+
+impl Default for WriteOptions {
+    fn default() -> Self {
+        WriteOptions {
+            delimiter: b',',
+            encoding: UTF_8,
+            na_value: None,
+            compression: None,
+        }
+    }
+}
+
+/// Returns whether a value is missing: empty or only whitespace.
+pub fn is_na(value: &str) -> bool {
+    value.trim().is_empty()
+}
+
+/// How [`CSVData::split`] divides the rows into parts.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SplitBy {
+    /// At most this many rows per part.
+    Rows(usize),
+    /// At most this many bytes per part once written, header included.
+    /// A row larger than the limit gets a part of its own.
+    Bytes(u64),
+    /// One part per distinct value of the column (0-based).
+    Column(usize),
+}
+
+/// How [`CSVData::concat`] matches the columns of the appended data.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum HeaderMatch {
+    /// Require the same columns in the same order
+    #[default]
+    Strict,
+    /// Match columns by name, adding empty values for columns either side lacks
+    Union,
+}
+
+/// Differences between two headers, as found by [`CSVData::concat`].
+/// Data without a header has columns named by their 1-based number.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct HeaderDiff {
+    /// Columns of the data that the appended data lacks.
+    pub missing: Vec<String>,
+    /// Columns of the appended data that the data lacks.
+    pub extra: Vec<String>,
+    /// Whether the shared columns come in a different order.
+    pub reordered: bool,
+}
+
+impl HeaderDiff {
+    /// Returns whether the headers are the same.
+    pub fn is_empty(&self) -> bool {
+        self.missing.is_empty() && self.extra.is_empty() && !self.reordered
+    }
+}
+
+impl fmt::Display for HeaderDiff {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut parts = Vec::new();
+        if !self.missing.is_empty() {
+            parts.push(format!("missing columns {}", self.missing.join(", ")));
+        }
+        if !self.extra.is_empty() {
+            parts.push(format!("extra columns {}", self.extra.join(", ")));
+        }
+        if self.reordered {
+            parts.push("columns in a different order".to_string());
+        }
+        f.write_str(&parts.join("; "))
+    }
+}
+
+/// A row whose field count differs from the width of the data.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RaggedRow {
+    /// Index of the row in the data (0-based).
+    pub row: usize,
+    /// Line of the file the row starts on, when known.
+    pub line: Option<u64>,
+    pub fields: usize,
+    pub expected: usize,
+}
+
+impl fmt::Display for RaggedRow {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "row {}", self.row + 1)?;
+        if let Some(line) = self.line {
+            write!(f, " (line {line})")?;
+        }
+        write!(f, ": {} field(s), expected {}", self.fields, self.expected)
+    }
+}
+
+/// What reading a file found out about one of its columns (see [`CSVData::metadata`]).
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ColumnInfo {
+    /// Name of the column, or its 1-based number when the data has no header.
+    pub name: String,
+    /// Number of rows with a value in the column, missing ones (see [`is_na`]) left out.
+    pub filled: usize,
+    /// Number of characters of the longest value.
+    pub max_width: usize,
+}
+
+impl ColumnInfo {
+    /// Counts the values of `record` into `columns`, one per field.
+    fn count(columns: &mut Vec<ColumnInfo>, record: &StringRecord) {
+        if columns.len() < record.len() {
+            columns.resize_with(record.len(), ColumnInfo::default);
+        }
+        for (column, value) in columns.iter_mut().zip(record) {
+            column.filled += usize::from(!is_na(value));
+            // A value has at most as many characters as bytes, so most are not counted.
+            if value.len() > column.max_width {
+                column.max_width = column.max_width.max(value.chars().count());
+            }
+        }
+    }
+
+    /// Adds the counts of `other`, e.g. from another chunk of the same rows, to `columns`.
+    fn merge(columns: &mut Vec<ColumnInfo>, other: Vec<ColumnInfo>) {
+        if columns.len() < other.len() {
+            columns.resize_with(other.len(), ColumnInfo::default);
+        }
+        for (column, other) in columns.iter_mut().zip(other) {
+            column.filled += other.filled;
+            column.max_width = column.max_width.max(other.max_width);
+        }
+    }
+}
+
+/// What reading a file found out about it, counted while parsing it (see
+/// [`CSVData::metadata`]).
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Metadata {
+    /// Number of rows, header left out.
+    pub rows: usize,
+    pub fields: usize,
+    /// Size of the file in bytes, as stored: compressed, and before decoding.
+    pub file_size: u64,
+    /// One per field.
+    pub columns: Vec<ColumnInfo>,
+}
+
+impl Metadata {
+    /// Returns the metadata of `data`, counting its values.
+    fn of(headers: &StringRecord, data: &[StringRecord], fields: usize) -> Metadata {
+        let mut columns = Vec::new();
+        for record in data {
+            ColumnInfo::count(&mut columns, record);
+        }
+        Metadata::new(headers, data.len(), fields, columns)
+    }
+
+    /// Returns the metadata of `rows` rows with `fields` fields, whose values were counted
+    /// in `columns`, naming the columns after `headers`.
+    fn new(
+        headers: &StringRecord,
+        rows: usize,
+        fields: usize,
+        mut columns: Vec<ColumnInfo>,
+    ) -> Metadata {
+        columns.resize_with(fields, ColumnInfo::default);
+        for (i, column) in columns.iter_mut().enumerate() {
+            column.name = match headers.get(i) {
+                Some(name) => name.to_string(),
+                None => (i + 1).to_string(),
+            };
+        }
+        Metadata {
+            rows,
+            fields,
+            file_size: 0,
+            columns,
+        }
+    }
+}
+
+/// A header and rows as they are parsed, with the counts of the values of each field.
+type Rows = (StringRecord, Vec<StringRecord>, Vec<ColumnInfo>);
+
+/// A header and rows parsed from the text of a file.
+struct Parsed {
+    headers: StringRecord,
+    data: Vec<StringRecord>,
+    fields: usize,
+    /// Counts of the values of each field, in every row.
+    columns: Vec<ColumnInfo>,
+}
+
+pub struct CSVData {
+    pub headers: StringRecord,
+    pub data: Vec<StringRecord>,
+    pub records: usize,
+    pub fields: usize,
+    pub pages: Vec<Page>,
+    pub file_name: String,
+    pub creation_date: SystemTime,
+    pub last_modified_date: SystemTime,
+    pub file_size: u64,
+    /// Encoding the file was decoded from.
+    pub encoding: &'static Encoding,
+    /// Compression the file was read from, if any.
+    pub compression: Option<Compression>,
+    /// Type of each column, or empty when the data is untyped.
+    pub types: Vec<ColumnType>,
+    /// Locale the numbers of each column are written in, or empty when they are all plain
+    /// numbers like `1234.5` (see [`CSVData::set_number_locales`]).
+    pub number_locales: Vec<Option<Locale>>,
+    /// Audit journal recording every change, if any (see [`CSVData::set_journal`]).
+    journal: Option<Journal>,
+    /// Data as of each open transaction, the innermost last (see [`CSVData::begin`]).
+    checkpoints: Vec<Checkpoint>,
+    /// What reading the file found out about it.
+    metadata: Metadata,
+}
+
+/// Copy of the data taken when a transaction begins, restored if it is rolled back.
+struct Checkpoint {
+    headers: StringRecord,
+    data: Vec<StringRecord>,
+    records: usize,
+    fields: usize,
+    pages: Vec<Page>,
+    types: Vec<ColumnType>,
+    number_locales: Vec<Option<Locale>>,
+    /// Number of journal entries held back, and the journal's checksum, at the time.
+    journal: Option<(usize, String)>,
+}
+
+impl CSVData {
+    /// Returns data made of `headers` and `data`, not read from a file but to be written
+    /// to `file_name`, compressed as its extension says. The header, or the first row when
+    /// it is empty, sets the number of fields.
+    pub fn new(file_name: &str, headers: StringRecord, data: Vec<StringRecord>) -> CSVData {
+        let fields = match headers.is_empty() {
+            true => data.first().map_or(0, StringRecord::len),
+            false => headers.len(),
+        };
+        let now = SystemTime::now();
+        CSVData {
+            metadata: Metadata::of(&headers, &data, fields),
+            headers,
+            records: data.len(),
+            data,
+            fields,
+            pages: Vec::new(),
+            file_name: file_name.to_string(),
+            creation_date: now,
+            last_modified_date: now,
+            file_size: 0,
+            encoding: UTF_8,
+            compression: Compression::from_extension(file_name),
+            types: Vec::new(),
+            number_locales: Vec::new(),
+            journal: None,
+            checkpoints: Vec::new(),
+        }
+    }
+
+    /// Reads CSV data from a file.
+    /// Returns an error if the file cannot be read.
+    pub fn read_from_file(file_name: &str) -> Result<CSVData, EditorError> {
+        CSVData::read_with_options(file_name, ReadOptions::default())
+    }
+
+    /// Reads CSV data from a file using the given delimiter, header mode, ragged row policy
+    /// and encoding. Files compressed with gzip, zstd or xz, as told by their first bytes or
+    /// else their extension, are decompressed, and the text is transcoded to UTF-8 before parsing.
+    /// Returns an error if the file cannot be read, or if a row is ragged under
+    /// [`RaggedPolicy::Error`].
+    pub fn read_with_options(
+        file_name: &str,
+        options: ReadOptions,
+    ) -> Result<CSVData, EditorError> {
+        CSVData::read_with_progress(file_name, options, &mut |_| {})
+    }
+
+    /// Same as [`CSVData::read_with_options`], reporting progress in bytes of decoded text.
+    /// Files with an index are parsed in parallel chunks, and large ones without one get
+    /// it, as `options.index` says (see [`crate::index`]).
+    pub fn read_with_progress(
+        file_name: &str,
+        options: ReadOptions,
+        progress: &mut dyn Progress,
+    ) -> Result<CSVData, EditorError> {
+        let stamp = match options.index {
+            IndexMode::Never => None,
+            _ => Stamp::of(file_name).ok(),
+        };
+        let index = stamp.and_then(|_| Index::load(file_name, &options));
+        CSVData::read_text(file_name, options, progress, |text, progress| {
+            if let Some(index) = index.filter(|index| index.matches(text)) {
+                log::debug!(file = file_name, offsets = index.offsets.len(); "reading with index");
+                return check_ragged(parse_indexed(text, options, &index)?, options);
+            }
+            let ((headers, data, columns), offsets) = parse_csv(text, options, progress)?;
+            if let Some(stamp) = stamp.filter(|stamp| options.index.indexes(stamp.size)) {
+                let index = Index::new(stamp, &options, text, data.len(), offsets);
+                // The index only speeds up the next read.
+                if let Err(e) = index.save(file_name) {
+                    log::warn!("cannot write the index of {file_name}: {e}");
+                }
+            }
+            check_ragged((headers, data, columns), options)
+        })
+    }
+
+    /// Reads a fixed-width text file whose columns are laid out as `layout` says (see
+    /// [`fixed_width`]), like [`CSVData::read_with_progress`] reads CSV. The delimiter
+    /// and ragged row policy of `options` do not apply.
+    pub fn read_fixed_width(
+        file_name: &str,
+        layout: &fixed_width::Layout,
+        options: ReadOptions,
+        progress: &mut dyn Progress,
+    ) -> Result<CSVData, EditorError> {
+        CSVData::read_text(file_name, options, progress, |text, _| {
+            let (headers, data, fields) = layout.parse(text, options.has_headers);
+            let mut columns = Vec::new();
+            for record in &data {
+                ColumnInfo::count(&mut columns, record);
+            }
+            Ok(Parsed {
+                headers,
+                data,
+                fields,
+                columns,
+            })
+        })
+    }
+
+    /// Loads, decompresses and decodes a file, then turns its text into a header and rows
+    /// with `parse`.
+    fn read_text(
+        file_name: &str,
+        options: ReadOptions,
+        progress: &mut dyn Progress,
+        parse: impl FnOnce(&str, &mut dyn Progress) -> Result<Parsed, EditorError>,
+    ) -> Result<CSVData, EditorError> {
+        let file = std::fs::File::open(file_name)?;
+        let metadata = file.metadata()?;
+        let mapped;
+        let read;
+        let bytes: &[u8] = if options.backend.maps(metadata.len()) {
+            // SAFETY: the map is only read while parsing below, and the records copy what
+            // they keep. Another process truncating the file meanwhile would fault the read,
+            // as documented on `ReadBackend::Mmap`.
+            mapped = unsafe { Mmap::map(&file)? };
+            &mapped
+        } else {
+            read = std::fs::read(file_name)?;
+            &read
+        };
+        log::debug!(
+            file = file_name,
+            bytes = metadata.len(),
+            mapped = options.backend.maps(metadata.len());
+            "loaded file"
+        );
+        let compression =
+            Compression::detect(bytes).or_else(|| Compression::from_extension(file_name));
+        let decompressed;
+        let bytes = match compression {
+            Some(compression) => {
+                decompressed = compression.decompress(bytes)?;
+                &decompressed
+            }
+            None => bytes,
+        };
+        let (text, encoding) = encoding::decode(bytes, options.encoding)?;
+        progress.report(ProgressEvent::Started {
+            stage: Stage::Read,
+            total: text.len() as u64,
+        });
+        let Parsed {
+            headers,
+            data,
+            fields,
+            columns,
+        } = parse(&text, progress)?;
+        let records = data.len();
+        let found = Metadata {
+            file_size: metadata.len(),
+            ..Metadata::new(&headers, records, fields, columns)
+        };
+        log::info!(
+            file = file_name,
+            rows = records,
+            fields,
+            encoding = encoding.name();
+            "read file"
+        );
+        progress.report(ProgressEvent::Finished {
+            stage: Stage::Read,
+            rows: records,
+        });
+        Ok(CSVData {
+            headers,
+            data,
+            records,
+            fields,
+            pages: Vec::new(),
+            file_name: file_name.to_string(),
+            creation_date: metadata.created()?,
+            last_modified_date: metadata.modified()?,
+            file_size: metadata.len(),
+            encoding,
+            compression,
+            types: Vec::new(),
+            number_locales: Vec::new(),
+            journal: None,
+            checkpoints: Vec::new(),
+            metadata: found,
+        })
+    }
+
+    /// Returns what reading the file found out about it, counted in the same pass as
+    /// parsing it: its rows, fields, size, and the values of each column. Edits made since
+    /// are not reflected, as with [`CSVData::file_size`]; data derived from other data,
+    /// such as by [`CSVData::group_by`], is counted when it is made.
+    pub fn metadata(&self) -> &Metadata {
+        &self.metadata
+    }
+
+    /// Declares column types from a spec such as `age:int,price:float`, or `auto`
+    /// to infer them (see [`schema::parse_types`]).
+    pub fn set_types(&mut self, spec: &str) -> Result<(), EditorError> {
+        self.types = schema::parse_types(
+            spec,
+            &self.headers,
+            &self.data,
+            self.fields,
+            &self.number_locales,
+        )?;
+        log::debug!(types:? = self.types; "set column types");
+        Ok(())
+    }
+
+    /// Declares the locales numbers are written in from a spec such as `price:de,qty:en`,
+    /// or `de` for every column (see [`schema::parse_locales`]). Set them before the types,
+    /// which are checked and inferred with them.
+    pub fn set_number_locales(&mut self, spec: &str) -> Result<(), EditorError> {
+        self.number_locales = schema::parse_locales(spec, &self.headers, self.fields)?;
+        log::debug!(locales:? = self.number_locales; "set number locales");
+        Ok(())
+    }
+
+    /// Returns the locale the numbers of `field` are written in, if any.
+    pub fn number_locale(&self, field: usize) -> Option<Locale> {
+        self.number_locales.get(field).copied().flatten()
+    }
+
+    /// Reads `value`, from column `field`, as a number written in the column's locale.
+    pub fn parse_number(&self, field: usize, value: &str) -> Option<f64> {
+        match self.number_locale(field) {
+            Some(locale) => locale.parse_number(value),
+            None => value.trim().parse().ok(),
+        }
+    }
+
+    /// Checks that `value` is valid for the declared type of `field`.
+    /// Untyped data accepts anything.
+    pub fn check_value(&self, field: usize, value: &str) -> Result<(), EditorError> {
+        match self.types.get(field) {
+            Some(expected) if !expected.accepts_in(value, self.number_locale(field)) => {
+                Err(EditorError::TypeMismatch {
+                    field,
+                    value: value.to_string(),
+                    expected: *expected,
+                })
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Lists the rows whose field count differs from `fields`.
+    pub fn ragged_rows(&self) -> Vec<RaggedRow> {
+        find_ragged(&self.data, self.fields)
+    }
+
+    /// Creates pagination pages for the CSV data.
+    /// Each page contains a range of records defined by `records_per_page`.
+    pub fn create_pages(&mut self, records_per_page: usize) {
+        let records_per_page = if records_per_page == 0 {
+            10
+        } else {
+            records_per_page
+        };
+        self.pages.clear();
+        let mut start = 0;
+        while start < self.records {
+            let end = std::cmp::min(start + records_per_page, self.records);
+            self.pages.push(Page { start, end });
+            start = end;
+        }
+        log::debug!(pages = self.pages.len(), records_per_page; "created pages");
+    }
+
+    /// Describes the page at `index` (0-based) as `page 3/17, rows 21-30`.
+    /// Returns `None` if there is no such page.
+    pub fn page_label(&self, index: usize) -> Option<String> {
+        let page = self.pages.get(index)?;
+        Some(format!(
+            "page {}/{}, rows {}-{}",
+            index + 1,
+            self.pages.len(),
+            page.start + 1,
+            page.end
+        ))
+    }
+
+    /// Returns the index of the page holding `row` (0-based), if any.
+    pub fn page_of(&self, row: usize) -> Option<usize> {
+        self.pages
+            .iter()
+            .position(|page| (page.start..page.end).contains(&row))
+    }
+
+    /// Displays the CSV data to the terminal.
+    pub fn display(&self) {
+        for record in &self.data {
+            println!("{record:#?}");
+        }
+    }
+
+    /// Paginates the CSV data and writes it to the specified writer.
+    /// Returns an error if the range is outside the data or writing fails.
+    pub fn paginate<W: std::io::Write>(
+        &self,
+        start: usize,
+        end: usize,
+        writer: &mut W,
+    ) -> Result<(), EditorError> {
+        for record in self.rows(start, end)? {
+            writeln!(writer, "{record:#?}")?;
+        }
+        Ok(())
+    }
+
+    /// Renders records `start..end` to the writer using the configured output format.
+    /// Returns an error if the range is outside the data or writing fails.
+    pub fn render<W: std::io::Write>(
+        &self,
+        start: usize,
+        end: usize,
+        settings: &Settings,
+        writer: &mut W,
+    ) -> Result<(), EditorError> {
+        self.render_scrolled(start, end, 0, settings, writer)
+    }
+
+    /// Returns the number of column pages: windows of `settings.cols_per_page` columns
+    /// following the `settings.freeze_cols` frozen ones, which are shown on every page.
+    pub fn col_page_count(&self, settings: &Settings) -> usize {
+        let frozen = settings.freeze_cols.min(self.fields);
+        match settings.cols_per_page {
+            Some(columns) => (self.fields - frozen).div_ceil(columns).max(1),
+            None => 1,
+        }
+    }
+
+    /// Describes the columns shown when scrolled `scroll` columns to the right,
+    /// as `columns 1-2, 11-20 of 340`. Returns `None` when every column is shown.
+    pub fn column_label(&self, scroll: usize, settings: &Settings) -> Option<String> {
+        let frozen = settings.freeze_cols.min(self.fields);
+        let window = column_window(frozen, scroll, settings, self.fields);
+        if window.start == frozen && window.end == self.fields {
+            return None;
+        }
+        Some(describe_window(frozen, window, self.fields))
+    }
+
+    /// Returns the width of the widest value of each of `columns`, header included,
+    /// over all rows, so that every page lines up the same way.
+    fn column_widths(&self, columns: &[usize]) -> Vec<usize> {
+        let mut widths = vec![0; columns.len()];
+        for record in std::iter::once(&self.headers).chain(&self.data) {
+            for (width, &i) in widths.iter_mut().zip(columns) {
+                *width = (*width).max(record.get(i).map_or(0, |value| value.chars().count()));
+            }
+        }
+        widths
+    }
+
+    /// Same as [`CSVData::render`], leaving out the `scroll` columns that follow the
+    /// `settings.freeze_cols` frozen ones, as when the view is scrolled to the right,
+    /// and showing at most `settings.cols_per_page` columns after them.
+    /// Table output then numbers the columns shown and lines up every page the same way.
+    pub fn render_scrolled<W: std::io::Write>(
+        &self,
+        start: usize,
+        end: usize,
+        scroll: usize,
+        settings: &Settings,
+        writer: &mut W,
+    ) -> Result<(), EditorError> {
+        let rows = self.display_rows(start, end, settings)?;
+        let rows = rows.as_ref();
+        let frozen = settings.freeze_cols.min(self.fields);
+        let width = rows
+            .iter()
+            .map(StringRecord::len)
+            .fold(self.fields, usize::max);
+        let window = column_window(frozen, scroll, settings, width);
+        if window.start == frozen && window.end == width {
+            let layout = Layout {
+                frozen,
+                first_row: settings.row_numbers.then_some(start + 1),
+                ..Layout::default()
+            };
+            render::write_layout(&self.headers, rows, &layout, settings, writer)?;
+            return Ok(());
+        }
+        let columns: Vec<usize> = (0..frozen).chain(window).collect();
+        self.render_columns(start, end, &columns, frozen, settings, writer)
+    }
+
+    /// Same as [`CSVData::render`], showing only `columns` (0-based), in that order.
+    /// Table output numbers them, and marks off the first `frozen` from the others.
+    pub fn render_columns<W: std::io::Write>(
+        &self,
+        start: usize,
+        end: usize,
+        columns: &[usize],
+        frozen: usize,
+        settings: &Settings,
+        writer: &mut W,
+    ) -> Result<(), EditorError> {
+        let rows = self.display_rows(start, end, settings)?;
+        let project = |record: &StringRecord| -> StringRecord {
+            columns
+                .iter()
+                .map(|&i| record.get(i).unwrap_or(""))
+                .collect()
+        };
+        let headers = if self.headers.is_empty() {
+            StringRecord::new()
+        } else {
+            project(&self.headers)
+        };
+        let rows: Vec<StringRecord> = rows.iter().map(project).collect();
+        let widths = self.column_widths(columns);
+        let layout = Layout {
+            frozen,
+            numbers: Some(columns),
+            widths: Some(&widths),
+            first_row: settings.row_numbers.then_some(start + 1),
+        };
+        render::write_layout(&headers, &rows, &layout, settings, writer)?;
+        Ok(())
+    }
+
+    /// Returns records `start..end` as they are displayed: typed values formatted with the
+    /// locale and decimals of `settings`, and missing values shown as `settings.na_value`.
+    /// Returns an error if the range is outside the data.
+    pub fn display_rows(
+        &self,
+        start: usize,
+        end: usize,
+        settings: &Settings,
+    ) -> Result<Cow<'_, [StringRecord]>, EditorError> {
+        let rows = self.rows(start, end)?;
+        let mut formatted = None;
+        if !self.types.is_empty() && (settings.locale.is_some() || settings.decimals.is_some()) {
+            formatted = Some(self.format_rows(rows, settings));
+        }
+        if let Some(na_value) = &settings.na_value {
+            let marked = formatted
+                .as_deref()
+                .unwrap_or(rows)
+                .iter()
+                .map(|record| {
+                    record
+                        .iter()
+                        .map(|value| if is_na(value) { na_value } else { value })
+                        .collect()
+                })
+                .collect();
+            formatted = Some(marked);
+        }
+        Ok(match formatted {
+            Some(formatted) => Cow::Owned(formatted),
+            None => Cow::Borrowed(rows),
+        })
+    }
+
+    /// Formats the typed values of `rows` for display with the locale and decimals of `settings`.
+    fn format_rows(&self, rows: &[StringRecord], settings: &Settings) -> Vec<StringRecord> {
+        rows.iter()
+            .map(|record| {
+                record
+                    .iter()
+                    .zip(&self.types)
+                    .enumerate()
+                    .map(|(field, (value, column_type))| {
+                        // Numbers are formatted from their plain form.
+                        let plain = match (column_type, self.number_locale(field)) {
+                            (ColumnType::Integer | ColumnType::Float, Some(locale)) => {
+                                locale.plain_number(value)
+                            }
+                            _ => None,
+                        };
+                        schema::format_value(
+                            plain.as_deref().unwrap_or(value),
+                            *column_type,
+                            settings.locale,
+                            settings.decimals,
+                        )
+                    })
+                    .collect()
+            })
+            .collect()
+    }
+
+    /// Returns records `start..end`, or an error if the range is outside the data.
+    pub fn rows(&self, start: usize, end: usize) -> Result<&[StringRecord], EditorError> {
+        let rows = self.data.len();
+        if end > rows {
+            return Err(EditorError::RowOutOfBounds { row: end - 1, rows });
+        }
+        Ok(&self.data[start.min(end)..end])
+    }
+
+    /// Returns the first `n` records, or all of them if there are fewer.
+    pub fn head(&self, n: usize) -> &[StringRecord] {
+        &self.data[..n.min(self.data.len())]
+    }
+
+    /// Returns the last `n` records, or all of them if there are fewer.
+    pub fn tail(&self, n: usize) -> &[StringRecord] {
+        &self.data[self.data.len().saturating_sub(n)..]
+    }
+
+    /// Returns views of the rows, in order, to read their values by column name or as
+    /// their column's type (see [`Row`]).
+    pub fn iter_rows(&self) -> impl ExactSizeIterator<Item = Row<'_>> + '_ {
+        (0..self.data.len()).map(|index| Row::new(self, index))
+    }
+
+    /// Maps every row into `T` with serde, as [`Row::deserialize`] does.
+    /// Returns an error for the first row that does not fit `T`.
+    pub fn deserialize_into<T: DeserializeOwned>(&self) -> Result<Vec<T>, EditorError> {
+        self.iter_rows().map(|row| row.deserialize()).collect()
+    }
+
+    /// Returns data made of `rows` serialized with serde, to be written to `file_name` as
+    /// [`CSVData::new`] does. The header is made of the field names of the rows, if they
+    /// are structs or maps; other rows, such as tuples, give data without a header.
+    /// Returns an error if a row cannot be written as a record or has another number of
+    /// fields than the first one.
+    pub fn serialize_from<T: Serialize>(
+        file_name: &str,
+        rows: impl IntoIterator<Item = T>,
+    ) -> Result<CSVData, EditorError> {
+        let mut rows = rows.into_iter().peekable();
+        // Serializing the first row with its header tells whether it has field names.
+        let mut headers = StringRecord::new();
+        if let Some(first) = rows.peek() {
+            let mut writer = Writer::from_writer(Vec::new());
+            writer.serialize(first)?;
+            let bytes = writer.into_inner().map_err(|e| e.into_error())?;
+            let mut records = ReaderBuilder::new()
+                .has_headers(false)
+                .flexible(true)
+                .from_reader(bytes.as_slice())
+                .into_records()
+                .collect::<Result<Vec<_>, _>>()?;
+            if records.len() > 1 {
+                headers = records.swap_remove(0);
+            }
+        }
+        let mut writer = WriterBuilder::new()
+            .has_headers(false)
+            .from_writer(Vec::new());
+        for row in rows {
+            writer.serialize(row)?;
+        }
+        let bytes = writer.into_inner().map_err(|e| e.into_error())?;
+        let data = ReaderBuilder::new()
+            .has_headers(false)
+            .from_reader(bytes.as_slice())
+            .into_records()
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(CSVData::new(file_name, headers, data))
+    }
+
+    /// Returns the line of the file the row at `row` starts on, counting lines taken by
+    /// quoted values with line breaks. Rows added after reading have no line.
+    pub fn line(&self, row: usize) -> Option<u64> {
+        Some(self.data.get(row)?.position()?.line())
+    }
+
+    /// Describes the row at `row` as `row 4 (line 5)`, or `row 4` if it has no line.
+    pub fn describe_row(&self, row: usize) -> String {
+        match self.line(row) {
+            Some(line) => format!("row {} (line {line})", row + 1),
+            None => format!("row {}", row + 1),
+        }
+    }
+
+    /// Returns the index of the first row from `from` on with a cell containing `text`,
+    /// ignoring case.
+    pub fn find(&self, text: &str, from: usize) -> Option<usize> {
+        let text = text.to_lowercase();
+        self.data
+            .iter()
+            .enumerate()
+            .skip(from)
+            .find(|(_, record)| {
+                record
+                    .iter()
+                    .any(|value| value.to_lowercase().contains(&text))
+            })
+            .map(|(row, _)| row)
+    }
+
+    /// Picks `n` records at random, keeping them in file order.
+    /// The same seed always yields the same sample.
+    pub fn sample(&self, n: usize, seed: u64) -> Vec<StringRecord> {
+        let mut rng = Xoshiro256PlusPlus::seed_from_u64(seed);
+        let amount = n.min(self.data.len());
+        let mut indices = rand::seq::index::sample(&mut rng, self.data.len(), amount).into_vec();
+        indices.sort_unstable();
+        indices.into_iter().map(|i| self.data[i].clone()).collect()
+    }
+
+    /// Deletes a row at the specified index; the rows after it move up.
+    /// Pages must be created again afterwards.
+    /// Returns an error if the index is out of bounds.
+    pub fn delete_row(&mut self, index: usize) -> Result<(), EditorError> {
+        let rows = self.records.min(self.data.len());
+        if index < rows {
+            let old = self.data.remove(index);
+            self.records -= 1;
+            log::info!(row = index; "deleted row");
+            self.record(Change::DeleteRow {
+                row: index,
+                old: values(&old),
+            })
+        } else {
+            Err(EditorError::RowOutOfBounds { row: index, rows })
+        }
+    }
+
+    /// Appends copies of `rows` to the end of the data.
+    /// Returns an error if a row's field count differs from the data's; nothing is appended then.
+    pub fn append_rows(&mut self, rows: &[StringRecord]) -> Result<(), EditorError> {
+        if let Some((i, row)) = rows
+            .iter()
+            .enumerate()
+            .find(|(_, row)| row.len() != self.fields)
+        {
+            return Err(EditorError::Schema {
+                row: self.data.len() + i,
+                message: format!("has {} field(s), expected {}", row.len(), self.fields),
+            });
+        }
+        self.data.extend_from_slice(rows);
+        self.records += rows.len();
+        log::info!(rows = rows.len(); "appended rows");
+        self.record(Change::AppendRows {
+            rows: rows.iter().map(values).collect(),
+        })
+    }
+
+    /// Appends the rows of `other`, matching its columns with `mode`, and returns how its
+    /// header differs. Under [`HeaderMatch::Union`] the columns only `other` has are added
+    /// after the last column.
+    /// Returns an error, without changing anything, if the headers differ under
+    /// [`HeaderMatch::Strict`].
+    pub fn concat(
+        &mut self,
+        other: &CSVData,
+        mode: HeaderMatch,
+    ) -> Result<HeaderDiff, EditorError> {
+        let ours: Vec<String> = (0..self.fields).map(|i| self.header_name(i)).collect();
+        let theirs: Vec<String> = (0..other.fields).map(|i| other.header_name(i)).collect();
+        // Repeated names are matched in order of appearance.
+        let mut taken = vec![false; ours.len()];
+        let mapping: Vec<Option<usize>> = theirs
+            .iter()
+            .map(|name| {
+                let i = (0..ours.len()).find(|&i| !taken[i] && ours[i] == *name)?;
+                taken[i] = true;
+                Some(i)
+            })
+            .collect();
+        let shared: Vec<usize> = mapping.iter().flatten().copied().collect();
+        let diff = HeaderDiff {
+            missing: (0..ours.len())
+                .filter(|&i| !taken[i])
+                .map(|i| ours[i].clone())
+                .collect(),
+            extra: (0..theirs.len())
+                .filter(|&j| mapping[j].is_none())
+                .map(|j| theirs[j].clone())
+                .collect(),
+            reordered: shared.windows(2).any(|pair| pair[0] > pair[1]),
+        };
+        if mode == HeaderMatch::Strict && !diff.is_empty() {
+            return Err(EditorError::HeaderMismatch {
+                file: other.file_name.clone(),
+                message: diff.to_string(),
+            });
+        }
+
+        let extra = diff.extra.len();
+        if extra > 0 {
+            self.reshape(&[], self.fields, &diff.extra, |_| {
+                vec![String::new(); extra]
+            })?;
+        }
+        let mut next = self.fields - extra;
+        let mapping: Vec<usize> = mapping
+            .into_iter()
+            .map(|i| {
+                i.unwrap_or_else(|| {
+                    next += 1;
+                    next - 1
+                })
+            })
+            .collect();
+        let mut rows = Vec::with_capacity(other.data.len());
+        for record in &other.data {
+            let mut values = vec![""; self.fields];
+            for (value, &i) in record.iter().zip(&mapping) {
+                values[i] = value;
+            }
+            rows.push(StringRecord::from(values));
+        }
+        self.append_rows(&rows)?;
+        log::info!(
+            file = other.file_name.as_str(),
+            rows = other.data.len(),
+            added_columns = extra;
+            "concatenated file"
+        );
+        Ok(diff)
+    }
+
+    /// Modifies a field at the specified row and field index.
+    /// Returns an error if the row or field index is out of bounds,
+    /// if the row is shorter than the data's field count,
+    /// or if the value does not match the column's declared type.
+    pub fn modify_field(
+        &mut self,
+        row: usize,
+        field: usize,
+        value: &str,
+    ) -> Result<(), EditorError> {
+        self.check_value(field, value)?;
+        self.modify_field_unchecked(row, field, value)
+    }
+
+    /// Same as [`CSVData::modify_field`], without the type check.
+    pub fn modify_field_unchecked(
+        &mut self,
+        row: usize,
+        field: usize,
+        value: &str,
+    ) -> Result<(), EditorError> {
+        let rows = self.records.min(self.data.len());
+        if row >= rows {
+            return Err(EditorError::RowOutOfBounds { row, rows });
+        }
+        if field >= self.fields {
+            return Err(EditorError::FieldOutOfBounds {
+                field,
+                fields: self.fields,
+            });
+        }
+        let mut new_row = self.data[row]
+            .iter()
+            .map(|s| s.to_string())
+            .collect::<Vec<String>>();
+        if field >= new_row.len() {
+            return Err(EditorError::Schema {
+                row,
+                message: format!("has {} field(s), expected {}", new_row.len(), self.fields),
+            });
+        }
+        log::info!(row, field, old = new_row[field].as_str(), new = value; "modified field");
+        let old = std::mem::replace(&mut new_row[field], value.to_string());
+        replace_values(&mut self.data[row], new_row);
+        self.record(Change::Set {
+            row,
+            column: field,
+            old,
+            new: value.to_string(),
+        })
+    }
+
+    /// Copies the cells in `range`.
+    /// Returns an error if the range goes past the last row or column.
+    pub fn copy_range(&self, range: &CellRange) -> Result<Clip, EditorError> {
+        self.check_range(range)?;
+        let rows = self.data[*range.rows.start()..=*range.rows.end()]
+            .iter()
+            .map(|record| columns::project(record, &range.columns))
+            .collect();
+        Ok(Clip { rows })
+    }
+
+    /// Copies the cells in `range`, then removes them: whole rows are deleted,
+    /// other cells are emptied.
+    /// Returns an error if the range goes past the last row or column.
+    pub fn cut_range(&mut self, range: &CellRange) -> Result<Clip, EditorError> {
+        let clip = self.copy_range(range)?;
+        if range.whole_rows(self.fields) {
+            self.data.drain(range.rows.clone());
+            self.records = self.records.saturating_sub(clip.rows.len());
+        } else {
+            for record in &mut self.data[range.rows.clone()] {
+                let mut fields: Vec<String> = record.iter().map(str::to_string).collect();
+                for &column in &range.columns {
+                    if let Some(field) = fields.get_mut(column) {
+                        field.clear();
+                    }
+                }
+                replace_values(record, fields);
+            }
+        }
+        log::info!(
+            rows = clip.rows.len(),
+            columns = range.columns.len();
+            "cut range"
+        );
+        self.record(Change::Cut {
+            first: *range.rows.start(),
+            last: *range.rows.end(),
+            columns: range.columns.clone(),
+            old: clip.rows.iter().map(values).collect(),
+        })?;
+        Ok(clip)
+    }
+
+    /// Pastes `clip` with its top-left cell at `row`, `field` (both 0-based), overwriting cells.
+    /// Rows past the end of the data are added. Returns an error, without changing anything,
+    /// if `row` is past the end, the clip does not fit in the columns, or a value does not
+    /// match its column's type.
+    pub fn paste(&mut self, row: usize, field: usize, clip: &Clip) -> Result<(), EditorError> {
+        let rows = self.data.len();
+        if row > rows {
+            return Err(EditorError::RowOutOfBounds { row, rows });
+        }
+        for values in &clip.rows {
+            if field + values.len() > self.fields {
+                return Err(EditorError::FieldOutOfBounds {
+                    field: field + values.len() - 1,
+                    fields: self.fields,
+                });
+            }
+            for (i, value) in values.iter().enumerate() {
+                self.check_value(field + i, value)?;
+            }
+        }
+        for (i, values) in clip.rows.iter().enumerate() {
+            let mut fields = match self.data.get(row + i) {
+                Some(record) => record.iter().map(str::to_string).collect(),
+                None => vec![String::new(); self.fields],
+            };
+            fields.resize(fields.len().max(field + values.len()), String::new());
+            for (j, value) in values.iter().enumerate() {
+                fields[field + j] = value.to_string();
+            }
+            if row + i < self.data.len() {
+                replace_values(&mut self.data[row + i], fields);
+            } else {
+                self.data.push(StringRecord::from(fields));
+                self.records += 1;
+            }
+        }
+        log::info!(row, field, rows = clip.rows.len(); "pasted range");
+        self.record(Change::Paste {
+            row,
+            column: field,
+            values: clip.rows.iter().map(values).collect(),
+        })
+    }
+
+    fn check_range(&self, range: &CellRange) -> Result<(), EditorError> {
+        let rows = self.data.len();
+        if *range.rows.end() >= rows {
+            return Err(EditorError::RowOutOfBounds {
+                row: *range.rows.end(),
+                rows,
+            });
+        }
+        match range.columns.iter().find(|&&column| column >= self.fields) {
+            Some(&field) => Err(EditorError::FieldOutOfBounds {
+                field,
+                fields: self.fields,
+            }),
+            None => Ok(()),
+        }
+    }
+
+    /// Sorts the rows by `field`. The sort is stable.
+    /// Numeric sorting, also used for integer and float columns, compares values as numbers
+    /// and puts values that are not numbers last.
+    /// Returns an error if the field index is out of bounds.
+    pub fn sort_by_column(
+        &mut self,
+        field: usize,
+        descending: bool,
+        numeric: bool,
+        progress: &mut dyn Progress,
+    ) -> Result<(), EditorError> {
+        if field >= self.fields {
+            return Err(EditorError::FieldOutOfBounds {
+                field,
+                fields: self.fields,
+            });
+        }
+        let numeric = numeric
+            || matches!(
+                self.types.get(field),
+                Some(ColumnType::Integer | ColumnType::Float)
+            );
+        let rows = self.data.len();
+        progress.report(ProgressEvent::Started {
+            stage: Stage::Sort,
+            total: rows as u64,
+        });
+        let mut keyed: Vec<(SortKey, StringRecord)> = std::mem::take(&mut self.data)
+            .into_par_iter()
+            .map(|record| {
+                let value = record.get(field).unwrap_or("");
+                let key = if numeric {
+                    SortKey::Number(self.parse_number(field, value).filter(|n| !n.is_nan()))
+                } else {
+                    SortKey::Text(value.to_string())
+                };
+                (key, record)
+            })
+            .collect();
+        keyed.par_sort_by(|(a, _), (b, _)| a.compare(b, descending));
+        self.data = keyed.into_par_iter().map(|(_, record)| record).collect();
+        log::info!(field, descending, numeric, rows; "sorted rows");
+        progress.report(ProgressEvent::Finished {
+            stage: Stage::Sort,
+            rows,
+        });
+        self.record(Change::Sort {
+            column: field,
+            descending,
+            numeric,
+        })
+    }
+
+    /// Returns a copy of the data limited to the columns in `spec` (see [`columns::parse_columns`]).
+    /// Returns an error if the spec names unknown columns.
+    pub fn select_columns(&self, spec: &str) -> Result<CSVData, EditorError> {
+        let columns = columns::parse_columns(spec, &self.headers, self.fields)?;
+        log::debug!(columns:? = columns; "selected columns");
+        let headers = if self.headers.is_empty() {
+            StringRecord::new()
+        } else {
+            columns::project(&self.headers, &columns)
+        };
+        Ok(CSVData {
+            headers,
+            data: self
+                .data
+                .iter()
+                .map(|record| columns::project(record, &columns))
+                .collect(),
+            records: self.records,
+            fields: columns.len(),
+            pages: Vec::new(),
+            file_name: self.file_name.clone(),
+            creation_date: self.creation_date,
+            last_modified_date: self.last_modified_date,
+            file_size: self.file_size,
+            encoding: self.encoding,
+            compression: self.compression,
+            types: columns
+                .iter()
+                .map(|&i| self.types.get(i).copied().unwrap_or_default())
+                .collect(),
+            number_locales: match self.number_locales.is_empty() {
+                true => Vec::new(),
+                false => columns.iter().map(|&i| self.number_locale(i)).collect(),
+            },
+            journal: None,
+            checkpoints: Vec::new(),
+            metadata: Metadata {
+                fields: columns.len(),
+                columns: columns
+                    .iter()
+                    .map(|&i| self.metadata.columns.get(i).cloned().unwrap_or_default())
+                    .collect(),
+                ..self.metadata.clone()
+            },
+        })
+    }
+
+    /// Returns each distinct value of column `field` with the number of rows holding it, the
+    /// most frequent first. Values as frequent as each other keep their order of first appearance.
+    /// Returns an error if the column index is out of bounds.
+    pub fn value_counts(&self, field: usize) -> Result<Vec<(String, usize)>, EditorError> {
+        if field >= self.fields {
+            return Err(EditorError::FieldOutOfBounds {
+                field,
+                fields: self.fields,
+            });
+        }
+        let mut index: HashMap<&str, usize> = HashMap::new();
+        let mut counts: Vec<(String, usize)> = Vec::new();
+        for record in &self.data {
+            let value = record.get(field).unwrap_or("");
+            let count = *index.entry(value).or_insert_with(|| {
+                counts.push((value.to_string(), 0));
+                counts.len() - 1
+            });
+            counts[count].1 += 1;
+        }
+        // A stable sort keeps ties in order of first appearance.
+        counts.sort_by(|(_, a), (_, b)| b.cmp(a));
+        Ok(counts)
+    }
+
+    /// Returns one row per distinct combination of the `keys` columns, in order of first
+    /// appearance, holding the key values followed by the `aggregations` of the group's rows.
+    /// Columns of data without a header are named by their 1-based number.
+    /// Returns an error if a column index is out of bounds, or if `sum` or `avg` meet a value
+    /// that is not a number.
+    pub fn group_by(
+        &self,
+        keys: &[usize],
+        aggregations: &[Aggregation],
+    ) -> Result<CSVData, EditorError> {
+        let used = aggregations
+            .iter()
+            .filter_map(|aggregation| aggregation.column);
+        if let Some(field) = keys.iter().copied().chain(used).find(|&i| i >= self.fields) {
+            return Err(EditorError::FieldOutOfBounds {
+                field,
+                fields: self.fields,
+            });
+        }
+        // Each chunk of rows is summarized on its own, then the chunks are merged in order,
+        // so that groups keep their order of first appearance.
+        let summarize = |records: &[StringRecord]| {
+            let mut index: HashMap<Vec<String>, usize> = HashMap::new();
+            let mut groups: Vec<(StringRecord, Vec<Accumulator>)> = Vec::new();
+            for record in records {
+                let key = columns::project(record, keys);
+                let values = key.iter().map(str::to_string).collect();
+                let group = *index.entry(values).or_insert_with(|| {
+                    let accumulators = aggregations.iter().map(|aggregation| {
+                        Accumulator::new(aggregation.column.and_then(|i| self.number_locale(i)))
+                    });
+                    groups.push((key, accumulators.collect()));
+                    groups.len() - 1
+                });
+                for (aggregation, accumulator) in aggregations.iter().zip(&mut groups[group].1) {
+                    accumulator.add(aggregation, record)?;
+                }
+            }
+            Ok::<_, EditorError>(groups)
+        };
+        let chunks: Vec<_> = self.data.par_chunks(GROUP_CHUNK).map(summarize).collect();
+        let mut index: HashMap<Vec<String>, usize> = HashMap::new();
+        let mut groups: Vec<(StringRecord, Vec<Accumulator>)> = Vec::new();
+        for chunk in chunks {
+            for (key, accumulators) in chunk? {
+                let values: Vec<String> = key.iter().map(str::to_string).collect();
+                match index.get(&values) {
+                    Some(&group) => {
+                        for (merged, accumulator) in groups[group].1.iter_mut().zip(accumulators) {
+                            merged.merge(accumulator);
+                        }
+                    }
+                    None => {
+                        index.insert(values, groups.len());
+                        groups.push((key, accumulators));
+                    }
+                }
+            }
+        }
+
+        let headers = keys
+            .iter()
+            .map(|&i| self.header_name(i))
+            .chain(
+                aggregations
+                    .iter()
+                    .map(|aggregation| aggregation.name.clone()),
+            )
+            .collect();
+        let data: Vec<StringRecord> = groups
+            .into_iter()
+            .map(|(key, accumulators)| {
+                let summaries = aggregations
+                    .iter()
+                    .zip(accumulators)
+                    .map(|(aggregation, accumulator)| accumulator.finish(aggregation.func));
+                key.iter().map(str::to_string).chain(summaries).collect()
+            })
+            .collect();
+        let types = if self.types.is_empty() {
+            Vec::new()
+        } else {
+            let column_type = |i: usize| self.types.get(i).copied().unwrap_or_default();
+            keys.iter()
+                .map(|&i| column_type(i))
+                .chain(aggregations.iter().map(|aggregation| {
+                    let column = aggregation.column.map(column_type).unwrap_or_default();
+                    aggregation.func.output_type(column)
+                }))
+                .collect()
+        };
+        log::info!(keys = keys.len(), groups = data.len(); "grouped rows");
+        Ok(self.derive(headers, data, types))
+    }
+
+    /// Turns rows into columns: returns one row per distinct combination of the `index`
+    /// columns, with a column for each distinct value of `names` holding the matching
+    /// value of `values`. Cells without a matching row are empty.
+    /// Several rows with the same index and name are summarized with `agg`; without it
+    /// they are an error.
+    /// Returns an error if a column index is out of bounds.
+    pub fn pivot(
+        &self,
+        index: &[usize],
+        names: usize,
+        values: usize,
+        agg: Option<AggFn>,
+    ) -> Result<CSVData, EditorError> {
+        if let Some(field) = index
+            .iter()
+            .copied()
+            .chain([names, values])
+            .find(|&i| i >= self.fields)
+        {
+            return Err(EditorError::FieldOutOfBounds {
+                field,
+                fields: self.fields,
+            });
+        }
+        let aggregation = agg.map(|func| Aggregation {
+            func,
+            column: Some(values),
+            name: func.to_string(),
+        });
+        let mut row_index: HashMap<Vec<String>, usize> = HashMap::new();
+        let mut column_index: HashMap<String, usize> = HashMap::new();
+        let mut keys: Vec<StringRecord> = Vec::new();
+        let mut columns: Vec<String> = Vec::new();
+        let mut cells: HashMap<(usize, usize), Accumulator> = HashMap::new();
+        let mut first: HashMap<(usize, usize), String> = HashMap::new();
+        for (row, record) in self.data.iter().enumerate() {
+            let key = columns::project(record, index);
+            let values_of_key = key.iter().map(str::to_string).collect();
+            let i = *row_index.entry(values_of_key).or_insert_with(|| {
+                keys.push(key);
+                keys.len() - 1
+            });
+            let name = record.get(names).unwrap_or("").to_string();
+            let j = *column_index.entry(name.clone()).or_insert_with(|| {
+                columns.push(name.clone());
+                columns.len() - 1
+            });
+            match &aggregation {
+                Some(aggregation) => cells
+                    .entry((i, j))
+                    .or_insert_with(|| Accumulator::new(self.number_locale(values)))
+                    .add(aggregation, record)?,
+                None => {
+                    let value = record.get(values).unwrap_or("").to_string();
+                    if first.insert((i, j), value).is_some() {
+                        return Err(EditorError::Schema {
+                            row,
+                            message: format!(
+                                "another row has the same key and '{name}'; \
+                                 an aggregation is needed to combine them"
+                            ),
+                        });
+                    }
+                }
+            }
+        }
+
+        let headers = index
+            .iter()
+            .map(|&i| self.header_name(i))
+            .chain(columns.iter().cloned())
+            .collect();
+        let data: Vec<StringRecord> = keys
+            .into_iter()
+            .enumerate()
+            .map(|(i, key)| {
+                let pivoted = (0..columns.len()).map(|j| match &aggregation {
+                    Some(aggregation) => cells
+                        .remove(&(i, j))
+                        .map(|cell| cell.finish(aggregation.func))
+                        .unwrap_or_default(),
+                    None => first.remove(&(i, j)).unwrap_or_default(),
+                });
+                key.iter().map(str::to_string).chain(pivoted).collect()
+            })
+            .collect();
+        let types = if self.types.is_empty() {
+            Vec::new()
+        } else {
+            let column_type = |i: usize| self.types.get(i).copied().unwrap_or_default();
+            let value_type = match agg {
+                Some(func) => func.output_type(column_type(values)),
+                None => column_type(values),
+            };
+            index
+                .iter()
+                .map(|&i| column_type(i))
+                .chain(std::iter::repeat_n(value_type, columns.len()))
+                .collect()
+        };
+        log::info!(rows = data.len(), columns = columns.len(); "pivoted rows");
+        Ok(self.derive(headers, data, types))
+    }
+
+    /// Turns columns into rows: returns, for each row and each of the `values` columns,
+    /// a row holding the `ids` columns, the name of the value column (under the header
+    /// `variable`) and its value (under the header `value`). `values` defaults to every
+    /// column that is not an id.
+    /// Returns an error if a column index is out of bounds.
+    pub fn melt(
+        &self,
+        ids: &[usize],
+        values: Option<&[usize]>,
+        variable: &str,
+        value: &str,
+    ) -> Result<CSVData, EditorError> {
+        let values: Vec<usize> = match values {
+            Some(values) => values.to_vec(),
+            None => (0..self.fields).filter(|i| !ids.contains(i)).collect(),
+        };
+        if let Some(&field) = ids.iter().chain(&values).find(|&&i| i >= self.fields) {
+            return Err(EditorError::FieldOutOfBounds {
+                field,
+                fields: self.fields,
+            });
+        }
+        let headers = ids
+            .iter()
+            .map(|&i| self.header_name(i))
+            .chain([variable.to_string(), value.to_string()])
+            .collect();
+        let mut data = Vec::with_capacity(self.data.len() * values.len());
+        for record in &self.data {
+            let id = columns::project(record, ids);
+            for &column in &values {
+                let mut row = id.clone();
+                row.push_field(&self.header_name(column));
+                row.push_field(record.get(column).unwrap_or(""));
+                data.push(row);
+            }
+        }
+        let types = if self.types.is_empty() {
+            Vec::new()
+        } else {
+            let column_type = |i: usize| self.types.get(i).copied().unwrap_or_default();
+            // The value column keeps the type of the melted columns only when they agree.
+            let value_type = match values.split_first() {
+                Some((&first, rest))
+                    if rest.iter().all(|&i| column_type(i) == column_type(first)) =>
+                {
+                    column_type(first)
+                }
+                _ => ColumnType::Text,
+            };
+            ids.iter()
+                .map(|&i| column_type(i))
+                .chain([ColumnType::Text, value_type])
+                .collect()
+        };
+        log::info!(rows = data.len(), columns = values.len(); "melted columns");
+        Ok(self.derive(headers, data, types))
+    }
+
+    /// Divides the rows into parts, in order, each keeping the header.
+    /// Parts are named by their 1-based number, or by their value when split by column.
+    /// Sizes are measured as written with `options`.
+    /// Returns an error if the column index is out of bounds or the row count is 0.
+    pub fn split(
+        &self,
+        by: SplitBy,
+        options: WriteOptions,
+    ) -> Result<Vec<(String, CSVData)>, EditorError> {
+        let mut parts: Vec<(String, Vec<StringRecord>)> = Vec::new();
+        match by {
+            SplitBy::Rows(0) => {
+                return Err(EditorError::InvalidRange(
+                    "a part needs at least one row".to_string(),
+                ))
+            }
+            SplitBy::Rows(rows) => {
+                for (i, chunk) in self.data.chunks(rows).enumerate() {
+                    parts.push(((i + 1).to_string(), chunk.to_vec()));
+                }
+            }
+            SplitBy::Bytes(max) => {
+                let header_size = if self.headers.is_empty() {
+                    0
+                } else {
+                    record_size(&self.headers, &options)?
+                };
+                let mut size = header_size;
+                for record in &self.data {
+                    let record_size = record_size(record, &options)?;
+                    let full = parts
+                        .last()
+                        .is_none_or(|(_, rows)| !rows.is_empty() && size + record_size > max);
+                    if full {
+                        parts.push(((parts.len() + 1).to_string(), Vec::new()));
+                        size = header_size;
+                    }
+                    size += record_size;
+                    parts
+                        .last_mut()
+                        .expect("a part was pushed")
+                        .1
+                        .push(record.clone());
+                }
+            }
+            SplitBy::Column(field) => {
+                if field >= self.fields {
+                    return Err(EditorError::FieldOutOfBounds {
+                        field,
+                        fields: self.fields,
+                    });
+                }
+                let mut index: HashMap<String, usize> = HashMap::new();
+                for record in &self.data {
+                    let value = record.get(field).unwrap_or("");
+                    let part = *index.entry(value.to_string()).or_insert_with(|| {
+                        parts.push((value.to_string(), Vec::new()));
+                        parts.len() - 1
+                    });
+                    parts[part].1.push(record.clone());
+                }
+            }
+        }
+        log::info!(parts = parts.len(), by:? = by; "split rows");
+        Ok(parts
+            .into_iter()
+            .map(|(name, data)| {
+                let part = self.derive(self.headers.clone(), data, self.types.clone());
+                let metadata = Metadata {
+                    file_size: self.file_size,
+                    ..Metadata::of(&part.headers, &part.data, self.fields)
+                };
+                (
+                    name,
+                    CSVData {
+                        fields: self.fields,
+                        metadata,
+                        ..part
+                    },
+                )
+            })
+            .collect())
+    }
+
+    /// Records every later change of the data in `journal` (see [`audit`]).
+    pub fn set_journal(&mut self, mut journal: Journal) -> Result<(), EditorError> {
+        journal.checksum = self.checksum()?;
+        self.journal = Some(journal);
+        Ok(())
+    }
+
+    /// Begins a transaction: the changes made until the matching [`CSVData::commit`] can be
+    /// undone at once with [`CSVData::rollback`], e.g. when a step of a multi-step edit fails.
+    /// Transactions nest. The data is copied, so that it can be restored, and journal entries
+    /// are held back until the outermost transaction is committed.
+    pub fn begin(&mut self) {
+        let journal = self
+            .journal
+            .as_mut()
+            .map(|journal| (journal.hold(), journal.checksum.clone()));
+        self.checkpoints.push(Checkpoint {
+            headers: self.headers.clone(),
+            data: self.data.clone(),
+            records: self.records,
+            fields: self.fields,
+            pages: self.pages.clone(),
+            types: self.types.clone(),
+            number_locales: self.number_locales.clone(),
+            journal,
+        });
+        log::debug!(depth = self.checkpoints.len(); "began transaction");
+    }
+
+    /// Keeps the changes made since the matching [`CSVData::begin`]. Committing the
+    /// outermost transaction writes the journal entries held back.
+    /// Returns an error if no transaction is open, or the journal cannot be written.
+    pub fn commit(&mut self) -> Result<(), EditorError> {
+        if self.checkpoints.pop().is_none() {
+            return Err(EditorError::Transaction(
+                "no transaction to commit".to_string(),
+            ));
+        }
+        log::debug!(depth = self.checkpoints.len(); "committed transaction");
+        match &mut self.journal {
+            Some(journal) if self.checkpoints.is_empty() => journal.release(),
+            _ => Ok(()),
+        }
+    }
+
+    /// Undoes the changes made since the matching [`CSVData::begin`], and forgets their
+    /// journal entries. Returns an error if no transaction is open.
+    pub fn rollback(&mut self) -> Result<(), EditorError> {
+        let Some(checkpoint) = self.checkpoints.pop() else {
+            return Err(EditorError::Transaction(
+                "no transaction to roll back".to_string(),
+            ));
+        };
+        self.headers = checkpoint.headers;
+        self.data = checkpoint.data;
+        self.records = checkpoint.records;
+        self.fields = checkpoint.fields;
+        self.pages = checkpoint.pages;
+        self.types = checkpoint.types;
+        self.number_locales = checkpoint.number_locales;
+        if let (Some(journal), Some((held, checksum))) = (&mut self.journal, checkpoint.journal) {
+            journal.discard(held, checksum, self.checkpoints.is_empty());
+        }
+        log::info!(depth = self.checkpoints.len(); "rolled back transaction");
+        Ok(())
+    }
+
+    /// Returns the number of open transactions.
+    pub fn transactions(&self) -> usize {
+        self.checkpoints.len()
+    }
+
+    /// Runs `edit` in a transaction, committed if it succeeds and rolled back if it fails,
+    /// so that the data is never left half-changed.
+    pub fn transaction<T>(
+        &mut self,
+        edit: impl FnOnce(&mut CSVData) -> Result<T, EditorError>,
+    ) -> Result<T, EditorError> {
+        self.begin();
+        match edit(self) {
+            Ok(value) => {
+                self.commit()?;
+                Ok(value)
+            }
+            Err(e) => {
+                self.rollback()?;
+                Err(e)
+            }
+        }
+    }
+
+    /// Returns the SHA-256 checksum, in hex, of the header and rows written as CSV with
+    /// the default options. The file name, encoding and types are not part of it.
+    pub fn checksum(&self) -> Result<String, EditorError> {
+        let mut writer = WriterBuilder::new().from_writer(HashWriter(Sha256::new()));
+        for record in std::iter::once(&self.headers).chain(&self.data) {
+            writer.write_record(record)?;
+        }
+        let HashWriter(hasher) = writer.into_inner().map_err(|e| e.into_error())?;
+        Ok(hasher
+            .finalize()
+            .iter()
+            .map(|byte| format!("{byte:02x}"))
+            .collect())
+    }
+
+    /// Appends `change`, which was just applied, to the journal if there is one.
+    fn record(&mut self, change: Change) -> Result<(), EditorError> {
+        if self.journal.is_none() {
+            return Ok(());
+        }
+        let after = self.checksum()?;
+        match &mut self.journal {
+            Some(journal) => journal.record(&self.file_name, change, after),
+            None => Ok(()),
+        }
+    }
+
+    /// Applies a change as recorded in an audit journal (see [`audit::replay`]).
+    /// [`Change::Pivot`] and [`Change::Melt`] replace the data with the result of
+    /// [`CSVData::pivot`] and [`CSVData::melt`], and [`Change::Merge`] the rows with its own.
+    pub fn apply(&mut self, change: &Change) -> Result<(), EditorError> {
+        let records = |rows: &[Vec<String>]| -> Vec<StringRecord> {
+            rows.iter()
+                .map(|row| StringRecord::from(row.as_slice()))
+                .collect()
+        };
+        match change {
+            Change::Set {
+                row, column, new, ..
+            } => self.modify_field_unchecked(*row, *column, new),
+            Change::DeleteRow { row, .. } => self.delete_row(*row),
+            Change::AppendRows { rows } => self.append_rows(&records(rows)),
+            Change::RemoveRows { rows, .. } => self.remove_rows(rows),
+            Change::Cut {
+                first,
+                last,
+                columns,
+                ..
+            } => {
+                let range = CellRange {
+                    rows: *first..=*last,
+                    columns: columns.clone(),
+                };
+                self.cut_range(&range).map(|_| ())
+            }
+            Change::Paste {
+                row,
+                column,
+                values,
+            } => self.paste(
+                *row,
+                *column,
+                &Clip {
+                    rows: records(values),
+                },
+            ),
+            Change::SetColumn { column, cells } => self.set_cells(*column, cells.clone()),
+            Change::Sort {
+                column,
+                descending,
+                numeric,
+            } => self.sort_by_column(*column, *descending, *numeric, &mut |_| {}),
+            Change::Reshape {
+                remove,
+                at,
+                names,
+                values,
+            } => {
+                if *at > self.fields {
+                    return Err(EditorError::FieldOutOfBounds {
+                        field: *at,
+                        fields: self.fields,
+                    });
+                }
+                let mut values = values.iter();
+                self.reshape(remove, *at, names, |_| {
+                    values.next().cloned().unwrap_or_default()
+                })
+            }
+            Change::Pivot {
+                index,
+                names,
+                values,
+                agg,
+            } => {
+                let pivoted = self.pivot(index, *names, *values, *agg)?;
+                self.replace(pivoted, change.clone())
+            }
+            Change::Melt {
+                ids,
+                values,
+                variable,
+                value,
+            } => {
+                let melted = self.melt(ids, values.as_deref(), variable, value)?;
+                self.replace(melted, change.clone())
+            }
+            Change::Merge { rows, .. } => self.replace_rows(records(rows), change.clone()),
+        }
+    }
+
+    /// Takes the header, rows and types of `other`, recording `change` as the cause.
+    fn replace(&mut self, other: CSVData, change: Change) -> Result<(), EditorError> {
+        self.headers = other.headers;
+        self.data = other.data;
+        self.records = other.records;
+        self.fields = other.fields;
+        self.types = other.types;
+        self.number_locales = other.number_locales;
+        self.pages.clear();
+        self.record(change)
+    }
+
+    /// Takes `rows` in place of the rows, keeping the header and types, recording `change`
+    /// as the cause.
+    pub(crate) fn replace_rows(
+        &mut self,
+        rows: Vec<StringRecord>,
+        change: Change,
+    ) -> Result<(), EditorError> {
+        self.records = rows.len();
+        self.data = rows;
+        self.pages.clear();
+        self.record(change)
+    }
+
+    /// Returns the header of column `i`, or its 1-based number when the data has no header.
+    fn header_name(&self, i: usize) -> String {
+        match self.headers.get(i) {
+            Some(name) => name.to_string(),
+            None => (i + 1).to_string(),
+        }
+    }
+
+    /// Returns new data made of `headers` and `data`, keeping the file details of `self`.
+    fn derive(
+        &self,
+        headers: StringRecord,
+        data: Vec<StringRecord>,
+        types: Vec<ColumnType>,
+    ) -> CSVData {
+        let metadata = Metadata {
+            file_size: self.file_size,
+            ..Metadata::of(&headers, &data, headers.len())
+        };
+        CSVData {
+            fields: headers.len(),
+            headers,
+            records: data.len(),
+            data,
+            pages: Vec::new(),
+            file_name: self.file_name.clone(),
+            creation_date: self.creation_date,
+            last_modified_date: self.last_modified_date,
+            file_size: self.file_size,
+            encoding: self.encoding,
+            compression: self.compression,
+            metadata,
+            types,
+            number_locales: Vec::new(),
+            journal: None,
+            checkpoints: Vec::new(),
+        }
+    }
+
+    /// Replaces the missing values of `field` (see [`is_na`]) with `value`,
+    /// returning how many were replaced.
+    /// Returns an error if the field index is out of bounds or `value` does not match
+    /// the column's declared type.
+    pub fn fill_na(&mut self, field: usize, value: &str) -> Result<usize, EditorError> {
+        let filled = self.map_column(field, |old| {
+            if is_na(old) {
+                value.to_string()
+            } else {
+                old.to_string()
+            }
+        })?;
+        log::info!(field, filled; "filled missing values");
+        Ok(filled)
+    }
+
+    /// Removes the rows missing a value (see [`is_na`]) in any of `fields`,
+    /// returning how many were removed. Pages must be created again afterwards.
+    pub fn drop_na(&mut self, fields: &[usize]) -> Result<usize, EditorError> {
+        if let Some(&field) = fields.iter().find(|&&field| field >= self.fields) {
+            return Err(EditorError::FieldOutOfBounds {
+                field,
+                fields: self.fields,
+            });
+        }
+        let rows: Vec<usize> = self
+            .data
+            .iter()
+            .enumerate()
+            .filter(|(_, record)| {
+                fields
+                    .iter()
+                    .any(|&field| record.get(field).is_none_or(is_na))
+            })
+            .map(|(row, _)| row)
+            .collect();
+        self.remove_rows(&rows)?;
+        log::info!(dropped = rows.len(); "dropped rows with missing values");
+        Ok(rows.len())
+    }
+
+    /// Runs `processor`, named `name` in errors, over every row: rewritten values are set
+    /// and dropped rows removed. Pages must be created again afterwards.
+    /// Returns an error, without changing anything, if the processor rejects the data or a row,
+    /// or a new value does not match the column's declared type.
+    pub fn process_rows(
+        &mut self,
+        name: &str,
+        processor: &mut dyn RowProcessor,
+    ) -> Result<Processed, EditorError> {
+        let error = |row, message| EditorError::Plugin {
+            name: name.to_string(),
+            row,
+            message,
+        };
+        processor
+            .begin(&self.headers)
+            .map_err(|message| error(None, message))?;
+        let mut columns: BTreeMap<usize, Vec<CellChange>> = BTreeMap::new();
+        let mut dropped = Vec::new();
+        for (row, record) in self.data.iter().enumerate() {
+            let mut new = values(record);
+            match processor.process(row, &mut new) {
+                Ok(Verdict::Keep) => {}
+                Ok(Verdict::Drop) => {
+                    dropped.push(row);
+                    continue;
+                }
+                Err(message) => return Err(error(Some(row), message)),
+            }
+            for (field, (old, new)) in record.iter().zip(new).enumerate() {
+                if old != new {
+                    self.check_value(field, &new)?;
+                    columns.entry(field).or_default().push(CellChange {
+                        row,
+                        old: old.to_string(),
+                        new,
+                    });
+                }
+            }
+        }
+        let changed = columns.values().map(Vec::len).sum();
+        for (field, cells) in columns {
+            self.set_cells(field, cells)?;
+        }
+        self.remove_rows(&dropped)?;
+        log::info!(plugin = name, changed, dropped = dropped.len(); "processed rows");
+        Ok(Processed {
+            changed,
+            dropped: dropped.len(),
+        })
+    }
+
+    /// Returns a copy of the data, without its pages or audit journal.
+    pub fn copy(&self) -> CSVData {
+        CSVData {
+            headers: self.headers.clone(),
+            data: self.data.clone(),
+            records: self.records,
+            fields: self.fields,
+            pages: Vec::new(),
+            file_name: self.file_name.clone(),
+            creation_date: self.creation_date,
+            last_modified_date: self.last_modified_date,
+            file_size: self.file_size,
+            encoding: self.encoding,
+            compression: self.compression,
+            types: self.types.clone(),
+            number_locales: self.number_locales.clone(),
+            journal: None,
+            checkpoints: Vec::new(),
+            metadata: self.metadata.clone(),
+        }
+    }
+
+    /// Replaces every value of `field` with `f(value)`, returning how many values changed.
+    /// Returns an error, without changing anything, if the field index is out of bounds
+    /// or a new value does not match the column's declared type.
+    pub fn map_column(
+        &mut self,
+        field: usize,
+        mut f: impl FnMut(&str) -> String,
+    ) -> Result<usize, EditorError> {
+        if field >= self.fields {
+            return Err(EditorError::FieldOutOfBounds {
+                field,
+                fields: self.fields,
+            });
+        }
+        let mut cells = Vec::new();
+        for (row, record) in self.data.iter().enumerate() {
+            let Some(value) = record.get(field) else {
+                continue;
+            };
+            let new = f(value);
+            if new != value {
+                self.check_value(field, &new)?;
+                cells.push(CellChange {
+                    row,
+                    old: value.to_string(),
+                    new,
+                });
+            }
+        }
+        log::info!(field, changed = cells.len(); "mapped column");
+        let changed = cells.len();
+        self.set_cells(field, cells)?;
+        Ok(changed)
+    }
+
+    /// Sets the cells of column `field` to their new values.
+    fn set_cells(&mut self, field: usize, cells: Vec<CellChange>) -> Result<(), EditorError> {
+        if cells.is_empty() {
+            return Ok(());
+        }
+        for cell in &cells {
+            let record = self.data.get(cell.row).ok_or(EditorError::RowOutOfBounds {
+                row: cell.row,
+                rows: self.data.len(),
+            })?;
+            let mut values = values(record);
+            if let Some(value) = values.get_mut(field) {
+                value.clone_from(&cell.new);
+            }
+            replace_values(&mut self.data[cell.row], values);
+        }
+        self.record(Change::SetColumn {
+            column: field,
+            cells,
+        })
+    }
+
+    /// Splits `field` at each `on` into new columns named `into`, which replace it,
+    /// or follow it when `keep` is set. The last new column holds whatever is left
+    /// after the other splits; parts a value does not have are empty.
+    /// Returns an error if the field index is out of bounds or `on` or `into` is empty.
+    pub fn split_column(
+        &mut self,
+        field: usize,
+        on: &str,
+        into: &[String],
+        keep: bool,
+    ) -> Result<(), EditorError> {
+        if field >= self.fields {
+            return Err(EditorError::FieldOutOfBounds {
+                field,
+                fields: self.fields,
+            });
+        }
+        if on.is_empty() {
+            return Err(EditorError::InvalidFormat("empty separator".to_string()));
+        }
+        if into.is_empty() {
+            return Err(EditorError::InvalidColumns(
+                "no columns to split into".to_string(),
+            ));
+        }
+        let remove = if keep { vec![] } else { vec![field] };
+        self.reshape(&remove, field + 1, into, |record| {
+            let mut parts: Vec<String> = record
+                .get(field)
+                .unwrap_or("")
+                .splitn(into.len(), on)
+                .map(str::to_string)
+                .collect();
+            parts.resize(into.len(), String::new());
+            parts
+        })?;
+        log::info!(field, columns = into.len(); "split column");
+        Ok(())
+    }
+
+    /// Adds a column `name` whose values are `pieces` filled from each row
+    /// (see [`transform::parse_format`]). The new column replaces `columns`, taking the
+    /// place of the first one, or follows the last of them when `keep` is set.
+    /// Returns an error if a column index is out of bounds.
+    pub fn merge_columns(
+        &mut self,
+        columns: &[usize],
+        pieces: &[Piece],
+        name: &str,
+        keep: bool,
+    ) -> Result<(), EditorError> {
+        let used = pieces.iter().filter_map(|piece| match piece {
+            Piece::Column(column) => Some(column),
+            Piece::Text(_) => None,
+        });
+        if let Some(&field) = columns.iter().chain(used).find(|&&i| i >= self.fields) {
+            return Err(EditorError::FieldOutOfBounds {
+                field,
+                fields: self.fields,
+            });
+        }
+        let (Some(&first), Some(&last)) = (columns.iter().min(), columns.iter().max()) else {
+            return Err(EditorError::InvalidColumns(
+                "no columns to merge".to_string(),
+            ));
+        };
+        let (remove, at) = if keep {
+            (vec![], last + 1)
+        } else {
+            (columns.to_vec(), first)
+        };
+        self.reshape(&remove, at, &[name.to_string()], |record| {
+            vec![transform::format_record(pieces, record)]
+        })?;
+        log::info!(columns = columns.len(), name; "merged columns");
+        Ok(())
+    }
+
+    /// Adds a text column `name` after the last column, holding `expr` evaluated on each row.
+    /// Nothing changes if the expression fails on any row.
+    pub fn add_column(&mut self, name: &str, expr: &Expr) -> Result<(), EditorError> {
+        let values = self
+            .data
+            .iter()
+            .enumerate()
+            .map(|(row, record)| {
+                expr.eval(record)
+                    .map(|value| value.to_string())
+                    .map_err(|e| in_row(e, row, record))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        let mut values = values.into_iter();
+        self.reshape(&[], self.fields, &[name.to_string()], |_| {
+            values.next().into_iter().collect()
+        })?;
+        log::info!(name; "added column");
+        Ok(())
+    }
+
+    /// Keeps only the rows for which `expr` is true (see [`Expr::matches`]).
+    /// Returns the number of rows removed; nothing changes if the expression fails on any row.
+    pub fn filter_rows(&mut self, expr: &Expr) -> Result<usize, EditorError> {
+        let keep = self
+            .data
+            .par_iter()
+            .enumerate()
+            .map(|(row, record)| expr.matches(record).map_err(|e| in_row(e, row, record)))
+            .collect::<Vec<_>>()
+            .into_iter()
+            // Collected first, so that the error reported is the first row's whatever the threads.
+            .collect::<Result<Vec<_>, _>>()?;
+        let removed: Vec<usize> = (0..keep.len()).filter(|&row| !keep[row]).collect();
+        log::info!(kept = self.data.len() - removed.len(), removed = removed.len(); "filtered rows");
+        self.remove_rows(&removed)?;
+        Ok(removed.len())
+    }
+
+    /// Removes the rows at the given indices, which must be in increasing order.
+    pub(crate) fn remove_rows(&mut self, rows: &[usize]) -> Result<(), EditorError> {
+        if rows.is_empty() {
+            return Ok(());
+        }
+        if let Some(&row) = rows.iter().find(|&&row| row >= self.data.len()) {
+            return Err(EditorError::RowOutOfBounds {
+                row,
+                rows: self.data.len(),
+            });
+        }
+        let old = match self.journal {
+            Some(_) => rows.iter().map(|&row| values(&self.data[row])).collect(),
+            None => Vec::new(),
+        };
+        let mut removed = rows.iter().peekable();
+        let mut row = 0;
+        self.data.retain(|_| {
+            let remove = removed.next_if_eq(&&row).is_some();
+            row += 1;
+            !remove
+        });
+        self.records = self.records.min(self.data.len());
+        self.record(Change::RemoveRows {
+            rows: rows.to_vec(),
+            old,
+        })
+    }
+
+    /// Removes the `remove` columns and inserts text columns named `names` at index `at`
+    /// (counted before the removal), with values computed from each original row.
+    fn reshape(
+        &mut self,
+        remove: &[usize],
+        at: usize,
+        names: &[String],
+        mut values: impl FnMut(&StringRecord) -> Vec<String>,
+    ) -> Result<(), EditorError> {
+        let journaling = self.journal.is_some();
+        let mut added = Vec::new();
+        let (original_remove, original_at) = (remove.to_vec(), at);
+        let mut remove = remove.to_vec();
+        remove.sort_unstable();
+        remove.dedup();
+        let at = at - remove.iter().filter(|&&i| i < at).count();
+        let fields = self.fields;
+        let rebuild = |record: &StringRecord, new: Vec<String>| {
+            // Short rows are padded first, so that the new columns land at the same place.
+            let mut values: Vec<String> = record
+                .iter()
+                .map(str::to_string)
+                .chain(std::iter::repeat(String::new()))
+                .take(record.len().max(fields))
+                .enumerate()
+                .filter(|(i, _)| !remove.contains(i))
+                .map(|(_, value)| value)
+                .collect();
+            values.splice(at..at, new);
+            values
+        };
+        for record in &mut self.data {
+            let new = values(record);
+            if journaling {
+                added.push(new.clone());
+            }
+            let values = rebuild(record, new);
+            replace_values(record, values);
+        }
+        if !self.headers.is_empty() {
+            self.headers = StringRecord::from(rebuild(&self.headers, names.to_vec()));
+        }
+        if !self.types.is_empty() {
+            let mut types: Vec<ColumnType> = (0..fields)
+                .filter(|i| !remove.contains(i))
+                .map(|i| self.types.get(i).copied().unwrap_or_default())
+                .collect();
+            types.splice(at..at, vec![ColumnType::Text; names.len()]);
+            self.types = types;
+        }
+        if !self.number_locales.is_empty() {
+            let mut locales: Vec<Option<Locale>> = (0..fields)
+                .filter(|i| !remove.contains(i))
+                .map(|i| self.number_locale(i))
+                .collect();
+            locales.splice(at..at, vec![None; names.len()]);
+            self.number_locales = locales;
+        }
+        self.fields = self.fields - remove.len() + names.len();
+        self.record(Change::Reshape {
+            remove: original_remove,
+            at: original_at,
+            names: names.to_vec(),
+            values: added,
+        })
+    }
+
+    /// Returns the compression to write `file_name` with: the data's own when it is the file
+    /// the data was read from, otherwise the one its extension stands for.
+    pub fn compression_for(&self, file_name: &str) -> Option<Compression> {
+        if file_name == self.file_name {
+            self.compression
+        } else {
+            Compression::from_extension(file_name)
+        }
+    }
+
+    /// Writes the CSV data to a file, header first when the data has one.
+    pub fn write_to_file(&self, file_name: &str) -> Result<(), EditorError> {
+        self.write_with_options(file_name, WriteOptions::default())
+    }
+
+    /// Writes the CSV data to a file in the given encoding, compressed if asked to.
+    /// Returns an error if the data cannot be represented in that encoding.
+    pub fn write_with_options(
+        &self,
+        file_name: &str,
+        options: WriteOptions,
+    ) -> Result<(), EditorError> {
+        self.write_with_progress(file_name, options, &mut |_| {})
+    }
+
+    /// Same as [`CSVData::write_with_options`], reporting progress in rows.
+    pub fn write_with_progress(
+        &self,
+        file_name: &str,
+        options: WriteOptions,
+        progress: &mut dyn Progress,
+    ) -> Result<(), EditorError> {
+        let mut builder = WriterBuilder::new();
+        builder.delimiter(options.delimiter);
+        if options.encoding == UTF_8 && options.compression.is_none() {
+            let mut writer = builder.from_path(file_name)?;
+            self.write_records(&mut writer, options.na_value.as_deref(), progress)?;
+            log::info!(
+                file = file_name,
+                rows = self.data.len(),
+                encoding = "UTF-8";
+                "wrote file"
+            );
+            return Ok(());
+        }
+        let mut writer = builder.from_writer(Vec::new());
+        self.write_records(&mut writer, options.na_value.as_deref(), progress)?;
+        let mut bytes = writer.into_inner().map_err(|e| e.into_error())?;
+        if options.encoding != UTF_8 {
+            let text = String::from_utf8(bytes).expect("records are valid UTF-8");
+            bytes = encoding::encode(&text, options.encoding)?;
+        }
+        if let Some(compression) = options.compression {
+            bytes = compression.compress(&bytes)?;
+        }
+        std::fs::write(file_name, bytes)?;
+        log::info!(
+            file = file_name,
+            rows = self.data.len(),
+            encoding = options.encoding.name(),
+            compression:? = options.compression;
+            "wrote file"
+        );
+        Ok(())
+    }
+
+    fn write_records<W: std::io::Write>(
+        &self,
+        writer: &mut Writer<W>,
+        na_value: Option<&str>,
+        progress: &mut dyn Progress,
+    ) -> Result<(), EditorError> {
+        progress.report(ProgressEvent::Started {
+            stage: Stage::Write,
+            total: self.data.len() as u64,
+        });
+        if !self.headers.is_empty() {
+            writer.write_record(&self.headers)?;
+        }
+        for (i, record) in self.data.iter().enumerate() {
+            write_record(writer, record, na_value)?;
+            if (i + 1) % REPORT_EVERY == 0 {
+                progress.report(ProgressEvent::Advanced {
+                    stage: Stage::Write,
+                    done: i as u64 + 1,
+                });
+            }
+        }
+        writer.flush()?;
+        progress.report(ProgressEvent::Finished {
+            stage: Stage::Write,
+            rows: self.data.len(),
+        });
+        Ok(())
+    }
+}
+
+/// Value a row is sorted on.
+enum SortKey {
+    Text(String),
+    /// `None` for values that are not numbers.
+    Number(Option<f64>),
+}
+
+impl SortKey {
+    fn compare(&self, other: &SortKey, descending: bool) -> Ordering {
+        let ordering = match (self, other) {
+            (SortKey::Text(a), SortKey::Text(b)) => a.cmp(b),
+            (SortKey::Number(Some(a)), SortKey::Number(Some(b))) => a.total_cmp(b),
+            // Non-numbers stay last whatever the direction.
+            (SortKey::Number(Some(_)), SortKey::Number(None)) => return Ordering::Less,
+            (SortKey::Number(None), SortKey::Number(Some(_))) => return Ordering::Greater,
+            _ => Ordering::Equal,
+        };
+        if descending {
+            ordering.reverse()
+        } else {
+            ordering
+        }
+    }
+}
+
+/// Writes `record`, with `na_value` in place of its missing values if given.
+fn write_record<W: std::io::Write>(
+    writer: &mut Writer<W>,
+    record: &StringRecord,
+    na_value: Option<&str>,
+) -> csv::Result<()> {
+    match na_value {
+        Some(na_value) => {
+            writer.write_record(
+                record
+                    .iter()
+                    .map(|value| if is_na(value) { na_value } else { value }),
+            )
+        }
+        None => writer.write_record(record),
+    }
+}
+
+/// Returns the number of bytes `record` takes once written with `options`.
+fn record_size(record: &StringRecord, options: &WriteOptions) -> Result<u64, EditorError> {
+    let mut writer = WriterBuilder::new()
+        .delimiter(options.delimiter)
+        .from_writer(Vec::new());
+    write_record(&mut writer, record, options.na_value.as_deref())?;
+    let bytes = writer.into_inner().map_err(|e| e.into_error())?;
+    if options.encoding == UTF_8 {
+        return Ok(bytes.len() as u64);
+    }
+    let text = String::from_utf8(bytes).expect("records are valid UTF-8");
+    Ok(encoding::encode(&text, options.encoding)?.len() as u64)
+}
+
+/// Feeds the bytes written to it to a SHA-256 hasher.
+struct HashWriter(Sha256);
+
+impl std::io::Write for HashWriter {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.0.update(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Returns the values of a record.
+fn values(record: &StringRecord) -> Vec<String> {
+    record.iter().map(str::to_string).collect()
+}
+
+/// Records the row an expression failed on, and the line of the file it starts on.
+fn in_row(e: EditorError, row: usize, record: &StringRecord) -> EditorError {
+    match e {
+        EditorError::Expression {
+            row: None, message, ..
+        } => EditorError::Expression {
+            row: Some(row),
+            line: record.position().map(|position| position.line()),
+            message,
+        },
+        e => e,
+    }
+}
+
+/// Replaces `record` with one holding `values`, keeping its position in the file,
+/// and returns the old record.
+fn replace_values<T: AsRef<str>>(
+    record: &mut StringRecord,
+    values: impl IntoIterator<Item = T>,
+) -> StringRecord {
+    let mut new = StringRecord::new();
+    for value in values {
+        new.push_field(value.as_ref());
+    }
+    new.set_position(record.position().cloned());
+    std::mem::replace(record, new)
+}
+
+/// Parses CSV text into a header and rows, also returning the counts of the values of each
+/// field and where every [`INDEX_EVERY`]th row starts.
+fn parse_csv(
+    text: &str,
+    options: ReadOptions,
+    progress: &mut dyn Progress,
+) -> Result<(Rows, Vec<Offset>), EditorError> {
+    let mut reader = ReaderBuilder::new()
+        .delimiter(options.delimiter)
+        .has_headers(options.has_headers)
+        .flexible(true)
+        .from_reader(text.as_bytes());
+    let headers = if options.has_headers {
+        reader.headers()?.clone()
+    } else {
+        StringRecord::new()
+    };
+    let mut data: Vec<StringRecord> = Vec::new();
+    let mut columns = Vec::new();
+    let mut offsets = Vec::new();
+    for record in reader.records() {
+        let record = record?;
+        if data.len().is_multiple_of(REPORT_EVERY) {
+            if let Some(position) = record.position() {
+                progress.report(ProgressEvent::Advanced {
+                    stage: Stage::Read,
+                    done: position.byte(),
+                });
+            }
+        }
+        if data.len().is_multiple_of(INDEX_EVERY) {
+            if let Some(position) = record.position() {
+                offsets.push(Offset {
+                    byte: position.byte(),
+                    line: position.line(),
+                });
+            }
+        }
+        ColumnInfo::count(&mut columns, &record);
+        data.push(record);
+    }
+    Ok(((headers, data, columns), offsets))
+}
+
+/// Parses CSV text in parallel, each chunk of rows between two offsets of `index` on its own.
+/// Also returns the counts of the values of each field.
+fn parse_indexed(text: &str, options: ReadOptions, index: &Index) -> Result<Rows, EditorError> {
+    let headers = match options.has_headers {
+        true => ReaderBuilder::new()
+            .delimiter(options.delimiter)
+            .flexible(true)
+            .from_reader(text.as_bytes())
+            .headers()?
+            .clone(),
+        false => StringRecord::new(),
+    };
+    let chunks: Vec<(Vec<StringRecord>, Vec<ColumnInfo>)> = index
+        .offsets
+        .par_iter()
+        .enumerate()
+        .map(|(i, start)| {
+            let end = index
+                .offsets
+                .get(i + 1)
+                .map_or(text.len(), |next| next.byte as usize);
+            let chunk = text.get(start.byte as usize..end).ok_or_else(|| {
+                EditorError::InvalidFormat("index: offset out of the text".into())
+            })?;
+            let first = i * index.every + usize::from(options.has_headers);
+            let mut reader = ReaderBuilder::new()
+                .delimiter(options.delimiter)
+                .has_headers(false)
+                .flexible(true)
+                .from_reader(chunk.as_bytes());
+            let mut rows = Vec::new();
+            let mut columns = Vec::new();
+            for record in reader.records() {
+                let mut record = record?;
+                // Positions count from the start of the file, not of the chunk.
+                if let Some(local) = record.position() {
+                    let mut position = csv::Position::new();
+                    position
+                        .set_byte(start.byte + local.byte())
+                        .set_line(start.line + local.line() - 1)
+                        .set_record((first as u64) + local.record());
+                    record.set_position(Some(position));
+                }
+                ColumnInfo::count(&mut columns, &record);
+                rows.push(record);
+            }
+            Ok((rows, columns))
+        })
+        .collect::<Result<_, EditorError>>()?;
+    let mut data = Vec::with_capacity(index.rows);
+    let mut columns = Vec::new();
+    for (rows, counts) in chunks {
+        data.extend(rows);
+        ColumnInfo::merge(&mut columns, counts);
+    }
+    if data.len() != index.rows {
+        return Err(EditorError::InvalidFormat(format!(
+            "index: expected {} rows, found {}",
+            index.rows,
+            data.len()
+        )));
+    }
+    Ok((headers, data, columns))
+}
+
+/// Returns the header and rows with the number of fields, applying the ragged row policy
+/// of `options`.
+fn check_ragged(
+    (mut headers, mut data, columns): Rows,
+    options: ReadOptions,
+) -> Result<Parsed, EditorError> {
+    let mut fields = if options.has_headers {
+        headers.len()
+    } else {
+        data.first().map_or(0, |record| record.len())
+    };
+    match options.ragged {
+        RaggedPolicy::Error => {
+            if let Some(ragged) = find_ragged(&data, fields).first() {
+                return Err(EditorError::Schema {
+                    row: ragged.row,
+                    message: ragged.to_string(),
+                });
+            }
+        }
+        RaggedPolicy::Pad => {
+            fields = data.iter().map(StringRecord::len).fold(fields, usize::max);
+            if options.has_headers {
+                resize(&mut headers, fields);
+            }
+            data.iter_mut().for_each(|record| resize(record, fields));
+        }
+        RaggedPolicy::Truncate => data.iter_mut().for_each(|record| resize(record, fields)),
+        RaggedPolicy::Report => {}
+    }
+    Ok(Parsed {
+        headers,
+        data,
+        fields,
+        columns,
+    })
+}
+
+/// Returns the positions of the columns shown after the `frozen` ones when scrolled `scroll`
+/// columns to the right, out of `width`, at most `settings.cols_per_page` of them.
+pub(crate) fn column_window(
+    frozen: usize,
+    scroll: usize,
+    settings: &Settings,
+    width: usize,
+) -> Range<usize> {
+    let start = (frozen + scroll).min(width);
+    match settings.cols_per_page {
+        Some(columns) => start..(start + columns).min(width),
+        None => start..width,
+    }
+}
+
+/// Describes the `frozen` columns and the `window` after them, out of `total`, as
+/// `columns 1-2, 11-20 of 340`.
+pub(crate) fn describe_window(frozen: usize, window: Range<usize>, total: usize) -> String {
+    let range = |start: usize, end: usize| match end - start {
+        1 => format!("{end}"),
+        _ => format!("{}-{end}", start + 1),
+    };
+    let mut label = String::from("columns ");
+    if frozen > 0 {
+        label.push_str(&range(0, frozen));
+        label.push_str(", ");
+    }
+    label.push_str(&range(window.start, window.end));
+    label.push_str(&format!(" of {total}"));
+    label
+}
+
+fn find_ragged(data: &[StringRecord], expected: usize) -> Vec<RaggedRow> {
+    data.iter()
+        .enumerate()
+        .filter(|(_, record)| record.len() != expected)
+        .map(|(row, record)| RaggedRow {
+            row,
+            line: record.position().map(|position| position.line()),
+            fields: record.len(),
+            expected,
+        })
+        .collect()
+}
+
+/// Pads `record` with empty fields, or cuts it, so it has exactly `fields` fields.
+fn resize(record: &mut StringRecord, fields: usize) {
+    if record.len() > fields {
+        record.truncate(fields);
+    }
+    while record.len() < fields {
+        record.push_field("");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn setup() -> CSVData {
+        let file_name = "testdata.csv";
+        CSVData::read_from_file(file_name).expect("Failed to read test CSV file")
+    }
+
+    #[test]
+    fn test_display() {
+        let csv_data = setup();
+        // Ensure no panic
+        let res = std::panic::catch_unwind(|| csv_data.display());
+        assert!(res.is_ok());
+    }
+
+    #[test]
+    #[serial_test::serial]
+    fn test_paginate() {
+        let csv_data = setup();
+        let mut buffer = Vec::new();
+        let _ = csv_data.paginate(0, 3, &mut buffer);
+
+        let output = String::from_utf8(buffer).expect("Not UTF-8");
+        // println!("{}", output);
+
+        assert!(output
+            .contains("\"community\", \"block\", \"along\", \"telephone\", \"jar\", \"play\""));
+        assert!(output.contains("StringRecord([\"environment\", \"managed\", \"valley\", \"potatoes\", \"there\", \"century\"])"));
+        assert!(output.contains(
+            "StringRecord([\"his\", \"soft\", \"breathing\", \"gun\", \"barn\", \"completely\"])"
+        ));
+    }
+
+    #[test]
+    fn test_delete_and_modify() {
+        let mut csv_data = setup();
+        let original_records = csv_data.records;
+        let original_fields = csv_data.fields;
+
+        // Test delete_row: the row is gone, not left empty
+        let second = csv_data.data[1].clone();
+        csv_data.delete_row(0).expect("Failed to delete row");
+        assert_eq!(csv_data.records, original_records - 1);
+        assert_eq!(csv_data.data[0], second);
+
+        // Test modify_field
+        csv_data
+            .modify_field(1, 1, "modified")
+            .expect("Failed to modify field");
+        assert_eq!(csv_data.records, original_records - 1);
+        assert_eq!(csv_data.fields, original_fields);
+    }
+
+    #[test]
+    fn test_transactions() {
+        let mut csv_data = setup();
+        csv_data.create_pages(2);
+        let original = csv_data.data.clone();
+        assert!(csv_data.commit().is_err());
+        assert!(csv_data.rollback().is_err());
+
+        csv_data.begin();
+        csv_data.modify_field(0, 0, "kept").unwrap();
+        csv_data.begin();
+        csv_data.delete_row(0).unwrap();
+        csv_data.create_pages(2);
+        assert_eq!(csv_data.transactions(), 2);
+        csv_data.rollback().unwrap();
+        assert_eq!(csv_data.records, 6);
+        assert_eq!(csv_data.pages.len(), 3);
+        csv_data.commit().unwrap();
+        assert_eq!(&csv_data.data[0][0], "kept");
+        assert_eq!(csv_data.transactions(), 0);
+
+        // A failing step undoes the steps before it.
+        let error = csv_data.transaction(|csv_data| {
+            csv_data.modify_field(1, 0, "lost")?;
+            csv_data.delete_row(99)
+        });
+        assert!(error.is_err());
+        assert_eq!(csv_data.data[1], original[1]);
+        assert_eq!(csv_data.transactions(), 0);
+    }
+
+    #[test]
+    fn test_out_of_bounds_errors() {
+        let mut csv_data = setup();
+        assert!(matches!(
+            csv_data.delete_row(6),
+            Err(EditorError::RowOutOfBounds { row: 6, rows: 6 })
+        ));
+        assert!(matches!(
+            csv_data.modify_field(0, 6, "x"),
+            Err(EditorError::FieldOutOfBounds {
+                field: 6,
+                fields: 6
+            })
+        ));
+        assert!(matches!(
+            csv_data.paginate(0, 7, &mut Vec::new()),
+            Err(EditorError::RowOutOfBounds { .. })
+        ));
+    }
+
+    #[test]
+    fn test_read_backends() {
+        let read = |backend| {
+            let options = ReadOptions {
+                backend,
+                ..ReadOptions::default()
+            };
+            CSVData::read_with_options("testdata.csv", options).expect("Failed to read")
+        };
+        let (buffered, mapped) = (read(ReadBackend::Read), read(ReadBackend::Mmap));
+        assert_eq!(buffered.headers, mapped.headers);
+        assert_eq!(buffered.data, mapped.data);
+        assert_eq!(mapped.line(2), buffered.line(2));
+        assert!(!ReadBackend::Auto.maps(MMAP_THRESHOLD - 1));
+        assert!(ReadBackend::Auto.maps(MMAP_THRESHOLD));
+    }
+
+    #[test]
+    fn test_read_with_index() {
+        let file_name = "test_read_with_index.csv";
+        let mut text = String::from("id,note\n");
+        for i in 0..INDEX_EVERY * 2 + 10 {
+            match i % 1000 {
+                7 => text.push_str(&format!("{i},\"two\nlines\"\n")),
+                _ => text.push_str(&format!("{i},row {i}\n")),
+            }
+        }
+        std::fs::write(file_name, &text).expect("Failed to write");
+        let options = ReadOptions {
+            index: IndexMode::Always,
+            ..ReadOptions::default()
+        };
+        let scanned = CSVData::read_with_options(file_name, options).expect("Failed to read");
+        let index = Index::load(file_name, &options).expect("No index written");
+        assert_eq!(index.rows, scanned.data.len());
+        assert_eq!(index.offsets.len(), 3);
+
+        let (headers, data, columns) =
+            parse_indexed(&text, options, &index).expect("Failed to parse");
+        assert_eq!(headers, scanned.headers);
+        assert_eq!(data, scanned.data);
+        let metadata = Metadata::new(&headers, data.len(), 2, columns);
+        assert_eq!(metadata.columns, scanned.metadata().columns);
+        let last = data.len() - 1;
+        assert_eq!(data[last].position(), scanned.data[last].position());
+        let indexed = CSVData::read_with_options(file_name, options).expect("Failed to read");
+        assert_eq!(indexed.line(last), scanned.line(last));
+
+        std::fs::remove_file(file_name).expect("Failed to remove file");
+        std::fs::remove_file(Index::path(file_name)).expect("Failed to remove index");
+        assert!(!IndexMode::Auto.indexes(1 << 20));
+    }
+
+    #[test]
+    fn test_ragged_policies() {
+        let file_name = "test_ragged.csv";
+        std::fs::write(file_name, "a,b,c\n1,2,3\n4,5\n6,7,8,9\n").expect("Failed to write");
+        let read = |ragged| {
+            let options = ReadOptions {
+                ragged,
+                ..ReadOptions::default()
+            };
+            CSVData::read_with_options(file_name, options)
+        };
+
+        assert!(matches!(
+            read(RaggedPolicy::Error),
+            Err(EditorError::Schema { row: 1, .. })
+        ));
+
+        let padded = read(RaggedPolicy::Pad).expect("Failed to pad");
+        assert_eq!(padded.fields, 4);
+        assert_eq!(padded.headers, StringRecord::from(vec!["a", "b", "c", ""]));
+        assert_eq!(padded.data[1], StringRecord::from(vec!["4", "5", "", ""]));
+        assert!(padded.ragged_rows().is_empty());
+
+        let truncated = read(RaggedPolicy::Truncate).expect("Failed to truncate");
+        assert_eq!(truncated.fields, 3);
+        assert_eq!(truncated.data[2], StringRecord::from(vec!["6", "7", "8"]));
+
+        let reported = read(RaggedPolicy::Report).expect("Failed to read");
+        let ragged = reported.ragged_rows();
+        assert_eq!(ragged.len(), 2);
+        assert_eq!(
+            ragged[0].to_string(),
+            "row 2 (line 3): 2 field(s), expected 3"
+        );
+        assert_eq!(
+            ragged[1].to_string(),
+            "row 3 (line 4): 4 field(s), expected 3"
+        );
+
+        std::fs::remove_file(file_name).expect("Failed to remove test file");
+    }
+
+    #[test]
+    fn test_encoding_round_trip() {
+        let file_name = "test_latin1.csv";
+        std::fs::write(file_name, b"city,price\nM\xfcnchen,5\xa2\n").expect("Failed to write");
+        let csv_data = CSVData::read_from_file(file_name).expect("Failed to read");
+        assert_eq!(csv_data.encoding, encoding_rs::WINDOWS_1252);
+        assert_eq!(csv_data.data[0], StringRecord::from(vec!["München", "5¢"]));
+
+        let options = WriteOptions {
+            encoding: encoding_rs::UTF_16LE,
+            ..WriteOptions::default()
+        };
+        csv_data
+            .write_with_options(file_name, options)
+            .expect("Failed to write UTF-16");
+        let reread = CSVData::read_from_file(file_name).expect("Failed to read UTF-16");
+        assert_eq!(reread.encoding, encoding_rs::UTF_16LE);
+        assert_eq!(reread.data, csv_data.data);
+
+        std::fs::remove_file(file_name).expect("Failed to remove test file");
+    }
+
+    #[test]
+    fn test_compressed_round_trip() {
+        let csv_data = CSVData::read_from_file("testdata.csv").expect("Failed to read");
+        let file_name = "test_compressed.csv.zst";
+        let options = WriteOptions {
+            compression: Some(Compression::Zstd),
+            ..WriteOptions::default()
+        };
+        csv_data.write_with_options(file_name, options).unwrap();
+        // The format is told by the first bytes, whatever the name.
+        std::fs::rename(file_name, "test_compressed.csv").unwrap();
+        let reread = CSVData::read_from_file("test_compressed.csv").unwrap();
+        std::fs::remove_file("test_compressed.csv").expect("Failed to remove test file");
+        assert_eq!(reread.compression, Some(Compression::Zstd));
+        assert_eq!(reread.headers, csv_data.headers);
+        assert_eq!(reread.data, csv_data.data);
+        assert_eq!(csv_data.compression, None);
+    }
+
+    #[test]
+    fn test_typed_modify_and_render() {
+        let mut csv_data = setup();
+        csv_data
+            .modify_field(0, 1, "1234.5")
+            .expect("Failed to modify");
+        csv_data
+            .set_types("carry:float")
+            .expect("Failed to set types");
+
+        assert!(matches!(
+            csv_data.modify_field(1, 1, "abc"),
+            Err(EditorError::TypeMismatch { field: 1, .. })
+        ));
+        csv_data.modify_field(1, 1, "42").expect("Failed to modify");
+        csv_data
+            .modify_field_unchecked(2, 1, "abc")
+            .expect("Failed to modify unchecked");
+
+        let settings = Settings {
+            locale: Some(schema::Locale::De),
+            decimals: Some(2),
+            ..Settings::default()
+        };
+        let mut buffer = Vec::new();
+        csv_data
+            .render(0, 3, &settings, &mut buffer)
+            .expect("Failed to render");
+        let output = String::from_utf8(buffer).expect("Not UTF-8");
+        assert!(output.contains("\"1.234,50\""));
+        assert!(output.contains("\"42,00\""));
+        assert!(output.contains("\"abc\""));
+    }
+
+    #[test]
+    fn test_number_locales() {
+        let file_name = "test_number_locales.csv";
+        std::fs::write(
+            file_name,
+            "item,price\na,\"1.234,50\"\nb,\"99,9\"\na,\"10\"\n",
+        )
+        .expect("Failed to write");
+        let mut csv_data = CSVData::read_from_file(file_name).expect("Failed to read");
+        std::fs::remove_file(file_name).expect("Failed to remove file");
+        csv_data.set_number_locales("price:de").unwrap();
+        csv_data.set_types("auto").unwrap();
+        assert_eq!(csv_data.types[1], ColumnType::Float);
+        assert!(csv_data.modify_field(2, 1, "1,234.5").is_err());
+        csv_data.modify_field(2, 1, "1.000").unwrap();
+
+        csv_data
+            .sort_by_column(1, false, false, &mut |_| {})
+            .unwrap();
+        let column: Vec<&str> = csv_data.data.iter().map(|r| &r[1]).collect();
+        assert_eq!(column, ["99,9", "1.000", "1.234,50"]);
+
+        let aggregations =
+            Aggregation::parse_list("sum(price),max(price)", &csv_data.headers, 2).unwrap();
+        let grouped = csv_data.group_by(&[0], &aggregations).unwrap();
+        assert_eq!(
+            grouped.data[1],
+            StringRecord::from(vec!["a", "2234.5", "1.234,50"])
+        );
+
+        let settings = Settings {
+            locale: Some(schema::Locale::En),
+            ..Settings::default()
+        };
+        let rows = csv_data.display_rows(0, 3, &settings).unwrap();
+        assert_eq!(&rows[2][1], "1,234.50");
+    }
+
+    #[test]
+    fn test_sort_with_progress() {
+        let mut csv_data = setup();
+        csv_data.modify_field(0, 1, "10").unwrap();
+        csv_data.modify_field(1, 1, "9").unwrap();
+        csv_data.modify_field(2, 1, "100").unwrap();
+
+        let mut events = Vec::new();
+        csv_data
+            .sort_by_column(1, false, true, &mut |event| events.push(event))
+            .expect("Failed to sort");
+        let column: Vec<&str> = csv_data.data.iter().map(|r| &r[1]).collect();
+        assert_eq!(column, ["9", "10", "100", "attention", "form", "great"]);
+        assert_eq!(
+            events,
+            [
+                ProgressEvent::Started {
+                    stage: Stage::Sort,
+                    total: 6
+                },
+                ProgressEvent::Finished {
+                    stage: Stage::Sort,
+                    rows: 6
+                }
+            ]
+        );
+
+        csv_data
+            .sort_by_column(0, true, false, &mut |_| {})
+            .expect("Failed to sort");
+        assert_eq!(&csv_data.data[0][0], "present");
+        assert!(csv_data
+            .sort_by_column(6, false, false, &mut |_| {})
+            .is_err());
+    }
+
+    #[test]
+    fn test_copy_cut_paste() {
+        let mut csv_data = setup();
+        let range = CellRange::parse("2-3:pattern,4", &csv_data.headers, 6).unwrap();
+        let clip = csv_data.cut_range(&range).expect("Failed to cut");
+        assert_eq!(
+            clip.rows,
+            vec![
+                StringRecord::from(vec!["breathing", "gun"]),
+                StringRecord::from(vec!["along", "telephone"]),
+            ]
+        );
+        assert_eq!(&csv_data.data[1][2], "");
+
+        csv_data.paste(5, 4, &clip).expect("Failed to paste");
+        assert_eq!(csv_data.data.len(), 7);
+        assert_eq!(
+            csv_data.data[6],
+            StringRecord::from(vec!["", "", "", "", "along", "telephone"])
+        );
+        assert!(matches!(
+            csv_data.paste(0, 5, &clip),
+            Err(EditorError::FieldOutOfBounds { field: 6, .. })
+        ));
+
+        let rows = CellRange::parse("1-2", &csv_data.headers, 6).unwrap();
+        csv_data.cut_range(&rows).expect("Failed to cut rows");
+        assert_eq!(csv_data.data.len(), 5);
+        assert_eq!(&csv_data.data[0][0], "community");
+        let past_end = CellRange::parse("5-6:1", &csv_data.headers, 6).unwrap();
+        assert!(csv_data.copy_range(&past_end).is_err());
+    }
+
+    #[test]
+    fn test_split_and_merge_columns() {
+        let mut csv_data = setup();
+        let pieces = transform::parse_format("{near}/{3}", &csv_data.headers, 6).unwrap();
+        csv_data
+            .merge_columns(&[0, 2], &pieces, "path", false)
+            .expect("Failed to merge");
+        assert_eq!(csv_data.fields, 5);
+        assert_eq!(
+            csv_data.headers,
+            StringRecord::from(vec!["path", "carry", "fourth", "whatever", "easier"])
+        );
+        assert_eq!(&csv_data.data[0][0], "environment/valley");
+
+        let into = vec!["dir".to_string(), "file".to_string()];
+        csv_data
+            .split_column(0, "/", &into, true)
+            .expect("Failed to split");
+        assert_eq!(csv_data.fields, 7);
+        assert_eq!(&csv_data.headers[1], "dir");
+        assert_eq!(
+            csv_data.data[0].iter().take(3).collect::<Vec<_>>(),
+            vec!["environment/valley", "environment", "valley"]
+        );
+        assert!(csv_data.split_column(7, "/", &into, false).is_err());
+    }
+
+    #[test]
+    fn test_fill_and_drop_na() {
+        let file_name = "test_na.csv";
+        std::fs::write(file_name, "id,amount,note\n1,,a\n2,5, \n3,,\n").expect("Failed to write");
+        let mut csv_data = CSVData::read_from_file(file_name).expect("Failed to read");
+        csv_data.set_types("amount:int").unwrap();
+        assert!(csv_data.fill_na(1, "zero").is_err());
+        assert_eq!(csv_data.fill_na(1, "0").unwrap(), 2);
+        assert_eq!(csv_data.drop_na(&[2]).unwrap(), 2);
+        assert_eq!(csv_data.data, vec![StringRecord::from(vec!["1", "0", "a"])]);
+
+        let mut csv_data = CSVData::read_from_file(file_name).expect("Failed to read");
+        let options = WriteOptions {
+            na_value: Some("NA".to_string()),
+            ..WriteOptions::default()
+        };
+        csv_data.write_with_options(file_name, options).unwrap();
+        let written = std::fs::read_to_string(file_name).expect("Failed to read back");
+        std::fs::remove_file(file_name).expect("Failed to remove file");
+        assert_eq!(written, "id,amount,note\n1,NA,a\n2,5,NA\n3,NA,NA\n");
+
+        csv_data.create_pages(10);
+        let settings = Settings {
+            na_value: Some("-".to_string()),
+            ..Settings::default()
+        };
+        let mut output = Vec::new();
+        csv_data.render(0, 1, &settings, &mut output).unwrap();
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            "StringRecord([\"1\", \"-\", \"a\"])\n"
+        );
+        assert!(csv_data.drop_na(&[3]).is_err());
+    }
+
+    #[test]
+    fn test_metadata() {
+        let file_name = "test_metadata.csv";
+        let options = ReadOptions {
+            ragged: RaggedPolicy::Pad,
+            ..ReadOptions::default()
+        };
+        std::fs::write(file_name, "id,name\n1,Zoë\n2, \n10,al,extra\n").expect("Failed to write");
+        let csv_data = CSVData::read_with_options(file_name, options).expect("Failed to read");
+        std::fs::remove_file(file_name).expect("Failed to remove file");
+        let metadata = csv_data.metadata();
+        assert_eq!((metadata.rows, metadata.fields), (3, 3));
+        assert_eq!(metadata.file_size, 31);
+        let columns: Vec<_> = metadata
+            .columns
+            .iter()
+            .map(|column| (column.name.as_str(), column.filled, column.max_width))
+            .collect();
+        assert_eq!(columns, vec![("id", 3, 2), ("name", 2, 3), ("", 1, 5)]);
+
+        let selected = csv_data.select_columns("name").unwrap();
+        assert_eq!(selected.metadata().columns[0].name, "name");
+        let aggregations = Aggregation::parse_list("count()", &csv_data.headers, 3).unwrap();
+        let grouped = csv_data.group_by(&[1], &aggregations).unwrap();
+        assert_eq!(grouped.metadata().rows, 3);
+        assert_eq!(grouped.metadata().columns[1].name, "count()");
+    }
+
+    #[test]
+    fn test_line_numbers() {
+        let file_name = "test_lines.csv";
+        std::fs::write(file_name, "id,note\n3,\"two\nlines\"\n1,x\n2,y\n")
+            .expect("Failed to write");
+        let mut csv_data = CSVData::read_from_file(file_name).expect("Failed to read");
+        std::fs::remove_file(file_name).expect("Failed to remove file");
+        assert_eq!(csv_data.line(0), Some(2));
+        assert_eq!(csv_data.line(1), Some(4));
+        assert_eq!(csv_data.describe_row(2), "row 3 (line 5)");
+
+        // Lines follow the rows through edits.
+        csv_data
+            .sort_by_column(0, false, true, &mut |_| {})
+            .unwrap();
+        csv_data.modify_field(0, 1, "edited").unwrap();
+        csv_data
+            .append_rows(&[StringRecord::from(vec!["4", "new"])])
+            .unwrap();
+        let lines: Vec<_> = (0..4).map(|row| csv_data.line(row)).collect();
+        assert_eq!(lines, vec![Some(4), Some(5), Some(2), None]);
+
+        let filter = Expr::parse("id / (id - 3) > 0", &csv_data.headers, csv_data.fields).unwrap();
+        let error = csv_data.filter_rows(&filter).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "invalid expression in row index 2 (line 2): division by zero"
+        );
+    }
+
+    #[test]
+    fn test_add_column_and_filter_rows() {
+        let mut csv_data = setup();
+        let expr = Expr::parse("upper(near) & '-' & len(carry)", &csv_data.headers, 6).unwrap();
+        csv_data
+            .add_column("tag", &expr)
+            .expect("Failed to add column");
+        assert_eq!(csv_data.fields, 7);
+        assert_eq!(&csv_data.headers[6], "tag");
+        assert_eq!(&csv_data.data[1][6], "HIS-4");
+
+        let filter = Expr::parse("len(near) > 7", &csv_data.headers, 7).unwrap();
+        assert_eq!(csv_data.filter_rows(&filter).unwrap(), 3);
+        assert_eq!(csv_data.records, 3);
+        assert_eq!(&csv_data.data[1][0], "community");
+
+        let failing = Expr::parse("near * 2", &csv_data.headers, 7).unwrap();
+        assert!(matches!(
+            csv_data.add_column("double", &failing),
+            Err(EditorError::Expression { row: Some(0), .. })
+        ));
+        assert_eq!(csv_data.fields, 7);
+    }
+
+    #[test]
+    fn test_group_by() {
+        let mut csv_data = setup();
+        csv_data.data[2] = StringRecord::from(vec!["his", "x", "", "", "", "3"]);
+        csv_data.data[4] = StringRecord::from(vec!["his", "y", "", "", "", "4.5"]);
+        let headers = csv_data.headers.clone();
+        let aggregations = Aggregation::parse_list(
+            "count(),distinct(carry),max(carry)",
+            &headers,
+            csv_data.fields,
+        )
+        .unwrap();
+        let grouped = csv_data.group_by(&[0], &aggregations).unwrap();
+        assert_eq!(
+            grouped.headers,
+            StringRecord::from(vec!["near", "count()", "distinct(carry)", "max(carry)"])
+        );
+        assert_eq!(grouped.records, 4);
+        assert_eq!(
+            grouped.data[1],
+            StringRecord::from(vec!["his", "3", "3", "y"])
+        );
+
+        let sum = Aggregation::parse_list("sum(easier)", &headers, csv_data.fields).unwrap();
+        assert!(csv_data.group_by(&[0], &sum).is_err());
+        csv_data.data.retain(|record| &record[0] == "his");
+        csv_data.data[0] = StringRecord::from(vec!["his", "z", "", "", "", ""]);
+        let grouped = csv_data.group_by(&[0], &sum).unwrap();
+        assert_eq!(&grouped.data[0][1], "7.5");
+
+        // Groups spanning several chunks are merged in order of first appearance.
+        csv_data.data = (0..GROUP_CHUNK * 3)
+            .map(|i| {
+                let key = ["b", "a", "c"][i % 3];
+                StringRecord::from(vec![key, &i.to_string(), "", "", "", "1"])
+            })
+            .collect();
+        let aggregations = Aggregation::parse_list(
+            "sum(easier),min(carry),max(carry),distinct(carry)",
+            &headers,
+            6,
+        )
+        .unwrap();
+        let grouped = csv_data.group_by(&[0], &aggregations).unwrap();
+        let expected = |key: &str, first: usize| {
+            let last = GROUP_CHUNK * 3 - 3 + first;
+            let count = GROUP_CHUNK.to_string();
+            StringRecord::from(vec![
+                key,
+                &count,
+                &first.to_string(),
+                &last.to_string(),
+                &count,
+            ])
+        };
+        assert_eq!(
+            grouped.data,
+            vec![expected("b", 0), expected("a", 1), expected("c", 2)]
+        );
+    }
+
+    #[test]
+    fn test_parallel_matches_sequential() {
+        // More rows than a group-by chunk, with many ties on the sorted column.
+        let rows = GROUP_CHUNK * 2 + 7;
+        let headers = StringRecord::from(vec!["id", "key", "amount", "flag"]);
+        let data = (0..rows)
+            .map(|i| {
+                let amount = (i * 7919 % 100).to_string();
+                let flag = ["yes", "no"][i % 2];
+                StringRecord::from(vec![&i.to_string(), ["b", "a", "c"][i % 3], &amount, flag])
+            })
+            .collect();
+        let csv_data = CSVData::new("parallel.csv", headers, data);
+        let aggregations =
+            Aggregation::parse_list("count(),sum(amount),distinct(amount)", &csv_data.headers, 4)
+                .unwrap();
+        let filter = Expr::parse("amount > 50", &csv_data.headers, 4).unwrap();
+        let run = |threads: usize| {
+            let pool = rayon::ThreadPoolBuilder::new()
+                .num_threads(threads)
+                .build()
+                .unwrap();
+            pool.install(|| {
+                let mut sorted = csv_data.copy();
+                sorted.sort_by_column(2, true, true, &mut |_| {}).unwrap();
+                let mut filtered = csv_data.copy();
+                filtered.filter_rows(&filter).unwrap();
+                let grouped = csv_data.group_by(&[1], &aggregations).unwrap();
+                let types = schema::infer_types(&csv_data.data, 4, &[]);
+                (sorted.data, filtered.data, grouped.data, types)
+            })
+        };
+        let sequential = run(1);
+        assert_eq!(run(4), sequential);
+
+        // Ties keep the order they had.
+        let (sorted, filtered, _, types) = sequential;
+        for pair in sorted.windows(2) {
+            let number = |record: &StringRecord, i: usize| record[i].parse::<usize>().unwrap();
+            assert!(number(&pair[0], 2) >= number(&pair[1], 2));
+            if pair[0][2] == pair[1][2] {
+                assert!(number(&pair[0], 0) < number(&pair[1], 0));
+            }
+        }
+        assert!(filtered
+            .iter()
+            .all(|record| record[2].parse::<u32>().unwrap() > 50));
+        assert_eq!(
+            types,
+            vec![
+                ColumnType::Integer,
+                ColumnType::Text,
+                ColumnType::Integer,
+                ColumnType::Boolean
+            ]
+        );
+    }
+
+    #[test]
+    fn test_value_counts() {
+        let mut csv_data = setup();
+        for (record, country) in csv_data
+            .data
+            .iter_mut()
+            .zip(["Germany", "France", "Gemany", "France", "Germany", "France"])
+        {
+            *record = StringRecord::from(vec!["", "", "", country, "", ""]);
+        }
+        assert_eq!(
+            csv_data.value_counts(3).unwrap(),
+            vec![
+                ("France".to_string(), 3),
+                ("Germany".to_string(), 2),
+                ("Gemany".to_string(), 1)
+            ]
+        );
+        assert!(csv_data.value_counts(6).is_err());
+    }
+
+    #[test]
+    fn test_melt_and_pivot() {
+        let csv_data = setup();
+        let long = csv_data
+            .melt(&[0], Some(&[1, 2]), "variable", "value")
+            .expect("Failed to melt");
+        assert_eq!(
+            long.headers,
+            StringRecord::from(vec!["near", "variable", "value"])
+        );
+        assert_eq!(long.records, 12);
+        assert_eq!(
+            long.data[1],
+            StringRecord::from(vec!["environment", "pattern", "valley"])
+        );
+
+        let wide = long.pivot(&[0], 1, 2, None).expect("Failed to pivot");
+        let original = csv_data.select_columns("1-3").unwrap();
+        assert_eq!(wide.headers, original.headers);
+        assert_eq!(wide.data, original.data);
+
+        assert!(matches!(
+            long.pivot(&[], 1, 2, None),
+            Err(EditorError::Schema { row: 2, .. })
+        ));
+        let counted = long.pivot(&[], 1, 2, Some(AggFn::Count)).unwrap();
+        assert_eq!(counted.data, vec![StringRecord::from(vec!["6", "6"])]);
+    }
+
+    #[test]
+    fn test_split() {
+        let mut csv_data = setup();
+        let options = WriteOptions::default();
+        let parts = csv_data.split(SplitBy::Rows(4), options.clone()).unwrap();
+        let sizes: Vec<_> = parts
+            .iter()
+            .map(|(name, part)| (name.as_str(), part.records))
+            .collect();
+        assert_eq!(sizes, vec![("1", 4), ("2", 2)]);
+        assert_eq!(parts[1].1.headers, csv_data.headers);
+        assert!(csv_data.split(SplitBy::Rows(0), options.clone()).is_err());
+
+        // The header takes 42 bytes and the rows 38 to 50, so two rows fit under 140.
+        let parts = csv_data
+            .split(SplitBy::Bytes(140), options.clone())
+            .unwrap();
+        assert_eq!(parts.len(), 3);
+        assert!(parts.iter().all(|(_, part)| part.records == 2));
+        let parts = csv_data.split(SplitBy::Bytes(1), options.clone()).unwrap();
+        assert_eq!(parts.len(), 6);
+
+        csv_data.data[3] = StringRecord::from(vec!["his", "", "", "", "", ""]);
+        let parts = csv_data.split(SplitBy::Column(0), options.clone()).unwrap();
+        assert_eq!(parts.len(), 5);
+        assert_eq!(parts[1].0, "his");
+        assert_eq!(parts[1].1.records, 2);
+        assert!(csv_data.split(SplitBy::Column(6), options.clone()).is_err());
+    }
+
+    #[test]
+    fn test_concat() {
+        let mut csv_data = setup();
+        let other = csv_data.select_columns("carry,near").unwrap();
+        assert!(matches!(
+            csv_data.concat(&other, HeaderMatch::Strict),
+            Err(EditorError::HeaderMismatch { .. })
+        ));
+        assert_eq!(csv_data.records, 6);
+
+        let mut other = other;
+        other.headers = StringRecord::from(vec!["carry", "new"]);
+        let diff = csv_data.concat(&other, HeaderMatch::Union).unwrap();
+        assert_eq!(diff.extra, vec!["new"]);
+        assert_eq!(diff.missing.len(), 5);
+        assert_eq!(csv_data.fields, 7);
+        assert_eq!(csv_data.records, 12);
+        assert_eq!(
+            csv_data.data[6],
+            StringRecord::from(vec!["", "managed", "", "", "", "", "environment"])
+        );
+        assert_eq!(&csv_data.data[0][6], "");
+
+        let same = setup();
+        let diff = csv_data.concat(&same, HeaderMatch::Union).unwrap();
+        assert_eq!(diff.missing, vec!["new"]);
+        assert!(!diff.reordered);
+    }
+
+    #[test]
+    fn test_select_columns() {
+        let csv_data = setup();
+        let selected = csv_data
+            .select_columns("fourth,1-2")
+            .expect("Failed to select columns");
+        assert_eq!(selected.fields, 3);
+        assert_eq!(
+            selected.headers,
+            StringRecord::from(vec!["fourth", "near", "carry"])
+        );
+        assert_eq!(
+            selected.data[0],
+            StringRecord::from(vec!["potatoes", "environment", "managed"])
+        );
+        assert!(matches!(
+            csv_data.select_columns("nope"),
+            Err(EditorError::InvalidColumns(_))
+        ));
+    }
+
+    #[test]
+    fn test_head_tail_sample() {
+        let csv_data = setup();
+        assert_eq!(csv_data.head(2), &csv_data.data[..2]);
+        assert_eq!(csv_data.tail(2), &csv_data.data[4..]);
+        assert_eq!(csv_data.head(100).len(), csv_data.data.len());
+        assert_eq!(csv_data.tail(100).len(), csv_data.data.len());
+
+        let sample = csv_data.sample(3, 42);
+        assert_eq!(sample.len(), 3);
+        assert_eq!(sample, csv_data.sample(3, 42));
+        let positions: Vec<usize> = sample
+            .iter()
+            .map(|record| csv_data.data.iter().position(|r| r == record).unwrap())
+            .collect();
+        assert!(positions.windows(2).all(|w| w[0] < w[1]));
+        assert_eq!(csv_data.sample(100, 1).len(), csv_data.data.len());
+    }
+
+    #[test]
+    fn test_write_to_file() {
+        let csv_data = setup();
+        let output_file = "test_output.csv";
+        csv_data
+            .write_to_file(output_file)
+            .expect("Failed to write to file");
+
+        // Assert the file exists
+        let metadata = std::fs::metadata(output_file);
+        assert!(metadata.is_ok());
+        assert!(metadata.unwrap().is_file());
+
+        // Clean up the test file
+        std::fs::remove_file(output_file).expect("Failed to remove test output file");
+    }
+}
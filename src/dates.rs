@@ -0,0 +1,193 @@
+//! Dates written in several ways, rewritten in one by `normalize-dates`: a column mixing
+//! `03/04/2024` and `2024-04-03` becomes `2024-04-03` throughout.
+
+use std::fmt::Write;
+
+use chrono::{
+    format::{Item, StrftimeItems},
+    DateTime, FixedOffset, NaiveDate, NaiveDateTime, SecondsFormat,
+};
+
+use crate::EditorError;
+
+/// Formats tried, in order, when none are configured: ISO dates first, then the usual
+/// month-first and day-first spellings, with or without a time.
+pub const DEFAULT_FORMATS: &[&str] = &[
+    "%Y-%m-%d",
+    "%Y-%m-%d %H:%M:%S",
+    "%Y-%m-%d %H:%M",
+    "%Y-%m-%dT%H:%M:%S",
+    "%Y/%m/%d",
+    "%m/%d/%Y",
+    "%m/%d/%Y %H:%M:%S",
+    "%m/%d/%Y %H:%M",
+    "%d.%m.%Y",
+    "%d.%m.%Y %H:%M",
+    "%d %b %Y",
+    "%b %d, %Y",
+    "%d %B %Y",
+    "%B %d, %Y",
+];
+
+/// Share of the non-empty values of a column that must be dates for it to be detected
+/// as a date column.
+const DETECT_RATIO: f64 = 0.8;
+
+/// A value read as a date, with as much precision as it was written with.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Parsed {
+    Date(NaiveDate),
+    DateTime(NaiveDateTime),
+    /// A date and time with a UTC offset.
+    Zoned(DateTime<FixedOffset>),
+}
+
+/// Reads dates in any of a list of formats and writes them in one.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Normalizer {
+    formats: Vec<String>,
+    to: Option<String>,
+}
+
+impl Normalizer {
+    /// Returns a normalizer reading `formats` (chrono `strftime` patterns, tried in order
+    /// after RFC 3339) and writing `to`, or RFC 3339 without one: `2024-04-03` for dates,
+    /// `2024-04-03T10:30:00` for times, and `2024-04-03T10:30:00+02:00` when the offset is
+    /// known. With `day_first`, `%m/%d` formats are read as `%d/%m` ones instead.
+    /// Returns an error if a format is not a valid pattern.
+    pub fn new(
+        formats: &[String],
+        day_first: bool,
+        to: Option<String>,
+    ) -> Result<Normalizer, EditorError> {
+        let formats = match formats.is_empty() {
+            true => DEFAULT_FORMATS.iter().map(|f| f.to_string()).collect(),
+            false => formats.to_vec(),
+        };
+        let formats: Vec<String> = match day_first {
+            true => formats
+                .iter()
+                .map(|f| f.replace("%m/%d", "%d/%m"))
+                .collect(),
+            false => formats,
+        };
+        for format in formats.iter().chain(&to) {
+            if StrftimeItems::new(format).any(|item| item == Item::Error) {
+                return Err(EditorError::InvalidFormat(format!(
+                    "invalid date format '{format}'"
+                )));
+            }
+        }
+        Ok(Normalizer { formats, to })
+    }
+
+    /// Reads `value`, surrounding spaces ignored, in the first format it matches.
+    pub fn parse(&self, value: &str) -> Option<Parsed> {
+        let value = value.trim();
+        if value.is_empty() {
+            return None;
+        }
+        if let Ok(zoned) = DateTime::parse_from_rfc3339(value) {
+            return Some(Parsed::Zoned(zoned));
+        }
+        self.formats.iter().find_map(|format| {
+            // A format without a time or offset fails the more precise parsers.
+            DateTime::parse_from_str(value, format)
+                .map(Parsed::Zoned)
+                .or_else(|_| NaiveDateTime::parse_from_str(value, format).map(Parsed::DateTime))
+                .or_else(|_| NaiveDate::parse_from_str(value, format).map(Parsed::Date))
+                .ok()
+        })
+    }
+
+    /// Returns `value` rewritten in the output format, `None` if it is not a date in any
+    /// format, or an error if the output format needs a time or offset the value lacks.
+    pub fn normalize(&self, value: &str) -> Result<Option<String>, EditorError> {
+        let Some(parsed) = self.parse(value) else {
+            return Ok(None);
+        };
+        let Some(to) = &self.to else {
+            return Ok(Some(match parsed {
+                Parsed::Date(date) => date.format("%Y-%m-%d").to_string(),
+                Parsed::DateTime(time) => time.format("%Y-%m-%dT%H:%M:%S").to_string(),
+                Parsed::Zoned(zoned) => zoned.to_rfc3339_opts(SecondsFormat::AutoSi, true),
+            }));
+        };
+        // Formatting fails, instead of panicking, when the value has no such field.
+        let mut text = String::new();
+        let written = match parsed {
+            Parsed::Date(date) => write!(text, "{}", date.format(to)),
+            Parsed::DateTime(time) => write!(text, "{}", time.format(to)),
+            Parsed::Zoned(zoned) => write!(text, "{}", zoned.format(to)),
+        };
+        match written {
+            Ok(()) => Ok(Some(text)),
+            Err(_) => Err(EditorError::InvalidFormat(format!(
+                "'{}' cannot be written as '{to}'",
+                value.trim()
+            ))),
+        }
+    }
+
+    /// Tells whether `values` look like a date column: at least 80% of the non-empty ones
+    /// are dates.
+    pub fn is_date_column<'a>(&self, values: impl IntoIterator<Item = &'a str>) -> bool {
+        let (mut total, mut dates) = (0, 0);
+        for value in values.into_iter().filter(|value| !value.trim().is_empty()) {
+            total += 1;
+            dates += usize::from(self.parse(value).is_some());
+        }
+        total > 0 && dates as f64 >= total as f64 * DETECT_RATIO
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_normalize() {
+        let normalizer = Normalizer::new(&[], false, None).unwrap();
+        let normalize = |value| normalizer.normalize(value).unwrap();
+        assert_eq!(normalize("03/04/2024").as_deref(), Some("2024-03-04"));
+        assert_eq!(normalize(" 2024-04-03 ").as_deref(), Some("2024-04-03"));
+        assert_eq!(normalize("3 Apr 2024").as_deref(), Some("2024-04-03"));
+        assert_eq!(
+            normalize("2024-04-03 10:30").as_deref(),
+            Some("2024-04-03T10:30:00")
+        );
+        assert_eq!(
+            normalize("2024-04-03T10:30:00+02:00").as_deref(),
+            Some("2024-04-03T10:30:00+02:00")
+        );
+        assert_eq!(normalize("13/31/2024"), None);
+        assert_eq!(normalize("soon"), None);
+
+        let day_first = Normalizer::new(&[], true, Some("%d %b %Y".to_string())).unwrap();
+        assert_eq!(
+            day_first.normalize("03/04/2024").unwrap().as_deref(),
+            Some("03 Apr 2024")
+        );
+        let with_time = Normalizer::new(&[], false, Some("%H:%M".to_string())).unwrap();
+        assert!(with_time.normalize("2024-04-03").is_err());
+        assert!(Normalizer::new(&["%Y-%Q".to_string()], false, None).is_err());
+    }
+
+    #[test]
+    fn test_is_date_column() {
+        let normalizer = Normalizer::new(&["%d.%m.%Y".to_string()], false, None).unwrap();
+        let dates = [
+            "01.02.2024",
+            "",
+            "15.03.2024",
+            "03.04.2024",
+            "04.05.2024",
+            "n/a",
+        ];
+        assert!(normalizer.is_date_column(dates));
+        assert!(!normalizer.is_date_column(["01.02.2024", "n/a"]));
+        assert!(!normalizer.is_date_column(["", " "]));
+        // Other formats are not tried once some are given.
+        assert!(!normalizer.is_date_column(["2024-02-01"]));
+    }
+}
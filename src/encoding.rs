@@ -0,0 +1,101 @@
+use std::borrow::Cow;
+
+use encoding_rs::{Encoding, UTF_16BE, UTF_16LE, UTF_8, WINDOWS_1252};
+
+use crate::EditorError;
+
+/// Looks up an encoding by its WHATWG label (`latin1`, `windows-1252`, `utf-16le`, ...).
+/// `utf16`, `utf16le` and `utf16be` are accepted as shorthands.
+pub fn parse_label(label: &str) -> Result<&'static Encoding, String> {
+    match label.to_ascii_lowercase().as_str() {
+        "utf16" | "utf16le" => Ok(UTF_16LE),
+        "utf16be" => Ok(UTF_16BE),
+        other => Encoding::for_label(other.as_bytes())
+            .ok_or_else(|| format!("unknown encoding '{label}'")),
+    }
+}
+
+/// Guesses the encoding of `bytes`: a byte order mark wins, then valid UTF-8,
+/// and anything else is assumed to be Windows-1252 (the usual Excel export).
+pub fn detect(bytes: &[u8]) -> &'static Encoding {
+    if let Some((encoding, _)) = Encoding::for_bom(bytes) {
+        encoding
+    } else if std::str::from_utf8(bytes).is_ok() {
+        UTF_8
+    } else {
+        WINDOWS_1252
+    }
+}
+
+/// Decodes `bytes` to UTF-8, detecting the encoding when none is given.
+/// Returns the text and the encoding that was used, or an error if the bytes
+/// are not valid in the requested encoding.
+pub fn decode<'a>(
+    bytes: &'a [u8],
+    encoding: Option<&'static Encoding>,
+) -> Result<(Cow<'a, str>, &'static Encoding), EditorError> {
+    let encoding = encoding.unwrap_or_else(|| detect(bytes));
+    let (text, had_errors) = encoding.decode_with_bom_removal(bytes);
+    if had_errors {
+        return Err(EditorError::Encoding(format!(
+            "input is not valid {}",
+            encoding.name()
+        )));
+    }
+    Ok((text, encoding))
+}
+
+/// Encodes UTF-8 `text` in the given encoding. UTF-16 output starts with a byte order mark.
+/// Returns an error if the text has characters the encoding cannot represent.
+pub fn encode(text: &str, encoding: &'static Encoding) -> Result<Vec<u8>, EditorError> {
+    if encoding == UTF_16LE || encoding == UTF_16BE {
+        // encoding_rs only decodes UTF-16, so the encoder is written out here.
+        let mut bytes = Vec::with_capacity(2 + text.len() * 2);
+        for unit in std::iter::once(0xFEFF).chain(text.encode_utf16()) {
+            if encoding == UTF_16LE {
+                bytes.extend_from_slice(&unit.to_le_bytes());
+            } else {
+                bytes.extend_from_slice(&unit.to_be_bytes());
+            }
+        }
+        return Ok(bytes);
+    }
+    let (bytes, _, had_errors) = encoding.encode(text);
+    if had_errors {
+        return Err(EditorError::Encoding(format!(
+            "data has characters that cannot be written as {}",
+            encoding.name()
+        )));
+    }
+    Ok(bytes.into_owned())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_detect_and_decode() {
+        let latin1 = b"name\ncaf\xe9\n";
+        assert_eq!(detect(latin1), WINDOWS_1252);
+        let (text, _) = decode(latin1, None).expect("Failed to decode");
+        assert_eq!(text, "name\ncafé\n");
+
+        let utf16 = encode("name\ncafé\n", UTF_16LE).expect("Failed to encode");
+        assert_eq!(detect(&utf16), UTF_16LE);
+        let (text, _) = decode(&utf16, None).expect("Failed to decode");
+        assert_eq!(text, "name\ncafé\n");
+
+        assert!(decode(latin1, Some(UTF_8)).is_err());
+    }
+
+    #[test]
+    fn test_encode_and_labels() {
+        assert_eq!(parse_label("latin1"), Ok(WINDOWS_1252));
+        assert_eq!(parse_label("UTF16"), Ok(UTF_16LE));
+        assert!(parse_label("klingon").is_err());
+
+        assert_eq!(encode("café", WINDOWS_1252).unwrap(), b"caf\xe9");
+        assert!(encode("日本", WINDOWS_1252).is_err());
+    }
+}
@@ -0,0 +1,207 @@
+use std::{fmt, io};
+
+use crate::schema::ColumnType;
+
+/// Errors returned by the editor's APIs.
+#[derive(Debug)]
+pub enum EditorError {
+    /// Reading or writing a file failed.
+    Io(io::Error),
+    /// A record could not be parsed. `row` is the record number reported by the
+    /// parser (the header, if any, is record 0).
+    Parse {
+        row: Option<u64>,
+        source: csv::Error,
+    },
+    /// A row index is outside the data.
+    RowOutOfBounds { row: usize, rows: usize },
+    /// A field index is outside the data.
+    FieldOutOfBounds { field: usize, fields: usize },
+    /// A page number is outside the pages created by `create_pages`.
+    PageOutOfBounds { page: usize, pages: usize },
+    /// A column spec names an unknown column or is malformed.
+    InvalidColumns(String),
+    /// A cell range is malformed (see [`crate::clipboard::CellRange`]).
+    InvalidRange(String),
+    /// A format string is malformed (see [`crate::transform::parse_format`]).
+    InvalidFormat(String),
+    /// An expression is malformed or cannot be evaluated (see [`crate::expr::Expr`]).
+    /// `row` is the row index being evaluated, if any, and `line` the line of the file it starts on.
+    Expression {
+        row: Option<usize>,
+        line: Option<u64>,
+        message: String,
+    },
+    /// A file cannot be decompressed or compressed (see [`crate::compression::Compression`]).
+    Compression(String),
+    /// A remote file cannot be downloaded (see [`crate::remote::fetch`]).
+    Remote(String),
+    /// Text cannot be decoded from, or encoded to, the requested encoding.
+    Encoding(String),
+    /// A value does not match the declared type of its column.
+    TypeMismatch {
+        field: usize,
+        value: String,
+        expected: ColumnType,
+    },
+    /// A record does not have the shape the data promises.
+    Schema { row: usize, message: String },
+    /// The header of a file does not match the data it is combined with.
+    HeaderMismatch { file: String, message: String },
+    /// An audit journal cannot be written, read or replayed (see [`crate::audit`]).
+    Journal(String),
+    /// A transaction cannot be committed or rolled back (see [`crate::CSVData::begin`]).
+    Transaction(String),
+    /// A session snapshot cannot be written or read (see [`crate::session::Snapshot`]).
+    Session(String),
+    /// A bookmark name or sidecar is invalid (see [`crate::bookmarks::Bookmarks`]).
+    InvalidBookmark(String),
+    /// A comment or its sidecar is invalid (see [`crate::comments::Comments`]).
+    InvalidComment(String),
+    /// An SQLite database cannot be written or read (see [`crate::sqlite`]).
+    Sqlite(String),
+    /// Overlay edits cannot be recorded or applied (see [`crate::overlay::Overlay`]).
+    Overlay(String),
+    /// Files cannot be merged (see [`crate::merge::merge`]).
+    Merge(String),
+    /// A bookmark or searched text was not found.
+    NotFound(String),
+    /// A row processor refused the data, or a row when `row` is set, or is not registered
+    /// (see [`crate::plugin::RowProcessor`]).
+    Plugin {
+        name: String,
+        row: Option<usize>,
+        message: String,
+    },
+    /// Another editor instance holds the lock on a file (see [`crate::lock::FileLock`]).
+    Locked { file: String, pid: Option<u32> },
+}
+
+impl fmt::Display for EditorError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            EditorError::Io(e) => write!(f, "I/O error: {e}"),
+            EditorError::Parse {
+                row: Some(row),
+                source,
+            } => write!(f, "parse error in row {row}: {source}"),
+            EditorError::Parse { row: None, source } => write!(f, "parse error: {source}"),
+            EditorError::RowOutOfBounds { row, rows } => {
+                write!(
+                    f,
+                    "row index {row} out of bounds, the data has {rows} row(s)"
+                )
+            }
+            EditorError::FieldOutOfBounds { field, fields } => write!(
+                f,
+                "field index {field} out of bounds, the data has {fields} field(s)"
+            ),
+            EditorError::PageOutOfBounds { page, pages } => {
+                write!(f, "page {page} out of range, the data has {pages} page(s)")
+            }
+            EditorError::InvalidColumns(message) => write!(f, "invalid columns: {message}"),
+            EditorError::InvalidRange(message) => write!(f, "invalid range: {message}"),
+            EditorError::InvalidFormat(message) => write!(f, "invalid format: {message}"),
+            EditorError::Expression {
+                row: Some(row),
+                line: Some(line),
+                message,
+            } => write!(
+                f,
+                "invalid expression in row index {row} (line {line}): {message}"
+            ),
+            EditorError::Expression {
+                row: Some(row),
+                message,
+                ..
+            } => write!(f, "invalid expression in row index {row}: {message}"),
+            EditorError::Expression { message, .. } => write!(f, "invalid expression: {message}"),
+            EditorError::Compression(message) => write!(f, "compression error: {message}"),
+            EditorError::Remote(message) => write!(f, "download failed: {message}"),
+            EditorError::Encoding(message) => write!(f, "encoding error: {message}"),
+            EditorError::TypeMismatch {
+                field,
+                value,
+                expected,
+            } => write!(
+                f,
+                "'{value}' is not a valid {expected} for field index {field}"
+            ),
+            EditorError::Schema { row, message } => write!(f, "row index {row}: {message}"),
+            EditorError::HeaderMismatch { file, message } => {
+                write!(f, "the header of {file} does not match: {message}")
+            }
+            EditorError::Journal(message) => write!(f, "journal error: {message}"),
+            EditorError::Transaction(message) => write!(f, "transaction error: {message}"),
+            EditorError::Session(message) => write!(f, "session error: {message}"),
+            EditorError::InvalidBookmark(message) => write!(f, "invalid bookmark: {message}"),
+            EditorError::InvalidComment(message) => write!(f, "invalid comment: {message}"),
+            EditorError::Sqlite(message) => write!(f, "sqlite error: {message}"),
+            EditorError::Overlay(message) => write!(f, "overlay error: {message}"),
+            EditorError::Merge(message) => write!(f, "merge error: {message}"),
+            EditorError::NotFound(message) => f.write_str(message),
+            EditorError::Plugin {
+                name,
+                row: Some(row),
+                message,
+            } => write!(f, "plugin '{name}' rejected row index {row}: {message}"),
+            EditorError::Plugin { name, message, .. } => write!(f, "plugin '{name}': {message}"),
+            EditorError::Locked { file, pid } => {
+                write!(f, "{file} is being edited by another instance")?;
+                match pid {
+                    Some(pid) => write!(f, " (pid {pid})"),
+                    None => Ok(()),
+                }
+            }
+        }
+    }
+}
+
+impl std::error::Error for EditorError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            EditorError::Io(e) => Some(e),
+            EditorError::Parse { source, .. } => Some(source),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for EditorError {
+    fn from(e: io::Error) -> Self {
+        EditorError::Io(e)
+    }
+}
+
+impl From<csv::Error> for EditorError {
+    fn from(e: csv::Error) -> Self {
+        if e.is_io_error() {
+            match e.into_kind() {
+                csv::ErrorKind::Io(e) => return EditorError::Io(e),
+                _ => unreachable!("is_io_error() checked the kind"),
+            }
+        }
+        EditorError::Parse {
+            row: e.position().map(|position| position.record()),
+            source: e,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_from_csv_error() {
+        let data = "a,b\n1,2\n3,4,5\n";
+        let mut reader = csv::Reader::from_reader(data.as_bytes());
+        let error = reader
+            .records()
+            .find_map(Result::err)
+            .expect("Expected a ragged row error");
+        let error = EditorError::from(error);
+        assert!(matches!(error, EditorError::Parse { row: Some(2), .. }));
+        assert!(error.to_string().starts_with("parse error in row 2"));
+    }
+}
@@ -0,0 +1,218 @@
+//! Tables written by `export`, for pasting into issues and wikis.
+
+use std::io::{self, Write};
+
+use clap::ValueEnum;
+use csv::StringRecord;
+
+use crate::{
+    fixed_width::{self, Layout},
+    schema::ColumnType,
+    EditorError,
+};
+
+/// Stylesheet of the HTML page: a compact table with a sticky header and striped rows.
+pub(crate) const STYLE: &str = "\
+body { font-family: system-ui, sans-serif; margin: 2em; color: #222; }
+h1 { font-size: 1.3em; }
+table { border-collapse: collapse; font-size: 0.9em; }
+th, td { border: 1px solid #ccc; padding: 0.3em 0.6em; text-align: left; }
+th { background: #f0f0f0; position: sticky; top: 0; }
+tr:nth-child(even) td { background: #fafafa; }
+td.number { text-align: right; font-variant-numeric: tabular-nums; }
+";
+
+/// What `export` writes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum ExportFormat {
+    /// A GitHub-flavored Markdown table
+    Markdown,
+    /// A standalone HTML page holding a styled table
+    Html,
+    /// Fixed-width text, each column as wide as its widest value (see [`crate::fixed_width`])
+    FixedWidth,
+}
+
+/// Writes `records` to `writer` as a table in `format`, numeric columns of `types`
+/// aligned to the right. `title` heads the HTML page.
+pub fn write<W: Write>(
+    format: ExportFormat,
+    title: &str,
+    headers: &StringRecord,
+    records: &[StringRecord],
+    types: &[ColumnType],
+    writer: &mut W,
+) -> Result<(), EditorError> {
+    match format {
+        ExportFormat::Markdown => write_markdown(headers, records, types, writer)?,
+        ExportFormat::Html => write_html(title, headers, records, types, writer)?,
+        ExportFormat::FixedWidth => {
+            let layout = Layout::fit(headers, records);
+            fixed_width::write(&layout, Some(headers), records, types, writer)?;
+        }
+    }
+    Ok(())
+}
+
+fn is_number(types: &[ColumnType], field: usize) -> bool {
+    matches!(
+        types.get(field),
+        Some(ColumnType::Integer | ColumnType::Float)
+    )
+}
+
+/// Returns the number of columns of the table: the widest of the header and the records.
+fn width(headers: &StringRecord, records: &[StringRecord]) -> usize {
+    records
+        .iter()
+        .map(StringRecord::len)
+        .fold(headers.len(), usize::max)
+}
+
+/// Writes a Markdown table. Markdown tables need a header, so files without one get
+/// the column numbers.
+fn write_markdown<W: Write>(
+    headers: &StringRecord,
+    records: &[StringRecord],
+    types: &[ColumnType],
+    writer: &mut W,
+) -> io::Result<()> {
+    let width = width(headers, records);
+    let row = |writer: &mut W, cells: Vec<String>| -> io::Result<()> {
+        writeln!(writer, "| {} |", cells.join(" | "))
+    };
+    let names = (0..width)
+        .map(|i| match headers.get(i) {
+            Some(name) if !headers.is_empty() => escape_markdown(name),
+            _ => (i + 1).to_string(),
+        })
+        .collect();
+    row(writer, names)?;
+    let rule = (0..width)
+        .map(|i| if is_number(types, i) { "---:" } else { "---" }.to_string())
+        .collect();
+    row(writer, rule)?;
+    for record in records {
+        let cells = (0..width)
+            .map(|i| escape_markdown(record.get(i).unwrap_or("")))
+            .collect();
+        row(writer, cells)?;
+    }
+    Ok(())
+}
+
+/// Escapes the pipes that would end a Markdown cell, and turns line breaks into `<br>`.
+fn escape_markdown(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('|', "\\|")
+        .replace("\r\n", "<br>")
+        .replace(['\n', '\r'], "<br>")
+}
+
+/// Writes a standalone HTML page holding the table. Files without a header get no `<thead>`.
+fn write_html<W: Write>(
+    title: &str,
+    headers: &StringRecord,
+    records: &[StringRecord],
+    types: &[ColumnType],
+    writer: &mut W,
+) -> io::Result<()> {
+    let width = width(headers, records);
+    let title = escape_html(title);
+    writeln!(writer, "<!DOCTYPE html>")?;
+    writeln!(writer, "<html>\n<head>\n<meta charset=\"utf-8\">")?;
+    writeln!(writer, "<title>{title}</title>\n<style>\n{STYLE}</style>")?;
+    writeln!(writer, "</head>\n<body>\n<h1>{title}</h1>\n<table>")?;
+    if !headers.is_empty() {
+        write!(writer, "<thead>\n<tr>")?;
+        for i in 0..width {
+            write!(
+                writer,
+                "<th>{}</th>",
+                escape_html(headers.get(i).unwrap_or(""))
+            )?;
+        }
+        writeln!(writer, "</tr>\n</thead>")?;
+    }
+    writeln!(writer, "<tbody>")?;
+    for record in records {
+        write!(writer, "<tr>")?;
+        for i in 0..width {
+            let value = escape_html(record.get(i).unwrap_or(""));
+            if is_number(types, i) {
+                write!(writer, "<td class=\"number\">{value}</td>")?;
+            } else {
+                write!(writer, "<td>{value}</td>")?;
+            }
+        }
+        writeln!(writer, "</tr>")?;
+    }
+    writeln!(writer, "</tbody>\n</table>\n</body>\n</html>")
+}
+
+pub(crate) fn escape_html(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_write() {
+        let headers = StringRecord::from(vec!["name", "price"]);
+        let records = [
+            StringRecord::from(vec!["a|b", "1.5"]),
+            StringRecord::from(vec!["two\nlines", "10"]),
+        ];
+        let types = [ColumnType::Text, ColumnType::Float];
+        let mut output = Vec::new();
+        write(
+            ExportFormat::Markdown,
+            "",
+            &headers,
+            &records,
+            &types,
+            &mut output,
+        )
+        .unwrap();
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            "| name | price |\n| --- | ---: |\n| a\\|b | 1.5 |\n| two<br>lines | 10 |\n"
+        );
+
+        let mut output = Vec::new();
+        write_markdown(&StringRecord::new(), &records[..1], &[], &mut output).unwrap();
+        assert!(String::from_utf8(output)
+            .unwrap()
+            .starts_with("| 1 | 2 |\n| --- | --- |\n"));
+
+        let mut output = Vec::new();
+        let headers = StringRecord::from(vec!["<b>", "price"]);
+        write(
+            ExportFormat::Html,
+            "R&D",
+            &headers,
+            &records,
+            &types,
+            &mut output,
+        )
+        .unwrap();
+        let page = String::from_utf8(output).unwrap();
+        assert!(page.contains("<title>R&amp;D</title>"));
+        assert!(page.contains("<th>&lt;b&gt;</th><th>price</th>"));
+        assert!(page.contains("<td>a|b</td><td class=\"number\">1.5</td>"));
+    }
+}
@@ -0,0 +1,641 @@
+use std::{cmp::Ordering, fmt};
+
+use csv::StringRecord;
+
+use crate::{columns, EditorError};
+
+/// A value computed by an expression.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    /// An empty cell. Arithmetic on an empty value gives an empty value.
+    Empty,
+    Number(f64),
+    Text(String),
+    Bool(bool),
+}
+
+impl Value {
+    /// Reads a cell: numbers become numbers, empty cells are empty, anything else is text.
+    fn from_cell(cell: &str) -> Value {
+        let trimmed = cell.trim();
+        if trimmed.is_empty() {
+            Value::Empty
+        } else {
+            match trimmed.parse::<f64>() {
+                Ok(number) if number.is_finite() => Value::Number(number),
+                _ => Value::Text(cell.to_string()),
+            }
+        }
+    }
+
+    /// Returns whether the value counts as true in a filter: true, a non-zero number or non-empty text.
+    pub fn is_truthy(&self) -> bool {
+        match self {
+            Value::Empty => false,
+            Value::Number(number) => *number != 0.0,
+            Value::Text(text) => !text.is_empty(),
+            Value::Bool(value) => *value,
+        }
+    }
+
+    fn number(&self, op: &str) -> Result<Option<f64>, EditorError> {
+        match self {
+            Value::Empty => Ok(None),
+            Value::Number(number) => Ok(Some(*number)),
+            Value::Bool(_) | Value::Text(_) => Err(error(format!(
+                "'{self}' is not a number and cannot be used with '{op}'"
+            ))),
+        }
+    }
+}
+
+impl fmt::Display for Value {
+    /// Writes the value as it is stored in a cell. Whole numbers have no decimals.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Value::Empty => Ok(()),
+            Value::Number(number) if number.fract() == 0.0 && number.abs() < 1e15 => {
+                write!(f, "{}", *number as i64)
+            }
+            Value::Number(number) => write!(f, "{number}"),
+            Value::Text(text) => f.write_str(text),
+            Value::Bool(value) => write!(f, "{value}"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum BinOp {
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Rem,
+    Concat,
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    And,
+    Or,
+}
+
+impl BinOp {
+    /// Returns how tightly the operator binds; higher binds tighter.
+    fn precedence(self) -> u8 {
+        match self {
+            BinOp::Or => 1,
+            BinOp::And => 2,
+            BinOp::Eq | BinOp::Ne | BinOp::Lt | BinOp::Le | BinOp::Gt | BinOp::Ge => 3,
+            BinOp::Concat => 4,
+            BinOp::Add | BinOp::Sub => 5,
+            BinOp::Mul | BinOp::Div | BinOp::Rem => 6,
+        }
+    }
+
+    fn symbol(self) -> &'static str {
+        match self {
+            BinOp::Add => "+",
+            BinOp::Sub => "-",
+            BinOp::Mul => "*",
+            BinOp::Div => "/",
+            BinOp::Rem => "%",
+            BinOp::Concat => "&",
+            BinOp::Eq => "==",
+            BinOp::Ne => "!=",
+            BinOp::Lt => "<",
+            BinOp::Le => "<=",
+            BinOp::Gt => ">",
+            BinOp::Ge => ">=",
+            BinOp::And => "and",
+            BinOp::Or => "or",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Func {
+    Upper,
+    Lower,
+    Trim,
+    Len,
+    Abs,
+    Round,
+    Contains,
+    If,
+}
+
+impl Func {
+    fn from_name(name: &str) -> Option<Func> {
+        match name.to_ascii_lowercase().as_str() {
+            "upper" => Some(Func::Upper),
+            "lower" => Some(Func::Lower),
+            "trim" => Some(Func::Trim),
+            "len" => Some(Func::Len),
+            "abs" => Some(Func::Abs),
+            "round" => Some(Func::Round),
+            "contains" => Some(Func::Contains),
+            "if" => Some(Func::If),
+            _ => None,
+        }
+    }
+
+    /// Returns the accepted numbers of arguments.
+    fn arity(self) -> (usize, usize) {
+        match self {
+            Func::Upper | Func::Lower | Func::Trim | Func::Len | Func::Abs => (1, 1),
+            Func::Round => (1, 2),
+            Func::Contains => (2, 2),
+            Func::If => (3, 3),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Node {
+    Literal(Value),
+    /// A column of the record (0-based).
+    Column(usize),
+    Neg(Box<Node>),
+    Not(Box<Node>),
+    Binary(BinOp, Box<Node>, Box<Node>),
+    Call(Func, Vec<Node>),
+}
+
+/// An expression over the columns of a row, used by `add-col --expr` and `--filter`.
+///
+/// Columns are referred to by header name (`price`), by name in backticks when it is not
+/// a plain word (`` `unit price` ``), or by 1-based number (`$3`). Values are numbers, text in
+/// single or double quotes, `true` and `false`. The operators are `+ - * / %`, `&` to join
+/// text, `== != < <= > >=`, and `and`, `or`, `not`. `+` joins text when either side is
+/// not a number. The functions are `upper`, `lower`, `trim`, `len`, `abs`, `round(x[, decimals])`,
+/// `contains(text, part)` and `if(condition, then, else)`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Expr {
+    root: Node,
+}
+
+impl Expr {
+    /// Parses `text`, resolving column names against `headers`.
+    pub fn parse(text: &str, headers: &StringRecord, fields: usize) -> Result<Expr, EditorError> {
+        let tokens = tokenize(text)?;
+        let mut parser = Parser {
+            tokens: &tokens,
+            position: 0,
+            headers,
+            fields,
+        };
+        let root = parser.expression(0)?;
+        if let Some(token) = parser.peek() {
+            return Err(error(format!("unexpected {token} in '{text}'")));
+        }
+        Ok(Expr { root })
+    }
+
+    /// Evaluates the expression on `record`.
+    pub fn eval(&self, record: &StringRecord) -> Result<Value, EditorError> {
+        eval(&self.root, record)
+    }
+
+    /// Returns whether the expression is true for `record` (see [`Value::is_truthy`]).
+    pub fn matches(&self, record: &StringRecord) -> Result<bool, EditorError> {
+        Ok(self.eval(record)?.is_truthy())
+    }
+}
+
+fn error(message: String) -> EditorError {
+    EditorError::Expression {
+        row: None,
+        line: None,
+        message,
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Number(f64),
+    Text(String),
+    Word(String),
+    /// A column named in backticks or by `$N`.
+    Column(String),
+    Op(BinOp),
+    Not,
+    LeftParen,
+    RightParen,
+    Comma,
+}
+
+impl fmt::Display for Token {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Token::Number(number) => write!(f, "number {number}"),
+            Token::Text(text) => write!(f, "text '{text}'"),
+            Token::Word(word) => write!(f, "'{word}'"),
+            Token::Column(column) => write!(f, "column '{column}'"),
+            Token::Op(op) => write!(f, "'{}'", op.symbol()),
+            Token::Not => f.write_str("'not'"),
+            Token::LeftParen => f.write_str("'('"),
+            Token::RightParen => f.write_str("')'"),
+            Token::Comma => f.write_str("','"),
+        }
+    }
+}
+
+fn tokenize(text: &str) -> Result<Vec<Token>, EditorError> {
+    let mut tokens = Vec::new();
+    let mut chars = text.char_indices().peekable();
+    while let Some(&(start, c)) = chars.peek() {
+        chars.next();
+        let next = chars.peek().map(|&(_, c)| c);
+        let token = match c {
+            c if c.is_whitespace() => continue,
+            '(' => Token::LeftParen,
+            ')' => Token::RightParen,
+            ',' => Token::Comma,
+            '+' => Token::Op(BinOp::Add),
+            '-' => Token::Op(BinOp::Sub),
+            '*' => Token::Op(BinOp::Mul),
+            '/' => Token::Op(BinOp::Div),
+            '%' => Token::Op(BinOp::Rem),
+            '&' if next == Some('&') => {
+                chars.next();
+                Token::Op(BinOp::And)
+            }
+            '&' => Token::Op(BinOp::Concat),
+            '|' if next == Some('|') => {
+                chars.next();
+                Token::Op(BinOp::Or)
+            }
+            '=' | '!' | '<' | '>' => {
+                let (op, long) = match (c, next) {
+                    ('=', Some('=')) => (Some(BinOp::Eq), true),
+                    ('=', _) => (Some(BinOp::Eq), false),
+                    ('!', Some('=')) => (Some(BinOp::Ne), true),
+                    ('!', _) => (None, false),
+                    ('<', Some('=')) => (Some(BinOp::Le), true),
+                    ('<', Some('>')) => (Some(BinOp::Ne), true),
+                    ('<', _) => (Some(BinOp::Lt), false),
+                    ('>', Some('=')) => (Some(BinOp::Ge), true),
+                    _ => (Some(BinOp::Gt), false),
+                };
+                if long {
+                    chars.next();
+                }
+                op.map_or(Token::Not, Token::Op)
+            }
+            '\'' | '"' | '`' => {
+                let mut value = String::new();
+                loop {
+                    match chars.next() {
+                        // A doubled quote stands for the quote itself.
+                        Some((_, q)) if q == c && chars.peek().map(|&(_, c)| c) == Some(c) => {
+                            chars.next();
+                            value.push(c);
+                        }
+                        Some((_, q)) if q == c => break,
+                        Some((_, q)) => value.push(q),
+                        None => return Err(error(format!("unclosed {c} in '{text}'"))),
+                    }
+                }
+                if c == '`' {
+                    Token::Column(value)
+                } else {
+                    Token::Text(value)
+                }
+            }
+            '$' => {
+                let mut number = String::new();
+                while let Some(&(_, d)) = chars.peek().filter(|(_, d)| d.is_ascii_digit()) {
+                    number.push(d);
+                    chars.next();
+                }
+                if number.is_empty() {
+                    return Err(error(format!(
+                        "expected a column number after '$' in '{text}'"
+                    )));
+                }
+                Token::Column(number)
+            }
+            c if c.is_ascii_digit() || c == '.' => {
+                let mut end = start + c.len_utf8();
+                while let Some(&(i, d)) = chars.peek() {
+                    if !(d.is_ascii_digit() || d == '.') {
+                        break;
+                    }
+                    end = i + d.len_utf8();
+                    chars.next();
+                }
+                let literal = &text[start..end];
+                let number = literal
+                    .parse()
+                    .map_err(|_| error(format!("invalid number '{literal}'")))?;
+                Token::Number(number)
+            }
+            c if c.is_alphabetic() || c == '_' => {
+                let mut end = start + c.len_utf8();
+                while let Some(&(i, d)) = chars.peek() {
+                    if !(d.is_alphanumeric() || d == '_') {
+                        break;
+                    }
+                    end = i + d.len_utf8();
+                    chars.next();
+                }
+                match text[start..end].to_ascii_lowercase().as_str() {
+                    "and" => Token::Op(BinOp::And),
+                    "or" => Token::Op(BinOp::Or),
+                    "not" => Token::Not,
+                    _ => Token::Word(text[start..end].to_string()),
+                }
+            }
+            c => return Err(error(format!("unexpected '{c}' in '{text}'"))),
+        };
+        tokens.push(token);
+    }
+    Ok(tokens)
+}
+
+struct Parser<'a> {
+    tokens: &'a [Token],
+    position: usize,
+    headers: &'a StringRecord,
+    fields: usize,
+}
+
+impl Parser<'_> {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.position)
+    }
+
+    fn next(&mut self) -> Option<&Token> {
+        let token = self.tokens.get(self.position);
+        self.position += 1;
+        token
+    }
+
+    fn expect(&mut self, expected: Token) -> Result<(), EditorError> {
+        match self.next() {
+            Some(token) if *token == expected => Ok(()),
+            Some(token) => Err(error(format!("expected {expected}, found {token}"))),
+            None => Err(error(format!("expected {expected} at the end"))),
+        }
+    }
+
+    /// Parses operators binding tighter than `min_precedence`, by precedence climbing.
+    fn expression(&mut self, min_precedence: u8) -> Result<Node, EditorError> {
+        let mut left = self.unary()?;
+        while let Some(&Token::Op(op)) = self.peek() {
+            if op.precedence() <= min_precedence {
+                break;
+            }
+            self.position += 1;
+            let right = self.expression(op.precedence())?;
+            left = Node::Binary(op, Box::new(left), Box::new(right));
+        }
+        Ok(left)
+    }
+
+    fn unary(&mut self) -> Result<Node, EditorError> {
+        match self.peek() {
+            Some(Token::Op(BinOp::Sub)) => {
+                self.position += 1;
+                Ok(Node::Neg(Box::new(self.unary()?)))
+            }
+            Some(Token::Not) => {
+                self.position += 1;
+                // `not` applies to a whole comparison, as in `not price > 10`.
+                let operand = self.expression(BinOp::And.precedence())?;
+                Ok(Node::Not(Box::new(operand)))
+            }
+            _ => self.primary(),
+        }
+    }
+
+    fn primary(&mut self) -> Result<Node, EditorError> {
+        let token = self
+            .next()
+            .cloned()
+            .ok_or_else(|| error("unexpected end of expression".to_string()))?;
+        match token {
+            Token::Number(number) => Ok(Node::Literal(Value::Number(number))),
+            Token::Text(text) => Ok(Node::Literal(Value::Text(text))),
+            Token::Column(name) => self.column(&name),
+            Token::Word(word) if self.peek() == Some(&Token::LeftParen) => {
+                let func = Func::from_name(&word)
+                    .ok_or_else(|| error(format!("unknown function '{word}'")))?;
+                self.position += 1;
+                let mut arguments = Vec::new();
+                if self.peek() != Some(&Token::RightParen) {
+                    loop {
+                        arguments.push(self.expression(0)?);
+                        if self.peek() != Some(&Token::Comma) {
+                            break;
+                        }
+                        self.position += 1;
+                    }
+                }
+                self.expect(Token::RightParen)?;
+                let (min, max) = func.arity();
+                if arguments.len() < min || arguments.len() > max {
+                    return Err(error(format!(
+                        "'{word}' takes {}, got {}",
+                        if min == max {
+                            format!("{min} argument(s)")
+                        } else {
+                            format!("{min} to {max} arguments")
+                        },
+                        arguments.len()
+                    )));
+                }
+                Ok(Node::Call(func, arguments))
+            }
+            Token::Word(word) if word.eq_ignore_ascii_case("true") => {
+                Ok(Node::Literal(Value::Bool(true)))
+            }
+            Token::Word(word) if word.eq_ignore_ascii_case("false") => {
+                Ok(Node::Literal(Value::Bool(false)))
+            }
+            Token::Word(word) => self.column(&word),
+            Token::LeftParen => {
+                let node = self.expression(0)?;
+                self.expect(Token::RightParen)?;
+                Ok(node)
+            }
+            token => Err(error(format!("unexpected {token}"))),
+        }
+    }
+
+    fn column(&self, name: &str) -> Result<Node, EditorError> {
+        let column = columns::parse_column(name, self.headers, self.fields)
+            .map_err(|_| error(format!("unknown column '{name}'")))?;
+        Ok(Node::Column(column))
+    }
+}
+
+fn eval(node: &Node, record: &StringRecord) -> Result<Value, EditorError> {
+    let value = match node {
+        Node::Literal(value) => value.clone(),
+        Node::Column(column) => Value::from_cell(record.get(*column).unwrap_or("")),
+        Node::Neg(operand) => match eval(operand, record)?.number("-")? {
+            Some(number) => Value::Number(-number),
+            None => Value::Empty,
+        },
+        Node::Not(operand) => Value::Bool(!eval(operand, record)?.is_truthy()),
+        Node::Binary(BinOp::And, left, right) => {
+            Value::Bool(eval(left, record)?.is_truthy() && eval(right, record)?.is_truthy())
+        }
+        Node::Binary(BinOp::Or, left, right) => {
+            Value::Bool(eval(left, record)?.is_truthy() || eval(right, record)?.is_truthy())
+        }
+        Node::Binary(op, left, right) => binary(*op, eval(left, record)?, eval(right, record)?)?,
+        Node::Call(func, arguments) => {
+            if *func == Func::If {
+                let branch = if eval(&arguments[0], record)?.is_truthy() {
+                    &arguments[1]
+                } else {
+                    &arguments[2]
+                };
+                return eval(branch, record);
+            }
+            let arguments = arguments
+                .iter()
+                .map(|argument| eval(argument, record))
+                .collect::<Result<Vec<_>, _>>()?;
+            call(*func, &arguments)?
+        }
+    };
+    Ok(value)
+}
+
+fn binary(op: BinOp, left: Value, right: Value) -> Result<Value, EditorError> {
+    let value = match op {
+        BinOp::Concat => Value::Text(format!("{left}{right}")),
+        BinOp::Add if matches!(left, Value::Text(_)) || matches!(right, Value::Text(_)) => {
+            Value::Text(format!("{left}{right}"))
+        }
+        BinOp::Add | BinOp::Sub | BinOp::Mul | BinOp::Div | BinOp::Rem => {
+            let (Some(a), Some(b)) = (left.number(op.symbol())?, right.number(op.symbol())?) else {
+                return Ok(Value::Empty);
+            };
+            let number = match op {
+                BinOp::Add => a + b,
+                BinOp::Sub => a - b,
+                BinOp::Mul => a * b,
+                BinOp::Div if b == 0.0 => return Err(error("division by zero".to_string())),
+                BinOp::Div => a / b,
+                BinOp::Rem if b == 0.0 => return Err(error("division by zero".to_string())),
+                _ => a % b,
+            };
+            Value::Number(number)
+        }
+        _ => {
+            let ordering = compare(&left, &right);
+            Value::Bool(match op {
+                BinOp::Eq => ordering == Ordering::Equal,
+                BinOp::Ne => ordering != Ordering::Equal,
+                BinOp::Lt => ordering == Ordering::Less,
+                BinOp::Le => ordering != Ordering::Greater,
+                BinOp::Gt => ordering == Ordering::Greater,
+                _ => ordering != Ordering::Less,
+            })
+        }
+    };
+    Ok(value)
+}
+
+/// Compares numerically when both values are numbers, and as text otherwise.
+fn compare(left: &Value, right: &Value) -> Ordering {
+    match (left, right) {
+        (Value::Number(a), Value::Number(b)) => a.total_cmp(b),
+        _ => left.to_string().cmp(&right.to_string()),
+    }
+}
+
+fn call(func: Func, arguments: &[Value]) -> Result<Value, EditorError> {
+    let text = |i: usize| arguments[i].to_string();
+    let value = match func {
+        Func::Upper => Value::Text(text(0).to_uppercase()),
+        Func::Lower => Value::Text(text(0).to_lowercase()),
+        Func::Trim => Value::Text(text(0).trim().to_string()),
+        Func::Len => Value::Number(text(0).chars().count() as f64),
+        Func::Contains => Value::Bool(text(0).contains(&text(1))),
+        Func::Abs => match arguments[0].number("abs")? {
+            Some(number) => Value::Number(number.abs()),
+            None => Value::Empty,
+        },
+        Func::Round => {
+            let decimals = match arguments.get(1) {
+                Some(decimals) => decimals.number("round")?.unwrap_or(0.0),
+                None => 0.0,
+            };
+            match arguments[0].number("round")? {
+                Some(number) => {
+                    let scale = 10f64.powi(decimals as i32);
+                    Value::Number((number * scale).round() / scale)
+                }
+                None => Value::Empty,
+            }
+        }
+        Func::If => unreachable!("if is evaluated lazily"),
+    };
+    Ok(value)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn eval_on(text: &str, values: Vec<&str>) -> Result<String, EditorError> {
+        let headers = StringRecord::from(vec!["name", "price", "qty", "unit price"]);
+        let expr = Expr::parse(text, &headers, 4)?;
+        Ok(expr.eval(&StringRecord::from(values))?.to_string())
+    }
+
+    #[test]
+    fn test_eval() {
+        let row = || vec!["Tea", "2.5", "4", "0.625"];
+        assert_eq!(eval_on("price * qty", row()).unwrap(), "10");
+        assert_eq!(eval_on("1 + 2 * -3", row()).unwrap(), "-5");
+        assert_eq!(eval_on("(1 + 2) * 3", row()).unwrap(), "9");
+        assert_eq!(eval_on("name & ' x' & $3", row()).unwrap(), "Tea x4");
+        assert_eq!(eval_on("name + qty", row()).unwrap(), "Tea4");
+        assert_eq!(eval_on("round(`unit price`, 1)", row()).unwrap(), "0.6");
+        assert_eq!(
+            eval_on(
+                "if(qty >= 4 and not name == 'Coffee', upper(name), '')",
+                row()
+            )
+            .unwrap(),
+            "TEA"
+        );
+        assert_eq!(
+            eval_on("price * qty", vec!["Tea", "", "4", ""]).unwrap(),
+            ""
+        );
+        assert!(eval_on("name * 2", row()).is_err());
+        assert!(eval_on("price / 0", row()).is_err());
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        let headers = StringRecord::from(vec!["price"]);
+        for text in [
+            "price *",
+            "cost > 1",
+            "(price",
+            "price price",
+            "'open",
+            "shout(price)",
+        ] {
+            assert!(
+                Expr::parse(text, &headers, 1).is_err(),
+                "'{text}' should not parse"
+            );
+        }
+        assert!(Expr::parse("len()", &headers, 1).is_err());
+        let filter = Expr::parse("price > 9 || price < 1", &headers, 1).unwrap();
+        assert!(filter.matches(&StringRecord::from(vec!["10"])).unwrap());
+        assert!(!filter.matches(&StringRecord::from(vec!["5"])).unwrap());
+    }
+}
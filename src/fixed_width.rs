@@ -0,0 +1,249 @@
+//! Fixed-width text, where every column takes the same number of characters on each line,
+//! as in the feeds of mainframe systems. `--fixed-width` reads it, `export --format
+//! fixed-width` writes it, and `widths` measures the values to lay it out.
+
+use std::{fmt, io::Write, str::FromStr};
+
+use csv::StringRecord;
+
+use crate::{schema::ColumnType, EditorError};
+
+/// Widths, in characters, of the columns of fixed-width text, and optionally their names.
+/// Written `10,5,8`, or `id:10,name:5,city:8` to name the columns.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Layout {
+    /// Names of the columns, or empty when the layout does not name them.
+    pub names: Vec<String>,
+    pub widths: Vec<usize>,
+}
+
+impl FromStr for Layout {
+    type Err = EditorError;
+
+    fn from_str(spec: &str) -> Result<Layout, EditorError> {
+        let invalid = |message: String| EditorError::InvalidFormat(format!("layout: {message}"));
+        let mut names = Vec::new();
+        let mut widths = Vec::new();
+        let mut named = None;
+        for column in spec.split(',') {
+            let (name, width) = match column.rsplit_once(':') {
+                Some((name, width)) => (Some(name.trim()), width.trim()),
+                None => (None, column.trim()),
+            };
+            let width = match width.parse() {
+                Ok(width) if width > 0 => width,
+                _ => return Err(invalid(format!("invalid width '{width}'"))),
+            };
+            if *named.get_or_insert(name.is_some()) != name.is_some() {
+                return Err(invalid("name every column or none".to_string()));
+            }
+            if let Some(name) = name {
+                names.push(name.to_string());
+            }
+            widths.push(width);
+        }
+        Ok(Layout { names, widths })
+    }
+}
+
+impl fmt::Display for Layout {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, width) in self.widths.iter().enumerate() {
+            if i > 0 {
+                f.write_str(",")?;
+            }
+            match self.names.get(i) {
+                Some(name) => write!(f, "{name}:{width}")?,
+                None => write!(f, "{width}")?,
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Layout {
+    /// Returns the layout fitting the header and `records`: each column as wide as its
+    /// widest value or name, and named after the header.
+    pub fn fit(headers: &StringRecord, records: &[StringRecord]) -> Layout {
+        Layout {
+            names: headers.iter().map(str::to_string).collect(),
+            widths: width_stats(headers, records)
+                .iter()
+                .enumerate()
+                .map(|(i, stats)| {
+                    let name = headers.get(i).map_or(0, |name| name.chars().count());
+                    stats.max.max(name).max(1)
+                })
+                .collect(),
+        }
+    }
+
+    /// Splits fixed-width `text` into a header, rows and the number of fields, trimming
+    /// the spaces that pad the values. A layout naming its columns gives the header, and
+    /// otherwise the first line does when `has_headers` is set. Blank lines are skipped,
+    /// short lines get empty values, and characters past the last column are ignored.
+    pub fn parse(&self, text: &str, has_headers: bool) -> (StringRecord, Vec<StringRecord>, usize) {
+        let mut lines = text
+            .lines()
+            .filter(|line| !line.trim().is_empty())
+            .map(|line| self.split(line));
+        let headers = match (self.names.is_empty(), has_headers) {
+            (false, _) => self.names.iter().collect(),
+            (true, true) => lines.next().unwrap_or_default(),
+            (true, false) => StringRecord::new(),
+        };
+        (headers, lines.collect(), self.widths.len())
+    }
+
+    /// Cuts `line` into the columns of the layout, trimmed.
+    fn split(&self, line: &str) -> StringRecord {
+        let mut rest = line;
+        self.widths
+            .iter()
+            .map(|&width| {
+                let end = rest
+                    .char_indices()
+                    .nth(width)
+                    .map_or(rest.len(), |(i, _)| i);
+                let (value, after) = rest.split_at(end);
+                rest = after;
+                value.trim()
+            })
+            .collect()
+    }
+}
+
+/// Width, in characters, of the values of a column, header left out.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct WidthStats {
+    pub min: usize,
+    pub max: usize,
+    pub mean: f64,
+}
+
+/// Returns the width of the values of each column. Columns without values have
+/// the width of their name.
+pub fn width_stats(headers: &StringRecord, records: &[StringRecord]) -> Vec<WidthStats> {
+    let fields = records
+        .iter()
+        .map(StringRecord::len)
+        .fold(headers.len(), usize::max);
+    (0..fields)
+        .map(|i| {
+            let widths: Vec<usize> = records
+                .iter()
+                .map(|record| record.get(i).map_or(0, |value| value.chars().count()))
+                .collect();
+            match (widths.iter().min(), widths.iter().max()) {
+                (Some(&min), Some(&max)) => WidthStats {
+                    min,
+                    max,
+                    mean: widths.iter().sum::<usize>() as f64 / widths.len() as f64,
+                },
+                _ => {
+                    let width = headers.get(i).map_or(0, |name| name.chars().count());
+                    WidthStats {
+                        min: width,
+                        max: width,
+                        mean: width as f64,
+                    }
+                }
+            }
+        })
+        .collect()
+}
+
+/// Writes `records`, preceded by `headers` if given, as fixed-width text laid out as
+/// `layout`: values are padded with spaces, on the left in the numeric columns of `types`
+/// and on the right in the others. Returns an error if a value does not fit in its column.
+pub fn write<W: Write>(
+    layout: &Layout,
+    headers: Option<&StringRecord>,
+    records: &[StringRecord],
+    types: &[ColumnType],
+    writer: &mut W,
+) -> Result<(), EditorError> {
+    let mut line = String::new();
+    let headers = headers.filter(|headers| !headers.is_empty());
+    for (row, record) in headers.into_iter().chain(records).enumerate() {
+        // Rows are counted from the first record, after the header.
+        let row = row.checked_sub(usize::from(headers.is_some()));
+        line.clear();
+        for (i, &width) in layout.widths.iter().enumerate() {
+            let value = record.get(i).unwrap_or("");
+            let length = value.chars().count();
+            if length > width {
+                let message = format!(
+                    "'{value}' does not fit in the {width} character(s) of column {}",
+                    i + 1
+                );
+                return Err(match row {
+                    Some(row) => EditorError::Schema { row, message },
+                    None => EditorError::InvalidFormat(format!("header {message}")),
+                });
+            }
+            let padding = " ".repeat(width - length);
+            let numeric = matches!(types.get(i), Some(ColumnType::Integer | ColumnType::Float));
+            if numeric && row.is_some() {
+                line.push_str(&padding);
+                line.push_str(value);
+            } else {
+                line.push_str(value);
+                line.push_str(&padding);
+            }
+        }
+        writeln!(writer, "{line}")?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_layout() {
+        let layout: Layout = "id:3, name:6".parse().unwrap();
+        assert_eq!(layout.names, vec!["id", "name"]);
+        assert_eq!(layout.widths, vec![3, 6]);
+        assert_eq!(layout.to_string(), "id:3,name:6");
+        assert_eq!("4,2".parse::<Layout>().unwrap().names, Vec::<String>::new());
+        assert!("id:3,6".parse::<Layout>().is_err());
+        assert!("3,name:6".parse::<Layout>().is_err());
+        assert!("3,0".parse::<Layout>().is_err());
+        assert!("3,x".parse::<Layout>().is_err());
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let layout: Layout = "3,8,5".parse().unwrap();
+        let text = "ID NAME    PRICE\n  1Ann Lee  1.50\n\n 12Zoë        20 trailing\n  3\n";
+        let (headers, records, fields) = layout.parse(text, true);
+        assert_eq!(headers, StringRecord::from(vec!["ID", "NAME", "PRICE"]));
+        assert_eq!(fields, 3);
+        assert_eq!(
+            records,
+            vec![
+                StringRecord::from(vec!["1", "Ann Lee", "1.50"]),
+                StringRecord::from(vec!["12", "Zoë", "20"]),
+                StringRecord::from(vec!["3", "", ""]),
+            ]
+        );
+
+        let types = [ColumnType::Integer, ColumnType::Text, ColumnType::Float];
+        let mut output = Vec::new();
+        write(&layout, Some(&headers), &records, &types, &mut output).unwrap();
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            "ID NAME    PRICE\n  1Ann Lee  1.50\n 12Zoë        20\n  3             \n"
+        );
+
+        let fitted = Layout::fit(&headers, &records);
+        assert_eq!(fitted.to_string(), "ID:2,NAME:7,PRICE:5");
+        let narrow: Layout = "3,2,5".parse().unwrap();
+        let error = write(&narrow, None, &records, &types, &mut Vec::new()).unwrap_err();
+        assert!(error
+            .to_string()
+            .starts_with("row index 0: 'Ann Lee' does not fit"));
+    }
+}
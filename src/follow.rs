@@ -0,0 +1,150 @@
+use std::{
+    fs::File,
+    io::{Read, Seek, SeekFrom},
+};
+
+use csv::{ReaderBuilder, StringRecord};
+use encoding_rs::UTF_8;
+
+use crate::{compression::Compression, EditorError, ReadOptions};
+
+const BOM: &[u8] = b"\xef\xbb\xbf";
+
+/// Reads records appended to a growing CSV file, like `tail -f`.
+/// Each [`Follower::poll`] parses only the bytes written since the previous one;
+/// a record is returned once its line is complete.
+pub struct Follower {
+    file: File,
+    file_name: String,
+    options: ReadOptions,
+    /// Bytes of the file read so far.
+    offset: u64,
+    /// Bytes read but not yet part of a complete record.
+    pending: Vec<u8>,
+    headers: StringRecord,
+}
+
+impl Follower {
+    /// Opens `file_name` for following. Nothing is read until the first [`Follower::poll`],
+    /// which returns the records already in the file.
+    /// Only UTF-8 files can be followed.
+    pub fn open(file_name: &str, options: ReadOptions) -> Result<Follower, EditorError> {
+        if options.encoding.is_some_and(|encoding| encoding != UTF_8) {
+            return Err(EditorError::Encoding(
+                "only UTF-8 files can be followed".to_string(),
+            ));
+        }
+        let mut file = File::open(file_name)?;
+        let mut start = Vec::new();
+        (&mut file).take(8).read_to_end(&mut start)?;
+        if let Some(compression) = Compression::detect(&start) {
+            return Err(EditorError::Compression(format!(
+                "{compression} files cannot be followed"
+            )));
+        }
+        Ok(Follower {
+            file,
+            file_name: file_name.to_string(),
+            options,
+            offset: 0,
+            pending: Vec::new(),
+            headers: StringRecord::new(),
+        })
+    }
+
+    /// Returns the header, or an empty record if the file has none or it was not written yet.
+    pub fn headers(&self) -> &StringRecord {
+        &self.headers
+    }
+
+    /// Returns the records completed since the last call.
+    /// If the file shrank, it is assumed to have been truncated and is read again from the start.
+    pub fn poll(&mut self) -> Result<Vec<StringRecord>, EditorError> {
+        if self.file.metadata()?.len() < self.offset {
+            log::warn!(
+                "{} was truncated, reading it from the start",
+                self.file_name
+            );
+            self.offset = 0;
+            self.pending.clear();
+            self.headers = StringRecord::new();
+        }
+        self.file.seek(SeekFrom::Start(self.offset))?;
+        let read = self.file.read_to_end(&mut self.pending)?;
+        if self.offset == 0 && self.pending.starts_with(BOM) {
+            self.pending.drain(..BOM.len());
+        }
+        self.offset += read as u64;
+
+        let complete = complete_len(&self.pending);
+        let mut reader = ReaderBuilder::new()
+            .delimiter(self.options.delimiter)
+            .has_headers(false)
+            .flexible(true)
+            .from_reader(&self.pending[..complete]);
+        let mut records = Vec::new();
+        for record in reader.records() {
+            let record = record?;
+            if self.options.has_headers && self.headers.is_empty() {
+                self.headers = record;
+            } else {
+                records.push(record);
+            }
+        }
+        self.pending.drain(..complete);
+        log::debug!(
+            file = self.file_name.as_str(),
+            records = records.len(),
+            offset = self.offset;
+            "followed file"
+        );
+        Ok(records)
+    }
+}
+
+/// Returns the length of the longest prefix of `bytes` made of complete lines.
+/// Line breaks inside quoted fields do not end a line; quotes inside a quoted field
+/// are doubled, so counting them is enough to know whether a break is quoted.
+fn complete_len(bytes: &[u8]) -> usize {
+    let mut quoted = false;
+    let mut end = 0;
+    for (i, &byte) in bytes.iter().enumerate() {
+        match byte {
+            b'"' => quoted = !quoted,
+            b'\n' if !quoted => end = i + 1,
+            _ => {}
+        }
+    }
+    end
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+
+    use super::*;
+
+    #[test]
+    fn test_follow_appends() {
+        let file_name = "test_follow.csv";
+        let mut file = File::create(file_name).expect("Failed to create file");
+        write!(file, "name,note\nann,one\nbob,\"two").expect("Failed to write");
+
+        let mut follower = Follower::open(file_name, ReadOptions::default()).unwrap();
+        let records = follower.poll().unwrap();
+        assert_eq!(follower.headers(), vec!["name", "note"]);
+        assert_eq!(records, vec![StringRecord::from(vec!["ann", "one"])]);
+
+        write!(file, "\nlines\"\ncy,three\n").expect("Failed to write");
+        let records = follower.poll().unwrap();
+        assert_eq!(
+            records,
+            vec![
+                StringRecord::from(vec!["bob", "two\nlines"]),
+                StringRecord::from(vec!["cy", "three"]),
+            ]
+        );
+        assert!(follower.poll().unwrap().is_empty());
+        std::fs::remove_file(file_name).expect("Failed to remove file");
+    }
+}
@@ -0,0 +1,222 @@
+//! Indexes of large CSV files, kept in a hidden sidecar next to them (`.name.csv.index`).
+//! An index tells where every few thousandth row starts, so that opening the file again
+//! parses chunks of it in parallel. It is only used while the file keeps the size and
+//! modification time it was built for.
+
+use std::{path::PathBuf, time::UNIX_EPOCH};
+
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::{lock, EditorError, ReadOptions};
+
+/// Files from this size up are indexed by [`IndexMode::Auto`].
+pub const INDEX_THRESHOLD: u64 = 64 << 20;
+/// Number of rows between two offsets of an index.
+pub const INDEX_EVERY: usize = 8192;
+/// Version of the sidecar format; indexes of other versions are rebuilt.
+const VERSION: u32 = 1;
+
+/// When files get an index.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum IndexMode {
+    /// Indexes files of 64 MiB or more
+    #[default]
+    Auto,
+    /// Indexes every file read
+    Always,
+    /// Neither reads nor writes indexes
+    Never,
+}
+
+impl IndexMode {
+    /// Returns whether a file of `size` bytes gets an index.
+    pub fn indexes(self, size: u64) -> bool {
+        match self {
+            IndexMode::Auto => size >= INDEX_THRESHOLD,
+            IndexMode::Always => true,
+            IndexMode::Never => false,
+        }
+    }
+}
+
+/// Where a row starts in the decoded text of a file.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Offset {
+    pub byte: u64,
+    /// Line of the file, starting at 1.
+    pub line: u64,
+}
+
+/// Size and modification time of a file, which must not change for its index to be used.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Stamp {
+    pub size: u64,
+    /// Nanoseconds since the Unix epoch.
+    pub modified: u64,
+}
+
+impl Stamp {
+    /// Returns the stamp of `file_name` as it is now.
+    pub fn of(file_name: &str) -> Result<Stamp, EditorError> {
+        let metadata = std::fs::metadata(file_name)?;
+        let modified = metadata
+            .modified()?
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_nanos() as u64;
+        Ok(Stamp {
+            size: metadata.len(),
+            modified,
+        })
+    }
+}
+
+/// The index of a CSV file.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Index {
+    version: u32,
+    pub stamp: Stamp,
+    pub delimiter: u8,
+    pub has_headers: bool,
+    /// Length in bytes of the decoded text, which differs when it is decoded another way.
+    pub text_len: u64,
+    /// SHA-256 of the text before the first row: the header, if any.
+    pub header_hash: String,
+    /// Number of rows, header left out.
+    pub rows: usize,
+    /// Number of rows between two offsets.
+    pub every: usize,
+    /// Where rows `0`, `every`, `2 * every`... start.
+    pub offsets: Vec<Offset>,
+}
+
+impl Index {
+    /// Returns the index of `text`, the decoded contents of a file stamped `stamp`, given
+    /// the rows found parsing it with `options` and the offset of every `INDEX_EVERY`th one.
+    pub fn new(
+        stamp: Stamp,
+        options: &ReadOptions,
+        text: &str,
+        rows: usize,
+        offsets: Vec<Offset>,
+    ) -> Index {
+        let header_end = offsets
+            .first()
+            .map_or(text.len(), |offset| offset.byte as usize);
+        Index {
+            version: VERSION,
+            stamp,
+            delimiter: options.delimiter,
+            has_headers: options.has_headers,
+            text_len: text.len() as u64,
+            header_hash: hash(&text[..header_end]),
+            rows,
+            every: INDEX_EVERY,
+            offsets,
+        }
+    }
+
+    /// Returns the sidecar path of the index of `file_name`.
+    pub fn path(file_name: &str) -> PathBuf {
+        lock::sidecar_path(file_name, "index")
+    }
+
+    /// Returns the index of `file_name` if it has one built for the file as it is now,
+    /// parsed with the delimiter and header mode of `options`. Unreadable indexes are
+    /// ignored, to be rebuilt.
+    pub fn load(file_name: &str, options: &ReadOptions) -> Option<Index> {
+        let path = Index::path(file_name);
+        let contents = std::fs::read_to_string(&path).ok()?;
+        let index: Index = match serde_json::from_str(&contents) {
+            Ok(index) => index,
+            Err(e) => {
+                log::debug!(file:? = path; "ignoring unreadable index: {e}");
+                return None;
+            }
+        };
+        let current = index.version == VERSION
+            && index.every > 0
+            && index.delimiter == options.delimiter
+            && index.has_headers == options.has_headers
+            && Stamp::of(file_name).is_ok_and(|stamp| stamp == index.stamp);
+        if !current {
+            log::debug!(file:? = path; "ignoring stale index");
+            return None;
+        }
+        Some(index)
+    }
+
+    /// Tells whether the index was built from `text`, as far as its length and header show.
+    pub fn matches(&self, text: &str) -> bool {
+        let header_end = self
+            .offsets
+            .first()
+            .map_or(text.len(), |offset| offset.byte as usize);
+        self.text_len == text.len() as u64
+            && text.is_char_boundary(header_end)
+            && self.header_hash == hash(&text[..header_end])
+    }
+
+    /// Writes the index next to `file_name`. It is written next to it first and then
+    /// renamed, so that readers never see half of it.
+    pub fn save(&self, file_name: &str) -> Result<(), EditorError> {
+        let path = Index::path(file_name);
+        let contents = serde_json::to_string(self)
+            .map_err(|e| EditorError::InvalidFormat(format!("index: {e}")))?;
+        let mut partial = path.as_os_str().to_owned();
+        partial.push(".part");
+        std::fs::write(&partial, contents)?;
+        std::fs::rename(&partial, &path)?;
+        log::debug!(file:? = path, rows = self.rows, offsets = self.offsets.len(); "saved index");
+        Ok(())
+    }
+}
+
+fn hash(text: &str) -> String {
+    Sha256::digest(text.as_bytes())
+        .iter()
+        .map(|byte| format!("{byte:02x}"))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, SystemTime};
+
+    use super::*;
+
+    /// Sets the time `file_name` was last modified.
+    fn touch(file_name: &str, time: SystemTime) {
+        std::fs::File::options()
+            .write(true)
+            .open(file_name)
+            .and_then(|file| file.set_modified(time))
+            .expect("Failed to touch file");
+    }
+
+    #[test]
+    fn test_load_checks_stamp_and_options() {
+        let file_name = "test_index.csv";
+        let text = "id,name\n1,ann\n2,bob\n";
+        std::fs::write(file_name, text).unwrap();
+        let options = ReadOptions::default();
+        let offsets = vec![Offset { byte: 8, line: 2 }];
+        let index = Index::new(Stamp::of(file_name).unwrap(), &options, text, 2, offsets);
+        index.save(file_name).unwrap();
+        assert_eq!(Index::load(file_name, &options), Some(index.clone()));
+        assert!(index.matches(text));
+        assert!(!index.matches("ID,name\n1,ann\n2,bob\n"));
+
+        let semicolons = ReadOptions {
+            delimiter: b';',
+            ..ReadOptions::default()
+        };
+        assert_eq!(Index::load(file_name, &semicolons), None);
+        touch(file_name, SystemTime::now() + Duration::from_secs(60));
+        assert_eq!(Index::load(file_name, &options), None);
+        std::fs::remove_file(file_name).unwrap();
+        std::fs::remove_file(Index::path(file_name)).unwrap();
+    }
+}
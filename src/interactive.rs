@@ -0,0 +1,1987 @@
+use std::{
+    error::Error,
+    io::{self, BufRead, Write},
+    ops::RangeInclusive,
+    path::{Path, PathBuf},
+};
+
+use bootle_geditor_3000::{
+    bookmarks::{Bookmarks, Target},
+    clipboard::{CellRange, Clip},
+    columns,
+    comments::Comments,
+    config::Settings,
+    index::Stamp,
+    lock::FileLock,
+    overlay::Overlay,
+    plugin::Registry,
+    search::{fuzzy_score, Match, Search},
+    session::{FileState, SavedSearch, Snapshot, Unsaved},
+    view::ColumnView,
+    CSVData, EditorError, WriteOptions,
+};
+use csv::StringRecord;
+
+const HELP: &str = "\
+commands:
+  n, next        go to the next page (n: the next match while searching)
+  p, prev        go to the previous page
+  /<TEXT>        fuzzy search: rows with a cell holding the letters of TEXT in order,
+                 best first, with the matching cells highlighted in table output
+  re <PATTERN>   search the cells matching a regular expression, in file order
+  n, N           jump to the next or previous match
+  /              end the search
+  <N>, g <N>     jump to page N
+  j <TARGET>     show the page holding a row: a row number, a bookmark, or /TEXT to search
+  m <NAME> [R]   bookmark row R (the first row of the page by default)
+  unmark <NAME>  remove a bookmark
+  marks          list the bookmarks
+  note <R[:C]> <TEXT>
+                 comment row R, or its cell in column C; comments show below the page
+  unnote <R[:C]> remove the comment on row R, or on its cell in column C
+  f, first       go to the first page
+  >, < [N]       scroll N columns (1 by default) to the right or left
+  ], [           go to the next or previous column page (see --cols-per-page)
+  freeze <N>     keep the first N columns in view when scrolling (0 to unfreeze)
+  hide <C>       hide columns, e.g. hide notes,5-7; the data is left as it is
+  unhide [C]     show hidden columns again (all by default)
+  pin <C>        keep columns on the left, in view when scrolling
+  unpin [C]      stop pinning columns (all by default)
+  move <C> <N>   move a column to be the Nth one shown
+  cols           list the pinned and hidden columns
+  cols reset     show every column in file order again
+                 (the column layout is kept for the next time the file is opened)
+  l, last        go to the last page
+  b              list the open files
+  b <N>          switch to file N
+  cmp <N>        show the current page next to the page shown for file N
+  cp <R> <N>     copy rows R of the current file (e.g. 3 or 3-5) to the end of file N
+  y <R[:C]>      copy cells to the clipboard, e.g. y 3-5:name,age (all columns by default)
+  x <R[:C]>      cut cells: whole rows are deleted, other cells emptied
+  v <R[:C]>      paste the clipboard with its top-left cell at row R, column C
+  P [FILE]       add the rows on the system clipboard, e.g. copied from a spreadsheet, at
+                 the end, or open them as the new file FILE, the first row as its header
+  begin          start a transaction: the edits until commit can be undone with rollback
+  commit         keep the edits made since begin, or undo them if one of them failed
+  rollback       undo the edits made since begin
+  w [FILE]       save the current file, or write it to FILE
+  : [TEXT], ^P   list the commands matching TEXT (fuzzy) to pick one by number, then
+                 type its arguments; an empty line closes the list
+  h, help        show this help
+  q, quit        leave interactive mode";
+
+/// The commands the palette lists: name, arguments and what the command does.
+const PALETTE: &[(&str, &str, &str)] = &[
+    ("next", "", "go to the next page"),
+    ("prev", "", "go to the previous page"),
+    ("first", "", "go to the first page"),
+    ("last", "", "go to the last page"),
+    ("goto", "<N>", "jump to page N"),
+    (
+        "jump",
+        "<TARGET>",
+        "show the page holding a row, bookmark or /TEXT",
+    ),
+    ("/", "<TEXT>", "fuzzy search the cells"),
+    (
+        "re",
+        "<PATTERN>",
+        "search the cells matching a regular expression",
+    ),
+    ("n", "", "jump to the next match"),
+    ("N", "", "jump to the previous match"),
+    ("/", "", "end the search"),
+    ("mark", "<NAME> [R]", "bookmark a row"),
+    ("unmark", "<NAME>", "remove a bookmark"),
+    ("marks", "", "list the bookmarks"),
+    ("note", "<R[:C]> <TEXT>", "comment a row or cell"),
+    ("unnote", "<R[:C]>", "remove the comment on a row or cell"),
+    ("right", "[N]", "scroll columns to the right"),
+    ("left", "[N]", "scroll columns to the left"),
+    ("]", "", "go to the next column page"),
+    ("[", "", "go to the previous column page"),
+    ("freeze", "<N>", "keep the first N columns in view"),
+    ("hide", "<C>", "hide columns"),
+    ("unhide", "[C]", "show hidden columns again"),
+    ("pin", "<C>", "keep columns on the left"),
+    ("unpin", "[C]", "stop pinning columns"),
+    ("move", "<C> <N>", "move a column to be the Nth one shown"),
+    ("cols", "", "list the pinned and hidden columns"),
+    ("cols reset", "", "show every column in file order again"),
+    ("buffers", "", "list the open files"),
+    ("buffer", "<N>", "switch to file N"),
+    ("compare", "<N>", "show the page next to the page of file N"),
+    ("cp", "<R> <N>", "copy rows to the end of file N"),
+    ("yank", "<R[:C]>", "copy cells to the clipboard"),
+    (
+        "cut",
+        "<R[:C]>",
+        "cut cells: delete rows, empty other cells",
+    ),
+    ("paste", "<R[:C]>", "paste the clipboard at a cell"),
+    (
+        "P",
+        "[FILE]",
+        "add the rows on the system clipboard, or open them as FILE",
+    ),
+    ("begin", "", "start a transaction"),
+    ("commit", "", "keep the edits made since begin"),
+    ("rollback", "", "undo the edits made since begin"),
+    ("write", "[FILE]", "save the file, or write it to FILE"),
+    ("help", "", "show the help"),
+    ("quit", "", "leave interactive mode"),
+];
+
+/// What the next line answers while the command palette is open.
+#[derive(Debug, PartialEq)]
+enum Palette {
+    /// The number of one of these commands, by index in [`PALETTE`].
+    Pick(Vec<usize>),
+    /// The arguments of this command.
+    Arguments(usize),
+}
+
+/// A single command typed at the interactive prompt.
+#[derive(Debug, PartialEq)]
+enum Action {
+    Next,
+    Prev,
+    Jump(usize),
+    JumpTo(Target),
+    Mark {
+        name: String,
+        row: Option<usize>,
+    },
+    Unmark(String),
+    Marks,
+    /// Comments a row (1-based), or its cell in a column, or removes the comment without text.
+    Note {
+        row: usize,
+        column: Option<String>,
+        text: Option<String>,
+    },
+    /// Scrolls this many columns to the right, or to the left if negative.
+    Scroll(isize),
+    /// Scrolls this many column pages to the right, or to the left if negative.
+    ColPage(isize),
+    Search(Search),
+    EndSearch,
+    /// Jumps this many matches forward, or backward if negative; `n` pages without a search.
+    NextMatch(isize),
+    Freeze(usize),
+    /// Changes how the columns are laid out, without changing the data.
+    Layout(LayoutChange),
+    /// Lists the pinned and hidden columns.
+    Columns,
+    First,
+    Last,
+    Buffers,
+    Switch(usize),
+    Compare(usize),
+    Copy {
+        rows: RangeInclusive<usize>,
+        to: usize,
+    },
+    Yank(String),
+    Cut(String),
+    Paste(String),
+    /// Adds the rows on the system clipboard to the current file, or opens them as a new file.
+    PasteRows(Option<String>),
+    Begin,
+    Commit,
+    Rollback,
+    Write(Option<String>),
+    /// Lists the commands matching the text, to pick one (see [`PALETTE`]).
+    Palette(String),
+    Help,
+    Quit,
+}
+
+/// A change to the column layout of the viewer, naming columns as `--columns` does.
+#[derive(Debug, PartialEq)]
+enum LayoutChange {
+    Hide(String),
+    /// Shows the columns again, or every column with `None`.
+    Unhide(Option<String>),
+    Pin(String),
+    /// Unpins the columns, or every column with `None`.
+    Unpin(Option<String>),
+    /// Moves a column to be the Nth (1-based) one shown.
+    Move(String, usize),
+    Reset,
+}
+
+impl Action {
+    /// Parses one input line. Returns an error message for unknown commands.
+    fn parse(line: &str) -> Result<Action, String> {
+        // Ctrl-P reaches the line as a control character.
+        if let Some(query) = line.trim().strip_prefix([':', '\u{10}']) {
+            return Ok(Action::Palette(query.trim().to_string()));
+        }
+        if let Some(text) = line.trim().strip_prefix('/') {
+            return match text.trim() {
+                "" => Ok(Action::EndSearch),
+                text => Search::fuzzy(text)
+                    .map(Action::Search)
+                    .map_err(|e| e.to_string()),
+            };
+        }
+        let mut words = line.split_whitespace();
+        let command = words.next().unwrap_or("");
+        let action = match command {
+            "n" => Action::NextMatch(1),
+            "N" => Action::NextMatch(-1),
+            "re" | "regex" => {
+                let pattern = line.trim()[command.len()..].trim();
+                if pattern.is_empty() {
+                    return Err("usage: re <pattern>".to_string());
+                }
+                Action::Search(Search::regex(pattern).map_err(|e| e.to_string())?)
+            }
+            "next" => Action::Next,
+            "p" | "prev" => Action::Prev,
+            "f" | "first" => Action::First,
+            "l" | "last" => Action::Last,
+            "h" | "help" | "?" => Action::Help,
+            "q" | "quit" | "exit" => Action::Quit,
+            "g" | "goto" => {
+                let page = words.next().ok_or("usage: g <page>")?;
+                Action::Jump(parse_page(page)?)
+            }
+            "j" | "jump" => {
+                let target = words.next().ok_or("usage: j <row|bookmark|/text>")?;
+                Action::JumpTo(target.parse().map_err(|e: EditorError| e.to_string())?)
+            }
+            "m" | "mark" => {
+                let name = words.next().ok_or("usage: m <name> [row]")?;
+                Action::Mark {
+                    name: name.to_string(),
+                    row: words
+                        .next()
+                        .map(|row| parse_number(row, "row"))
+                        .transpose()?,
+                }
+            }
+            "unmark" => {
+                let name = words.next().ok_or("usage: unmark <name>")?;
+                Action::Unmark(name.to_string())
+            }
+            "marks" => Action::Marks,
+            "note" | "unnote" => {
+                let usage = match command {
+                    "note" => "usage: note <row>[:<column>] <text>",
+                    _ => "usage: unnote <row>[:<column>]",
+                };
+                let cell = words.next().ok_or(usage)?;
+                let (row, column) = match cell.split_once(':') {
+                    Some((row, column)) => (row, Some(column.to_string())),
+                    None => (cell, None),
+                };
+                let text = match command {
+                    "note" => {
+                        let text = line.trim()[command.len()..].trim()[cell.len()..].trim();
+                        if text.is_empty() {
+                            return Err(usage.to_string());
+                        }
+                        Some(text.to_string())
+                    }
+                    _ => None,
+                };
+                Action::Note {
+                    row: parse_number(row, "row")?,
+                    column,
+                    text,
+                }
+            }
+            ">" | "right" | "<" | "left" => {
+                let columns = match words.next() {
+                    Some(columns) => parse_number(columns, "column")? as isize,
+                    None => 1,
+                };
+                match command {
+                    ">" | "right" => Action::Scroll(columns),
+                    _ => Action::Scroll(-columns),
+                }
+            }
+            "]" => Action::ColPage(1),
+            "[" => Action::ColPage(-1),
+            "freeze" => {
+                let columns = words.next().ok_or("usage: freeze <columns>")?;
+                let columns = columns
+                    .parse()
+                    .map_err(|_| format!("invalid column count '{columns}'"))?;
+                Action::Freeze(columns)
+            }
+            "hide" | "pin" => {
+                let columns = words
+                    .next()
+                    .ok_or(format!("usage: {command} <columns>"))?
+                    .to_string();
+                Action::Layout(match command {
+                    "hide" => LayoutChange::Hide(columns),
+                    _ => LayoutChange::Pin(columns),
+                })
+            }
+            "unhide" => Action::Layout(LayoutChange::Unhide(words.next().map(str::to_string))),
+            "unpin" => Action::Layout(LayoutChange::Unpin(words.next().map(str::to_string))),
+            "move" => {
+                let (Some(column), Some(to)) = (words.next(), words.next()) else {
+                    return Err("usage: move <column> <position>".to_string());
+                };
+                Action::Layout(LayoutChange::Move(
+                    column.to_string(),
+                    parse_number(to, "position")?,
+                ))
+            }
+            "cols" => match words.next() {
+                Some("reset") => Action::Layout(LayoutChange::Reset),
+                Some(_) => return Err("usage: cols [reset]".to_string()),
+                None => Action::Columns,
+            },
+            "b" | "buffer" | "buffers" => match words.next() {
+                Some(number) => Action::Switch(parse_number(number, "file")?),
+                None => Action::Buffers,
+            },
+            "cmp" | "compare" => {
+                let number = words.next().ok_or("usage: cmp <file>")?;
+                Action::Compare(parse_number(number, "file")?)
+            }
+            "cp" => {
+                let (Some(rows), Some(to)) = (words.next(), words.next()) else {
+                    return Err("usage: cp <rows> <file>".to_string());
+                };
+                Action::Copy {
+                    rows: parse_rows(rows)?,
+                    to: parse_number(to, "file")?,
+                }
+            }
+            "y" | "yank" | "x" | "cut" | "v" | "paste" => {
+                let range = words
+                    .next()
+                    .ok_or(format!("usage: {command} <rows>[:<columns>]"))?;
+                match command {
+                    "y" | "yank" => Action::Yank(range.to_string()),
+                    "x" | "cut" => Action::Cut(range.to_string()),
+                    _ => Action::Paste(range.to_string()),
+                }
+            }
+            "P" => Action::PasteRows(words.next().map(str::to_string)),
+            "begin" => Action::Begin,
+            "commit" => Action::Commit,
+            "rollback" => Action::Rollback,
+            "w" | "write" => Action::Write(words.next().map(str::to_string)),
+            _ => Action::Jump(parse_page(command)?),
+        };
+        Ok(action)
+    }
+}
+
+fn parse_page(value: &str) -> Result<usize, String> {
+    match value.parse::<usize>() {
+        Ok(page) if page > 0 => Ok(page),
+        _ => Err(format!("unknown command '{value}', type 'h' for help")),
+    }
+}
+
+/// Parses a 1-based number, naming `what` it is in the error message.
+fn parse_number(value: &str, what: &str) -> Result<usize, String> {
+    match value.parse::<usize>() {
+        Ok(number) if number > 0 => Ok(number),
+        _ => Err(format!("invalid {what} number '{value}'")),
+    }
+}
+
+/// Parses a 1-based row or row range, e.g. `3` or `3-5`.
+fn parse_rows(value: &str) -> Result<RangeInclusive<usize>, String> {
+    let (start, end) = value.split_once('-').unwrap_or((value, value));
+    let start = parse_number(start, "row")?;
+    let end = parse_number(end, "row")?;
+    if end < start {
+        return Err(format!("invalid row range '{value}'"));
+    }
+    Ok(start..=end)
+}
+
+/// Something the session reacts to.
+pub enum Event {
+    /// A line typed at the prompt.
+    Line(String),
+    /// The file changed on disk (see `--watch`).
+    Changed,
+    /// The input ended.
+    Closed,
+}
+
+/// Loads the file again after it changed.
+pub type Reload<'a> = dyn FnMut() -> Result<CSVData, Box<dyn Error>> + 'a;
+
+/// A file open in the session.
+struct Buffer {
+    csv_data: CSVData,
+    /// Index of the page currently shown (0-based).
+    page: usize,
+    /// Number of columns after the frozen ones scrolled out of view to the left.
+    scroll: usize,
+    bookmarks: Bookmarks,
+    comments: Comments,
+    view: ColumnView,
+    /// The last search, its matches and the index of the one shown.
+    search: Option<(Search, Vec<Match>, usize)>,
+    /// Whether the data has changes not saved to its file yet.
+    modified: bool,
+    /// Whether an edit failed in the open transaction, which is then rolled back on commit.
+    failed: bool,
+    /// Size and modification time of the file when it was read or last saved, to refuse
+    /// saving over changes made to it since.
+    stamp: Option<Stamp>,
+    /// Lock on the file, held from when it is locked for editing until the session ends.
+    lock: Option<FileLock>,
+    /// Whether the data is only part of the file, read with `--columns` or `--filter`.
+    partial: bool,
+}
+
+impl Buffer {
+    /// Opens `csv_data` on its first page, with the bookmarks, comments and column layout
+    /// saved for its file.
+    fn new(csv_data: CSVData) -> Buffer {
+        let bookmarks = Bookmarks::load(&csv_data.file_name).unwrap_or_else(|e| {
+            log::warn!("{e}");
+            Bookmarks::new(&csv_data.file_name)
+        });
+        let comments = Comments::load(&csv_data.file_name).unwrap_or_else(|e| {
+            log::warn!("{e}");
+            Comments::new(&csv_data.file_name)
+        });
+        let view = load_view(&csv_data);
+        Buffer {
+            stamp: Stamp::of(&csv_data.file_name).ok(),
+            csv_data,
+            page: 0,
+            scroll: 0,
+            bookmarks,
+            comments,
+            view,
+            search: None,
+            modified: false,
+            failed: false,
+            lock: None,
+            partial: false,
+        }
+    }
+}
+
+/// Returns the column layout saved for the file of `csv_data`, or the file order if it
+/// cannot be read.
+fn load_view(csv_data: &CSVData) -> ColumnView {
+    let CSVData {
+        file_name,
+        headers,
+        fields,
+        ..
+    } = csv_data;
+    ColumnView::load(file_name, headers, *fields).unwrap_or_else(|e| {
+        log::warn!("{e}");
+        ColumnView::new(file_name, headers, *fields)
+    })
+}
+
+/// State of an interactive browsing session over one or more files.
+pub struct Session {
+    buffers: Vec<Buffer>,
+    /// Index of the buffer currently shown.
+    current: usize,
+    settings: Settings,
+    read_only: bool,
+    /// Cells copied or cut with `y` and `x`, shared by all buffers.
+    clipboard: Clip,
+    autosave: Option<Autosave>,
+    /// The open command palette, if any.
+    palette: Option<Palette>,
+}
+
+/// Where the session keeps its snapshot, and the view its files were opened with.
+struct Autosave {
+    path: PathBuf,
+    columns: Option<String>,
+    filter: Option<String>,
+}
+
+impl Session {
+    pub fn new(csv_data: CSVData, settings: Settings) -> Session {
+        Session {
+            buffers: vec![Buffer::new(csv_data)],
+            current: 0,
+            settings,
+            read_only: false,
+            clipboard: Clip::default(),
+            autosave: None,
+            palette: None,
+        }
+    }
+
+    /// Adds another file to the session. `b <N>` switches to it.
+    pub fn open(&mut self, csv_data: CSVData) {
+        self.buffers.push(Buffer::new(csv_data));
+    }
+
+    /// Locks the files of every buffer until the session ends, so that other instances do
+    /// not save over the changes made in it. Files created in the session are locked when
+    /// first saved.
+    /// Returns [`EditorError::Locked`] if another instance holds the lock on one of them.
+    pub fn lock(&mut self) -> Result<(), EditorError> {
+        for buffer in &mut self.buffers {
+            if buffer.lock.is_none() && Path::new(&buffer.csv_data.file_name).is_file() {
+                buffer.lock = Some(FileLock::acquire(&buffer.csv_data.file_name)?);
+            }
+        }
+        Ok(())
+    }
+
+    /// Marks the data of the files open so far as only part of them, as read with `--columns`
+    /// or `--filter`, so that `w` refuses to save it over the files.
+    pub fn set_partial(&mut self) {
+        for buffer in &mut self.buffers {
+            buffer.partial = true;
+        }
+    }
+
+    /// Refuses the commands that change or save data when `read_only` is set.
+    pub fn set_read_only(&mut self, read_only: bool) {
+        self.read_only = read_only;
+    }
+
+    /// Writes a snapshot of the session to `path` after every command, so that `--resume`
+    /// can pick up where it was left. `columns` and `filter` are the `--columns` and `--filter`
+    /// the files were opened with.
+    pub fn autosave(&mut self, path: PathBuf, columns: Option<String>, filter: Option<String>) {
+        self.autosave = Some(Autosave {
+            path,
+            columns,
+            filter,
+        });
+    }
+
+    /// Returns the state of the session: the position, search and bookmarks in each file,
+    /// the data of the files with unsaved changes, and the clipboard.
+    pub fn snapshot(&self) -> Snapshot {
+        let files = self
+            .buffers
+            .iter()
+            .map(|buffer| {
+                let csv_data = &buffer.csv_data;
+                let to_strings =
+                    |record: &StringRecord| record.iter().map(str::to_string).collect();
+                FileState {
+                    // Resuming may happen from another directory.
+                    file: std::fs::canonicalize(&csv_data.file_name)
+                        .map_or(csv_data.file_name.clone(), |path| {
+                            path.to_string_lossy().into_owned()
+                        }),
+                    page: buffer.page + 1,
+                    scroll: buffer.scroll,
+                    search: buffer.search.as_ref().map(|(search, _, current)| {
+                        let (pattern, regex) = match search {
+                            Search::Fuzzy(text) => (text.clone(), false),
+                            Search::Regex(regex) => (regex.as_str().to_string(), true),
+                        };
+                        SavedSearch {
+                            pattern,
+                            regex,
+                            current: current + 1,
+                        }
+                    }),
+                    bookmarks: buffer
+                        .bookmarks
+                        .iter()
+                        .map(|(name, row)| (name.to_string(), row + 1))
+                        .collect(),
+                    unsaved: buffer.modified.then(|| Unsaved {
+                        headers: to_strings(&csv_data.headers),
+                        rows: csv_data.data.iter().map(to_strings).collect(),
+                    }),
+                }
+            })
+            .collect();
+        let (columns, filter) = match &self.autosave {
+            Some(autosave) => (autosave.columns.clone(), autosave.filter.clone()),
+            None => (None, None),
+        };
+        Snapshot {
+            columns,
+            filter,
+            current: self.current,
+            freeze_cols: self.settings.freeze_cols,
+            clipboard: self
+                .clipboard
+                .rows
+                .iter()
+                .map(|record| record.iter().map(str::to_string).collect())
+                .collect(),
+            files,
+        }
+    }
+
+    /// Restores the state saved in `snapshot`, whose files must be open in the same order.
+    /// Pages and matches that are gone are replaced by the last ones.
+    /// Returns an error if other files are open, or a bookmark or search is invalid.
+    pub fn restore(&mut self, snapshot: &Snapshot) -> Result<(), EditorError> {
+        if snapshot.files.len() != self.buffers.len() {
+            return Err(EditorError::Session(format!(
+                "the session has {} file(s), {} are open",
+                snapshot.files.len(),
+                self.buffers.len()
+            )));
+        }
+        for (buffer, state) in self.buffers.iter_mut().zip(&snapshot.files) {
+            let csv_data = &mut buffer.csv_data;
+            if let Some(unsaved) = &state.unsaved {
+                let rows: Vec<StringRecord> = unsaved
+                    .rows
+                    .iter()
+                    .map(|row| row.iter().collect())
+                    .collect();
+                csv_data.records =
+                    (csv_data.records + rows.len()).saturating_sub(csv_data.data.len());
+                csv_data.headers = unsaved.headers.iter().collect();
+                csv_data.fields = match rows.first() {
+                    _ if !csv_data.headers.is_empty() => csv_data.headers.len(),
+                    Some(row) => row.len(),
+                    None => csv_data.fields,
+                };
+                csv_data.data = rows;
+                csv_data.create_pages(self.settings.records_per_page);
+                buffer.modified = true;
+            }
+            buffer.page = (state.page.max(1) - 1).min(csv_data.pages.len().saturating_sub(1));
+            buffer.scroll = state.scroll;
+            let mut bookmarks = Bookmarks::new(&csv_data.file_name);
+            for (name, &row) in &state.bookmarks {
+                bookmarks.add(name, row.saturating_sub(1))?;
+            }
+            buffer.bookmarks = bookmarks;
+            buffer.search = None;
+            if let Some(saved) = &state.search {
+                let search = match saved.regex {
+                    true => Search::regex(&saved.pattern)?,
+                    false => Search::fuzzy(&saved.pattern)?,
+                };
+                let matches = search.find(csv_data);
+                if !matches.is_empty() {
+                    let current = (saved.current.max(1) - 1).min(matches.len() - 1);
+                    buffer.search = Some((search, matches, current));
+                }
+            }
+        }
+        // The unsaved data restored is only the part of the files the view showed.
+        if snapshot.columns.is_some() || snapshot.filter.is_some() {
+            self.set_partial();
+        }
+        self.current = snapshot.current.min(self.buffers.len() - 1);
+        self.settings.freeze_cols = snapshot.freeze_cols;
+        self.clipboard = Clip {
+            rows: snapshot
+                .clipboard
+                .iter()
+                .map(|row| row.iter().collect())
+                .collect(),
+        };
+        Ok(())
+    }
+
+    /// Writes the snapshot of the session, if it keeps one, warning when it cannot.
+    fn save_snapshot(&self) {
+        if let Some(autosave) = &self.autosave {
+            if let Err(e) = self.snapshot().save(&autosave.path) {
+                log::warn!(
+                    "could not save the session to {}: {e}",
+                    autosave.path.display()
+                );
+            }
+        }
+    }
+
+    /// Shows the first page, then executes commands from `input` until `quit` or end of input.
+    pub fn run<R: BufRead, W: Write>(
+        &mut self,
+        input: R,
+        output: &mut W,
+    ) -> Result<(), EditorError> {
+        let events = input.lines().map(|line| line.map(Event::Line));
+        self.run_events(events, output, &mut || unreachable!("no change events"))
+    }
+
+    /// Same as [`Session::run`], also reloading the first file with `reload` on [`Event::Changed`].
+    /// The current page is kept if the reloaded file still has it.
+    pub fn run_events<W: Write>(
+        &mut self,
+        events: impl IntoIterator<Item = io::Result<Event>>,
+        output: &mut W,
+        reload: &mut Reload,
+    ) -> Result<(), EditorError> {
+        self.show_page(output)?;
+        write!(output, "{}", self.prompt())?;
+        output.flush()?;
+        for event in events {
+            match event? {
+                Event::Line(line) => {
+                    let line = match self.palette.take() {
+                        Some(palette) => self.answer(palette, line, output)?,
+                        None => line,
+                    };
+                    match Action::parse(&line) {
+                        _ if line.trim().is_empty() => {}
+                        Ok(Action::Quit) => return Ok(()),
+                        Ok(action) => {
+                            self.execute(action, output)?;
+                            self.save_snapshot();
+                        }
+                        Err(message) => writeln!(output, "{message}")?,
+                    }
+                }
+                Event::Changed => match reload() {
+                    Ok(csv_data) => {
+                        let buffer = &mut self.buffers[0];
+                        buffer.page = buffer.page.min(csv_data.pages.len().saturating_sub(1));
+                        // The columns may have changed with the file.
+                        buffer.view = load_view(&csv_data);
+                        buffer.stamp = Stamp::of(&csv_data.file_name).ok();
+                        buffer.csv_data = csv_data;
+                        buffer.modified = false;
+                        self.save_snapshot();
+                        writeln!(output, "\n(file changed, reloaded)")?;
+                        self.show_page(output)?;
+                    }
+                    Err(e) => writeln!(output, "\n(file changed, could not reload: {e})")?,
+                },
+                Event::Closed => break,
+            }
+            write!(output, "{}", self.prompt())?;
+            output.flush()?;
+        }
+        Ok(())
+    }
+
+    /// Returns the prompt: the command palette's while it is open.
+    fn prompt(&self) -> String {
+        match &self.palette {
+            None => "> ".to_string(),
+            Some(Palette::Pick(_)) => "command number> ".to_string(),
+            Some(Palette::Arguments(index)) => {
+                let (name, args, _) = PALETTE[*index];
+                format!("{name} {args}> ")
+            }
+        }
+    }
+
+    /// Takes `line` as the answer to the open command `palette`, and returns the command
+    /// line to run, empty if there is none yet. Lines that are not a number of the list
+    /// close it and run as they are.
+    fn answer<W: Write>(
+        &mut self,
+        palette: Palette,
+        line: String,
+        output: &mut W,
+    ) -> Result<String, EditorError> {
+        match palette {
+            Palette::Pick(entries) => {
+                if line.trim().is_empty() {
+                    writeln!(output, "(palette closed)")?;
+                    return Ok(String::new());
+                }
+                let Ok(number) = line.trim().parse::<usize>() else {
+                    return Ok(line);
+                };
+                let Some(&index) = number.checked_sub(1).and_then(|i| entries.get(i)) else {
+                    writeln!(output, "no command {number}, pick 1 to {}", entries.len())?;
+                    self.palette = Some(Palette::Pick(entries));
+                    return Ok(String::new());
+                };
+                let (name, args, _) = PALETTE[index];
+                if args.is_empty() {
+                    return Ok(name.to_string());
+                }
+                self.palette = Some(Palette::Arguments(index));
+                Ok(String::new())
+            }
+            Palette::Arguments(index) => {
+                let (name, args, _) = PALETTE[index];
+                match line.trim() {
+                    "" if args.starts_with('[') => Ok(name.to_string()),
+                    "" => {
+                        writeln!(output, "(palette closed)")?;
+                        Ok(String::new())
+                    }
+                    arguments => Ok(format!("{name} {arguments}")),
+                }
+            }
+        }
+    }
+
+    fn execute<W: Write>(&mut self, action: Action, output: &mut W) -> Result<(), EditorError> {
+        let message = match action {
+            Action::Palette(query) => {
+                // Matches on the command itself count double.
+                let mut entries: Vec<(i64, usize)> = PALETTE
+                    .iter()
+                    .enumerate()
+                    .filter_map(|(i, (name, args, description))| {
+                        if query.is_empty() {
+                            return Some((0, i));
+                        }
+                        let command = fuzzy_score(&query, &format!("{name} {args}"));
+                        let score = [
+                            command.map(|score| score * 2),
+                            fuzzy_score(&query, description),
+                        ]
+                        .into_iter()
+                        .flatten()
+                        .max()?;
+                        Some((score, i))
+                    })
+                    .collect();
+                if entries.is_empty() {
+                    writeln!(output, "no command matches '{query}'")?;
+                    return Ok(());
+                }
+                entries.sort_by_key(|&(score, _)| std::cmp::Reverse(score));
+                for (number, &(_, i)) in entries.iter().enumerate() {
+                    let (name, args, description) = PALETTE[i];
+                    let command = format!("{name} {args}");
+                    writeln!(output, "{:>3}  {command:<20} {description}", number + 1)?;
+                }
+                self.palette = Some(Palette::Pick(entries.into_iter().map(|(_, i)| i).collect()));
+                return Ok(());
+            }
+            Action::Buffers => {
+                for (i, buffer) in self.buffers.iter().enumerate() {
+                    let marker = if i == self.current { '*' } else { ' ' };
+                    let csv_data = &buffer.csv_data;
+                    writeln!(
+                        output,
+                        "{marker}{}: {} ({} rows)",
+                        i + 1,
+                        csv_data.file_name,
+                        csv_data.data.len()
+                    )?;
+                }
+                return Ok(());
+            }
+            Action::Switch(number) => match self.buffer_index(number) {
+                Ok(index) => {
+                    self.current = index;
+                    return self.show_page(output);
+                }
+                Err(message) => message,
+            },
+            Action::Compare(number) => match self.buffer_index(number) {
+                Ok(index) => return self.compare(index, output),
+                Err(message) => message,
+            },
+            Action::Copy { rows, to } => match self.copy_rows(rows, to) {
+                Ok(message) => message,
+                Err(message) => {
+                    if let Ok(to) = self.buffer_index(to) {
+                        self.fail(to);
+                    }
+                    message
+                }
+            },
+            Action::Yank(spec) => match self.yank(&spec, false) {
+                Ok(message) | Err(message) => message,
+            },
+            Action::Cut(spec) => {
+                let result = self.yank(&spec, true);
+                if result.is_err() {
+                    self.fail(self.current);
+                }
+                return self.show_change(result, output);
+            }
+            Action::Paste(spec) => {
+                let result = self.paste(&spec);
+                if result.is_err() {
+                    self.fail(self.current);
+                }
+                return self.show_change(result, output);
+            }
+            Action::PasteRows(file_name) => {
+                let result = arboard::Clipboard::new()
+                    .and_then(|mut clipboard| clipboard.get_text())
+                    .map_err(|e| format!("cannot read the clipboard: {e}"))
+                    .and_then(|text| self.paste_rows(&text, file_name));
+                if result.is_err() {
+                    self.fail(self.current);
+                }
+                return self.show_change(result, output);
+            }
+            Action::Begin => {
+                let csv_data = &mut self.buffers[self.current].csv_data;
+                csv_data.begin();
+                format!("began a transaction ({} open)", csv_data.transactions())
+            }
+            Action::Commit => {
+                let buffer = &mut self.buffers[self.current];
+                let failed = buffer.failed && buffer.csv_data.transactions() > 0;
+                let result = match failed {
+                    true => buffer.csv_data.rollback(),
+                    false => buffer.csv_data.commit(),
+                };
+                if buffer.csv_data.transactions() == 0 {
+                    buffer.failed = false;
+                }
+                match result {
+                    Ok(()) if failed => {
+                        let message = "an edit failed in the transaction, rolled it back";
+                        return self.show_change(Ok(message.to_string()), output);
+                    }
+                    Ok(()) => "committed the transaction".to_string(),
+                    Err(e) => e.to_string(),
+                }
+            }
+            Action::Rollback => {
+                let buffer = &mut self.buffers[self.current];
+                let result = buffer.csv_data.rollback().map_err(|e| e.to_string());
+                if buffer.csv_data.transactions() == 0 {
+                    buffer.failed = false;
+                }
+                let last = buffer.csv_data.pages.len().saturating_sub(1);
+                buffer.page = buffer.page.min(last);
+                let result = result.map(|()| "rolled back the transaction".to_string());
+                return self.show_change(result, output);
+            }
+            Action::JumpTo(target) => {
+                let buffer = &mut self.buffers[self.current];
+                let csv_data = &buffer.csv_data;
+                let found = target.resolve(csv_data, &buffer.bookmarks).and_then(|row| {
+                    let page = csv_data.page_of(row).ok_or(EditorError::RowOutOfBounds {
+                        row,
+                        rows: csv_data.records,
+                    })?;
+                    Ok((row, page))
+                });
+                match found {
+                    Ok((row, page)) => {
+                        writeln!(output, "{}", csv_data.describe_row(row))?;
+                        buffer.page = page;
+                        return self.show_page(output);
+                    }
+                    Err(e) => e.to_string(),
+                }
+            }
+            Action::Mark { name, row } => match self.mark(&name, row) {
+                Ok(message) | Err(message) => message,
+            },
+            Action::Unmark(name) => {
+                let bookmarks = &mut self.buffers[self.current].bookmarks;
+                match bookmarks.remove(&name) {
+                    Some(_) => match bookmarks.save() {
+                        Ok(()) => format!("removed bookmark {name}"),
+                        Err(e) => e.to_string(),
+                    },
+                    None => format!("no bookmark named '{name}'"),
+                }
+            }
+            Action::Marks => {
+                let bookmarks = &self.buffers[self.current].bookmarks;
+                if bookmarks.iter().next().is_none() {
+                    "no bookmarks".to_string()
+                } else {
+                    for (name, row) in bookmarks.iter() {
+                        writeln!(output, "{name}: row {}", row + 1)?;
+                    }
+                    return Ok(());
+                }
+            }
+            Action::Note { row, column, text } => match self.note(row, column, text.as_deref()) {
+                Ok(message) | Err(message) => message,
+            },
+            Action::Scroll(columns) => {
+                let buffer = &mut self.buffers[self.current];
+                let frozen = buffer.view.frozen(&self.settings);
+                // Keep at least one column after the frozen ones in view.
+                let last = buffer.view.shown().len().saturating_sub(frozen + 1);
+                let scroll = buffer.scroll.saturating_add_signed(columns).min(last);
+                if scroll == buffer.scroll {
+                    "cannot scroll further".to_string()
+                } else {
+                    buffer.scroll = scroll;
+                    return self.show_page(output);
+                }
+            }
+            Action::ColPage(pages) => match self.settings.cols_per_page {
+                Some(columns) => {
+                    return self.execute(Action::Scroll(pages * columns as isize), output)
+                }
+                None => "every column is on one page, see --cols-per-page".to_string(),
+            },
+            Action::Search(search) => {
+                let buffer = &mut self.buffers[self.current];
+                let matches = search.find(&buffer.csv_data);
+                if matches.is_empty() {
+                    buffer.search = None;
+                    "no match".to_string()
+                } else {
+                    buffer.search = Some((search, matches, 0));
+                    return self.show_match(output);
+                }
+            }
+            Action::EndSearch => {
+                self.buffers[self.current].search = None;
+                return self.show_page(output);
+            }
+            Action::NextMatch(step) => match &mut self.buffers[self.current].search {
+                Some((_, matches, current)) => {
+                    *current =
+                        (*current as isize + step).rem_euclid(matches.len() as isize) as usize;
+                    return self.show_match(output);
+                }
+                None if step > 0 => return self.navigate(Action::Next, output),
+                None => "no search, type /TEXT or re PATTERN".to_string(),
+            },
+            Action::Freeze(columns) => {
+                self.settings.freeze_cols = columns;
+                return self.show_page(output);
+            }
+            Action::Layout(change) => match self.change_layout(change) {
+                Ok(()) => return self.show_page(output),
+                Err(message) => message,
+            },
+            Action::Columns => {
+                let buffer = &self.buffers[self.current];
+                let names = |fields: &[usize]| match fields {
+                    [] => "none".to_string(),
+                    fields => fields
+                        .iter()
+                        .map(|&field| columns::column_name(&buffer.csv_data.headers, field))
+                        .collect::<Vec<_>>()
+                        .join(", "),
+                };
+                writeln!(output, "pinned: {}", names(buffer.view.pinned()))?;
+                format!("hidden: {}", names(&buffer.view.hidden()))
+            }
+            Action::Write(file_name) => match self.save(file_name) {
+                Ok(message) | Err(message) => message,
+            },
+            action => return self.navigate(action, output),
+        };
+        writeln!(output, "{message}")?;
+        Ok(())
+    }
+
+    /// Executes a page navigation command on the current buffer.
+    fn navigate<W: Write>(&mut self, action: Action, output: &mut W) -> Result<(), EditorError> {
+        let buffer = &self.buffers[self.current];
+        let last = buffer.csv_data.pages.len().saturating_sub(1);
+        // Either the page to show next, or a message to print instead.
+        let target = match action {
+            Action::Next if buffer.page >= last => Err("already on the last page".to_string()),
+            Action::Prev if buffer.page == 0 => Err("already on the first page".to_string()),
+            Action::Next => Ok(buffer.page + 1),
+            Action::Prev => Ok(buffer.page - 1),
+            Action::First => Ok(0),
+            Action::Last => Ok(last),
+            Action::Jump(page) if page - 1 > last => {
+                Err(format!("no page {page}, the file has {} page(s)", last + 1))
+            }
+            Action::Jump(page) => Ok(page - 1),
+            Action::Help => Err(HELP.to_string()),
+            _ => return Ok(()),
+        };
+        match target {
+            Ok(page) => {
+                self.buffers[self.current].page = page;
+                self.show_page(output)
+            }
+            Err(message) => {
+                writeln!(output, "{message}")?;
+                Ok(())
+            }
+        }
+    }
+
+    /// Bookmarks the 1-based `row` of the current buffer, or the first row of its page,
+    /// and saves the bookmarks.
+    fn mark(&mut self, name: &str, row: Option<usize>) -> Result<String, String> {
+        let buffer = &mut self.buffers[self.current];
+        let row = match row {
+            Some(row) => Target::Row(row - 1)
+                .resolve(&buffer.csv_data, &buffer.bookmarks)
+                .map_err(|e| e.to_string())?,
+            None => buffer
+                .csv_data
+                .pages
+                .get(buffer.page)
+                .map(|page| page.start)
+                .ok_or("no row to bookmark")?,
+        };
+        buffer
+            .bookmarks
+            .add(name, row)
+            .and_then(|_| buffer.bookmarks.save())
+            .map_err(|e| e.to_string())?;
+        Ok(format!("bookmarked row {} as {name}", row + 1))
+    }
+
+    /// Comments the 1-based `row` of the current buffer, or its cell in `column`, with
+    /// `text`, or removes the comment without it, and saves the comments.
+    fn note(
+        &mut self,
+        row: usize,
+        column: Option<String>,
+        text: Option<&str>,
+    ) -> Result<String, String> {
+        let buffer = &mut self.buffers[self.current];
+        let csv_data = &buffer.csv_data;
+        let row = Target::Row(row - 1)
+            .resolve(csv_data, &buffer.bookmarks)
+            .map_err(|e| e.to_string())?;
+        let column = column
+            .map(|spec| {
+                columns::parse_column(&spec, &csv_data.headers, csv_data.fields)
+                    .map(|field| columns::column_name(&csv_data.headers, field))
+            })
+            .transpose()
+            .map_err(|e| e.to_string())?;
+        let cell = match &column {
+            Some(column) => format!("row {}, {column}", row + 1),
+            None => format!("row {}", row + 1),
+        };
+        let message = match text {
+            Some(text) => {
+                buffer
+                    .comments
+                    .add(row, column, text)
+                    .map_err(|e| e.to_string())?;
+                format!("commented {cell}")
+            }
+            None if buffer.comments.remove(row, column).is_some() => {
+                format!("removed the comment on {cell}")
+            }
+            None => return Err(format!("no comment on {cell}")),
+        };
+        buffer.comments.save().map_err(|e| e.to_string())?;
+        Ok(message)
+    }
+
+    /// Changes the column layout of the current buffer and saves it.
+    fn change_layout(&mut self, change: LayoutChange) -> Result<(), String> {
+        let buffer = &mut self.buffers[self.current];
+        let csv_data = &buffer.csv_data;
+        let parse = |spec: &str| {
+            columns::parse_columns(spec, &csv_data.headers, csv_data.fields)
+                .map_err(|e| e.to_string())
+        };
+        let view = &mut buffer.view;
+        match change {
+            LayoutChange::Hide(spec) => view.hide(&parse(&spec)?).map_err(|e| e.to_string())?,
+            LayoutChange::Unhide(spec) => {
+                view.unhide(spec.map(|s| parse(&s)).transpose()?.as_deref())
+            }
+            LayoutChange::Pin(spec) => view.pin(&parse(&spec)?),
+            LayoutChange::Unpin(spec) => {
+                view.unpin(spec.map(|s| parse(&s)).transpose()?.as_deref())
+            }
+            LayoutChange::Move(spec, to) => {
+                let field = columns::parse_column(&spec, &csv_data.headers, csv_data.fields)
+                    .map_err(|e| e.to_string())?;
+                view.move_to(field, to).map_err(|e| e.to_string())?;
+            }
+            LayoutChange::Reset => view.reset(),
+        }
+        // The columns scrolled past may be fewer now.
+        buffer.scroll = 0;
+        view.save().map_err(|e| e.to_string())
+    }
+
+    /// Marks the open transaction of buffer `index`, if any, as failed.
+    fn fail(&mut self, index: usize) {
+        let buffer = &mut self.buffers[index];
+        if buffer.csv_data.transactions() > 0 {
+            buffer.failed = true;
+        }
+    }
+
+    /// Returns the index of the 1-based buffer `number`, or a message if there is none.
+    fn buffer_index(&self, number: usize) -> Result<usize, String> {
+        if number > self.buffers.len() {
+            return Err(format!(
+                "no file {number}, {} file(s) are open",
+                self.buffers.len()
+            ));
+        }
+        Ok(number - 1)
+    }
+
+    /// Appends a copy of the 1-based `rows` of the current buffer to buffer `to`.
+    fn copy_rows(&mut self, rows: RangeInclusive<usize>, to: usize) -> Result<String, String> {
+        if self.read_only {
+            return Err("cannot copy rows, the session is read-only".to_string());
+        }
+        let to = self.buffer_index(to)?;
+        let data = &self.buffers[self.current].csv_data.data;
+        let copied = data
+            .get(rows.start() - 1..*rows.end())
+            .ok_or_else(|| {
+                let (start, end) = rows.into_inner();
+                format!("no rows {start}-{end}, the file has {} row(s)", data.len())
+            })?
+            .to_vec();
+        let target = &mut self.buffers[to].csv_data;
+        target.append_rows(&copied).map_err(|e| e.to_string())?;
+        target.create_pages(self.settings.records_per_page);
+        self.buffers[to].modified = true;
+        let target = &self.buffers[to].csv_data;
+        Ok(format!(
+            "copied {} row(s) to {}",
+            copied.len(),
+            target.file_name
+        ))
+    }
+
+    /// Prints the outcome of a command that edits the current buffer,
+    /// followed by the page again if it succeeded.
+    fn show_change<W: Write>(
+        &self,
+        result: Result<String, String>,
+        output: &mut W,
+    ) -> Result<(), EditorError> {
+        match result {
+            Ok(message) => {
+                writeln!(output, "{message}")?;
+                self.show_page(output)
+            }
+            Err(message) => {
+                writeln!(output, "{message}")?;
+                Ok(())
+            }
+        }
+    }
+
+    /// Copies the cells in `spec` of the current buffer to the clipboard, removing them if `cut`.
+    fn yank(&mut self, spec: &str, cut: bool) -> Result<String, String> {
+        if cut && self.read_only {
+            return Err("cannot cut, the session is read-only".to_string());
+        }
+        let buffer = &mut self.buffers[self.current];
+        let csv_data = &mut buffer.csv_data;
+        let range = CellRange::parse(spec, &csv_data.headers, csv_data.fields)
+            .map_err(|e| e.to_string())?;
+        let clip = if cut {
+            let clip = csv_data.cut_range(&range).map_err(|e| e.to_string())?;
+            csv_data.create_pages(self.settings.records_per_page);
+            buffer.modified = true;
+            clip
+        } else {
+            csv_data.copy_range(&range).map_err(|e| e.to_string())?
+        };
+        let message = format!(
+            "{} {} row(s) of {} cell(s)",
+            if cut { "cut" } else { "copied" },
+            clip.rows.len(),
+            range.columns.len()
+        );
+        self.clipboard = clip;
+        Ok(message)
+    }
+
+    /// Pastes the clipboard into the current buffer with its top-left cell at `spec`.
+    fn paste(&mut self, spec: &str) -> Result<String, String> {
+        if self.read_only {
+            return Err("cannot paste, the session is read-only".to_string());
+        }
+        if self.clipboard.rows.is_empty() {
+            return Err("the clipboard is empty".to_string());
+        }
+        let buffer = &mut self.buffers[self.current];
+        let csv_data = &mut buffer.csv_data;
+        let at = CellRange::parse(spec, &csv_data.headers, csv_data.fields)
+            .map_err(|e| e.to_string())?;
+        let field = at.columns.first().copied().unwrap_or(0);
+        csv_data
+            .paste(*at.rows.start(), field, &self.clipboard)
+            .map_err(|e| e.to_string())?;
+        csv_data.create_pages(self.settings.records_per_page);
+        buffer.modified = true;
+        Ok(format!("pasted {} row(s)", self.clipboard.rows.len()))
+    }
+
+    /// Adds the rows of `text`, tab-separated values such as copied from a spreadsheet, to
+    /// the end of the current buffer and shows the first of them. A first row repeating the
+    /// header is left out. With `file_name`, opens the rows as that new file instead, the
+    /// first row being its header; `w` creates it.
+    fn paste_rows(&mut self, text: &str, file_name: Option<String>) -> Result<String, String> {
+        if self.read_only {
+            return Err("cannot paste, the session is read-only".to_string());
+        }
+        let mut clip = Clip::from_tsv(text).map_err(|e| e.to_string())?;
+        let fitted = |fitted: usize, fields: usize| match fitted {
+            0 => String::new(),
+            _ => format!(", {fitted} of them padded to {fields} value(s)"),
+        };
+        if let Some(file_name) = file_name {
+            if Path::new(&file_name).exists()
+                || self
+                    .buffers
+                    .iter()
+                    .any(|b| b.csv_data.file_name == file_name)
+            {
+                return Err(format!("{file_name} already exists"));
+            }
+            let headers = clip.take_header().ok_or("the clipboard is empty")?;
+            let fields = headers.len();
+            let padded = clip.fit(fields, false).map_err(|e| e.to_string())?;
+            let rows = clip.rows.len();
+            let mut csv_data = CSVData::new(&file_name, headers, clip.rows);
+            csv_data.create_pages(self.settings.records_per_page);
+            let mut buffer = Buffer::new(csv_data);
+            buffer.modified = true;
+            self.buffers.push(buffer);
+            self.current = self.buffers.len() - 1;
+            return Ok(format!(
+                "opened {rows} row(s) as {file_name}{}",
+                fitted(padded, fields)
+            ));
+        }
+        let buffer = &mut self.buffers[self.current];
+        let csv_data = &mut buffer.csv_data;
+        clip.strip_header(&csv_data.headers);
+        if clip.rows.is_empty() {
+            return Err("the clipboard has no rows to paste".to_string());
+        }
+        let padded = clip
+            .fit(csv_data.fields, false)
+            .map_err(|e| e.to_string())?;
+        let first = csv_data.data.len();
+        csv_data.paste(first, 0, &clip).map_err(|e| e.to_string())?;
+        csv_data.create_pages(self.settings.records_per_page);
+        buffer.page = csv_data.page_of(first).unwrap_or(buffer.page);
+        buffer.modified = true;
+        Ok(format!(
+            "pasted {} row(s){}",
+            clip.rows.len(),
+            fitted(padded, csv_data.fields)
+        ))
+    }
+
+    /// Writes the current buffer to `file_name`, or back to its own file.
+    fn save(&mut self, file_name: Option<String>) -> Result<String, String> {
+        if self.read_only {
+            return Err("cannot save, the session is read-only".to_string());
+        }
+        let mut csv_data = &self.buffers[self.current].csv_data;
+        if csv_data.transactions() > 0 {
+            return Err("commit or roll back the transaction before saving".to_string());
+        }
+        let file_name = file_name.unwrap_or_else(|| csv_data.file_name.clone());
+        let own_file = file_name == csv_data.file_name;
+        let processed;
+        if !self.settings.on_write.is_empty() {
+            let mut copy = csv_data.copy();
+            Registry::builtin()
+                .run(&self.settings.on_write, &mut copy)
+                .map_err(|e| e.to_string())?;
+            processed = copy;
+            csv_data = &processed;
+        }
+        let options = WriteOptions {
+            delimiter: self.settings.delimiter,
+            encoding: csv_data.encoding,
+            na_value: self.settings.na_value.clone(),
+            compression: csv_data.compression_for(&file_name),
+        };
+        if !own_file {
+            FileLock::acquire(&file_name)
+                .and_then(|_lock| csv_data.write_with_options(&file_name, options))
+                .map_err(|e| e.to_string())?;
+            return Ok(format!(
+                "wrote {} row(s) to {file_name}",
+                csv_data.data.len()
+            ));
+        }
+        let buffer = &self.buffers[self.current];
+        if buffer.partial {
+            return Err(format!(
+                "{file_name} was opened with --columns or --filter, save to another file with w <FILE>"
+            ));
+        }
+        let lock = match buffer.lock {
+            Some(_) => None,
+            None => Some(FileLock::acquire(&file_name).map_err(|e| e.to_string())?),
+        };
+        if buffer.stamp.is_some() && Stamp::of(&file_name).ok() != buffer.stamp {
+            return Err(format!(
+                "{file_name} changed since it was read, save to another file with w <FILE>"
+            ));
+        }
+        csv_data
+            .write_with_options(&file_name, options)
+            .map_err(|e| e.to_string())?;
+        let message = format!("wrote {} row(s) to {file_name}", csv_data.data.len());
+        // The buffer was read with the overlay edits applied, and the file now has them.
+        Overlay::discard(&file_name).map_err(|e| e.to_string())?;
+        let buffer = &mut self.buffers[self.current];
+        buffer.modified = false;
+        buffer.stamp = Stamp::of(&file_name).ok();
+        buffer.lock = buffer.lock.take().or(lock);
+        Ok(message)
+    }
+
+    /// Shows the current page of the current buffer next to the current page of buffer `other`.
+    fn compare<W: Write>(&self, other: usize, output: &mut W) -> Result<(), EditorError> {
+        let mut left = Vec::new();
+        self.show_buffer(self.current, &mut left)?;
+        let mut right = Vec::new();
+        self.show_buffer(other, &mut right)?;
+        let left = String::from_utf8_lossy(&left);
+        let right = String::from_utf8_lossy(&right);
+        let left: Vec<&str> = left.lines().collect();
+        let right: Vec<&str> = right.lines().collect();
+        let width = left
+            .iter()
+            .map(|line| line.chars().count())
+            .max()
+            .unwrap_or(0);
+        for i in 0..left.len().max(right.len()) {
+            let line = format!(
+                "{:<width$} | {}",
+                left.get(i).unwrap_or(&""),
+                right.get(i).unwrap_or(&"")
+            );
+            writeln!(output, "{}", line.trim_end())?;
+        }
+        Ok(())
+    }
+
+    fn show_page<W: Write>(&self, output: &mut W) -> Result<(), EditorError> {
+        self.show_buffer(self.current, output)
+    }
+
+    /// Shows the page holding the current match of the current buffer's search.
+    fn show_match<W: Write>(&mut self, output: &mut W) -> Result<(), EditorError> {
+        let buffer = &mut self.buffers[self.current];
+        let Some((_, matches, current)) = &buffer.search else {
+            return Ok(());
+        };
+        let found = matches[*current];
+        let csv_data = &buffer.csv_data;
+        let column = match csv_data.headers.get(found.field) {
+            Some(name) if !name.is_empty() => name.to_string(),
+            _ => format!("column {}", found.field + 1),
+        };
+        writeln!(
+            output,
+            "match {}/{}: {}, {column}",
+            current + 1,
+            matches.len(),
+            csv_data.describe_row(found.row)
+        )?;
+        // Rows past --dimension are on no page.
+        if let Some(page) = csv_data.page_of(found.row) {
+            buffer.page = page;
+        }
+        self.show_page(output)
+    }
+
+    /// Shows the current page of buffer `index`, labelled with the file name when several are open.
+    fn show_buffer<W: Write>(&self, index: usize, output: &mut W) -> Result<(), EditorError> {
+        let buffer = &self.buffers[index];
+        let csv_data = &buffer.csv_data;
+        let Some(page) = csv_data.pages.get(buffer.page) else {
+            writeln!(output, "(no records)")?;
+            return Ok(());
+        };
+        let highlighted;
+        let mut settings = &self.settings;
+        if let Some((search, _, _)) = &buffer.search {
+            let mut copy = self.settings.clone();
+            copy.color_rules.insert(0, search.highlight());
+            highlighted = copy;
+            settings = &highlighted;
+        }
+        if let Some(mut label) = csv_data.page_label(buffer.page) {
+            if let Some(columns) = buffer.view.label(csv_data, buffer.scroll, settings) {
+                label = format!("{label}, {columns}");
+            }
+            if self.buffers.len() > 1 {
+                writeln!(output, "-- {}: {label} --", csv_data.file_name)?;
+            } else {
+                writeln!(output, "-- {label} --")?;
+            }
+        }
+        let (start, end) = (page.start, page.end);
+        let view = &buffer.view;
+        view.render(csv_data, start, end, buffer.scroll, settings, output)?;
+        for (row, column, text) in buffer.comments.on_rows(page.start..page.end) {
+            match column {
+                Some(column) => writeln!(output, "# row {}, {column}: {text}", row + 1)?,
+                None => writeln!(output, "# row {}: {text}", row + 1)?,
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bootle_geditor_3000::config::OutputFormat;
+
+    fn run_commands(commands: &str) -> String {
+        let mut csv_data = CSVData::read_from_file("testdata.csv").expect("Failed to read CSV");
+        csv_data.create_pages(2);
+        let mut session = Session::new(csv_data, Settings::default());
+        let mut output = Vec::new();
+        session
+            .run(commands.as_bytes(), &mut output)
+            .expect("Failed to run session");
+        String::from_utf8(output).expect("Not UTF-8")
+    }
+
+    #[test]
+    fn test_navigation() {
+        let output = run_commands("n\nl\nn\np\n1\n9\nq\n");
+        assert!(output.contains("-- page 1/3, rows 1-2 --"));
+        assert!(output.contains("-- page 2/3, rows 3-4 --"));
+        assert!(output.contains("-- page 3/3, rows 5-6 --"));
+        assert!(output.contains("already on the last page"));
+        assert!(output.contains("no page 9, the file has 3 page(s)"));
+    }
+
+    #[test]
+    fn test_reload_keeps_page() {
+        let load = |per_page| {
+            let mut csv_data = CSVData::read_from_file("testdata.csv").expect("Failed to read CSV");
+            csv_data.create_pages(per_page);
+            csv_data
+        };
+        let mut session = Session::new(load(2), Settings::default());
+        let events = [
+            Event::Line("3".to_string()),
+            Event::Changed,
+            Event::Changed,
+            Event::Line("n".to_string()),
+            Event::Closed,
+        ];
+        let mut per_page = 0;
+        let mut output = Vec::new();
+        session
+            .run_events(events.map(Ok), &mut output, &mut || {
+                per_page += 3;
+                Ok(load(per_page))
+            })
+            .expect("Failed to run session");
+        let output = String::from_utf8(output).expect("Not UTF-8");
+        assert!(output.contains("(file changed, reloaded)\n-- page 2/2, rows 4-6 --"));
+        assert!(output.contains("(file changed, reloaded)\n-- page 1/1, rows 1-6 --"));
+        assert!(output.ends_with("already on the last page\n> "));
+    }
+
+    #[test]
+    fn test_buffers() {
+        let load = || {
+            let mut csv_data = CSVData::read_from_file("testdata.csv").expect("Failed to read CSV");
+            csv_data.create_pages(2);
+            csv_data
+        };
+        let settings = Settings {
+            records_per_page: 2,
+            ..Settings::default()
+        };
+        let mut session = Session::new(load(), settings);
+        session.open(load());
+        let mut output = Vec::new();
+        session
+            .run(
+                "cp 5-6 2\nb 2\nl\ncmp 1\nb 3\ncp 8-9 1\nb\n".as_bytes(),
+                &mut output,
+            )
+            .expect("Failed to run session");
+        let output = String::from_utf8(output).expect("Not UTF-8");
+        assert!(output.contains("copied 2 row(s) to testdata.csv"));
+        assert!(output.contains("-- testdata.csv: page 4/4, rows 7-8 --"));
+        assert!(output.contains(" | -- testdata.csv: page 1/3, rows 1-2 --"));
+        assert!(output.contains("no file 3, 2 file(s) are open"));
+        assert!(output.contains("no rows 8-9, the file has 8 row(s)"));
+        assert!(output.contains(" 1: testdata.csv (6 rows)\n*2: testdata.csv (8 rows)"));
+    }
+
+    #[test]
+    fn test_clipboard() {
+        let output = run_commands("v 1\ny 2:1-2\nv 6:5\nx 1\nv 9\n");
+        assert!(output.contains("the clipboard is empty"));
+        assert!(output.contains("copied 1 row(s) of 2 cell(s)"));
+        assert!(output.contains("pasted 1 row(s)\n-- page 1/1, rows 1-6 --"));
+        assert!(output.contains("cut 1 row(s) of 6 cell(s)\n-- page 1/1, rows 1-5 --"));
+        assert!(output.contains("row index 8 out of bounds, the data has 5 row(s)"));
+    }
+
+    #[test]
+    fn test_palette() {
+        let output = run_commands(":goto\n99\n1\n3\n:last page\n1\n:\n\n:zzz\nq\n");
+        assert!(output.contains("  1  goto <N>"));
+        assert!(output.contains("no command 99, pick 1 to"));
+        assert!(output.contains("command number> goto <N>> "));
+        assert!(output.contains("-- page 3/3, rows 5-6 --"));
+        assert!(output.contains(&format!("{:>3}  quit", PALETTE.len())));
+        assert!(output.contains("(palette closed)"));
+        assert!(output.contains("no command matches 'zzz'"));
+
+        let output = run_commands(":\nl\nq\n");
+        assert!(output.contains("-- page 3/3, rows 5-6 --"));
+    }
+
+    #[test]
+    fn test_paste_rows() {
+        let mut csv_data = CSVData::read_from_file("testdata.csv").expect("Failed to read CSV");
+        csv_data.create_pages(2);
+        let settings = Settings {
+            records_per_page: 2,
+            ..Settings::default()
+        };
+        let mut session = Session::new(csv_data, settings);
+        let header = "near\tcarry\tpattern\tfourth\twhatever\teasier\n";
+        let rows = format!("{header}a\t\"b\tc\"\t1\n\td\te\tf\tg\th\t\t\n");
+        assert_eq!(
+            session.paste_rows(&rows, None),
+            Ok("pasted 2 row(s), 1 of them padded to 6 value(s)".to_string())
+        );
+        let csv_data = &session.buffers[0].csv_data;
+        assert_eq!(csv_data.data.len(), 8);
+        assert_eq!(csv_data.data[6], vec!["a", "b\tc", "1", "", "", ""]);
+        assert_eq!(session.buffers[0].page, 3);
+        assert!(session
+            .paste_rows("1\t2\t3\t4\t5\t6\t7\n", None)
+            .unwrap_err()
+            .contains("has 7 value(s), expected 6"));
+        assert_eq!(
+            session.paste_rows(header, None).unwrap_err(),
+            "the clipboard has no rows to paste"
+        );
+
+        let file_name = "test_paste_rows.csv";
+        assert_eq!(
+            session.paste_rows("id\tname\t\n1\tann\n2\n", Some(file_name.to_string())),
+            Ok(
+                "opened 2 row(s) as test_paste_rows.csv, 1 of them padded to 2 value(s)"
+                    .to_string()
+            )
+        );
+        assert_eq!(session.current, 1);
+        assert!(session.buffers[1].modified);
+        session.save(None).unwrap();
+        let written = std::fs::read_to_string(file_name).expect("Failed to read");
+        std::fs::remove_file(file_name).expect("Failed to remove file");
+        std::fs::remove_file(".test_paste_rows.csv.lock").expect("Failed to remove file");
+        assert_eq!(written, "id,name\n1,ann\n2,\n");
+        assert!(session
+            .paste_rows("a\n", Some("testdata.csv".to_string()))
+            .unwrap_err()
+            .contains("already exists"));
+    }
+
+    #[test]
+    fn test_save_locks_and_checks_file() {
+        let file_name = "test_interactive_lock.csv";
+        std::fs::write(file_name, "id,name\n1,ann\n").expect("Failed to write");
+        let mut csv_data = CSVData::read_from_file(file_name).expect("Failed to read CSV");
+        csv_data.create_pages(2);
+        let mut session = Session::new(csv_data, Settings::default());
+        session.lock().unwrap();
+        assert!(matches!(
+            FileLock::acquire(file_name),
+            Err(EditorError::Locked { .. })
+        ));
+        session.save(None).unwrap();
+
+        // Another program changed the file since it was saved.
+        std::fs::write(file_name, "id,name\n1,ann\n2,bob\n").expect("Failed to write");
+        assert!(session
+            .save(None)
+            .unwrap_err()
+            .contains("changed since it was read"));
+        drop(session);
+        assert!(FileLock::acquire(file_name).is_ok());
+        std::fs::remove_file(file_name).expect("Failed to remove file");
+        std::fs::remove_file(".test_interactive_lock.csv.lock").expect("Failed to remove file");
+    }
+
+    #[test]
+    fn test_partial_data_not_saved_over_file() {
+        let file_name = "test_interactive_partial.csv";
+        let contents = "id,name,age\n1,ann,30\n2,bob,40\n3,cy,50\n";
+        std::fs::write(file_name, contents).expect("Failed to write");
+        let mut csv_data = CSVData::read_from_file(file_name).expect("Failed to read CSV");
+        csv_data = csv_data.select_columns("name").expect("Failed to select");
+        csv_data.create_pages(2);
+        let mut session = Session::new(csv_data, Settings::default());
+        session.set_partial();
+        assert!(session
+            .save(None)
+            .unwrap_err()
+            .contains("opened with --columns or --filter"));
+        let copy = "test_interactive_partial_copy.csv";
+        session.save(Some(copy.to_string())).unwrap();
+        let written = std::fs::read_to_string(copy).expect("Failed to read");
+        let unchanged = std::fs::read_to_string(file_name).expect("Failed to read");
+        for file in [file_name, copy, ".test_interactive_partial_copy.csv.lock"] {
+            std::fs::remove_file(file).expect("Failed to remove file");
+        }
+        assert_eq!(written, "name\nann\nbob\ncy\n");
+        assert_eq!(unchanged, contents);
+    }
+
+    #[test]
+    fn test_bookmarks() {
+        let output = run_commands("j 5\nm here\nm top 1\nf\nj here\nmarks\nj /ATTENTION\nj nope\nm 3\nunmark here\nunmark top\nmarks\n");
+        assert!(output.contains("> row 5 (line 6)\n-- page 3/3, rows 5-6 --"));
+        assert!(output.contains("bookmarked row 5 as here"));
+        assert!(output.contains("> row 4 (line 5)\n-- page 2/3, rows 3-4 --"));
+        assert!(output.contains("bookmarked row 1 as top"));
+        assert!(output.contains("here: row 5\ntop: row 1"));
+        assert!(output.contains("no bookmark named 'nope'"));
+        assert!(output.contains("invalid bookmark: '3' cannot be a bookmark name"));
+        assert!(output.contains("removed bookmark top\n> no bookmarks"));
+    }
+
+    #[test]
+    fn test_comments() {
+        let output = run_commands(
+            "note 3:2 looks off\nnote 4 check again\n2\nunnote 3:carry\nunnote 3:carry\nnote 9 x\nunnote 4\n",
+        );
+        assert!(output.contains("commented row 3, carry"));
+        assert!(output.contains("-- page 2/3, rows 3-4 --"));
+        assert!(output.contains("# row 3, carry: looks off\n# row 4: check again\n"));
+        assert!(
+            output.contains("removed the comment on row 3, carry\n> no comment on row 3, carry")
+        );
+        assert!(output.contains("row index 8 out of bounds"));
+        assert!(!std::path::Path::new(".testdata.csv.comments").exists());
+    }
+
+    #[test]
+    fn test_search() {
+        let output = run_commands("/captred\nre ^p\nn\nN\nN\n/\nn\n/zzzq\nN\n");
+        assert!(output.contains("> match 1/1: row 6 (line 7), fourth\n-- page 3/3, rows 5-6 --"));
+        assert!(output.contains("> match 1/4: row 1 (line 2), fourth\n-- page 1/3"));
+        assert!(output.contains("> match 2/4: row 3 (line 4), easier\n-- page 2/3"));
+        assert!(output.contains("> match 4/4: row 5 (line 6), near\n-- page 3/3"));
+        // Without a search, n goes to the next page again.
+        assert!(output.contains("> already on the last page\n"));
+        assert!(output.contains("no match\n> no search, type /TEXT or re PATTERN"));
+    }
+
+    #[test]
+    fn test_snapshot_and_restore() {
+        let load = || {
+            let mut csv_data = CSVData::read_from_file("testdata.csv").expect("Failed to read CSV");
+            csv_data.create_pages(2);
+            csv_data
+        };
+        let settings = Settings {
+            records_per_page: 2,
+            ..Settings::default()
+        };
+        let mut session = Session::new(load(), settings.clone());
+        session.open(load());
+        let mut output = Vec::new();
+        session
+            .run(
+                "b 2\nx 1\ny 1:1-2\nre ^p\nn\nfreeze 1\n".as_bytes(),
+                &mut output,
+            )
+            .expect("Failed to run session");
+        let snapshot = session.snapshot();
+        assert_eq!(snapshot.current, 1);
+        assert!(snapshot.files[0].unsaved.is_none());
+        assert_eq!(snapshot.files[1].unsaved.as_ref().unwrap().rows.len(), 5);
+
+        let mut resumed = Session::new(load(), settings);
+        resumed.open(load());
+        resumed.restore(&snapshot).expect("Failed to restore");
+        assert_eq!(resumed.snapshot(), snapshot);
+        let mut output = Vec::new();
+        resumed
+            .run("n\nb\n".as_bytes(), &mut output)
+            .expect("Failed to run session");
+        let output = String::from_utf8(output).expect("Not UTF-8");
+        assert!(output.starts_with("-- testdata.csv: page 2/3, rows 3-4 --"));
+        // Rows restored from the snapshot no longer know their line.
+        assert!(output.contains("> match 3/3: row 4, near\n"));
+        assert!(output.contains(" 1: testdata.csv (6 rows)\n*2: testdata.csv (5 rows)"));
+
+        let mut other = Session::new(load(), Settings::default());
+        assert!(other.restore(&snapshot).is_err());
+
+        // A session viewing part of the files cannot save it over them once resumed.
+        let partial = Snapshot {
+            columns: Some("near".to_string()),
+            ..snapshot
+        };
+        let mut resumed = Session::new(load(), Settings::default());
+        resumed.open(load());
+        resumed.restore(&partial).expect("Failed to restore");
+        assert!(resumed
+            .save(None)
+            .unwrap_err()
+            .contains("opened with --columns or --filter"));
+    }
+
+    #[test]
+    fn test_transactions() {
+        let output = run_commands(
+            "begin\nx 1\nw\nrollback\nbegin\nx 1\nv 99\ncommit\nrollback\nbegin\nx 1\ncommit\n",
+        );
+        assert!(output.contains("began a transaction (1 open)"));
+        assert!(output.contains("commit or roll back the transaction before saving"));
+        assert!(output.contains("rolled back the transaction\n-- page 1/3, rows 1-2 --"));
+        // The failed paste makes commit undo the cut.
+        assert!(output.contains(
+            "an edit failed in the transaction, rolled it back\n-- page 1/3, rows 1-2 --"
+        ));
+        assert!(output.contains("transaction error: no transaction to roll back"));
+        assert!(output.ends_with("committed the transaction\n> "));
+    }
+
+    #[test]
+    fn test_scroll_with_frozen_columns() {
+        let output = run_commands("freeze 1\n> 2\n> 9\n>\n< 9\n<\n]\n");
+        assert!(output.contains(
+            "-- page 1/3, rows 1-2, columns 1, 4-6 of 6 --\n\
+             StringRecord([\"environment\", \"potatoes\", \"there\", \"century\"])"
+        ));
+        assert!(output.contains("-- page 1/3, rows 1-2, columns 1, 6 of 6 --"));
+        assert!(output.contains("cannot scroll further\n> -- page 1/3, rows 1-2 --"));
+        assert!(output.contains("every column is on one page, see --cols-per-page"));
+    }
+
+    #[test]
+    fn test_column_pages() {
+        let mut csv_data = CSVData::read_from_file("testdata.csv").expect("Failed to read CSV");
+        csv_data.create_pages(2);
+        let settings = Settings {
+            output_format: OutputFormat::Table,
+            freeze_cols: 1,
+            cols_per_page: Some(2),
+            ..Settings::default()
+        };
+        let mut session = Session::new(csv_data, settings);
+        let mut output = Vec::new();
+        session
+            .run("]\n]\n]\n[\nn\n".as_bytes(), &mut output)
+            .expect("Failed to run session");
+        let output = String::from_utf8(output).expect("Not UTF-8");
+        assert!(output.contains("-- page 1/3, rows 1-2, columns 1, 2-3 of 6 --"));
+        assert!(output.contains("-- page 1/3, rows 1-2, columns 1, 6 of 6 --"));
+        assert!(output
+            .contains("cannot scroll further\n> -- page 1/3, rows 1-2, columns 1, 4-5 of 6 --"));
+        // Widths are measured over every row, so the pages line up.
+        assert!(output.contains(
+            "-- page 2/3, rows 3-4, columns 1, 4-5 of 6 --\n\
+             1           || 4         | 5\n\
+             near        || fourth    | whatever\n\
+             ------------++-----------+---------\n\
+             community   || telephone | jar\n"
+        ));
+    }
+
+    #[test]
+    fn test_column_layout() {
+        // A copy, so that the layout does not show in the other tests.
+        let file_name = "test_layout.csv";
+        std::fs::copy("testdata.csv", file_name).expect("Failed to copy CSV");
+        let open = || {
+            let mut csv_data = CSVData::read_from_file(file_name).expect("Failed to read CSV");
+            csv_data.create_pages(2);
+            Session::new(csv_data, Settings::default())
+        };
+        let mut output = Vec::new();
+        open()
+            .run(
+                "hide carry,5\npin easier\nmove pattern 2\nhide 1-6\n".as_bytes(),
+                &mut output,
+            )
+            .expect("Failed to run session");
+        let output = String::from_utf8(output).expect("Not UTF-8");
+        assert!(output.contains(
+            "> -- page 1/3, rows 1-2, 2 column(s) hidden --\n\
+             StringRecord([\"century\", \"valley\", \"environment\", \"potatoes\"])"
+        ));
+        assert!(output.contains("invalid columns: cannot hide every column"));
+
+        // The layout is there when the file is opened again.
+        let mut output = Vec::new();
+        open()
+            .run("cols\ncols reset\n".as_bytes(), &mut output)
+            .expect("Failed to run session");
+        let output = String::from_utf8(output).expect("Not UTF-8");
+        assert!(output.starts_with("-- page 1/3, rows 1-2, 2 column(s) hidden --"));
+        assert!(output.contains("pinned: easier\nhidden: carry, whatever"));
+        assert!(output.ends_with("-- page 1/3, rows 1-2 --\nStringRecord([\"environment\", \"managed\", \"valley\", \"potatoes\", \"there\", \"century\"])\nStringRecord([\"his\", \"soft\", \"breathing\", \"gun\", \"barn\", \"completely\"])\n> "));
+        assert!(!std::path::Path::new(".test_layout.csv.view").exists());
+        std::fs::remove_file(file_name).expect("Failed to remove file");
+    }
+
+    #[test]
+    fn test_parse_action() {
+        assert_eq!(Action::parse("g 4"), Ok(Action::Jump(4)));
+        assert_eq!(Action::parse("12"), Ok(Action::Jump(12)));
+        assert_eq!(Action::parse("next"), Ok(Action::Next));
+        assert_eq!(
+            Action::parse("cp 3-5 2"),
+            Ok(Action::Copy { rows: 3..=5, to: 2 })
+        );
+        assert_eq!(Action::parse("P"), Ok(Action::PasteRows(None)));
+        assert_eq!(
+            Action::parse(": hide "),
+            Ok(Action::Palette("hide".to_string()))
+        );
+        assert_eq!(Action::parse("\u{10}"), Ok(Action::Palette(String::new())));
+        assert_eq!(
+            Action::parse("P new.csv"),
+            Ok(Action::PasteRows(Some("new.csv".to_string())))
+        );
+        assert_eq!(Action::parse("b"), Ok(Action::Buffers));
+        assert_eq!(
+            Action::parse("j /smith"),
+            Ok(Action::JumpTo(Target::Search("smith".to_string())))
+        );
+        assert_eq!(
+            Action::parse("m invoice-42 12"),
+            Ok(Action::Mark {
+                name: "invoice-42".to_string(),
+                row: Some(12)
+            })
+        );
+        assert!(Action::parse("j 0").is_err());
+        assert_eq!(Action::parse("< 3"), Ok(Action::Scroll(-3)));
+        assert_eq!(Action::parse("freeze 0"), Ok(Action::Freeze(0)));
+        assert!(Action::parse("cp 5-3 2").is_err());
+        assert!(Action::parse("0").is_err());
+        assert!(Action::parse("dance").is_err());
+        assert_eq!(
+            Action::parse("/ jhn smth "),
+            Ok(Action::Search(Search::Fuzzy("jhn smth".to_string())))
+        );
+        assert_eq!(Action::parse("/"), Ok(Action::EndSearch));
+        assert_eq!(Action::parse("rollback"), Ok(Action::Rollback));
+        assert_eq!(Action::parse("N"), Ok(Action::NextMatch(-1)));
+        assert!(Action::parse("re").is_err());
+        assert_eq!(
+            Action::parse("note 4:price  looks too high "),
+            Ok(Action::Note {
+                row: 4,
+                column: Some("price".to_string()),
+                text: Some("looks too high".to_string())
+            })
+        );
+        assert!(Action::parse("note 4").is_err());
+        assert_eq!(
+            Action::parse("move price 2"),
+            Ok(Action::Layout(LayoutChange::Move("price".to_string(), 2)))
+        );
+        assert_eq!(
+            Action::parse("unhide"),
+            Ok(Action::Layout(LayoutChange::Unhide(None)))
+        );
+        assert!(Action::parse("cols all").is_err());
+    }
+}
@@ -0,0 +1,47 @@
+//! Reading, paging, editing and writing CSV files of fixed dimensions.
+//! The `bootle-geditor-3000` binary is a thin CLI over this library.
+
+pub mod aggregate;
+pub mod anonymize;
+pub mod audit;
+pub mod bookmarks;
+pub mod clipboard;
+pub mod color;
+pub mod columns;
+pub mod comments;
+pub mod compression;
+pub mod config;
+mod csv_data;
+pub mod dates;
+pub mod encoding;
+mod error;
+pub mod export;
+pub mod expr;
+pub mod fixed_width;
+pub mod follow;
+pub mod index;
+pub mod lock;
+pub mod merge;
+pub mod overlay;
+pub mod plugin;
+pub mod profile;
+pub mod progress;
+pub mod remote;
+pub mod render;
+pub mod report;
+pub mod row;
+pub mod rules;
+pub mod schema;
+#[cfg(feature = "scripting")]
+pub mod script;
+pub mod search;
+pub mod session;
+pub mod sqlite;
+pub mod transform;
+pub mod view;
+
+pub use csv_data::{
+    is_na, CSVData, ColumnInfo, HeaderDiff, HeaderMatch, Metadata, Page, RaggedRow, ReadBackend,
+    ReadOptions, SplitBy, WriteOptions, MMAP_THRESHOLD,
+};
+pub use error::EditorError;
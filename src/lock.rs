@@ -0,0 +1,86 @@
+use std::{
+    fs::{File, OpenOptions, TryLockError},
+    io::{Read, Seek, Write},
+    path::{Path, PathBuf},
+};
+
+use crate::EditorError;
+
+/// An advisory lock telling other editor instances that a file is being edited.
+/// The lock is held on a hidden sidecar file (`.name.csv.lock`) holding the owner's
+/// process id. It is released when the `FileLock` is dropped, and the operating system also
+/// releases it if the process dies. The sidecar is emptied but stays: removing it would let
+/// an instance waiting on the old file and one creating a new file both hold a lock.
+#[derive(Debug)]
+pub struct FileLock {
+    file: File,
+}
+
+impl FileLock {
+    /// Locks `file_name` for editing.
+    /// Returns [`EditorError::Locked`] if another instance holds the lock.
+    pub fn acquire(file_name: &str) -> Result<FileLock, EditorError> {
+        let path = sidecar_path(file_name, "lock");
+        let mut file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(&path)?;
+        match file.try_lock() {
+            Ok(()) => {}
+            Err(TryLockError::WouldBlock) => {
+                let mut owner = String::new();
+                file.read_to_string(&mut owner)?;
+                return Err(EditorError::Locked {
+                    file: file_name.to_string(),
+                    pid: owner.trim().parse().ok(),
+                });
+            }
+            Err(TryLockError::Error(e)) => return Err(e.into()),
+        }
+        file.set_len(0)?;
+        file.rewind()?;
+        write!(file, "{}", std::process::id())?;
+        log::debug!(file = file_name; "locked file");
+        Ok(FileLock { file })
+    }
+}
+
+impl Drop for FileLock {
+    fn drop(&mut self) {
+        let _ = self.file.set_len(0);
+        let _ = self.file.unlock();
+    }
+}
+
+/// Returns the hidden sidecar file of `file_name` with the given extension,
+/// e.g. `data/.people.csv.lock` for `data/people.csv`.
+pub(crate) fn sidecar_path(file_name: &str, extension: &str) -> PathBuf {
+    let path = Path::new(file_name);
+    let name = path.file_name().unwrap_or_default().to_string_lossy();
+    path.with_file_name(format!(".{name}.{extension}"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_acquire_and_release() {
+        let file_name = "test_lock.csv";
+        let lock = FileLock::acquire(file_name).expect("Failed to lock");
+        assert!(Path::new(".test_lock.csv.lock").exists());
+        match FileLock::acquire(file_name) {
+            Err(EditorError::Locked { pid, .. }) => assert_eq!(pid, Some(std::process::id())),
+            other => panic!("Expected a locked error, got {other:?}"),
+        }
+        drop(lock);
+        assert_eq!(
+            std::fs::read_to_string(".test_lock.csv.lock").expect("Failed to read"),
+            ""
+        );
+        drop(FileLock::acquire(file_name).expect("Failed to lock again"));
+        std::fs::remove_file(".test_lock.csv.lock").expect("Failed to remove file");
+    }
+}
@@ -0,0 +1,118 @@
+use std::{
+    fmt::Write as _,
+    fs::{File, OpenOptions},
+    io::{self, Write},
+    path::Path,
+    sync::Mutex,
+    time::SystemTime,
+};
+
+use chrono::{DateTime, Utc};
+use log::{
+    kv::{self, Key, Value, VisitSource},
+    Level, LevelFilter, Log, Metadata, Record,
+};
+
+/// Writes log records to stderr, or to a file given with `--log-file`.
+struct Logger {
+    level: LevelFilter,
+    file: Option<Mutex<File>>,
+}
+
+/// Installs the logger. `-d` enables info, `-dd` debug and `-ddd` trace; warnings are shown
+/// unless `quiet` is set. A log file records at least debug events, so it can be attached to a
+/// bug report.
+pub fn init(verbosity: u8, quiet: bool, log_file: Option<&Path>) -> io::Result<()> {
+    let mut level = match verbosity {
+        0 if quiet => LevelFilter::Error,
+        0 => LevelFilter::Warn,
+        1 => LevelFilter::Info,
+        2 => LevelFilter::Debug,
+        _ => LevelFilter::Trace,
+    };
+    let file = match log_file {
+        Some(path) => {
+            level = level.max(LevelFilter::Debug);
+            Some(Mutex::new(
+                OpenOptions::new().create(true).append(true).open(path)?,
+            ))
+        }
+        None => None,
+    };
+    log::set_boxed_logger(Box::new(Logger { level, file }))
+        .map_err(|e| io::Error::other(e.to_string()))?;
+    log::set_max_level(level);
+    Ok(())
+}
+
+impl Log for Logger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.level() <= self.level
+    }
+
+    fn log(&self, record: &Record) {
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+        let line = format_record(record);
+        match &self.file {
+            Some(file) => {
+                let timestamp = DateTime::<Utc>::from(SystemTime::now());
+                if let Ok(mut file) = file.lock() {
+                    let _ = writeln!(
+                        file,
+                        "{} {} {line}",
+                        timestamp.format("%Y-%m-%dT%H:%M:%S%.3fZ"),
+                        record.target()
+                    );
+                }
+            }
+            None => eprintln!("{line}"),
+        }
+    }
+
+    fn flush(&self) {
+        if let Some(Ok(mut file)) = self.file.as_ref().map(Mutex::lock) {
+            let _ = file.flush();
+        }
+    }
+}
+
+/// Formats a record as `level: message key=value ...`.
+fn format_record(record: &Record) -> String {
+    let level = match record.level() {
+        Level::Error => "error",
+        Level::Warn => "warning",
+        Level::Info => "info",
+        Level::Debug => "debug",
+        Level::Trace => "trace",
+    };
+    let mut line = format!("{level}: {}", record.args());
+    let _ = record.key_values().visit(&mut Fields(&mut line));
+    line
+}
+
+/// Appends the structured fields of a record to a line.
+struct Fields<'a>(&'a mut String);
+
+impl<'kvs> VisitSource<'kvs> for Fields<'_> {
+    fn visit_pair(&mut self, key: Key<'kvs>, value: Value<'kvs>) -> Result<(), kv::Error> {
+        write!(self.0, " {key}={value}").map_err(|_| kv::Error::msg("formatting failed"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_format_record() {
+        let fields: &[(&str, usize)] = &[("row", 3), ("field", 1)];
+        let record = Record::builder()
+            .level(Level::Info)
+            .args(format_args!("modified field"))
+            .key_values(&fields)
+            .build();
+        assert_eq!(format_record(&record), "info: modified field row=3 field=1");
+    }
+}
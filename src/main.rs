@@ -1,209 +1,2998 @@
-#![allow(dead_code)]
+mod interactive;
+mod logger;
+mod reporter;
+mod watch;
 
-use std::{error::Error, time::SystemTime};
+use std::{
+    collections::HashSet,
+    error::Error,
+    fmt,
+    fs::File,
+    io::{BufWriter, Write},
+    ops::RangeInclusive,
+    path::{Path, PathBuf},
+    process::ExitCode,
+    sync::mpsc,
+};
 
-use clap::Parser;
-use csv::{Reader, StringRecord, Writer};
+#[cfg(feature = "scripting")]
+use bootle_geditor_3000::script::Script;
+use bootle_geditor_3000::{
+    aggregate::{AggFn, Aggregation},
+    anonymize::Strategy,
+    audit::{self, Change, Journal},
+    bookmarks::{Bookmarks, Target},
+    clipboard::{CellRange, Clip},
+    color::{ColorMode, ColorRule},
+    columns,
+    comments::Comments,
+    compression::Compression,
+    config::{ColorScheme, Config, HeaderMode, OutputFormat, OutputMode, RaggedPolicy, Settings},
+    dates::Normalizer,
+    encoding,
+    export::{self, ExportFormat},
+    expr::Expr,
+    fixed_width::{self, Layout},
+    follow::Follower,
+    index::IndexMode,
+    lock::FileLock,
+    merge::{self, Choice, Conflict, Resolve, Side},
+    overlay::{self, Overlay},
+    plugin::{Processed, Registry},
+    profile,
+    progress::Progress,
+    remote, render,
+    report::{self, Records},
+    rules::{Rule, Rules},
+    schema::Locale,
+    session::Snapshot,
+    sqlite::{self, Format, IfExists},
+    transform::{self, Piece, TextOp},
+    CSVData, EditorError, HeaderMatch, ReadBackend, ReadOptions, SplitBy, WriteOptions,
+};
+use clap::{ArgGroup, CommandFactory, Parser, Subcommand};
+use clap_complete::Shell;
+use encoding_rs::Encoding;
+use interactive::Event;
+use rand::RngExt;
+use reporter::Reporter;
 
-#[derive(Debug)]
-struct Page {
-    start: usize,
-    end: usize,
+#[derive(Parser)]
+#[command(author, version, about, long_about = None)]
+#[command(subcommand_precedence_over_arg = true, subcommand_negates_reqs = true)]
+struct Cli {
+    /// Sets the input CSV file to use, or an https:// or s3:// URL to download it from
+    #[arg(
+        required_unless_present = "resume",
+        default_value = "",
+        hide_default_value = true
+    )]
+    file: String,
+
+    /// Opens more files next to the first one, to switch between them in interactive mode
+    /// or to concatenate them
+    more_files: Vec<String>,
+
+    /// Sets the dimensions (rows, columns) of the CSV file
+    #[arg(long)]
+    dimension: Option<String>,
+
+    /// Logs what the editor does: -d for info, -dd for debug, -ddd for trace
+    #[arg(short, long, action = clap::ArgAction::Count)]
+    debug: u8,
+
+    /// Writes the log to this file instead of stderr, at debug level or above
+    #[arg(long)]
+    log_file: Option<PathBuf>,
+
+    /// Sets the number of records per page for pagination [default: 10]
+    #[arg(short, long)]
+    records_per_page: Option<usize>,
+
+    /// Reads defaults from this file instead of ~/.config/bootleg-editor3000/config.toml
+    #[arg(long)]
+    config: Option<PathBuf>,
+
+    /// Sets the field delimiter [default: ,]
+    #[arg(long)]
+    delimiter: Option<char>,
+
+    /// Sets whether the first row is a header [default: first-row]
+    #[arg(long, value_enum)]
+    header: Option<HeaderMode>,
+
+    /// Sets the color scheme used in table output [default: plain]
+    #[arg(long, value_enum)]
+    color_scheme: Option<ColorScheme>,
+
+    /// Sets when table output is colored; auto colors a terminal unless NO_COLOR is set
+    /// [default: auto]
+    #[arg(long, value_enum)]
+    color: Option<ColorMode>,
+
+    /// Colors table output, repeatable: /REGEX/[=STYLE] for matching cells, negative[=STYLE]
+    /// for negative numbers, stripes[=STYLE] to shade every other row. Styles are red, green,
+    /// yellow, blue, magenta, cyan, bold, dim, underline, reverse and on-gray
+    #[arg(long = "color-rule", value_name = "RULE")]
+    color_rules: Vec<ColorRule>,
+
+    /// Sets how records are printed [default: debug]
+    #[arg(long, value_enum)]
+    format: Option<OutputFormat>,
+
+    /// Sets what to do with rows whose field count differs from the header [default: error]
+    #[arg(long, value_enum)]
+    ragged: Option<RaggedPolicy>,
+
+    /// Prints text for people, or JSON documents for scripts [default: text]. There is one
+    /// document for what view, head, tail, sample, goto (searching), lint and check
+    /// (validating), value-counts and profile (statistics) print, and for the outcome of
+    /// copy-range, split, merge, overlay list, normalize-dates, bookmark list, comment list,
+    /// apply-plugin and script
+    #[arg(long = "output", value_enum, value_name = "MODE")]
+    output_mode: Option<OutputMode>,
+
+    /// Sets the encoding of the input file, e.g. latin1 or utf-16 [default: detected]
+    #[arg(long, value_parser = encoding::parse_label)]
+    encoding: Option<&'static Encoding>,
+
+    /// Sets how the input file is loaded before parsing [default: auto]
+    #[arg(long, value_enum)]
+    read_backend: Option<ReadBackend>,
+
+    /// Sets when the input file gets a sidecar index, which makes opening it again faster
+    /// while it does not change [default: auto]
+    #[arg(long, value_enum)]
+    index: Option<IndexMode>,
+
+    /// Declares column types, e.g. age:int,price:float,born:date, or `auto` to infer them
+    #[arg(long)]
+    types: Option<String>,
+
+    /// Reads, checks, sorts and sums the numbers of some columns as written with the
+    /// separators of a locale, e.g. price:de,qty:en for 1.234,56 and 1,234.56, or de for
+    /// every column
+    #[arg(long)]
+    number_locale: Option<String>,
+
+    /// Reads the file as fixed-width text with these column widths, e.g. 10,5,8, or
+    /// id:10,name:5,city:8 to name the columns
+    #[arg(long, value_name = "LAYOUT")]
+    fixed_width: Option<String>,
+
+    /// Sets how typed numbers and dates are displayed
+    #[arg(long, value_enum)]
+    locale: Option<Locale>,
+
+    /// Sets the number of decimals displayed for float columns
+    #[arg(long)]
+    decimals: Option<usize>,
+
+    /// Keeps the first N columns in view when scrolling to the right [default: 0]
+    #[arg(long, value_name = "N")]
+    freeze_cols: Option<usize>,
+
+    /// Shows empty values as MARKER, e.g. NA, and writes them that way when saving or converting
+    #[arg(long, value_name = "MARKER")]
+    na_value: Option<String>,
+
+    /// Numbers the rows shown in a leading # column
+    #[arg(long)]
+    row_numbers: bool,
+
+    /// Shows the columns N at a time after the frozen ones, in column pages [default: all]
+    #[arg(long, value_name = "N")]
+    cols_per_page: Option<usize>,
+
+    /// Runs a row processor on the data read, repeatable and run in order
+    /// (see apply-plugin --list)
+    #[arg(long = "on-read", value_name = "PLUGIN")]
+    on_read: Vec<String>,
+
+    /// Runs a row processor on the data written, repeatable and run in order; the data
+    /// shown or edited is left as it is
+    #[arg(long = "on-write", value_name = "PLUGIN")]
+    on_write: Vec<String>,
+
+    /// Sets the number of threads sorting, filtering, grouping and inferring types
+    /// [default: one per core]
+    #[arg(long, value_name = "N")]
+    threads: Option<usize>,
+
+    /// Keeps downloaded files in DIR and downloads them again only when they changed
+    #[arg(long, value_name = "DIR")]
+    cache_dir: Option<PathBuf>,
+
+    /// Prints the elapsed time and throughput of reading, sorting and writing
+    #[arg(long)]
+    timing: bool,
+
+    /// Prints only the data asked for: no progress bars, warnings, summaries or, without
+    /// a subcommand, walkthrough
+    #[arg(short, long, conflicts_with = "debug")]
+    quiet: bool,
+
+    /// Resumes the interactive session saved in this file by `interactive --session`, with
+    /// the files it had open, in the same state
+    #[arg(long, value_name = "SESSION", conflicts_with_all = ["file", "more_files"])]
+    resume: Option<PathBuf>,
+
+    /// Opens the file for viewing only: subcommands that edit data are refused
+    #[arg(long)]
+    read_only: bool,
+
+    /// Appends every change made to the data to this JSON Lines journal, with its
+    /// time, user, old and new values, and checksums of the data before and after
+    #[arg(long, value_name = "JOURNAL")]
+    audit: Option<String>,
+
+    #[command(subcommand)]
+    command: Option<Command>,
 }
 
-struct CSVData {
-    data: Vec<StringRecord>,
-    records: usize,
-    fields: usize,
-    pages: Vec<Page>,
-    file_name: String,
-    creation_date: SystemTime,
-    last_modified_date: SystemTime,
-    file_size: u64,
+#[derive(Subcommand)]
+enum Command {
+    /// Prints one or more pages of the file (the first page by default)
+    #[command(visible_alias = "paginate")]
+    View {
+        /// Prints the page with this number, starting at 1
+        #[arg(long, conflicts_with = "pages")]
+        page: Option<usize>,
+
+        /// Prints a range of pages, e.g. 2..5 for pages 2 through 5
+        #[arg(long, value_parser = parse_page_range)]
+        pages: Option<RangeInclusive<usize>>,
+
+        /// Only shows these columns, by name or 1-based number, e.g. name,age,3-5
+        #[arg(long)]
+        columns: Option<String>,
+
+        /// Only shows rows for which this expression is true, e.g. "price > 10 and qty > 0"
+        #[arg(long)]
+        filter: Option<String>,
+
+        /// Scrolls N columns to the right, keeping the columns frozen with --freeze-cols
+        #[arg(long, value_name = "N", default_value_t = 0)]
+        scroll: usize,
+
+        /// Prints the column page with this number, starting at 1 (see --cols-per-page)
+        #[arg(long, value_name = "N", conflicts_with = "scroll")]
+        col_page: Option<usize>,
+
+        /// Keeps running and prints the pages again whenever the file changes
+        #[arg(long)]
+        watch: bool,
+    },
+    /// Browses the file page by page, reading navigation commands from stdin
+    Interactive {
+        /// Only shows these columns, by name or 1-based number, e.g. name,age,3-5
+        #[arg(long)]
+        columns: Option<String>,
+
+        /// Only shows rows for which this expression is true, e.g. "price > 10 and qty > 0"
+        #[arg(long)]
+        filter: Option<String>,
+
+        /// Reloads the file and shows the current page again whenever the file changes
+        #[arg(long)]
+        watch: bool,
+
+        /// Keeps the state of the session, unsaved changes included, in this file after
+        /// every command, to pick up where it was left with --resume
+        #[arg(long, value_name = "FILE")]
+        session: Option<PathBuf>,
+    },
+    /// Names rows of the file, kept in a sidecar next to it, to jump back to them with goto
+    Bookmark {
+        #[command(subcommand)]
+        action: BookmarkAction,
+    },
+    /// Notes rows and cells of the file, kept in a sidecar next to it, to flag values without
+    /// changing them
+    Comment {
+        #[command(subcommand)]
+        action: CommentAction,
+    },
+    /// Prints the page holding a row: a 1-based row number, a bookmark name, or /TEXT for
+    /// the first row with a cell containing TEXT (ignoring case)
+    Goto { target: Target },
+    /// Writes the data to a new CSV file
+    #[command(visible_alias = "write")]
+    Convert {
+        /// Sets the file to write to
+        #[arg(short, long)]
+        output: String,
+
+        /// Only writes these columns, by name or 1-based number, e.g. name,age,3-5
+        #[arg(long)]
+        columns: Option<String>,
+
+        /// Sets the encoding of the written file, e.g. latin1 or utf-16 [default: utf-8]
+        #[arg(long, value_parser = encoding::parse_label)]
+        output_encoding: Option<&'static Encoding>,
+
+        /// Compresses the written file [default: told by its extension, e.g. .gz]
+        #[arg(long, value_enum)]
+        compress: Option<Compression>,
+
+        /// Sets the format to write; SQLite tables get typed columns, from --types or else
+        /// guessed from the data
+        #[arg(long, value_enum, default_value_t)]
+        to: Format,
+
+        /// Sets the format of the input file
+        #[arg(long, value_enum, default_value_t)]
+        from: Format,
+
+        /// Sets the SQLite table to write [default: the input file's name] or to read
+        /// [default: the database's only table]
+        #[arg(long)]
+        table: Option<String>,
+
+        /// Sets what --to sqlite does when the database already has the table
+        #[arg(long, value_enum, default_value_t)]
+        if_exists: IfExists,
+    },
+    /// Writes the rows as a Markdown table or a standalone HTML page, for pasting into issues
+    /// and wikis
+    Export {
+        #[arg(long, value_enum)]
+        format: ExportFormat,
+
+        /// Only exports the page with this number, starting at 1 [default: every row]
+        #[arg(long)]
+        page: Option<usize>,
+
+        /// Only exports these columns, by name or 1-based number, e.g. name,age,3-5
+        #[arg(long)]
+        columns: Option<String>,
+
+        /// Only exports rows for which this expression is true, e.g. "price > 10 and qty > 0"
+        #[arg(long)]
+        filter: Option<String>,
+
+        /// Sets the title of the HTML page [default: the file name]
+        #[arg(long)]
+        title: Option<String>,
+
+        /// Sets the column widths of fixed-width output, e.g. 10,5,8 [default: the widest
+        /// value or name of each column, see the widths subcommand]
+        #[arg(long, value_name = "LAYOUT")]
+        widths: Option<String>,
+
+        /// Leaves the header line out of fixed-width output
+        #[arg(long)]
+        no_header: bool,
+
+        /// Writes the table to this file instead of printing it
+        #[arg(short, long)]
+        output: Option<String>,
+    },
+    /// Prints the last N records, then records as they are appended to the file, like `tail -f`
+    Follow {
+        /// Sets how many existing records to print first
+        #[arg(short = 'n', long, default_value_t = 10)]
+        lines: usize,
+
+        /// Only shows these columns, by name or 1-based number, e.g. name,age,3-5
+        #[arg(long)]
+        columns: Option<String>,
+
+        /// Only shows records for which this expression is true, e.g. "level == 'error'"
+        #[arg(long)]
+        filter: Option<String>,
+    },
+    /// Prints the first N records
+    Head {
+        #[arg(default_value_t = 10)]
+        count: usize,
+    },
+    /// Prints the last N records
+    Tail {
+        #[arg(default_value_t = 10)]
+        count: usize,
+    },
+    /// Prints N records picked at random, in file order
+    Sample {
+        count: usize,
+
+        /// Sets the random seed, to get the same sample on every run
+        #[arg(long)]
+        seed: Option<u64>,
+    },
+    /// Lists the rows whose field count differs from the header
+    Lint,
+    /// Checks the values against rules, like unique(email) or range(age, 0, 120), and lists
+    /// the ones breaking them
+    #[command(group(ArgGroup::new("given").required(true).multiple(true).args(["rules", "rule"])))]
+    Check {
+        /// Reads the rules from this file, one per line
+        #[arg(long, value_name = "FILE")]
+        rules: Option<String>,
+
+        /// Adds a rule: not_empty(COL), unique(COL), range(COL, MIN, MAX) or
+        /// matches(COL, 'REGEX'); may be repeated
+        #[arg(long)]
+        rule: Vec<Rule>,
+    },
+    /// Sets one cell and saves the file
+    Set {
+        /// Row to change, starting at 1
+        row: usize,
+
+        /// Column to change, by name or 1-based number
+        column: String,
+
+        value: String,
+
+        /// Writes the value even if it does not match the column type
+        #[arg(long)]
+        force: bool,
+
+        /// Records the edit in the file's overlay instead of rewriting the file; values are not
+        /// checked against --types (see `overlay`)
+        #[arg(long, conflicts_with = "output")]
+        overlay: bool,
+
+        /// Writes the result to this file instead of overwriting the input
+        #[arg(short, long)]
+        output: Option<String>,
+    },
+    /// Lists, applies or discards the cell edits recorded with `set --overlay`, which are kept
+    /// in a sidecar until applied so that large files are not rewritten for each one
+    Overlay {
+        #[command(subcommand)]
+        action: OverlayAction,
+    },
+    /// Prints a range of cells as tab-separated values, or puts them on the system clipboard
+    CopyRange {
+        /// Cells to copy, as ROWS[:COLUMNS], e.g. 3-5:name,age; all columns by default
+        range: String,
+
+        /// Puts the cells on the system clipboard instead of printing them
+        #[arg(long)]
+        clipboard: bool,
+
+        /// Also removes the cells and saves the file: whole rows are deleted, other cells emptied
+        #[arg(long)]
+        cut: bool,
+
+        /// Writes the result of --cut to this file instead of overwriting the input
+        #[arg(short, long, requires = "cut")]
+        output: Option<String>,
+    },
+    /// Pastes tab-separated values from stdin, or the system clipboard, and saves the file
+    PasteRange {
+        /// Top-left cell to paste at, as ROW[:COLUMN]; rows past the end are added
+        at: String,
+
+        /// Reads the cells from the system clipboard instead of stdin
+        #[arg(long)]
+        clipboard: bool,
+
+        /// Writes the result to this file instead of overwriting the input
+        #[arg(short, long)]
+        output: Option<String>,
+    },
+    /// Adds rows copied from a spreadsheet, as tab-separated values on the system clipboard,
+    /// to the end of the file and saves it
+    Paste {
+        /// Reads the rows from stdin instead of the system clipboard
+        #[arg(long)]
+        stdin: bool,
+
+        /// Creates the file from the rows instead, the first one being its header
+        #[arg(long)]
+        new: bool,
+
+        /// Drops the values past the last column instead of failing on rows that have them
+        #[arg(long)]
+        truncate: bool,
+
+        /// Writes the result to this file instead of overwriting the input
+        #[arg(short, long, conflicts_with = "new")]
+        output: Option<String>,
+    },
+    /// Splits a column into several columns and saves the file
+    SplitCol {
+        /// Column to split, by name or 1-based number
+        column: String,
+
+        /// Sets the text to split values at
+        #[arg(long)]
+        on: String,
+
+        /// Names the new columns, e.g. first,last; the last one gets whatever is left
+        #[arg(long, value_delimiter = ',', required = true)]
+        into: Vec<String>,
+
+        /// Keeps the original column, adding the new ones after it
+        #[arg(long)]
+        keep: bool,
+
+        /// Writes the result to this file instead of overwriting the input
+        #[arg(short, long)]
+        output: Option<String>,
+    },
+    /// Merges several columns into one and saves the file
+    MergeCol {
+        /// Columns to merge, by name or 1-based number, e.g. first,last
+        columns: String,
+
+        /// Names the new column
+        #[arg(long)]
+        into: String,
+
+        /// Builds the value from a format such as "{last}, {first}" instead of joining the columns
+        #[arg(long, conflicts_with = "sep")]
+        format: Option<String>,
+
+        /// Sets the text put between the joined values
+        #[arg(long, default_value = " ")]
+        sep: String,
+
+        /// Keeps the original columns, adding the new one after them
+        #[arg(long)]
+        keep: bool,
+
+        /// Writes the result to this file instead of overwriting the input
+        #[arg(short, long)]
+        output: Option<String>,
+    },
+    /// Adds a column computed from the other columns of each row and saves the file
+    AddCol {
+        /// Names the new column
+        name: String,
+
+        /// Expression giving the value, e.g. "price * qty" or "last & ', ' & first";
+        /// columns are referred to by name, `quoted name` or $N
+        #[arg(long)]
+        expr: String,
+
+        /// Writes the result to this file instead of overwriting the input
+        #[arg(short, long)]
+        output: Option<String>,
+    },
+    /// Summarizes the rows sharing the same key columns, one output row per key
+    GroupBy {
+        /// Columns forming the key, by name or 1-based number, e.g. region or region,year
+        #[arg(long)]
+        key: String,
+
+        /// Summaries to compute, e.g. "sum(amount),count(),avg(price)"; the functions are
+        /// count, sum, min, max, avg and distinct
+        #[arg(long)]
+        agg: String,
+
+        /// Writes the summary to this file instead of printing it
+        #[arg(short, long)]
+        output: Option<String>,
+    },
+    /// Prints each distinct value of a column with its count and share of the rows, the most
+    /// frequent first
+    #[command(alias = "freq")]
+    ValueCounts {
+        /// Column to count, by name or 1-based number
+        column: String,
+
+        /// Sorts the values printed by value instead of by count
+        #[arg(long)]
+        by_value: bool,
+
+        /// Only prints the N most frequent values
+        #[arg(long, value_name = "N")]
+        top: Option<usize>,
+    },
+    /// Prints the shortest, longest and average width in characters of the values of each
+    /// column, e.g. to lay out fixed-width output
+    Widths {
+        /// Only prints the fixed-width layout fitting the values, e.g. id:3,name:12
+        #[arg(long)]
+        layout: bool,
+    },
+    /// Reports on the values of each column: how sure its type guess is, the shapes and
+    /// lengths of its values, its numeric outliers, and example rows that stand out
+    Profile {
+        /// Only profiles these columns, by name or 1-based number, e.g. name,age,3-5
+        #[arg(long)]
+        columns: Option<String>,
+
+        /// Only profiles rows for which this expression is true, e.g. "country == 'FR'"
+        #[arg(long)]
+        filter: Option<String>,
+
+        /// Sets how many values that stand out are listed for each column
+        #[arg(long, value_name = "N", default_value_t = 5)]
+        examples: usize,
+
+        /// Writes a standalone HTML page instead of text
+        #[arg(long)]
+        html: bool,
+
+        /// Writes the report to this file instead of printing it
+        #[arg(short, long)]
+        output: Option<String>,
+    },
+    /// Turns rows into columns: one row per index, one column per distinct name, and saves the file
+    Pivot {
+        /// Columns identifying an output row, by name or 1-based number, e.g. id or id,year
+        #[arg(long)]
+        index: String,
+
+        /// Column whose values become the new column names
+        #[arg(long)]
+        names: String,
+
+        /// Column whose values fill the new columns
+        #[arg(long)]
+        values: String,
+
+        /// Combines several values for the same cell: count, sum, min, max, avg or distinct
+        #[arg(long)]
+        agg: Option<AggFn>,
+
+        /// Writes the result to this file instead of overwriting the input
+        #[arg(short, long)]
+        output: Option<String>,
+    },
+    /// Turns columns into rows: one row per original row and value column, and saves the file
+    #[command(visible_alias = "unpivot")]
+    Melt {
+        /// Columns copied to every output row, by name or 1-based number, e.g. id,name
+        #[arg(long)]
+        id: String,
+
+        /// Columns turned into rows [default: every column not in --id]
+        #[arg(long)]
+        values: Option<String>,
+
+        /// Names the column holding the former column names
+        #[arg(long, default_value = "variable")]
+        var_name: String,
+
+        /// Names the column holding the values
+        #[arg(long, default_value = "value")]
+        value_name: String,
+
+        /// Writes the result to this file instead of overwriting the input
+        #[arg(short, long)]
+        output: Option<String>,
+    },
+    /// Writes the rows to several files, each with the header, named PREFIX_PART.csv
+    #[command(group(ArgGroup::new("split_by").required(true)))]
+    Split {
+        /// Puts at most this many rows in each file
+        #[arg(long, group = "split_by")]
+        rows_per_file: Option<usize>,
+
+        /// Keeps each file under this size, e.g. 500K, 10M or 1G
+        #[arg(long, group = "split_by", value_parser = parse_size)]
+        max_size: Option<u64>,
+
+        /// Writes one file per distinct value of this column, named after the value
+        #[arg(long, group = "split_by")]
+        by: Option<String>,
+
+        /// Starts the file names with this path [default: the input file without its extension]
+        #[arg(long)]
+        prefix: Option<String>,
+    },
+    /// Appends the rows of the other files given after FILE and writes them all to one file
+    Concat {
+        /// How the headers of the files must match
+        #[arg(long, value_enum, default_value_t)]
+        mode: HeaderMatch,
+
+        /// Sets the file to write to
+        #[arg(short, long)]
+        output: String,
+    },
+    /// Merges into FILE the changes another copy made to a common ancestor of both, matching
+    /// rows by key, and saves the file. Conflicts are left between markers, unless resolved
+    /// with --resolve, and make the command fail
+    #[command(
+        override_usage = "bootle-geditor-3000 FILE merge [OPTIONS] --key <KEY> BASE THEIRS\n       \
+        bootle-geditor-3000 merge [OPTIONS] --key <KEY> BASE OURS THEIRS"
+    )]
+    Merge {
+        /// BASE THEIRS, merging into FILE ("ours") the changes THEIRS made to BASE, their
+        /// common ancestor; or BASE OURS THEIRS without FILE, merging into OURS. The result
+        /// is written to FILE or OURS unless --output is given
+        #[arg(num_args = 2..=3, value_name = "FILES", required = true)]
+        files: Vec<String>,
+
+        /// Columns identifying a row, by name or 1-based number, e.g. id or country,code
+        #[arg(long)]
+        key: String,
+
+        /// Sets how cells changed on both sides are settled
+        #[arg(long, value_enum, default_value_t)]
+        resolve: Resolve,
+
+        /// Writes the merged data to this file instead of FILE or OURS
+        #[arg(short, long)]
+        output: Option<String>,
+    },
+    /// Applies the changes recorded in an audit journal to FILE, a copy of the original data,
+    /// verifying each one against its checksums, and saves the file
+    Replay {
+        /// Journal written with --audit
+        journal: String,
+
+        /// Writes the result to this file instead of overwriting the input
+        #[arg(short, long)]
+        output: Option<String>,
+    },
+    /// Replaces empty values with defaults and saves the file
+    #[command(group(ArgGroup::new("defaults").required(true).multiple(true)))]
+    FillNa {
+        /// Default for every column given with --columns
+        #[arg(long, group = "defaults")]
+        value: Option<String>,
+
+        /// Default for one column, repeatable, e.g. --with amount=0; wins over --value
+        #[arg(long, value_name = "COLUMN=VALUE", group = "defaults")]
+        with: Vec<String>,
+
+        /// Columns filled with --value, by name or 1-based number [default: all]
+        #[arg(long)]
+        columns: Option<String>,
+
+        /// Writes the result to this file instead of overwriting the input
+        #[arg(short, long)]
+        output: Option<String>,
+    },
+    /// Removes the rows with an empty value in a required column and saves the file
+    DropNa {
+        /// Required columns, by name or 1-based number [default: all]
+        #[arg(long)]
+        columns: Option<String>,
+
+        /// Writes the result to this file instead of overwriting the input
+        #[arg(short, long)]
+        output: Option<String>,
+    },
+    /// Runs row processors over every row and saves the file
+    ApplyPlugin {
+        /// Processors to run, in order, e.g. trim drop-blank
+        #[arg(required_unless_present = "list")]
+        names: Vec<String>,
+
+        /// Lists the available processors instead
+        #[arg(long, conflicts_with = "output")]
+        list: bool,
+
+        /// Writes the result to this file instead of overwriting the input
+        #[arg(short, long)]
+        output: Option<String>,
+    },
+    /// Runs a rhai script over every row and saves the file. The script gets the row as a
+    /// map named `row` and its number as `row_number`; it changes `row`, returns a map of
+    /// the values to change, or returns false to drop the row
+    Script {
+        /// Script to run, e.g. clean.rhai
+        #[arg(long)]
+        file: PathBuf,
+
+        /// Writes the result to this file instead of overwriting the input
+        #[arg(short, long)]
+        output: Option<String>,
+    },
+    /// Rewrites every value of some columns and saves the file
+    Transform {
+        /// Columns to rewrite, by name or 1-based number, e.g. name,3-5
+        columns: String,
+
+        /// Operation to apply, repeatable and applied in order: trim, upper, lower,
+        /// zero-pad:N, strip-currency, or s/PATTERN/REPLACEMENT/ with a regular expression
+        #[arg(long = "op", required = true)]
+        ops: Vec<TextOp>,
+
+        /// Writes the result to this file instead of overwriting the input
+        #[arg(short, long)]
+        output: Option<String>,
+    },
+    /// Replaces personal data in some columns, e.g. before handing the file to a vendor,
+    /// and saves the file
+    Anonymize {
+        /// Columns and how to anonymize them, repeatable: COLUMNS=STRATEGY, where the strategy
+        /// is hash, redact, fake-name, fake-email, or mask[:N] keeping the N last characters
+        /// (4 by default), e.g. email=hash or card=mask
+        #[arg(long = "column", value_name = "COLUMNS=STRATEGY", required = true)]
+        columns: Vec<String>,
+
+        /// Mixed into hashes and fake data; give the same salt to get the same results in
+        /// several files [default: random]
+        #[arg(long)]
+        salt: Option<String>,
+
+        /// Writes the result to this file instead of overwriting the input
+        #[arg(short, long)]
+        output: Option<String>,
+    },
+    /// Rewrites the dates of some columns, read in any of several formats, in one format
+    /// and saves the file. Values that are not dates are left as they are and reported
+    NormalizeDates {
+        /// Columns to rewrite, by name or 1-based number [default: those holding dates]
+        #[arg(long)]
+        columns: Option<String>,
+
+        /// Format to read dates in, as a chrono strftime pattern, repeatable and tried in
+        /// order, e.g. %d/%m/%Y [default: date_formats from the config file, or common
+        /// ISO, US and European formats]
+        #[arg(long = "format", value_name = "FORMAT")]
+        formats: Vec<String>,
+
+        /// Reads dates like 03/04/2024 as 3 April instead of March 4
+        #[arg(long)]
+        day_first: bool,
+
+        /// Format to write dates in [default: RFC 3339, e.g. 2024-04-03 or
+        /// 2024-04-03T10:30:00]
+        #[arg(long, value_name = "FORMAT")]
+        to: Option<String>,
+
+        /// Fails without saving if a value is not a date
+        #[arg(long)]
+        strict: bool,
+
+        /// Writes the result to this file instead of overwriting the input
+        #[arg(short, long)]
+        output: Option<String>,
+    },
+    /// Sorts the rows by a column and saves the file
+    Sort {
+        /// Column to sort on, by name or 1-based number
+        column: String,
+
+        /// Sorts from the largest to the smallest value
+        #[arg(long)]
+        desc: bool,
+
+        /// Compares values as numbers (the default for int and float columns)
+        #[arg(long)]
+        numeric: bool,
+
+        /// Writes the result to this file instead of overwriting the input
+        #[arg(short, long)]
+        output: Option<String>,
+    },
+    /// Prints a script completing the subcommands and options in a shell, e.g.
+    /// `bootle-geditor-3000 completions bash > /etc/bash_completion.d/bootle-geditor-3000`.
+    /// Needs no FILE
+    Completions {
+        #[arg(value_enum)]
+        shell: Shell,
+    },
 }
 
-impl CSVData {
-    /// Reads CSV data from a file.
-    /// Returns an error if the file cannot be read.
-    fn read_from_file(file_name: &str) -> Result<CSVData, Box<dyn std::error::Error>> {
-        let mut reader = Reader::from_path(file_name)?;
-        let data: Vec<StringRecord> = reader.records().collect::<Result<_, _>>()?;
-        let records = data.len();
-        let fields = data.get(0).map_or(0, |record| record.len());
-        let metadata = std::fs::metadata(file_name)?;
-        Ok(CSVData {
-            data,
-            records,
-            fields,
-            pages: Vec::new(),
-            file_name: file_name.to_string(),
-            creation_date: metadata.created()?,
-            last_modified_date: metadata.modified()?,
-            file_size: metadata.len(),
-        })
+#[derive(Subcommand)]
+enum BookmarkAction {
+    /// Bookmarks a row, replacing any bookmark with the same name
+    Add {
+        /// Name of the bookmark, e.g. invoice-42; it cannot be a number or start with /
+        name: String,
+
+        /// Row to bookmark, starting at 1
+        #[arg(long)]
+        row: usize,
+    },
+    /// Removes a bookmark
+    #[command(visible_alias = "rm")]
+    Remove { name: String },
+    /// Lists the bookmarks and their rows
+    #[command(visible_alias = "ls")]
+    List,
+}
+
+#[derive(Subcommand)]
+enum CommentAction {
+    /// Comments a row, or one of its cells with --column, replacing any comment already there
+    Add {
+        /// Row to comment, starting at 1
+        row: usize,
+
+        text: String,
+
+        /// Comments the cell in this column, by name or 1-based number
+        #[arg(long)]
+        column: Option<String>,
+    },
+    /// Removes the comment on a row, or on one of its cells with --column
+    #[command(visible_alias = "rm")]
+    Remove {
+        /// Row of the comment, starting at 1
+        row: usize,
+
+        #[arg(long)]
+        column: Option<String>,
+    },
+    /// Lists the comments with the values they are about
+    #[command(visible_alias = "ls")]
+    List,
+    /// Writes the comments to a CSV report: row, line, column, value and text
+    Export {
+        /// Sets the file to write the report to
+        #[arg(short, long)]
+        output: String,
+    },
+}
+
+#[derive(Subcommand)]
+enum OverlayAction {
+    /// Lists the edits waiting to be applied
+    #[command(visible_alias = "ls")]
+    List,
+    /// Copies the file one record at a time with the edits applied, then drops them
+    Apply {
+        /// Writes the result to this file instead of overwriting the input, keeping the edits
+        #[arg(short, long)]
+        output: Option<String>,
+    },
+    /// Drops the edits without applying them
+    Discard,
+}
+
+impl Command {
+    /// Returns the name of a subcommand that edits the data, or `None` for viewing ones.
+    fn edit_name(&self) -> Option<&'static str> {
+        match self {
+            Command::Set { .. } => Some("set"),
+            Command::Overlay {
+                action: OverlayAction::Apply { .. },
+            } => Some("overlay apply"),
+            Command::Overlay {
+                action: OverlayAction::Discard,
+            } => Some("overlay discard"),
+            Command::Sort { .. } => Some("sort"),
+            Command::CopyRange { cut: true, .. } => Some("copy-range --cut"),
+            Command::PasteRange { .. } => Some("paste-range"),
+            Command::Paste { .. } => Some("paste"),
+            Command::SplitCol { .. } => Some("split-col"),
+            Command::MergeCol { .. } => Some("merge-col"),
+            Command::AddCol { .. } => Some("add-col"),
+            Command::Pivot { .. } => Some("pivot"),
+            Command::Melt { .. } => Some("melt"),
+            Command::Merge { .. } => Some("merge"),
+            Command::Replay { .. } => Some("replay"),
+            Command::FillNa { .. } => Some("fill-na"),
+            Command::DropNa { .. } => Some("drop-na"),
+            Command::ApplyPlugin { list: false, .. } => Some("apply-plugin"),
+            Command::Script { .. } => Some("script"),
+            Command::Transform { .. } => Some("transform"),
+            Command::Anonymize { .. } => Some("anonymize"),
+            Command::NormalizeDates { .. } => Some("normalize-dates"),
+            _ => None,
+        }
+    }
+
+    /// Returns the file the subcommand writes to, if any.
+    fn output<'a>(&'a self, input: &'a str) -> Option<&'a str> {
+        match self {
+            Command::Convert { output, .. } => Some(output),
+            Command::GroupBy { output, .. }
+            | Command::Export { output, .. }
+            | Command::Profile { output, .. } => output.as_deref(),
+            Command::Concat { output, .. } => Some(output),
+            Command::Paste { new: true, .. } => Some(input),
+            Command::Overlay {
+                action: OverlayAction::Apply { output },
+            } => Some(output.as_deref().unwrap_or(input)),
+            Command::Overlay {
+                action: OverlayAction::Discard,
+            } => Some(input),
+            Command::Set { output, .. }
+            | Command::Sort { output, .. }
+            | Command::CopyRange {
+                cut: true, output, ..
+            }
+            | Command::PasteRange { output, .. }
+            | Command::Paste { output, .. }
+            | Command::SplitCol { output, .. }
+            | Command::MergeCol { output, .. }
+            | Command::AddCol { output, .. }
+            | Command::Pivot { output, .. }
+            | Command::Melt { output, .. }
+            | Command::Merge { output, .. }
+            | Command::Replay { output, .. }
+            | Command::FillNa { output, .. }
+            | Command::DropNa { output, .. }
+            | Command::ApplyPlugin {
+                list: false,
+                output,
+                ..
+            }
+            | Command::Script { output, .. }
+            | Command::Transform { output, .. }
+            | Command::Anonymize { output, .. }
+            | Command::NormalizeDates { output, .. } => Some(output.as_deref().unwrap_or(input)),
+            _ => None,
+        }
+    }
+
+    /// Returns the `--columns` spec of the subcommand, if any.
+    fn columns(&self) -> Option<&str> {
+        match self {
+            Command::View { columns, .. }
+            | Command::Interactive { columns, .. }
+            | Command::Follow { columns, .. }
+            | Command::Convert { columns, .. }
+            | Command::Export { columns, .. }
+            | Command::Profile { columns, .. } => columns.as_deref(),
+            Command::Head { .. }
+            | Command::Tail { .. }
+            | Command::Sample { .. }
+            | Command::Lint
+            | Command::Check { .. }
+            | Command::Bookmark { .. }
+            | Command::Comment { .. }
+            | Command::Overlay { .. }
+            | Command::Goto { .. }
+            | Command::Set { .. }
+            | Command::Sort { .. }
+            | Command::CopyRange { .. }
+            | Command::PasteRange { .. }
+            | Command::Paste { .. }
+            | Command::SplitCol { .. }
+            | Command::MergeCol { .. }
+            | Command::AddCol { .. }
+            | Command::GroupBy { .. }
+            | Command::ValueCounts { .. }
+            | Command::Widths { .. }
+            | Command::Pivot { .. }
+            | Command::Melt { .. }
+            | Command::Split { .. }
+            | Command::Concat { .. }
+            | Command::Merge { .. }
+            | Command::Replay { .. }
+            | Command::FillNa { .. }
+            | Command::DropNa { .. }
+            | Command::ApplyPlugin { .. }
+            | Command::Script { .. }
+            | Command::Transform { .. }
+            | Command::Anonymize { .. }
+            | Command::NormalizeDates { .. }
+            | Command::Completions { .. } => None,
+        }
     }
 
-    /// Creates pagination pages for the CSV data.
-    /// Each page contains a range of records defined by `records_per_page`.
-    pub fn create_pages(&mut self, records_per_page: usize) {
-        let records_per_page = if records_per_page == 0 {
-            10
-        } else {
-            records_per_page
+    /// Returns the `--filter` expression of the subcommand, if any.
+    fn filter(&self) -> Option<&str> {
+        match self {
+            Command::View { filter, .. }
+            | Command::Interactive { filter, .. }
+            | Command::Follow { filter, .. }
+            | Command::Export { filter, .. }
+            | Command::Profile { filter, .. } => filter.as_deref(),
+            _ => None,
+        }
+    }
+}
+
+/// Parses an inclusive, 1-based page range such as `2..5`.
+fn parse_page_range(value: &str) -> Result<RangeInclusive<usize>, String> {
+    let (start, end) = value
+        .split_once("..")
+        .ok_or_else(|| format!("expected a range like 2..5, got '{value}'"))?;
+    let start: usize = start
+        .parse()
+        .map_err(|_| format!("invalid start page '{start}'"))?;
+    let end: usize = end
+        .parse()
+        .map_err(|_| format!("invalid end page '{end}'"))?;
+    if start == 0 || end < start {
+        return Err(format!("invalid page range '{value}'"));
+    }
+    Ok(start..=end)
+}
+
+/// Parses a size in bytes, optionally followed by K, M or G (powers of 1024), e.g. `10M`.
+fn parse_size(value: &str) -> Result<u64, String> {
+    let upper = value.trim().to_ascii_uppercase();
+    let digits = upper.trim_end_matches('B');
+    let (digits, unit) = match digits.char_indices().last() {
+        Some((i, 'K')) => (&digits[..i], 1 << 10),
+        Some((i, 'M')) => (&digits[..i], 1 << 20),
+        Some((i, 'G')) => (&digits[..i], 1 << 30),
+        _ => (digits, 1),
+    };
+    match digits.trim().parse::<u64>() {
+        Ok(size) if size > 0 => size
+            .checked_mul(unit)
+            .ok_or_else(|| format!("size '{value}' is too large")),
+        _ => Err(format!("invalid size '{value}', expected e.g. 500K or 10M")),
+    }
+}
+
+impl Cli {
+    /// Collects the settings given on the command line, to be layered over the config file.
+    fn overrides(&self) -> Config {
+        Config {
+            records_per_page: self.records_per_page,
+            delimiter: self.delimiter,
+            color_scheme: self.color_scheme,
+            header: self.header,
+            output_format: self.format,
+            output_mode: self.output_mode,
+            ragged: self.ragged,
+            locale: self.locale,
+            decimals: self.decimals,
+            freeze_cols: self.freeze_cols,
+            cols_per_page: self.cols_per_page,
+            color: self.color,
+            color_rules: (!self.color_rules.is_empty()).then(|| self.color_rules.clone()),
+            row_numbers: self.row_numbers.then_some(true),
+            na_value: self.na_value.clone(),
+            on_read: (!self.on_read.is_empty()).then(|| self.on_read.clone()),
+            on_write: (!self.on_write.is_empty()).then(|| self.on_write.clone()),
+            threads: self.threads,
+            cache_dir: self.cache_dir.clone(),
+            // Given to `normalize-dates` as --format instead.
+            date_formats: None,
+        }
+    }
+}
+
+/// Everything needed to load the input file, so it can be loaded again when it changes.
+#[derive(Clone)]
+struct Loader {
+    file: String,
+    options: ReadOptions,
+    /// Whether rows found by the `report` ragged policy are logged as warnings.
+    warn_ragged: bool,
+    dimension: Option<String>,
+    types: Option<String>,
+    number_locale: Option<String>,
+    /// Layout of the file when it is fixed-width text.
+    fixed_width: Option<String>,
+    filter: Option<String>,
+    columns: Option<String>,
+    /// Format of the file, and the SQLite table to read from it, if any.
+    format: Format,
+    table: Option<String>,
+    records_per_page: usize,
+    /// Row processors run on the data read.
+    on_read: Vec<String>,
+}
+
+impl Loader {
+    /// Reads the file and applies `--dimension`, `--number-locale`, `--types`, `--on-read`,
+    /// `--filter` and `--columns`, then paginates it.
+    fn load(&self, progress: &mut dyn Progress) -> Result<CSVData, Box<dyn Error>> {
+        let mut csv_data = match (self.format, self.fixed_width.as_deref()) {
+            (Format::Sqlite, _) => sqlite::import(&self.file, self.table.as_deref(), &self.file)?,
+            (Format::Csv, Some(layout)) => {
+                CSVData::read_fixed_width(&self.file, &layout.parse()?, self.options, progress)?
+            }
+            (Format::Csv, None) => CSVData::read_with_progress(&self.file, self.options, progress)?,
         };
-        self.pages.clear();
-        let mut start = 0;
-        while start < self.records {
-            let end = std::cmp::min(start + records_per_page, self.records);
-            self.pages.push(Page { start, end });
-            start = end;
-        }
-        println!("Created {} pages", self.pages.len());
-        println!("pages: {:#?}", self.pages);
-    }
-
-    /// Displays the CSV data to the terminal.
-    fn display(&self) {
-        for record in &self.data {
-            println!("{record:#?}");
-        }
-    }
-
-    /// Paginates the CSV data and writes it to the specified writer.
-    pub fn paginate<W: std::io::Write>(
-        &self,
-        start: usize,
-        end: usize,
-        writer: &mut W,
-    ) -> Result<(), std::io::Error> {
-        for record in self.data[start..end].iter() {
-            writeln!(writer, "{record:#?}").expect("Failed to write to writer");
-        }
-        Ok(())
-    }
-
-    /// Deletes a row at the specified index.
-    /// The row is replaced with a row of empty strings
-    /// The length of row matches the number of fields in CSV data
-    /// => ensuring that the dimensions are maintained.
-    /// Returns an error if the index is out of bounds.
-    fn delete_row(&mut self, index: usize) -> Result<(), &'static str> {
-        if index < self.records {
-            let empty_row = vec!["".to_string(); self.fields]; // Create a row with empty strings
-            self.data[index] = StringRecord::from(empty_row); // Replace the row at the specified index
-            Ok(())
-        } else {
-            Err("Index out of bounds")
+        // Edits waiting in the overlay show as if they had been saved.
+        if let Err(e) = Overlay::load(&self.file).and_then(|o| o.apply_to(&mut csv_data)) {
+            log::warn!("the overlay edits of {} are left out: {e}", self.file);
+        }
+        if self.options.ragged == RaggedPolicy::Report && self.warn_ragged {
+            for ragged in csv_data.ragged_rows() {
+                log::warn!("{ragged}");
+            }
+        }
+
+        if let Some(dimension) = self.dimension.as_deref() {
+            let dimensions: Vec<usize> = dimension
+                .split(',')
+                .map(|d| d.parse::<usize>().unwrap_or(0))
+                .collect();
+            if dimensions.len() == 2 {
+                csv_data.records = dimensions[0];
+                csv_data.fields = dimensions[1];
+            }
+        }
+        if let Some(spec) = self.number_locale.as_deref() {
+            csv_data.set_number_locales(spec)?;
+        }
+        if let Some(spec) = self.types.as_deref() {
+            csv_data.set_types(spec)?;
+        }
+        Registry::builtin().run(&self.on_read, &mut csv_data)?;
+        if let Some(filter) = self.filter.as_deref() {
+            let filter = Expr::parse(filter, &csv_data.headers, csv_data.fields)?;
+            csv_data.filter_rows(&filter)?;
         }
+        if let Some(spec) = self.columns.as_deref() {
+            csv_data = csv_data.select_columns(spec)?;
+        }
+        // Paginate the data based on the records_per_page argument
+        csv_data.create_pages(self.records_per_page);
+        Ok(csv_data)
     }
+}
 
-    /// Modifies a field at the specified row and field index.
-    /// Returns an error if the row or field index is out of bounds.
-    fn modify_field(&mut self, row: usize, field: usize, value: &str) -> Result<(), &'static str> {
-        if row < self.records && field < self.fields {
-            if let Some(record) = self.data.get_mut(row) {
-                let mut new_row = record
-                    .iter()
-                    .map(|s| s.to_string())
-                    .collect::<Vec<String>>();
-                if field < new_row.len() {
-                    new_row[field] = value.to_string();
-                    self.data[row] = StringRecord::from(new_row);
-                    Ok(())
-                } else {
-                    Err("Field index out of bounds")
+/// Records `set --overlay` in the overlay of `file_name`, or lists, applies or discards its
+/// edits, reading no more of the file than its header, or one record at a time.
+fn edit_overlay(
+    file_name: &str,
+    command: &Command,
+    options: &ReadOptions,
+    settings: &Settings,
+) -> Result<(), Box<dyn Error>> {
+    match command {
+        Command::Set {
+            row, column, value, ..
+        } => {
+            let mut overlay = Overlay::load(file_name)?;
+            let (headers, fields) = overlay::read_header(file_name, options)?;
+            let field = columns::parse_column(column, &headers, fields)?;
+            let row = row.checked_sub(1).ok_or("rows start at 1")?;
+            if let Some(old) = overlay.set(row, field, value) {
+                log::info!(row = row + 1, field, old = old.as_str(); "replaced overlay edit");
+            }
+            overlay.save()?;
+        }
+        Command::Overlay {
+            action: OverlayAction::List,
+        } => {
+            let overlay = Overlay::load(file_name)?;
+            let headers = match overlay.is_empty() {
+                true => csv::StringRecord::new(),
+                false => overlay::read_header(file_name, options)?.0,
+            };
+            let edits: Vec<report::OverlayEdit> = overlay
+                .iter()
+                .map(|(row, field, value)| report::OverlayEdit {
+                    row: row + 1,
+                    column: columns::column_name(&headers, field),
+                    value: value.to_string(),
+                })
+                .collect();
+            match settings.output_mode {
+                OutputMode::Json => report::write(&edits, &mut std::io::stdout())?,
+                OutputMode::Text => {
+                    for edit in edits {
+                        println!("row {}, {}: {}", edit.row, edit.column, edit.value);
+                    }
                 }
-            } else {
-                Err("Row index out of bounds")
             }
-        } else {
-            Err("Row index or field index out of bounds")
         }
+        Command::Overlay {
+            action: OverlayAction::Apply { output },
+        } => {
+            let overlay = Overlay::load(file_name)?;
+            if overlay.is_empty() {
+                log::info!(file = file_name; "no overlay edits to apply");
+                return Ok(());
+            }
+            let output = output.as_deref().unwrap_or(file_name);
+            overlay.apply(output, options)?;
+            if output == file_name {
+                Overlay::discard(file_name)?;
+            }
+        }
+        Command::Overlay {
+            action: OverlayAction::Discard,
+        } => {
+            Overlay::discard(file_name)?;
+        }
+        _ => unreachable!("not an overlay command"),
     }
+    Ok(())
+}
 
-    /// Writes the CSV data to a file.
-    fn write_to_file(&self, file_name: &str) -> Result<(), Box<dyn std::error::Error>> {
-        let mut writer = Writer::from_path(file_name)?;
-        for record in &self.data {
-            writer.write_record(record)?;
+/// Prints the last `lines` records of `file_name`, then each batch of records appended to it.
+/// Runs until interrupted.
+fn follow(
+    file_name: &str,
+    options: ReadOptions,
+    settings: &Settings,
+    lines: usize,
+    columns: Option<&str>,
+    filter: Option<&str>,
+) -> Result<(), Box<dyn Error>> {
+    let mut follower = Follower::open(file_name, options)?;
+    let records = follower.poll()?;
+    let fields = match follower.headers() {
+        headers if !headers.is_empty() => headers.len(),
+        _ => records.first().map_or(0, |record| record.len()),
+    };
+    let columns = match columns {
+        Some(spec) => Some(columns::parse_columns(spec, follower.headers(), fields)?),
+        None => None,
+    };
+    let filter = match filter {
+        Some(filter) => Some(Expr::parse(filter, follower.headers(), fields)?),
+        None => None,
+    };
+    let select = |records: Vec<csv::StringRecord>| -> Result<Vec<_>, EditorError> {
+        let mut selected = Vec::new();
+        for record in records {
+            if let Some(filter) = &filter {
+                if !filter.matches(&record)? {
+                    continue;
+                }
+            }
+            selected.push(record);
+        }
+        Ok(selected)
+    };
+    let records = select(records)?;
+    let project = |record: &csv::StringRecord| match &columns {
+        Some(columns) => columns::project(record, columns),
+        None => record.clone(),
+    };
+    // The header is only printed with the first batch.
+    let headers = project(follower.headers());
+    let records: Vec<_> = records[records.len().saturating_sub(lines)..]
+        .iter()
+        .map(project)
+        .collect();
+    render::write_records(&headers, &records, settings, &mut std::io::stdout())?;
+
+    let (sender, changes) = mpsc::channel();
+    let _watcher = watch::watch(file_name, move || sender.send(()).is_ok())?;
+    for () in changes {
+        let records: Vec<_> = select(follower.poll()?)?.iter().map(project).collect();
+        if !records.is_empty() {
+            render::write_records(
+                &csv::StringRecord::new(),
+                &records,
+                settings,
+                &mut std::io::stdout(),
+            )?;
         }
-        Ok(())
     }
+    Ok(())
 }
 
-#[derive(Parser)]
-#[command(author, version, about, long_about = None)]
-struct Cli {
-    /// Sets the input CSV file to use
-    file: String,
+/// Prints the given 1-based pages, each preceded by its page indicator,
+/// scrolled `scroll` columns to the right.
+fn view<W: std::io::Write>(
+    csv_data: &CSVData,
+    settings: &Settings,
+    pages: RangeInclusive<usize>,
+    scroll: usize,
+    writer: &mut W,
+) -> Result<(), Box<dyn Error>> {
+    let count = csv_data.pages.len();
+    if *pages.end() > count {
+        return Err(EditorError::PageOutOfBounds {
+            page: *pages.end(),
+            pages: count,
+        }
+        .into());
+    }
+    if settings.output_mode == OutputMode::Json {
+        let mut rows = Vec::new();
+        for number in pages {
+            let page = &csv_data.pages[number - 1];
+            rows.extend_from_slice(csv_data.rows(page.start, page.end)?);
+        }
+        report::write(&Records::new(&csv_data.headers, &rows), writer)?;
+        return Ok(());
+    }
+    for number in pages {
+        let page = &csv_data.pages[number - 1];
+        if let Some(mut label) = csv_data.page_label(number - 1) {
+            if let Some(columns) = csv_data.column_label(scroll, settings) {
+                label = format!("{label}, {columns}");
+            }
+            writeln!(writer, "-- {label} --")?;
+        }
+        csv_data.render_scrolled(page.start, page.end, scroll, settings, writer)?;
+    }
+    Ok(())
+}
 
-    /// Sets the dimensions (rows, columns) of the CSV file
-    #[arg(long)]
-    dimension: Option<String>,
+/// Prints what `apply-plugin` or `script` did.
+fn print_processed(
+    processed: Processed,
+    settings: &Settings,
+    quiet: bool,
+) -> Result<(), Box<dyn Error>> {
+    match settings.output_mode {
+        OutputMode::Text if quiet => {}
+        OutputMode::Text => println!(
+            "{} value(s) changed, {} row(s) dropped",
+            processed.changed, processed.dropped
+        ),
+        OutputMode::Json => report::write(&processed, &mut std::io::stdout())?,
+    }
+    Ok(())
+}
 
-    /// Turn debugging information on
-    #[arg(short, long, action = clap::ArgAction::Count)]
-    debug: u8,
+/// Exit status when a check found problems, or the command failed for another reason.
+const EXIT_INVALID: u8 = 1;
+/// Exit status when the file, or an argument, cannot be parsed.
+const EXIT_PARSE: u8 = 2;
+/// Exit status when a file cannot be read, written or downloaded.
+const EXIT_IO: u8 = 3;
 
-    /// Sets the number of records per page for pagination
-    #[arg(short, long, default_value_t = 10)]
-    records_per_page: usize,
+/// Returned by commands that check the data when they found problems: `lint` ragged rows,
+/// `check` rules broken, `merge` conflicts left between markers and `normalize-dates --strict`
+/// values that are not dates. They have been printed already, so the process exits with
+/// [`EXIT_INVALID`] without an error message.
+#[derive(Debug)]
+struct Failed;
+
+impl fmt::Display for Failed {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("the check failed")
+    }
 }
 
-/// Gets the dimensions of a CSV file if it's not provided by the user.
-fn get_dimensions(file_name: &str) -> Result<(usize, usize), Box<dyn Error>> {
-    let mut reader = Reader::from_path(file_name)?;
-    let records = reader.records();
-    let rows = records.count();
-    let mut reader = Reader::from_path(file_name)?; // Recreate the reader because counting the records consumes the iterator
-    let columns = match reader.headers() {
-        Ok(headers) => headers.iter().count(),
-        Err(_) => 0,
-    };
-    Ok((rows, columns))
+impl Error for Failed {}
+
+/// Returns the exit status for `error`.
+fn exit_code(error: &(dyn Error + 'static)) -> u8 {
+    if let Some(error) = error.downcast_ref::<EditorError>() {
+        return match error {
+            EditorError::Io(_) | EditorError::Remote(_) | EditorError::Locked { .. } => EXIT_IO,
+            EditorError::Parse { .. }
+            | EditorError::Encoding(_)
+            | EditorError::Schema { .. }
+            | EditorError::Compression(_)
+            | EditorError::Expression { .. }
+            | EditorError::InvalidColumns(_)
+            | EditorError::InvalidRange(_)
+            | EditorError::InvalidFormat(_)
+            | EditorError::Session(_) => EXIT_PARSE,
+            _ => EXIT_INVALID,
+        };
+    }
+    if let Some(error) = error.downcast_ref::<csv::Error>() {
+        return match error.is_io_error() {
+            true => EXIT_IO,
+            false => EXIT_PARSE,
+        };
+    }
+    match error.is::<std::io::Error>() {
+        true => EXIT_IO,
+        false => EXIT_INVALID,
+    }
 }
 
-fn main() -> Result<(), Box<dyn Error>> {
-    let cli = Cli::parse();
+fn main() -> ExitCode {
+    match run(Cli::parse()) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(error) => {
+            if !error.is::<Failed>() {
+                eprintln!("error: {error}");
+            }
+            ExitCode::from(exit_code(&*error))
+        }
+    }
+}
+
+fn run(cli: Cli) -> Result<(), Box<dyn Error>> {
+    logger::init(cli.debug, cli.quiet, cli.log_file.as_deref())?;
+    execute(cli)
+}
+
+/// Runs the command of `cli`, once logging is set up.
+fn execute(mut cli: Cli) -> Result<(), Box<dyn Error>> {
+    if let Some(Command::Completions { shell }) = cli.command {
+        let mut command = Cli::command();
+        let name = command.get_name().to_string();
+        // Written at once, as generate panics on write errors such as a closed pipe.
+        let mut script = Vec::new();
+        clap_complete::generate(shell, &mut command, name, &mut script);
+        std::io::stdout().write_all(&script)?;
+        return Ok(());
+    }
+    // `merge BASE OURS THEIRS` merges into OURS, as `OURS merge BASE THEIRS` does.
+    if let Some(Command::Merge { files, .. }) = &mut cli.command {
+        if files.len() == 3 {
+            if !cli.file.is_empty() {
+                return Err("merge takes OURS either as FILE or between BASE and THEIRS".into());
+            }
+            cli.file = files.remove(1);
+        }
+    }
+    // Other subcommands need a file, though clap does not ask for it with a subcommand.
+    if cli.file.is_empty() && cli.resume.is_none() {
+        Cli::command()
+            .error(
+                clap::error::ErrorKind::MissingRequiredArgument,
+                "the input FILE is required by this subcommand",
+            )
+            .exit();
+    }
+
+    // Resuming a session opens its files in interactive mode, with the same view.
+    let resumed = match cli.resume.clone() {
+        Some(path) => {
+            let snapshot = Snapshot::load(&path)?;
+            let watch = match cli.command {
+                None => false,
+                Some(Command::Interactive { watch, .. }) => watch,
+                Some(_) => return Err("--resume only resumes interactive mode".into()),
+            };
+            let mut files = snapshot.files.iter().map(|state| state.file.clone());
+            cli.file = files.next().unwrap_or_default();
+            cli.more_files = files.collect();
+            cli.command = Some(Command::Interactive {
+                columns: snapshot.columns.clone(),
+                filter: snapshot.filter.clone(),
+                watch,
+                session: Some(path),
+            });
+            Some(snapshot)
+        }
+        None => None,
+    };
+
+    if cli.read_only {
+        if let Some(name) = cli.command.as_ref().and_then(Command::edit_name) {
+            return Err(format!("'{name}' edits the file and is disabled by --read-only").into());
+        }
+    }
+    if !cli.more_files.is_empty()
+        && !matches!(
+            cli.command,
+            Some(Command::Interactive { .. } | Command::Concat { .. })
+        )
+    {
+        return Err("only interactive mode and concat open several files".into());
+    }
+    if remote::is_remote(&cli.file) {
+        if cli.command.as_ref().and_then(|c| c.output(&cli.file)) == Some(cli.file.as_str()) {
+            return Err(format!("cannot save to {}, use --output", cli.file).into());
+        }
+        if matches!(
+            cli.command,
+            Some(
+                Command::Follow { .. }
+                    | Command::View { watch: true, .. }
+                    | Command::Interactive { watch: true, .. }
+            )
+        ) {
+            return Err(format!("cannot watch {} for changes", cli.file).into());
+        }
+    }
+    // Held until the command is done, so other instances do not save over our changes.
+    let _lock = match cli
+        .command
+        .as_ref()
+        .and_then(|command| command.output(&cli.file))
+    {
+        Some(output) => Some(FileLock::acquire(output)?),
+        None => None,
+    };
+
+    let settings = cli
+        .overrides()
+        .or(Config::load(cli.config.as_deref())?)
+        .settings()?;
+    if let Some(threads) = settings.threads {
+        rayon::ThreadPoolBuilder::new()
+            .num_threads(threads)
+            .build_global()?;
+    }
+    // Remote files are downloaded first, and the local copies read in their place.
+    let mut downloads = Vec::new();
+    for file in std::iter::once(&mut cli.file).chain(&mut cli.more_files) {
+        if remote::is_remote(file) {
+            let download = remote::fetch(file, settings.cache_dir.as_deref())?;
+            *file = download.path().to_string_lossy().into_owned();
+            downloads.push(download);
+        }
+    }
+    let registry = Registry::builtin();
+    if let Some(Command::ApplyPlugin { list: true, .. }) = cli.command {
+        if settings.output_mode == OutputMode::Json {
+            let plugins: Vec<report::Plugin> = registry
+                .iter()
+                .map(|(name, description)| report::Plugin {
+                    name: name.to_string(),
+                    description: description.to_string(),
+                })
+                .collect();
+            report::write(&plugins, &mut std::io::stdout())?;
+            return Ok(());
+        }
+        for (name, description) in registry.iter() {
+            println!("{name:<12} {description}");
+        }
+        return Ok(());
+    }
+    // Fail before reading the file rather than when saving it.
+    for name in settings.on_read.iter().chain(&settings.on_write) {
+        registry.create(name)?;
+    }
+    let mut options = ReadOptions::from(&settings);
+    options.encoding = cli.encoding;
+    options.backend = cli.read_backend.unwrap_or_default();
+    options.index = cli.index.unwrap_or_default();
+    let linting = matches!(cli.command, Some(Command::Lint));
+    if linting {
+        options.ragged = RaggedPolicy::Report;
+    }
+
+    if let Some(command @ (Command::Set { overlay: true, .. } | Command::Overlay { .. })) =
+        &cli.command
+    {
+        if cli.fixed_width.is_some() {
+            return Err("overlay edits only apply to CSV, not fixed-width text".into());
+        }
+        return edit_overlay(&cli.file, command, &options, &settings);
+    }
+
+    if let Some(Command::Paste {
+        stdin,
+        new: true,
+        truncate,
+        ..
+    }) = &cli.command
+    {
+        if std::path::Path::new(&cli.file).exists() {
+            return Err(format!(
+                "{} already exists, paste without --new to add rows to it",
+                cli.file
+            )
+            .into());
+        }
+        let mut clip = read_clip(*stdin)?;
+        let headers = clip.take_header().ok_or("there are no rows to paste")?;
+        warn_fitted(clip.fit(headers.len(), *truncate)?, headers.len());
+        let csv_data = CSVData::new(&cli.file, headers, clip.rows);
+        return save(
+            &csv_data,
+            &cli.file,
+            &settings,
+            &mut Reporter::new(cli.timing, cli.quiet),
+        );
+    }
+
+    if let Some(Command::Follow {
+        lines,
+        columns,
+        filter,
+    }) = &cli.command
+    {
+        if cli.fixed_width.is_some() {
+            return Err("follow only reads CSV, not fixed-width text".into());
+        }
+        return follow(
+            &cli.file,
+            options,
+            &settings,
+            *lines,
+            columns.as_deref(),
+            filter.as_deref(),
+        );
+    }
+
+    let (format, table) = match &cli.command {
+        Some(Command::Convert {
+            from: Format::Sqlite,
+            table,
+            ..
+        }) => (Format::Sqlite, table.clone()),
+        _ => (Format::Csv, None),
+    };
+    let loader = Loader {
+        file: cli.file.clone(),
+        options,
+        warn_ragged: !linting,
+        dimension: cli.dimension.clone(),
+        types: cli.types.clone(),
+        number_locale: cli.number_locale.clone(),
+        fixed_width: cli.fixed_width.clone(),
+        filter: cli
+            .command
+            .as_ref()
+            .and_then(Command::filter)
+            .map(str::to_string),
+        columns: cli
+            .command
+            .as_ref()
+            .and_then(Command::columns)
+            .map(str::to_string),
+        format,
+        table,
+        records_per_page: settings.records_per_page,
+        on_read: settings.on_read.clone(),
+    };
+    let mut reporter = Reporter::new(cli.timing, cli.quiet);
+    let mut csv_data = loader.load(&mut reporter)?;
+    if let Some(journal) = cli.audit.as_deref() {
+        csv_data.set_journal(Journal::open(journal)?)?;
+    }
 
-    println!("dbug = {}", cli.debug);
+    match cli.command {
+        Some(Command::View {
+            page,
+            pages,
+            scroll,
+            col_page,
+            watch,
+            ..
+        }) => {
+            let pages = pages.unwrap_or_else(|| {
+                let page = page.unwrap_or(1);
+                page..=page
+            });
+            let scroll = match col_page {
+                Some(number) => {
+                    let count = csv_data.col_page_count(&settings);
+                    if number == 0 || number > count {
+                        return Err(format!(
+                            "no column page {number}, the file has {count} column page(s)"
+                        )
+                        .into());
+                    }
+                    (number - 1) * settings.cols_per_page.unwrap_or(0)
+                }
+                None => scroll,
+            };
+            view(
+                &csv_data,
+                &settings,
+                pages.clone(),
+                scroll,
+                &mut std::io::stdout().lock(),
+            )?;
+            if !watch {
+                return Ok(());
+            }
+            let (sender, changes) = mpsc::channel();
+            let _watcher = watch::watch(&cli.file, move || sender.send(()).is_ok())?;
+            for () in changes {
+                let csv_data = match loader.load(&mut |_| {}) {
+                    Ok(csv_data) => csv_data,
+                    Err(e) => {
+                        log::warn!("could not reload {}: {e}", cli.file);
+                        continue;
+                    }
+                };
+                // Show the same pages if the file still has them, otherwise its last page.
+                let last = csv_data.pages.len().max(1);
+                let pages = (*pages.start()).min(last)..=(*pages.end()).min(last);
+                // Clear the screen so the reloaded pages replace the old ones.
+                if settings.output_mode == OutputMode::Text {
+                    print!("\x1b[2J\x1b[H");
+                }
+                if let Err(e) = view(
+                    &csv_data,
+                    &settings,
+                    pages,
+                    scroll,
+                    &mut std::io::stdout().lock(),
+                ) {
+                    log::warn!("{e}");
+                }
+            }
+            Ok(())
+        }
+        Some(Command::Interactive {
+            columns,
+            filter,
+            watch,
+            session: session_file,
+        }) => {
+            let mut session = interactive::Session::new(csv_data, settings);
+            for file in &cli.more_files {
+                let loader = Loader {
+                    file: file.clone(),
+                    ..loader.clone()
+                };
+                session.open(loader.load(&mut reporter)?);
+            }
+            if columns.is_some() || filter.is_some() {
+                session.set_partial();
+            }
+            session.set_read_only(cli.read_only);
+            if !cli.read_only {
+                session.lock()?;
+            }
+            if let Some(snapshot) = &resumed {
+                session.restore(snapshot)?;
+            }
+            if let Some(path) = session_file {
+                session.autosave(path, columns, filter);
+            }
+            if !watch {
+                session.run(std::io::stdin().lock(), &mut std::io::stdout())?;
+                return Ok(());
+            }
+            let (sender, events) = mpsc::channel();
+            let changes = sender.clone();
+            let _watcher =
+                watch::watch(&cli.file, move || changes.send(Ok(Event::Changed)).is_ok())?;
+            std::thread::spawn(move || {
+                for line in std::io::stdin().lines() {
+                    if sender.send(line.map(Event::Line)).is_err() {
+                        return;
+                    }
+                }
+                let _ = sender.send(Ok(Event::Closed));
+            });
+            session.run_events(events, &mut std::io::stdout(), &mut || {
+                loader.load(&mut |_| {})
+            })?;
+            Ok(())
+        }
+        Some(Command::Convert {
+            output,
+            output_encoding,
+            compress,
+            to: Format::Sqlite,
+            table,
+            if_exists,
+            ..
+        }) => {
+            if output_encoding.is_some() || compress.is_some() {
+                return Err("--output-encoding and --compress only apply to CSV output".into());
+            }
+            if csv_data.types.is_empty() {
+                csv_data.set_types("auto")?;
+            }
+            let table = table.unwrap_or_else(|| {
+                Path::new(&cli.file)
+                    .file_stem()
+                    .map_or(cli.file.clone(), |stem| stem.to_string_lossy().into_owned())
+            });
+            registry.run(&settings.on_write, &mut csv_data)?;
+            sqlite::export(&csv_data, &output, &table, if_exists)?;
+            Ok(())
+        }
+        Some(Command::Convert {
+            output,
+            output_encoding,
+            compress,
+            ..
+        }) => {
+            let mut write_options = WriteOptions {
+                na_value: settings.na_value.clone(),
+                compression: compress.or_else(|| Compression::from_extension(&output)),
+                ..WriteOptions::default()
+            };
+            if let Some(encoding) = output_encoding {
+                write_options.encoding = encoding;
+            }
+            registry.run(&settings.on_write, &mut csv_data)?;
+            csv_data.write_with_progress(&output, write_options, &mut reporter)?;
+            Ok(())
+        }
+        Some(Command::Export {
+            format,
+            page,
+            title,
+            widths,
+            no_header,
+            output,
+            ..
+        }) => {
+            let rows = match page {
+                Some(number) => match csv_data.pages.get(number.wrapping_sub(1)) {
+                    Some(page) => page.start..page.end,
+                    None => {
+                        return Err(EditorError::PageOutOfBounds {
+                            page: number,
+                            pages: csv_data.pages.len(),
+                        }
+                        .into())
+                    }
+                },
+                None => 0..csv_data.data.len(),
+            };
+            let records = csv_data.display_rows(rows.start, rows.end, &settings)?;
+            let title = title.unwrap_or_else(|| {
+                Path::new(&cli.file)
+                    .file_name()
+                    .map_or(cli.file.clone(), |name| name.to_string_lossy().into_owned())
+            });
+            let mut writer: Box<dyn Write> = match &output {
+                Some(output) => Box::new(BufWriter::new(File::create(output)?)),
+                None => Box::new(std::io::stdout().lock()),
+            };
+            if format == ExportFormat::FixedWidth && (widths.is_some() || no_header) {
+                let layout = match widths {
+                    Some(spec) => spec.parse()?,
+                    None => Layout::fit(&csv_data.headers, &records),
+                };
+                let headers = (!no_header).then_some(&csv_data.headers);
+                fixed_width::write(&layout, headers, &records, &csv_data.types, &mut writer)?;
+            } else {
+                export::write(
+                    format,
+                    &title,
+                    &csv_data.headers,
+                    &records,
+                    &csv_data.types,
+                    &mut writer,
+                )?;
+            }
+            writer.flush()?;
+            Ok(())
+        }
+        Some(Command::Head { count }) => {
+            let records = csv_data.head(count);
+            render::write_records(
+                &csv_data.headers,
+                records,
+                &settings,
+                &mut std::io::stdout(),
+            )?;
+            Ok(())
+        }
+        Some(Command::Tail { count }) => {
+            let records = csv_data.tail(count);
+            render::write_records(
+                &csv_data.headers,
+                records,
+                &settings,
+                &mut std::io::stdout(),
+            )?;
+            Ok(())
+        }
+        Some(Command::Sample { count, seed }) => {
+            let seed = seed.unwrap_or_else(|| {
+                let seed = rand::rng().random();
+                if !cli.quiet {
+                    eprintln!("Sampling with --seed {seed}");
+                }
+                seed
+            });
+            let records = csv_data.sample(count, seed);
+            render::write_records(
+                &csv_data.headers,
+                &records,
+                &settings,
+                &mut std::io::stdout(),
+            )?;
+            Ok(())
+        }
+        Some(Command::Lint) => {
+            let ragged = csv_data.ragged_rows();
+            if settings.output_mode == OutputMode::Json {
+                let lint = report::Lint {
+                    file: csv_data.file_name.clone(),
+                    ragged_rows: ragged.iter().map(report::Ragged::from).collect(),
+                };
+                report::write(&lint, &mut std::io::stdout())?;
+            } else {
+                for row in &ragged {
+                    println!("{row}");
+                }
+                if !cli.quiet {
+                    println!("{} ragged row(s) in {}", ragged.len(), csv_data.file_name);
+                }
+            }
+            match ragged.is_empty() {
+                true => Ok(()),
+                false => Err(Failed.into()),
+            }
+        }
+        Some(Command::Check { rules, rule }) => {
+            let mut checked = match rules {
+                Some(file) => Rules::load(&file)?,
+                None => Rules::default(),
+            };
+            checked.rules.extend(rule);
+            let violations = checked.check(&csv_data)?;
+            if settings.output_mode == OutputMode::Json {
+                let check = report::Check {
+                    file: csv_data.file_name.clone(),
+                    violations: violations.iter().map(report::Violation::from).collect(),
+                };
+                report::write(&check, &mut std::io::stdout())?;
+            } else {
+                for violation in &violations {
+                    println!("{violation}");
+                }
+                if !cli.quiet {
+                    println!(
+                        "{} violation(s) of {} rule(s) in {}",
+                        violations.len(),
+                        checked.rules.len(),
+                        csv_data.file_name
+                    );
+                }
+            }
+            match violations.is_empty() {
+                true => Ok(()),
+                false => Err(Failed.into()),
+            }
+        }
+        Some(Command::Bookmark { action }) => {
+            let mut bookmarks = Bookmarks::load(&cli.file)?;
+            match action {
+                BookmarkAction::Add { name, row } => {
+                    let row =
+                        Target::Row(row.checked_sub(1).ok_or(EditorError::RowOutOfBounds {
+                            row: 0,
+                            rows: csv_data.data.len(),
+                        })?)
+                        .resolve(&csv_data, &bookmarks)?;
+                    if let Some(old) = bookmarks.add(&name, row)? {
+                        log::info!(bookmark = name.as_str(), old = old + 1; "moved bookmark");
+                    }
+                    bookmarks.save()?;
+                }
+                BookmarkAction::Remove { name } => {
+                    if bookmarks.remove(&name).is_none() {
+                        return Err(
+                            EditorError::NotFound(format!("no bookmark named '{name}'")).into()
+                        );
+                    }
+                    bookmarks.save()?;
+                }
+                BookmarkAction::List if settings.output_mode == OutputMode::Json => {
+                    let list: Vec<report::Bookmark> = bookmarks
+                        .iter()
+                        .map(|(name, row)| report::Bookmark {
+                            name: name.to_string(),
+                            row: row + 1,
+                        })
+                        .collect();
+                    report::write(&list, &mut std::io::stdout())?;
+                }
+                BookmarkAction::List => {
+                    for (name, row) in bookmarks.iter() {
+                        println!("{name}\t{}", row + 1);
+                    }
+                }
+            }
+            Ok(())
+        }
+        Some(Command::Comment { action }) => {
+            let mut comments = Comments::load(&cli.file)?;
+            // Cells are commented by column name, so that comments follow reordered columns.
+            let column = |spec: Option<String>| -> Result<Option<String>, EditorError> {
+                spec.map(|spec| {
+                    let field = columns::parse_column(&spec, &csv_data.headers, csv_data.fields)?;
+                    Ok(columns::column_name(&csv_data.headers, field))
+                })
+                .transpose()
+            };
+            match action {
+                CommentAction::Add {
+                    row,
+                    text,
+                    column: spec,
+                } => {
+                    let rows = csv_data.data.len();
+                    let row = row.checked_sub(1).filter(|&row| row < rows).ok_or(
+                        EditorError::RowOutOfBounds {
+                            row: row.saturating_sub(1),
+                            rows,
+                        },
+                    )?;
+                    if let Some(old) = comments.add(row, column(spec)?, &text)? {
+                        log::info!(row = row + 1, old = old.as_str(); "replaced comment");
+                    }
+                    comments.save()?;
+                }
+                CommentAction::Remove { row, column: spec } => {
+                    let column = column(spec)?;
+                    let cell = match &column {
+                        Some(column) => format!("row {row}, {column}"),
+                        None => format!("row {row}"),
+                    };
+                    if comments.remove(row.wrapping_sub(1), column).is_none() {
+                        return Err(EditorError::NotFound(format!("no comment on {cell}")).into());
+                    }
+                    comments.save()?;
+                }
+                CommentAction::List | CommentAction::Export { .. } => {
+                    let list: Vec<report::Comment> = comments
+                        .iter()
+                        .map(|(row, column, text)| {
+                            let value = column.and_then(|column| {
+                                let field = columns::parse_column(
+                                    column,
+                                    &csv_data.headers,
+                                    csv_data.fields,
+                                )
+                                .ok()?;
+                                Some(csv_data.data.get(row)?.get(field)?.to_string())
+                            });
+                            report::Comment {
+                                row: row + 1,
+                                line: csv_data.line(row),
+                                column: column.map(str::to_string),
+                                value,
+                                text: text.to_string(),
+                            }
+                        })
+                        .collect();
+                    match action {
+                        CommentAction::Export { output } => {
+                            let mut writer = csv::Writer::from_path(&output)?;
+                            for comment in &list {
+                                writer.serialize(comment)?;
+                            }
+                            writer.flush()?;
+                            log::info!(file = output.as_str(), comments = list.len(); "exported comments");
+                        }
+                        _ if settings.output_mode == OutputMode::Json => {
+                            report::write(&list, &mut std::io::stdout())?;
+                        }
+                        _ => {
+                            for comment in &list {
+                                let cell = match (&comment.column, &comment.value) {
+                                    (Some(column), Some(value)) => {
+                                        format!(", {column} '{value}'")
+                                    }
+                                    (Some(column), None) => format!(", {column}"),
+                                    _ => String::new(),
+                                };
+                                println!(
+                                    "{}{cell}: {}",
+                                    csv_data.describe_row(comment.row - 1),
+                                    comment.text
+                                );
+                            }
+                        }
+                    }
+                }
+            }
+            Ok(())
+        }
+        Some(Command::Goto { target }) => {
+            let bookmarks = Bookmarks::load(&cli.file)?;
+            let row = target.resolve(&csv_data, &bookmarks)?;
+            // Rows past --dimension are on no page.
+            let page = csv_data.page_of(row).ok_or(EditorError::RowOutOfBounds {
+                row,
+                rows: csv_data.records,
+            })? + 1;
+            if settings.output_mode == OutputMode::Json {
+                let range = &csv_data.pages[page - 1];
+                let location = report::Location {
+                    row: row + 1,
+                    line: csv_data.line(row),
+                    page,
+                    records: Records::new(
+                        &csv_data.headers,
+                        csv_data.rows(range.start, range.end)?,
+                    ),
+                };
+                report::write(&location, &mut std::io::stdout())?;
+                return Ok(());
+            }
+            println!("{} is on page {page}", csv_data.describe_row(row));
+            view(&csv_data, &settings, page..=page, 0, &mut std::io::stdout())
+        }
+        Some(Command::Set {
+            row,
+            column,
+            value,
+            force,
+            output,
+            ..
+        }) => {
+            let field = columns::parse_column(&column, &csv_data.headers, csv_data.fields)?;
+            let row = row.checked_sub(1).ok_or(EditorError::RowOutOfBounds {
+                row: 0,
+                rows: csv_data.data.len(),
+            })?;
+            match csv_data.modify_field(row, field, &value) {
+                Err(e @ EditorError::TypeMismatch { .. }) if force => {
+                    log::warn!("{e}");
+                    csv_data.modify_field_unchecked(row, field, &value)?;
+                }
+                result => result?,
+            }
+            save(
+                &csv_data,
+                output.as_deref().unwrap_or(&cli.file),
+                &settings,
+                &mut reporter,
+            )
+        }
+        Some(Command::Sort {
+            column,
+            desc,
+            numeric,
+            output,
+        }) => {
+            let field = columns::parse_column(&column, &csv_data.headers, csv_data.fields)?;
+            csv_data.sort_by_column(field, desc, numeric, &mut reporter)?;
+            save(
+                &csv_data,
+                output.as_deref().unwrap_or(&cli.file),
+                &settings,
+                &mut reporter,
+            )
+        }
+        Some(Command::CopyRange {
+            range,
+            clipboard,
+            cut,
+            output,
+        }) => {
+            let range = CellRange::parse(&range, &csv_data.headers, csv_data.fields)?;
+            let clip = if cut {
+                csv_data.cut_range(&range)?
+            } else {
+                csv_data.copy_range(&range)?
+            };
+            let tsv = clip.to_tsv()?;
+            if clipboard {
+                set_clipboard_text(tsv)?;
+            } else if settings.output_mode == OutputMode::Json {
+                let cells: Vec<Vec<&str>> =
+                    clip.rows.iter().map(|row| row.iter().collect()).collect();
+                report::write(&cells, &mut std::io::stdout())?;
+            } else {
+                print!("{tsv}");
+            }
+            if cut {
+                save(
+                    &csv_data,
+                    output.as_deref().unwrap_or(&cli.file),
+                    &settings,
+                    &mut reporter,
+                )?;
+            }
+            Ok(())
+        }
+        Some(Command::PasteRange {
+            at,
+            clipboard,
+            output,
+        }) => {
+            let at = CellRange::parse(&at, &csv_data.headers, csv_data.fields)?;
+            let text = if clipboard {
+                arboard::Clipboard::new()?.get_text()?
+            } else {
+                std::io::read_to_string(std::io::stdin())?
+            };
+            let field = at.columns.first().copied().unwrap_or(0);
+            csv_data.paste(*at.rows.start(), field, &Clip::from_tsv(&text)?)?;
+            save(
+                &csv_data,
+                output.as_deref().unwrap_or(&cli.file),
+                &settings,
+                &mut reporter,
+            )
+        }
+        Some(Command::Paste {
+            stdin,
+            truncate,
+            output,
+            ..
+        }) => {
+            let mut clip = read_clip(stdin)?;
+            clip.strip_header(&csv_data.headers);
+            if clip.rows.is_empty() {
+                return Err("there are no rows to paste".into());
+            }
+            warn_fitted(clip.fit(csv_data.fields, truncate)?, csv_data.fields);
+            csv_data.paste(csv_data.data.len(), 0, &clip)?;
+            save(
+                &csv_data,
+                output.as_deref().unwrap_or(&cli.file),
+                &settings,
+                &mut reporter,
+            )
+        }
+        Some(Command::SplitCol {
+            column,
+            on,
+            into,
+            keep,
+            output,
+        }) => {
+            let field = columns::parse_column(&column, &csv_data.headers, csv_data.fields)?;
+            csv_data.split_column(field, &on, &into, keep)?;
+            save(
+                &csv_data,
+                output.as_deref().unwrap_or(&cli.file),
+                &settings,
+                &mut reporter,
+            )
+        }
+        Some(Command::MergeCol {
+            columns,
+            into,
+            format,
+            sep,
+            keep,
+            output,
+        }) => {
+            let merged = columns::parse_columns(&columns, &csv_data.headers, csv_data.fields)?;
+            let pieces = match format {
+                Some(format) => {
+                    transform::parse_format(&format, &csv_data.headers, csv_data.fields)?
+                }
+                None => {
+                    let mut pieces = Vec::new();
+                    for (i, &column) in merged.iter().enumerate() {
+                        if i > 0 {
+                            pieces.push(Piece::Text(sep.clone()));
+                        }
+                        pieces.push(Piece::Column(column));
+                    }
+                    pieces
+                }
+            };
+            csv_data.merge_columns(&merged, &pieces, &into, keep)?;
+            save(
+                &csv_data,
+                output.as_deref().unwrap_or(&cli.file),
+                &settings,
+                &mut reporter,
+            )
+        }
+        Some(Command::AddCol { name, expr, output }) => {
+            let expr = Expr::parse(&expr, &csv_data.headers, csv_data.fields)?;
+            csv_data.add_column(&name, &expr)?;
+            save(
+                &csv_data,
+                output.as_deref().unwrap_or(&cli.file),
+                &settings,
+                &mut reporter,
+            )
+        }
+        Some(Command::GroupBy { key, agg, output }) => {
+            let keys = columns::parse_columns(&key, &csv_data.headers, csv_data.fields)?;
+            let aggregations = Aggregation::parse_list(&agg, &csv_data.headers, csv_data.fields)?;
+            let grouped = csv_data.group_by(&keys, &aggregations)?;
+            match output {
+                Some(output) => save(&grouped, &output, &settings, &mut reporter),
+                None => {
+                    render::write_records(
+                        &grouped.headers,
+                        &grouped.data,
+                        &settings,
+                        &mut std::io::stdout(),
+                    )?;
+                    Ok(())
+                }
+            }
+        }
+        Some(Command::ValueCounts {
+            column,
+            by_value,
+            top,
+        }) => {
+            let field = columns::parse_column(&column, &csv_data.headers, csv_data.fields)?;
+            let name = match csv_data.headers.get(field) {
+                Some(name) if !name.is_empty() => name.to_string(),
+                _ => (field + 1).to_string(),
+            };
+            let mut counts = csv_data.value_counts(field)?;
+            if let Some(top) = top {
+                counts.truncate(top);
+            }
+            if by_value {
+                counts.sort_by(|(a, _), (b, _)| a.cmp(b));
+            }
+            let rows = csv_data.data.len();
+            let percent = |count: usize| count as f64 * 100.0 / rows.max(1) as f64;
+            if settings.output_mode == OutputMode::Json {
+                let values = counts
+                    .into_iter()
+                    .map(|(value, count)| report::ValueCount {
+                        value,
+                        count,
+                        percent: percent(count),
+                    })
+                    .collect();
+                let counts = report::ValueCounts {
+                    column: name,
+                    rows,
+                    values,
+                };
+                report::write(&counts, &mut std::io::stdout())?;
+                return Ok(());
+            }
+            let headers = csv::StringRecord::from(vec![name.as_str(), "count", "percent"]);
+            let records: Vec<csv::StringRecord> = counts
+                .iter()
+                .map(|(value, count)| {
+                    let (count, percent) = (count.to_string(), format!("{:.1}", percent(*count)));
+                    csv::StringRecord::from(vec![value.as_str(), &count, &percent])
+                })
+                .collect();
+            render::write_records(&headers, &records, &settings, &mut std::io::stdout())?;
+            Ok(())
+        }
+        Some(Command::Widths { layout }) => {
+            if layout {
+                println!("{}", Layout::fit(&csv_data.headers, &csv_data.data));
+                return Ok(());
+            }
+            let stats = fixed_width::width_stats(&csv_data.headers, &csv_data.data);
+            let headers = csv::StringRecord::from(vec!["column", "min", "max", "mean"]);
+            let records: Vec<csv::StringRecord> = stats
+                .iter()
+                .enumerate()
+                .map(|(i, stats)| {
+                    let name = match csv_data.headers.get(i) {
+                        Some(name) if !name.is_empty() => name.to_string(),
+                        _ => (i + 1).to_string(),
+                    };
+                    csv::StringRecord::from(vec![
+                        name,
+                        stats.min.to_string(),
+                        stats.max.to_string(),
+                        format!("{:.1}", stats.mean),
+                    ])
+                })
+                .collect();
+            render::write_records(&headers, &records, &settings, &mut std::io::stdout())?;
+            Ok(())
+        }
+        Some(Command::Profile {
+            examples,
+            html,
+            output,
+            ..
+        }) => {
+            let mut profile = profile::profile(&csv_data, examples);
+            profile.file = cli.file.clone();
+            let mut writer: Box<dyn Write> = match &output {
+                Some(output) => Box::new(BufWriter::new(File::create(output)?)),
+                None => Box::new(std::io::stdout().lock()),
+            };
+            if html {
+                profile::write_html(&profile, &mut writer)?;
+            } else if settings.output_mode == OutputMode::Json {
+                report::write(&profile, &mut writer)?;
+            } else {
+                profile::write_text(&profile, &mut writer)?;
+            }
+            writer.flush()?;
+            Ok(())
+        }
+        Some(Command::Pivot {
+            index,
+            names,
+            values,
+            agg,
+            output,
+        }) => {
+            let (headers, fields) = (&csv_data.headers, csv_data.fields);
+            let index = columns::parse_columns(&index, headers, fields)?;
+            let names = columns::parse_column(&names, headers, fields)?;
+            let values = columns::parse_column(&values, headers, fields)?;
+            csv_data.apply(&Change::Pivot {
+                index,
+                names,
+                values,
+                agg,
+            })?;
+            save(
+                &csv_data,
+                output.as_deref().unwrap_or(&cli.file),
+                &settings,
+                &mut reporter,
+            )
+        }
+        Some(Command::Melt {
+            id,
+            values,
+            var_name,
+            value_name,
+            output,
+        }) => {
+            let (headers, fields) = (&csv_data.headers, csv_data.fields);
+            let ids = columns::parse_columns(&id, headers, fields)?;
+            let values = match values {
+                Some(values) => Some(columns::parse_columns(&values, headers, fields)?),
+                None => None,
+            };
+            csv_data.apply(&Change::Melt {
+                ids,
+                values,
+                variable: var_name,
+                value: value_name,
+            })?;
+            save(
+                &csv_data,
+                output.as_deref().unwrap_or(&cli.file),
+                &settings,
+                &mut reporter,
+            )
+        }
+        Some(Command::Split {
+            rows_per_file,
+            max_size,
+            by,
+            prefix,
+        }) => {
+            let by = match (rows_per_file, max_size, by) {
+                (Some(rows), _, _) => SplitBy::Rows(rows),
+                (_, Some(size), _) => SplitBy::Bytes(size),
+                (_, _, Some(column)) => SplitBy::Column(columns::parse_column(
+                    &column,
+                    &csv_data.headers,
+                    csv_data.fields,
+                )?),
+                _ => unreachable!("clap requires one of the split options"),
+            };
+            // Sizes are measured before compression.
+            let write_options = WriteOptions {
+                delimiter: settings.delimiter,
+                encoding: csv_data.encoding,
+                na_value: settings.na_value.clone(),
+                compression: None,
+            };
+            // The parts of a compressed file are compressed too: sales.csv.gz gives sales_1.csv.gz.
+            let (path, compressed) = match Compression::from_extension(&cli.file) {
+                Some(compression) => (
+                    Path::new(&cli.file).with_extension(""),
+                    format!(".{}", compression.extension()),
+                ),
+                None => (PathBuf::from(&cli.file), String::new()),
+            };
+            let prefix =
+                prefix.unwrap_or_else(|| path.with_extension("").to_string_lossy().into_owned());
+            let extension = path
+                .extension()
+                .map_or("csv".into(), |e| e.to_string_lossy());
+            let mut used = HashSet::new();
+            let mut parts = Vec::new();
+            for (name, part) in csv_data.split(by, write_options)? {
+                let mut file =
+                    format!("{prefix}_{}.{extension}{compressed}", file_name_part(&name));
+                // Values that only differ in characters unfit for file names share a name.
+                let mut n = 2;
+                while !used.insert(file.clone()) {
+                    file = format!(
+                        "{prefix}_{}_{n}.{extension}{compressed}",
+                        file_name_part(&name)
+                    );
+                    n += 1;
+                }
+                save(&part, &file, &settings, &mut reporter)?;
+                match settings.output_mode {
+                    OutputMode::Text if cli.quiet => {}
+                    OutputMode::Text => println!("{file}: {} row(s)", part.records),
+                    OutputMode::Json => parts.push(report::Part {
+                        file,
+                        rows: part.records,
+                    }),
+                }
+            }
+            if settings.output_mode == OutputMode::Json {
+                report::write(&parts, &mut std::io::stdout())?;
+            }
+            Ok(())
+        }
+        Some(Command::Concat { mode, output }) => {
+            for file in &cli.more_files {
+                let loader = Loader {
+                    file: file.clone(),
+                    ..loader.clone()
+                };
+                let diff = csv_data.concat(&loader.load(&mut reporter)?, mode)?;
+                if !diff.is_empty() {
+                    log::warn!("{file}: {diff}");
+                }
+            }
+            save(&csv_data, &output, &settings, &mut reporter)
+        }
+        Some(Command::Merge {
+            files,
+            key,
+            resolve,
+            output,
+        }) => {
+            let [base, theirs] =
+                <[String; 2]>::try_from(files).map_err(|_| "merge takes BASE and THEIRS")?;
+            let base = Loader {
+                file: base,
+                ..loader.clone()
+            }
+            .load(&mut reporter)?;
+            let theirs = Loader {
+                file: theirs,
+                ..loader.clone()
+            }
+            .load(&mut reporter)?;
+            let key = columns::parse_columns(&key, &csv_data.headers, csv_data.fields)?;
+            let merged = merge::merge(&base, &mut csv_data, &theirs, &key)?;
+            let mut answers = std::io::stdin().lines();
+            let left = merged.resolve(&mut csv_data, |conflict| match resolve {
+                Resolve::Markers => Ok(Choice::Markers),
+                Resolve::Ours => Ok(Choice::Take(Side::Ours)),
+                Resolve::Theirs => Ok(Choice::Take(Side::Theirs)),
+                Resolve::Ask => ask(conflict, &mut answers),
+            })?;
+            save(
+                &csv_data,
+                output.as_deref().unwrap_or(&cli.file),
+                &settings,
+                &mut reporter,
+            )?;
+            if settings.output_mode == OutputMode::Json {
+                let merged = report::Merged {
+                    changes: merged.changes,
+                    conflicts: left.iter().map(report::MergeConflict::from).collect(),
+                };
+                report::write(&merged, &mut std::io::stdout())?;
+            } else {
+                for conflict in &left {
+                    println!(
+                        "conflict in row {} (key {}), column {}",
+                        conflict.row + 1,
+                        conflict.key,
+                        conflict.column
+                    );
+                }
+                if !cli.quiet {
+                    println!(
+                        "merged {} change(s) from {}, {} conflict(s) of {} left between markers",
+                        merged.changes,
+                        theirs.file_name,
+                        left.len(),
+                        merged.conflicts.len()
+                    );
+                }
+            }
+            match left.is_empty() {
+                true => Ok(()),
+                false => Err(Failed.into()),
+            }
+        }
+        Some(Command::Replay { journal, output }) => {
+            let entries = audit::read_journal(&journal)?;
+            audit::replay(&mut csv_data, &entries)?;
+            save(
+                &csv_data,
+                output.as_deref().unwrap_or(&cli.file),
+                &settings,
+                &mut reporter,
+            )
+        }
+        Some(Command::FillNa {
+            value,
+            with,
+            columns,
+            output,
+        }) => {
+            let mut defaults = Vec::new();
+            if let Some(value) = value {
+                let fields = match columns {
+                    Some(spec) => {
+                        columns::parse_columns(&spec, &csv_data.headers, csv_data.fields)?
+                    }
+                    None => (0..csv_data.fields).collect(),
+                };
+                defaults.extend(fields.into_iter().map(|field| (field, value.clone())));
+            }
+            for spec in &with {
+                let (column, value) = spec
+                    .split_once('=')
+                    .ok_or_else(|| format!("expected COLUMN=VALUE, got '{spec}'"))?;
+                let field = columns::parse_column(column, &csv_data.headers, csv_data.fields)?;
+                defaults.retain(|&(other, _)| other != field);
+                defaults.push((field, value.to_string()));
+            }
+            for (field, value) in defaults {
+                csv_data.fill_na(field, &value)?;
+            }
+            save(
+                &csv_data,
+                output.as_deref().unwrap_or(&cli.file),
+                &settings,
+                &mut reporter,
+            )
+        }
+        Some(Command::DropNa { columns, output }) => {
+            let fields = match columns {
+                Some(spec) => columns::parse_columns(&spec, &csv_data.headers, csv_data.fields)?,
+                None => (0..csv_data.fields).collect(),
+            };
+            csv_data.drop_na(&fields)?;
+            save(
+                &csv_data,
+                output.as_deref().unwrap_or(&cli.file),
+                &settings,
+                &mut reporter,
+            )
+        }
+        Some(Command::ApplyPlugin { names, output, .. }) => {
+            let processed = registry.run(&names, &mut csv_data)?;
+            print_processed(processed, &settings, cli.quiet)?;
+            save(
+                &csv_data,
+                output.as_deref().unwrap_or(&cli.file),
+                &settings,
+                &mut reporter,
+            )
+        }
+        Some(Command::Script { file, output }) => {
+            #[cfg(feature = "scripting")]
+            {
+                let mut script = Script::read_from_file(&file)?;
+                let name = script.name().to_string();
+                let processed = csv_data.process_rows(&name, &mut script)?;
+                print_processed(processed, &settings, cli.quiet)?;
+                save(
+                    &csv_data,
+                    output.as_deref().unwrap_or(&cli.file),
+                    &settings,
+                    &mut reporter,
+                )
+            }
+            #[cfg(not(feature = "scripting"))]
+            {
+                let _ = (file, output);
+                Err("script needs the editor to be built with the scripting feature".into())
+            }
+        }
+        Some(Command::Transform {
+            columns,
+            ops,
+            output,
+        }) => {
+            for field in columns::parse_columns(&columns, &csv_data.headers, csv_data.fields)? {
+                csv_data.map_column(field, |value| {
+                    ops.iter()
+                        .fold(value.to_string(), |value, op| op.apply(&value))
+                })?;
+            }
+            save(
+                &csv_data,
+                output.as_deref().unwrap_or(&cli.file),
+                &settings,
+                &mut reporter,
+            )
+        }
+        Some(Command::Anonymize {
+            columns,
+            salt,
+            output,
+        }) => {
+            let mut strategies = Vec::new();
+            for spec in &columns {
+                let (spec, strategy) = spec
+                    .rsplit_once('=')
+                    .ok_or_else(|| format!("expected COLUMNS=STRATEGY, got '{spec}'"))?;
+                let strategy: Strategy = strategy.parse()?;
+                for field in columns::parse_columns(spec, &csv_data.headers, csv_data.fields)? {
+                    strategies.retain(|&(other, _)| other != field);
+                    strategies.push((field, strategy));
+                }
+            }
+            // Without a salt, values cannot be linked to other files or runs.
+            let salt = salt.unwrap_or_else(|| format!("{:016x}", rand::rng().random::<u64>()));
+            for (field, strategy) in strategies {
+                let changed = csv_data.map_column(field, |value| strategy.apply(value, &salt))?;
+                log::info!(field, strategy:? = strategy, changed; "anonymized");
+            }
+            save(
+                &csv_data,
+                output.as_deref().unwrap_or(&cli.file),
+                &settings,
+                &mut reporter,
+            )
+        }
+        Some(Command::NormalizeDates {
+            columns,
+            formats,
+            day_first,
+            to,
+            strict,
+            output,
+        }) => {
+            let formats = match formats.is_empty() {
+                true => &settings.date_formats,
+                false => &formats,
+            };
+            let normalizer = Normalizer::new(formats, day_first, to)?;
+            let fields = match columns {
+                Some(columns) => {
+                    columns::parse_columns(&columns, &csv_data.headers, csv_data.fields)?
+                }
+                None => (0..csv_data.fields)
+                    .filter(|&field| {
+                        normalizer.is_date_column(
+                            csv_data.data.iter().filter_map(|record| record.get(field)),
+                        )
+                    })
+                    .collect(),
+            };
+            let mut normalized = report::NormalizedDates::default();
+            for &field in &fields {
+                let column = csv_data
+                    .headers
+                    .get(field)
+                    .map_or_else(|| (field + 1).to_string(), str::to_string);
+                for (row, record) in csv_data.data.iter().enumerate() {
+                    let value = record.get(field).unwrap_or("");
+                    if !value.trim().is_empty() && normalizer.parse(value).is_none() {
+                        log::warn!(
+                            "{}, {column}: '{value}' is not a date",
+                            csv_data.describe_row(row)
+                        );
+                        normalized.unparsed.push(report::Unparsed {
+                            row: row + 1,
+                            line: csv_data.line(row),
+                            column: column.clone(),
+                            value: value.to_string(),
+                        });
+                    }
+                }
+                let mut error = None;
+                let changed =
+                    csv_data.map_column(field, |value| match normalizer.normalize(value) {
+                        Ok(Some(date)) => date,
+                        Ok(None) => value.to_string(),
+                        Err(e) => {
+                            error.get_or_insert(e);
+                            value.to_string()
+                        }
+                    })?;
+                if let Some(error) = error {
+                    return Err(error.into());
+                }
+                normalized
+                    .columns
+                    .push(report::NormalizedColumn { column, changed });
+            }
+            match settings.output_mode {
+                OutputMode::Text if cli.quiet => {}
+                OutputMode::Text => println!(
+                    "{} date(s) rewritten in {} column(s), {} value(s) not dates",
+                    normalized.columns.iter().map(|c| c.changed).sum::<usize>(),
+                    normalized.columns.len(),
+                    normalized.unparsed.len()
+                ),
+                OutputMode::Json => report::write(&normalized, &mut std::io::stdout())?,
+            }
+            if strict && !normalized.unparsed.is_empty() {
+                return Err(Failed.into());
+            }
+            save(
+                &csv_data,
+                output.as_deref().unwrap_or(&cli.file),
+                &settings,
+                &mut reporter,
+            )
+        }
+        Some(Command::Follow { .. }) => unreachable!("follow does not load the file"),
+        Some(Command::Overlay { .. }) => unreachable!("overlay edits do not load the file"),
+        Some(Command::Completions { .. }) => unreachable!("completions do not load the file"),
+        // Reading the file was the check.
+        None if cli.quiet => Ok(()),
+        None => run_demo(csv_data, &settings),
+    }
+}
 
-    match cli.debug {
-        0 => println!("Debug mode is off"),
-        1 => println!("Debug mode is kind of on"),
-        2 => println!("Debug mode is on"),
-        _ => println!("Don't be crazy"),
+/// Saves the data to `file_name` with the configured delimiter, keeping the input's encoding
+/// and, when saving over it, its compression, after running the `--on-write` processors on
+/// a copy of it.
+fn save(
+    csv_data: &CSVData,
+    file_name: &str,
+    settings: &Settings,
+    progress: &mut dyn Progress,
+) -> Result<(), Box<dyn Error>> {
+    let processed;
+    let csv_data = if settings.on_write.is_empty() {
+        csv_data
+    } else {
+        let mut copy = csv_data.copy();
+        Registry::builtin().run(&settings.on_write, &mut copy)?;
+        processed = copy;
+        &processed
+    };
+    let write_options = WriteOptions {
+        delimiter: settings.delimiter,
+        encoding: csv_data.encoding,
+        na_value: settings.na_value.clone(),
+        compression: csv_data.compression_for(file_name),
+    };
+    csv_data.write_with_progress(file_name, write_options, progress)?;
+    // The data was read with the overlay edits applied, and now holds them.
+    if file_name == csv_data.file_name {
+        Overlay::discard(file_name)?;
     }
+    Ok(())
+}
 
-    let mut csv_data = CSVData::read_from_file(&cli.file)?;
+/// Turns a column value into text usable in a file name.
+fn file_name_part(value: &str) -> String {
+    let part: String = value
+        .trim()
+        .chars()
+        .map(|c| {
+            if c.is_alphanumeric() || matches!(c, '-' | '_' | '.') {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect();
+    match part.trim_start_matches('.') {
+        "" => "empty".to_string(),
+        part => part.to_string(),
+    }
+}
 
-    if let Some(dimension) = cli.dimension.as_deref() {
-        let dimensions: Vec<usize> = dimension
-            .split(',')
-            .map(|d| d.parse::<usize>().unwrap_or(0))
-            .collect();
-        if dimensions.len() == 2 {
-            csv_data.records = dimensions[0];
-            csv_data.fields = dimensions[1];
+/// Asks on stderr how to settle `conflict`, for `merge --resolve ask`, reading the answer
+/// from `answers` until it is understood. Once the answers run out, conflicts are left
+/// between markers.
+fn ask(
+    conflict: &Conflict,
+    answers: &mut impl Iterator<Item = std::io::Result<String>>,
+) -> Result<Choice, EditorError> {
+    let show = |side: Side| match conflict.value(side) {
+        Some(value) => format!("{value:?}"),
+        None => "(row removed)".to_string(),
+    };
+    eprintln!(
+        "conflict in row {} (key {}), column {}:",
+        conflict.row + 1,
+        conflict.key,
+        conflict.column
+    );
+    eprintln!("  base:   {}", show(Side::Base));
+    eprintln!("  ours:   {}", show(Side::Ours));
+    eprintln!("  theirs: {}", show(Side::Theirs));
+    loop {
+        eprint!("keep [o]urs, [t]heirs, [b]ase, [m]arkers, or =VALUE? ");
+        let Some(answer) = answers.next() else {
+            eprintln!();
+            return Ok(Choice::Markers);
+        };
+        let answer = answer?;
+        if let Some(value) = answer.strip_prefix('=') {
+            return Ok(Choice::Value(value.to_string()));
         }
+        match answer.trim() {
+            "o" | "ours" => return Ok(Choice::Take(Side::Ours)),
+            "t" | "theirs" => return Ok(Choice::Take(Side::Theirs)),
+            "b" | "base" => return Ok(Choice::Take(Side::Base)),
+            "m" | "markers" => return Ok(Choice::Markers),
+            answer => eprintln!("unknown answer '{answer}'"),
+        }
+    }
+}
+
+/// Reads tab-separated values from stdin, or else the system clipboard.
+fn read_clip(stdin: bool) -> Result<Clip, Box<dyn Error>> {
+    let text = if stdin {
+        std::io::read_to_string(std::io::stdin())?
     } else {
-        let (rows, columns) = get_dimensions(&cli.file)?;
-        csv_data.records = rows;
-        csv_data.fields = columns;
+        arboard::Clipboard::new()?.get_text()?
+    };
+    Ok(Clip::from_tsv(&text)?)
+}
+
+/// Warns that `fitted` pasted rows did not have `fields` values (see [`Clip::fit`]).
+fn warn_fitted(fitted: usize, fields: usize) {
+    if fitted > 0 {
+        log::warn!("{fitted} pasted row(s) did not have {fields} value(s) and were padded or cut");
     }
-    // Paginate the data based on the records_per_page argument
-    csv_data.create_pages(cli.records_per_page);
+}
+
+/// Puts `text` on the system clipboard.
+fn set_clipboard_text(text: String) -> Result<(), arboard::Error> {
+    let mut clipboard = arboard::Clipboard::new()?;
+    // On Linux the clipboard is served by the process that set it, so wait
+    // until another application has taken the text before exiting.
+    #[cfg(target_os = "linux")]
+    {
+        use arboard::SetExtLinux;
+        clipboard.set().wait().text(text)
+    }
+    #[cfg(not(target_os = "linux"))]
+    clipboard.set_text(text)
+}
+
+/// Runs the original walkthrough: display, paginate, delete, modify and write.
+fn run_demo(mut csv_data: CSVData, settings: &Settings) -> Result<(), Box<dyn Error>> {
+    let stdout = std::io::stdout();
+    let all_rows = csv_data.data.len();
 
     // Display entire file
     println!("Displaying entire file:");
-    csv_data.display();
+    csv_data.render(0, all_rows, settings, &mut stdout.lock())?;
 
     // Example of using paginate function
     println!("\nDisplaying paginated data (first page):");
-    if let Some(first_page) = csv_data.pages.get(0) {
-        let stdout = std::io::stdout();
-        let mut handle = stdout.lock();
-        csv_data.paginate(first_page.start, first_page.end, &mut handle)?;
+    if let Some(first_page) = csv_data.pages.first() {
+        csv_data.render(
+            first_page.start,
+            first_page.end,
+            settings,
+            &mut stdout.lock(),
+        )?;
     }
 
     // Example of deleting a row - deleting the first row
@@ -212,7 +3001,7 @@ fn main() -> Result<(), Box<dyn Error>> {
         println!("Error deleting row: {}", e);
     }
     println!("Data after deleting the first row:");
-    csv_data.display();
+    csv_data.render(0, csv_data.data.len(), settings, &mut stdout.lock())?;
     println!("========== End of DELETE demonstration ==========");
 
     // Example of modifying a field - modifying the first field of the second row
@@ -221,7 +3010,7 @@ fn main() -> Result<(), Box<dyn Error>> {
         println!("Error modifying field: {}", e);
     }
     println!("Data after modifying a field:");
-    csv_data.display();
+    csv_data.render(0, csv_data.data.len(), settings, &mut stdout.lock())?;
     println!("========== End of MODIFY FIELD demonstration ==========");
 
     // Example of writing data to a new file
@@ -244,64 +3033,90 @@ mod tests {
     }
 
     #[test]
-    fn test_display() {
-        let csv_data = setup();
-        // Ensure no panic
-        let res = std::panic::catch_unwind(|| csv_data.display());
-        assert!(res.is_ok());
-    }
-
-    #[test]
-    #[serial_test::serial]
-    fn test_paginate() {
-        let csv_data = setup();
+    fn test_view_pages() {
+        let mut csv_data = setup();
+        csv_data.create_pages(4);
         let mut buffer = Vec::new();
-        let _ = csv_data.paginate(0, 3, &mut buffer);
+        view(&csv_data, &Settings::default(), 1..=2, 0, &mut buffer).expect("Failed to view pages");
 
         let output = String::from_utf8(buffer).expect("Not UTF-8");
-        // println!("{}", output);
+        assert!(output.contains("-- page 1/2, rows 1-4 --"));
+        assert!(output.contains("-- page 2/2, rows 5-6 --"));
+        assert!(view(&csv_data, &Settings::default(), 3..=3, 0, &mut Vec::new()).is_err());
 
-        assert!(output
-            .contains("\"community\", \"block\", \"along\", \"telephone\", \"jar\", \"play\""));
-        assert!(output.contains("StringRecord([\"environment\", \"managed\", \"valley\", \"potatoes\", \"there\", \"century\"])"));
-        assert!(output.contains(
-            "StringRecord([\"his\", \"soft\", \"breathing\", \"gun\", \"barn\", \"completely\"])"
-        ));
+        assert_eq!(parse_page_range("2..5"), Ok(2..=5));
+        assert!(parse_page_range("5..2").is_err());
+        assert!(parse_page_range("0..2").is_err());
     }
 
     #[test]
-    fn test_delete_and_modify() {
-        let mut csv_data = setup();
-        let original_records = csv_data.records;
-        let original_fields = csv_data.fields;
-
-        // Test delete_row
-        csv_data.delete_row(0).expect("Failed to delete row");
-        assert_eq!(csv_data.records, original_records);
-        assert_eq!(csv_data.data[0].len(), original_fields);
+    fn test_exit_code() {
+        let missing = CSVData::read_from_file("missing.csv").err().unwrap();
+        assert_eq!(exit_code(&missing), EXIT_IO);
+        let parse = Expr::parse("price >", &csv::StringRecord::new(), 1).unwrap_err();
+        assert_eq!(exit_code(&parse), EXIT_PARSE);
+        let ragged = Box::<dyn Error>::from(EditorError::Schema {
+            row: 1,
+            message: "3 field(s), expected 2".to_string(),
+        });
+        assert_eq!(exit_code(&*ragged), EXIT_PARSE);
+        assert_eq!(exit_code(&Failed), EXIT_INVALID);
+        let other = Box::<dyn Error>::from("only interactive mode and concat open several files");
+        assert_eq!(exit_code(&*other), EXIT_INVALID);
+        let merge = Box::<dyn Error>::from(EditorError::Merge("same key".to_string()));
+        assert_eq!(exit_code(&*merge), EXIT_INVALID);
 
-        // Test modify_field
-        csv_data
-            .modify_field(1, 1, "modified")
-            .expect("Failed to modify field");
-        assert_eq!(csv_data.records, original_records);
-        assert_eq!(csv_data.fields, original_fields);
+        // Commands finding problems in the data.
+        let file_name = "test_exit_code.csv";
+        let fail = |contents: &str, args: &[&str]| {
+            std::fs::write(file_name, contents).expect("Failed to write");
+            let cli = Cli::parse_from(
+                ["bootle-geditor-3000", "--quiet", file_name]
+                    .iter()
+                    .chain(args),
+            );
+            let error = execute(cli).expect_err("Expected the command to fail");
+            assert!(error.is::<Failed>());
+            exit_code(&*error)
+        };
+        let ragged = "id,name\n1,ann\n2\n";
+        assert_eq!(fail(ragged, &["--ragged", "pad", "lint"]), EXIT_INVALID);
+        let check = ["check", "--rule", "unique(id)"];
+        assert_eq!(fail("id\n1\n1\n", &check), EXIT_INVALID);
+        let dates = ["normalize-dates", "--columns", "day", "--strict"];
+        assert_eq!(fail("day\n2024-04-03\nsoon\n", &dates), EXIT_INVALID);
+        std::fs::write("test_exit_code_base.csv", "id,name\n1,ann\n").expect("Failed to write");
+        std::fs::write("test_exit_code_theirs.csv", "id,name\n1,anne\n").expect("Failed to write");
+        let merge = [
+            "merge",
+            "test_exit_code_base.csv",
+            "test_exit_code_theirs.csv",
+            "--key",
+            "id",
+        ];
+        assert_eq!(fail("id,name\n1,annie\n", &merge), EXIT_INVALID);
+        for file in [
+            file_name,
+            ".test_exit_code.csv.lock",
+            "test_exit_code_base.csv",
+            "test_exit_code_theirs.csv",
+        ] {
+            std::fs::remove_file(file).expect("Failed to remove file");
+        }
     }
 
     #[test]
-    fn test_write_to_file() {
-        let csv_data = setup();
-        let output_file = "test_output.csv";
-        csv_data
-            .write_to_file(output_file)
-            .expect("Failed to write to file");
-
-        // Assert the file exists
-        let metadata = std::fs::metadata(output_file);
-        assert!(metadata.is_ok());
-        assert!(metadata.unwrap().is_file());
-
-        // Clean up the test file
-        std::fs::remove_file(output_file).expect("Failed to remove test output file");
+    fn test_parse_size() {
+        assert_eq!(parse_size("500"), Ok(500));
+        assert_eq!(parse_size("10m"), Ok(10 << 20));
+        assert_eq!(parse_size("1GB"), Ok(1 << 30));
+        assert!(parse_size("0").is_err());
+        assert!(parse_size("lots").is_err());
+        assert_eq!(
+            parse_size("20000000000G"),
+            Err("size '20000000000G' is too large".to_string())
+        );
+        assert_eq!(file_name_part("North/East"), "North_East");
+        assert_eq!(file_name_part(" "), "empty");
     }
 }
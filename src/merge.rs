@@ -0,0 +1,497 @@
+//! Three-way merges of edits made to copies of the same file, as version control needs when
+//! two branches changed it (`merge`). Rows are matched by key rather than by position, so
+//! that rows added, removed or moved on one side do not shift the rows of the other, and
+//! columns by name. Cells changed on one side only are merged; cells changed on both sides
+//! to different values, or rows one side removed and the other changed, are conflicts.
+
+use std::collections::{BTreeSet, HashMap};
+
+use clap::ValueEnum;
+use csv::StringRecord;
+
+use crate::{audit::Change, columns, CSVData, EditorError, HeaderDiff};
+
+/// How `merge` settles conflicts.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum Resolve {
+    /// Write both values in the cell between conflict markers, to be edited by hand
+    #[default]
+    Markers,
+    /// Keep our value, or our removal of the row
+    Ours,
+    /// Take their value, or their removal of the row
+    Theirs,
+    /// Ask which value to keep for each conflict
+    Ask,
+}
+
+/// One of the three versions of the data.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Side {
+    Base,
+    Ours,
+    Theirs,
+}
+
+/// How to settle one conflict (see [`Merge::resolve`]).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Choice {
+    /// Write both values between conflict markers (see [`Conflict::markers`]).
+    Markers,
+    /// Keep the value of a side, removing the row if that side does not have it.
+    Take(Side),
+    /// Write this value.
+    Value(String),
+}
+
+/// What [`merge`] did.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Merge {
+    /// Number of their changes taken: cells set, rows added and rows removed.
+    pub changes: usize,
+    /// Cells changed on both sides, in row order.
+    pub conflicts: Vec<Conflict>,
+}
+
+/// A cell changed on both sides of a merge, or a cell of a row removed on one side and
+/// changed on the other. A missing value means the side does not have the row.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Conflict {
+    /// Index of the row in the merged data (0-based).
+    pub row: usize,
+    pub field: usize,
+    /// Values of the key columns of the row, separated by commas.
+    pub key: String,
+    pub column: String,
+    pub base: Option<String>,
+    pub ours: Option<String>,
+    pub theirs: Option<String>,
+}
+
+impl Conflict {
+    /// Returns the value of `side`, if it has the row.
+    pub fn value(&self, side: Side) -> Option<&str> {
+        match side {
+            Side::Base => self.base.as_deref(),
+            Side::Ours => self.ours.as_deref(),
+            Side::Theirs => self.theirs.as_deref(),
+        }
+    }
+
+    /// Returns both values between conflict markers, as version control writes them:
+    /// `<<<<<<< ours`, our value, `=======`, their value and `>>>>>>> theirs`, each on its
+    /// own line. A side that removed the row is marked `(row removed)` and has no value.
+    pub fn markers(&self) -> String {
+        let section = |label: &str, value: Option<&str>| match value {
+            Some(value) => (label.to_string(), format!("{value}\n")),
+            None => (format!("{label} (row removed)"), String::new()),
+        };
+        let (ours, our_value) = section("ours", self.value(Side::Ours));
+        let (theirs, their_value) = section("theirs", self.value(Side::Theirs));
+        format!("<<<<<<< {ours}\n{our_value}=======\n{their_value}>>>>>>> {theirs}")
+    }
+}
+
+/// Merges into `ours` the changes `theirs` made to `base`, matching rows by the values of
+/// their `key` columns (fields of `ours`, 0-based) and columns by name. Rows only `theirs`
+/// added go after the row they follow in `theirs`. Conflicting cells keep our value for now,
+/// and rows removed on one side but changed on the other are kept, until the conflicts are
+/// resolved (see [`Merge::resolve`]).
+/// Returns an error, without changing anything, if the files do not have the same columns
+/// or a key is not unique in one of them.
+pub fn merge(
+    base: &CSVData,
+    ours: &mut CSVData,
+    theirs: &CSVData,
+    key: &[usize],
+) -> Result<Merge, EditorError> {
+    let width = ours.fields;
+    let base_rows = align(base, ours)?;
+    let their_rows = align(theirs, ours)?;
+    let our_rows: Vec<Vec<&str>> = ours
+        .data
+        .iter()
+        .map(|record| (0..width).map(|i| record.get(i).unwrap_or("")).collect())
+        .collect();
+    let base_keys = index(&base_rows, key, &base.file_name)?;
+    let our_keys = index(&our_rows, key, &ours.file_name)?;
+    let their_keys = index(&their_rows, key, &theirs.file_name)?;
+
+    let mut merge = Merge::default();
+    // Each row with the conflicts found in it, their row index still unknown.
+    type Entry = (StringRecord, Vec<Conflict>);
+    let conflict = |field: usize, b: Option<&str>, o: Option<&str>, t: Option<&str>| Conflict {
+        row: 0,
+        field,
+        key: String::new(),
+        column: columns::column_name(&ours.headers, field),
+        base: b.map(str::to_string),
+        ours: o.map(str::to_string),
+        theirs: t.map(str::to_string),
+    };
+    let changed = |a: &[&str], b: &[&str]| (0..width).filter(|&i| a[i] != b[i]).collect::<Vec<_>>();
+
+    let mut kept: Vec<Option<Entry>> = Vec::with_capacity(our_rows.len());
+    for row in &our_rows {
+        let key = key_of(row, key);
+        let b = base_keys.get(&key).map(|&i| &base_rows[i]);
+        let t = their_keys.get(&key).map(|&i| &their_rows[i]);
+        let entry = match (b, t) {
+            (_, None) if b == Some(row) => {
+                merge.changes += 1;
+                None
+            }
+            (Some(b), None) => {
+                let conflicts = changed(row, b)
+                    .into_iter()
+                    .map(|i| conflict(i, Some(b[i]), Some(row[i]), None))
+                    .collect();
+                Some((StringRecord::from(row.clone()), conflicts))
+            }
+            (None, None) => Some((StringRecord::from(row.clone()), Vec::new())),
+            (b, Some(t)) => {
+                let mut values = row.clone();
+                let mut conflicts = Vec::new();
+                for i in changed(row, t) {
+                    match b.map(|b| b[i]) {
+                        Some(b) if b == row[i] => {
+                            values[i] = t[i];
+                            merge.changes += 1;
+                        }
+                        Some(b) if b == t[i] => {}
+                        b => conflicts.push(conflict(i, b, Some(row[i]), Some(t[i]))),
+                    }
+                }
+                Some((StringRecord::from(values), conflicts))
+            }
+        };
+        kept.push(entry);
+    }
+
+    // Rows only they have, by the index of our row they follow (none for the first rows).
+    let mut added: HashMap<Option<usize>, Vec<Entry>> = HashMap::new();
+    let mut anchor = None;
+    for row in &their_rows {
+        let key = key_of(row, key);
+        if let Some(&i) = our_keys.get(&key) {
+            anchor = Some(i);
+            continue;
+        }
+        let entry = match base_keys.get(&key).map(|&i| &base_rows[i]) {
+            Some(b) if b == row => continue,
+            Some(b) => {
+                let conflicts = changed(row, b)
+                    .into_iter()
+                    .map(|i| conflict(i, Some(b[i]), None, Some(row[i])))
+                    .collect();
+                (StringRecord::from(row.clone()), conflicts)
+            }
+            None => {
+                merge.changes += 1;
+                (StringRecord::from(row.clone()), Vec::new())
+            }
+        };
+        added.entry(anchor).or_default().push(entry);
+    }
+
+    let mut data = Vec::with_capacity(kept.len());
+    let entries =
+        added
+            .remove(&None)
+            .into_iter()
+            .flatten()
+            .chain(kept.into_iter().enumerate().flat_map(|(i, entry)| {
+                entry
+                    .into_iter()
+                    .chain(added.remove(&Some(i)).into_iter().flatten())
+            }));
+    for (record, conflicts) in entries {
+        let row = data.len();
+        let key: Vec<&str> = key.iter().map(|&i| record.get(i).unwrap_or("")).collect();
+        merge
+            .conflicts
+            .extend(conflicts.into_iter().map(|conflict| Conflict {
+                row,
+                key: key.join(","),
+                ..conflict
+            }));
+        data.push(record);
+    }
+    log::info!(
+        base = base.file_name.as_str(),
+        theirs = theirs.file_name.as_str(),
+        changes = merge.changes,
+        conflicts = merge.conflicts.len();
+        "merged"
+    );
+    if data != ours.data {
+        let change = Change::Merge {
+            base: base.file_name.clone(),
+            theirs: theirs.file_name.clone(),
+            rows: data
+                .iter()
+                .map(|record| record.iter().map(str::to_string).collect())
+                .collect(),
+        };
+        ours.replace_rows(data, change)?;
+    }
+    Ok(merge)
+}
+
+impl Merge {
+    /// Settles each conflict in `csv_data`, the data merged into, with the choice `choose`
+    /// makes for it. Rows whose chosen side removed them are removed once every conflict is
+    /// settled, and their other conflicts are not asked about.
+    /// Returns the conflicts left between markers, with their row in the resulting data.
+    pub fn resolve(
+        &self,
+        csv_data: &mut CSVData,
+        mut choose: impl FnMut(&Conflict) -> Result<Choice, EditorError>,
+    ) -> Result<Vec<Conflict>, EditorError> {
+        let mut removed = BTreeSet::new();
+        let mut left = Vec::new();
+        for conflict in &self.conflicts {
+            if removed.contains(&conflict.row) {
+                continue;
+            }
+            let value = match choose(conflict)? {
+                Choice::Markers => {
+                    left.push(conflict.clone());
+                    conflict.markers()
+                }
+                Choice::Take(side) => match conflict.value(side) {
+                    Some(value) => value.to_string(),
+                    None => {
+                        removed.insert(conflict.row);
+                        continue;
+                    }
+                },
+                Choice::Value(value) => value,
+            };
+            csv_data.modify_field_unchecked(conflict.row, conflict.field, &value)?;
+        }
+        csv_data.remove_rows(&removed.iter().copied().collect::<Vec<_>>())?;
+        left.retain(|conflict| !removed.contains(&conflict.row));
+        for conflict in &mut left {
+            conflict.row -= removed.range(..conflict.row).count();
+        }
+        Ok(left)
+    }
+}
+
+/// Returns the rows of `other` with their values in the order of the columns of `ours`.
+fn align<'a>(other: &'a CSVData, ours: &CSVData) -> Result<Vec<Vec<&'a str>>, EditorError> {
+    let names = |csv_data: &CSVData| -> Vec<String> {
+        (0..csv_data.fields)
+            .map(|i| columns::column_name(&csv_data.headers, i))
+            .collect()
+    };
+    let (our_names, their_names) = (names(ours), names(other));
+    // Repeated names are matched in order of appearance.
+    let mut taken = vec![false; their_names.len()];
+    let mapping: Vec<Option<usize>> = our_names
+        .iter()
+        .map(|name| {
+            let i = (0..their_names.len()).find(|&i| !taken[i] && their_names[i] == *name)?;
+            taken[i] = true;
+            Some(i)
+        })
+        .collect();
+    let diff = HeaderDiff {
+        missing: (0..our_names.len())
+            .filter(|&i| mapping[i].is_none())
+            .map(|i| our_names[i].clone())
+            .collect(),
+        extra: (0..their_names.len())
+            .filter(|&i| !taken[i])
+            .map(|i| their_names[i].clone())
+            .collect(),
+        reordered: false,
+    };
+    if !diff.is_empty() {
+        return Err(EditorError::HeaderMismatch {
+            file: other.file_name.clone(),
+            message: diff.to_string(),
+        });
+    }
+    Ok(other
+        .data
+        .iter()
+        .map(|record| {
+            mapping
+                .iter()
+                .flatten()
+                .map(|&i| record.get(i).unwrap_or(""))
+                .collect()
+        })
+        .collect())
+}
+
+fn key_of<'a>(row: &[&'a str], key: &[usize]) -> Vec<&'a str> {
+    key.iter().map(|&i| row[i]).collect()
+}
+
+/// Returns the index of each row of `rows` by key.
+/// Returns an error if two rows of `file_name` have the same key.
+fn index<'a>(
+    rows: &[Vec<&'a str>],
+    key: &[usize],
+    file_name: &str,
+) -> Result<HashMap<Vec<&'a str>, usize>, EditorError> {
+    let mut index = HashMap::with_capacity(rows.len());
+    for (i, row) in rows.iter().enumerate() {
+        if let Some(first) = index.insert(key_of(row, key), i) {
+            return Err(EditorError::Merge(format!(
+                "rows {} and {} of {file_name} have the same key {}, which must be unique",
+                first + 1,
+                i + 1,
+                key_of(row, key).join(",")
+            )));
+        }
+    }
+    Ok(index)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::audit::{read_journal, replay, Journal};
+
+    fn data(file_name: &str, contents: &str) -> CSVData {
+        std::fs::write(file_name, contents).expect("Failed to write");
+        let csv_data = CSVData::read_from_file(file_name).expect("Failed to read");
+        std::fs::remove_file(file_name).expect("Failed to remove file");
+        csv_data
+    }
+
+    #[test]
+    fn test_merge() {
+        let base = data(
+            "test_merge_base.csv",
+            "id,name,price\n1,ann,10\n2,bob,20\n3,cy,30\n4,dee,40\n",
+        );
+        // We change 1's price, remove 3, add 5 and change 4's name.
+        let mut ours = data(
+            "test_merge_ours.csv",
+            "id,name,price\n1,ann,11\n2,bob,20\n5,eve,50\n4,dea,40\n",
+        );
+        // They change 1's name and 2's price, move 4 first, add 6 after 2, remove 3 and
+        // change 4's name too. Their columns come in another order.
+        let theirs = data(
+            "test_merge_theirs.csv",
+            "price,id,name\n40,4,dei\n10,1,anne\n25,2,bob\n60,6,fay\n",
+        );
+        let merge = merge(&base, &mut ours, &theirs, &[0]).unwrap();
+        assert_eq!(merge.changes, 3);
+        assert_eq!(
+            ours.data,
+            vec![
+                vec!["1", "anne", "11"],
+                vec!["2", "bob", "25"],
+                vec!["6", "fay", "60"],
+                vec!["5", "eve", "50"],
+                vec!["4", "dea", "40"],
+            ]
+        );
+        assert_eq!(
+            merge.conflicts,
+            vec![Conflict {
+                row: 4,
+                field: 1,
+                key: "4".to_string(),
+                column: "name".to_string(),
+                base: Some("dee".to_string()),
+                ours: Some("dea".to_string()),
+                theirs: Some("dei".to_string()),
+            }]
+        );
+
+        let left = merge.resolve(&mut ours, |_| Ok(Choice::Markers)).unwrap();
+        assert_eq!(left, merge.conflicts);
+        assert_eq!(
+            &ours.data[4][1],
+            "<<<<<<< ours\ndea\n=======\ndei\n>>>>>>> theirs"
+        );
+    }
+
+    #[test]
+    fn test_removed_and_changed() {
+        let base = data(
+            "test_merge_removed_base.csv",
+            "id,name\n1,ann\n2,bob\n3,cy\n",
+        );
+        let mut ours = data("test_merge_removed_ours.csv", "id,name\n1,ann\n3,cyd\n");
+        let theirs = data("test_merge_removed_theirs.csv", "id,name\n1,ann\n2,bobby\n");
+        let merge = merge(&base, &mut ours, &theirs, &[0]).unwrap();
+        assert_eq!(merge.changes, 0);
+        assert_eq!(ours.data.len(), 3);
+        assert_eq!(merge.conflicts.len(), 2);
+        assert_eq!(merge.conflicts[0].row, 1);
+        assert_eq!(
+            merge.conflicts[0].markers(),
+            "<<<<<<< ours (row removed)\n=======\nbobby\n>>>>>>> theirs"
+        );
+        assert_eq!(merge.conflicts[1].theirs, None);
+
+        // Keeping our side removes the row they changed, and keeps the row they removed.
+        let left = merge
+            .resolve(&mut ours, |conflict| match conflict.row {
+                1 => Ok(Choice::Take(Side::Ours)),
+                _ => Ok(Choice::Markers),
+            })
+            .unwrap();
+        assert_eq!(ours.data.len(), 2);
+        assert_eq!(left[0].row, 1);
+        assert!(ours.data[1][1].starts_with("<<<<<<< ours\ncyd\n"));
+
+        let error = merge_error(&base, "id,name\n1,ann\n1,bob\n");
+        assert_eq!(
+            error,
+            "merge error: rows 1 and 2 of test_merge_dup.csv have the same key 1, which must be unique"
+        );
+        let error = merge_error(&base, "id,title\n1,ann\n");
+        assert!(error.contains("missing columns name; extra columns title"));
+    }
+
+    #[test]
+    fn test_replay_journal() {
+        let journal_file = "test_merge_journal.jsonl";
+        let _ = std::fs::remove_file(journal_file);
+        let base = data(
+            "test_merge_journal_base.csv",
+            "id,name\n1,ann\n2,bob\n3,cy\n",
+        );
+        let our_contents = "id,name\n1,ann\n2,bo\n3,cyd\n";
+        let mut ours = data("test_merge_journal_ours.csv", our_contents);
+        let theirs = data(
+            "test_merge_journal_theirs.csv",
+            "id,name\n1,anne\n2,bobby\n4,dee\n",
+        );
+        ours.set_journal(Journal::open(journal_file).unwrap())
+            .unwrap();
+        let merge = merge(&base, &mut ours, &theirs, &[0]).unwrap();
+        merge
+            .resolve(&mut ours, |conflict| match conflict.row {
+                1 => Ok(Choice::Markers),
+                _ => Ok(Choice::Take(Side::Theirs)),
+            })
+            .unwrap();
+        assert_eq!(ours.data.len(), 3);
+
+        let entries = read_journal(journal_file).unwrap();
+        let operations: Vec<_> = entries.iter().map(|e| e.change.operation()).collect();
+        assert_eq!(operations, vec!["merge", "set", "remove-rows"]);
+        let mut copy = data("test_merge_journal_copy.csv", our_contents);
+        replay(&mut copy, &entries).unwrap();
+        assert_eq!(copy.data, ours.data);
+        std::fs::remove_file(journal_file).expect("Failed to remove file");
+    }
+
+    fn merge_error(base: &CSVData, contents: &str) -> String {
+        let theirs = data("test_merge_dup.csv", contents);
+        let mut ours = data("test_merge_dup_ours.csv", "id,name\n1,ann\n");
+        merge(base, &mut ours, &theirs, &[0])
+            .unwrap_err()
+            .to_string()
+    }
+}
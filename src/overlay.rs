@@ -0,0 +1,370 @@
+//! Edits to single cells of large files, kept apart from the file until they are applied.
+//! Recording an edit only reads the header of the file and writes a hidden sidecar next to
+//! it (`.name.csv.overlay`); [`Overlay::apply`] then copies the file record by record,
+//! changing the edited cells on the way, so that memory use grows with the number of edits
+//! rather than with the size of the file. The sidecar remembers the size and modification
+//! time of the file, and edits recorded before the file changed in another way are refused
+//! rather than applied to rows that may have moved.
+
+use std::{
+    collections::BTreeMap,
+    fs::File,
+    io::{BufReader, BufWriter, Read, Seek, Write},
+    path::{Path, PathBuf},
+};
+
+use csv::{ByteRecord, ReaderBuilder, StringRecord, WriterBuilder};
+use encoding_rs::{Encoding, UTF_8};
+use serde::{Deserialize, Serialize};
+
+use crate::{compression::Compression, index::Stamp, lock, CSVData, EditorError, ReadOptions};
+
+const BOM: &[u8] = b"\xef\xbb\xbf";
+
+/// Cell edits waiting to be applied to a file.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Overlay {
+    file_name: String,
+    path: PathBuf,
+    /// New value of each edited cell, by row and field (both 0-based).
+    cells: BTreeMap<(usize, usize), String>,
+}
+
+/// The sidecar's contents. Rows and columns are 1-based, as shown to the user.
+#[derive(Debug, Serialize, Deserialize)]
+struct Sidecar {
+    /// The file the edits were recorded for.
+    stamp: Stamp,
+    #[serde(default)]
+    cells: Vec<Entry>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct Entry {
+    row: usize,
+    column: usize,
+    value: String,
+}
+
+impl Overlay {
+    /// Returns the edits recorded for `file_name`, none if it has no sidecar.
+    /// Returns an error if the file changed since they were recorded.
+    pub fn load(file_name: &str) -> Result<Overlay, EditorError> {
+        let path = lock::sidecar_path(file_name, "overlay");
+        let contents = match std::fs::read_to_string(&path) {
+            Ok(contents) => contents,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                return Ok(Overlay {
+                    file_name: file_name.to_string(),
+                    path,
+                    cells: BTreeMap::new(),
+                })
+            }
+            Err(e) => return Err(e.into()),
+        };
+        let sidecar: Sidecar = toml::from_str(&contents)
+            .map_err(|e| EditorError::Overlay(format!("{}: {}", path.display(), e.message())))?;
+        if Stamp::of(file_name)? != sidecar.stamp {
+            return Err(EditorError::Overlay(format!(
+                "{file_name} changed since its edits were recorded in {}, they may not point at the right rows any more",
+                path.display()
+            )));
+        }
+        let cells = sidecar
+            .cells
+            .into_iter()
+            .filter(|entry| entry.row > 0 && entry.column > 0)
+            .map(|entry| ((entry.row - 1, entry.column - 1), entry.value))
+            .collect();
+        Ok(Overlay {
+            file_name: file_name.to_string(),
+            path,
+            cells,
+        })
+    }
+
+    /// Writes the edits to the sidecar, removing it when there are none left.
+    pub fn save(&self) -> Result<(), EditorError> {
+        if self.cells.is_empty() {
+            return Overlay::discard(&self.file_name).map(|_| ());
+        }
+        let sidecar = Sidecar {
+            stamp: Stamp::of(&self.file_name)?,
+            cells: self
+                .iter()
+                .map(|(row, field, value)| Entry {
+                    row: row + 1,
+                    column: field + 1,
+                    value: value.to_string(),
+                })
+                .collect(),
+        };
+        let contents =
+            toml::to_string(&sidecar).map_err(|e| EditorError::Overlay(e.to_string()))?;
+        std::fs::write(&self.path, contents)?;
+        log::debug!(file:? = self.path, cells = self.cells.len(); "saved overlay");
+        Ok(())
+    }
+
+    /// Removes the sidecar of `file_name`, dropping its edits. Returns whether it had one.
+    pub fn discard(file_name: &str) -> Result<bool, EditorError> {
+        match std::fs::remove_file(lock::sidecar_path(file_name, "overlay")) {
+            Ok(()) => Ok(true),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(false),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// Sets the cell at `row`, `field` (both 0-based) to `value`, returning the value an
+    /// earlier edit gave it, if any. Whether the file has the cell is only checked when
+    /// the edits are applied.
+    pub fn set(&mut self, row: usize, field: usize, value: &str) -> Option<String> {
+        self.cells.insert((row, field), value.to_string())
+    }
+
+    /// Returns the number of edited cells.
+    pub fn len(&self) -> usize {
+        self.cells.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.cells.is_empty()
+    }
+
+    /// Returns the edited cells as row, field (both 0-based) and new value, by row.
+    pub fn iter(&self) -> impl Iterator<Item = (usize, usize, &str)> {
+        self.cells
+            .iter()
+            .map(|((row, field), value)| (*row, *field, value.as_str()))
+    }
+
+    /// Applies the edits to `csv_data`, read from the file in full, as if they had been
+    /// saved. Returns an error, without changing anything, if the data does not have an
+    /// edited cell.
+    pub fn apply_to(&self, csv_data: &mut CSVData) -> Result<(), EditorError> {
+        for (row, field, _) in self.iter() {
+            let rows = csv_data.data.len();
+            let record = csv_data
+                .data
+                .get(row)
+                .ok_or(EditorError::RowOutOfBounds { row, rows })?;
+            if field >= record.len() {
+                return Err(EditorError::FieldOutOfBounds {
+                    field,
+                    fields: record.len(),
+                });
+            }
+        }
+        for (row, field, value) in self.iter() {
+            csv_data.modify_field_unchecked(row, field, value)?;
+        }
+        Ok(())
+    }
+
+    /// Copies the file to `output`, which may be the file itself, with the edits applied,
+    /// reading and writing one record at a time. Records are written back with the
+    /// delimiter of `options`, quoted only where needed. Returns an error, leaving `output`
+    /// as it was, if the file is compressed, is not UTF-8, or does not have an edited cell.
+    pub fn apply(&self, output: &str, options: &ReadOptions) -> Result<(), EditorError> {
+        if options.encoding.is_some_and(|encoding| encoding != UTF_8) {
+            return Err(EditorError::Encoding(
+                "overlay edits only apply to UTF-8 files".to_string(),
+            ));
+        }
+        let mut file = File::open(&self.file_name)?;
+        let mut start = Vec::new();
+        (&mut file).take(8).read_to_end(&mut start)?;
+        if let Some(compression) = Compression::detect(&start) {
+            return Err(EditorError::Compression(format!(
+                "overlay edits do not apply to {compression} files"
+            )));
+        }
+        // Detected as the reader does; records without a byte order mark are checked as
+        // they are copied.
+        if let Some((encoding, _)) = Encoding::for_bom(&start) {
+            if encoding != UTF_8 {
+                return Err(EditorError::Encoding(format!(
+                    "overlay edits only apply to UTF-8 files, {} is {}",
+                    self.file_name,
+                    encoding.name()
+                )));
+            }
+        }
+        file.rewind()?;
+        // Written next to the output, then renamed over it once complete.
+        let temporary = lock::sidecar_path(output, "overlay.tmp");
+        let result = self.copy(file, start.starts_with(BOM), &temporary, options);
+        match result.and_then(|()| Ok(std::fs::rename(&temporary, output)?)) {
+            Ok(()) => {
+                log::info!(file = self.file_name.as_str(), output, cells = self.cells.len(); "applied overlay");
+                Ok(())
+            }
+            Err(e) => {
+                let _ = std::fs::remove_file(&temporary);
+                Err(e)
+            }
+        }
+    }
+
+    fn copy(
+        &self,
+        file: File,
+        bom: bool,
+        temporary: &Path,
+        options: &ReadOptions,
+    ) -> Result<(), EditorError> {
+        let mut reader = ReaderBuilder::new()
+            .delimiter(options.delimiter)
+            .has_headers(false)
+            .flexible(true)
+            .from_reader(BufReader::new(file));
+        let mut output = BufWriter::new(File::create(temporary)?);
+        if bom {
+            output.write_all(BOM)?;
+        }
+        let mut writer = WriterBuilder::new()
+            .delimiter(options.delimiter)
+            .flexible(true)
+            .from_writer(output);
+        let utf8 = |record: csv::Result<ByteRecord>| {
+            let record = record?;
+            match std::str::from_utf8(record.as_slice()) {
+                Ok(_) => Ok(record),
+                Err(_) => Err(EditorError::Encoding(format!(
+                    "overlay edits only apply to UTF-8 files, line {} of {} is not",
+                    record.position().map_or(0, |position| position.line()),
+                    self.file_name
+                ))),
+            }
+        };
+        let mut records = reader.byte_records().map(utf8);
+        if options.has_headers {
+            if let Some(headers) = records.next() {
+                writer.write_byte_record(&headers?)?;
+            }
+        }
+        let mut edits = self.cells.iter().peekable();
+        let mut rows = 0;
+        for record in records {
+            let record = record?;
+            let row = rows;
+            rows += 1;
+            if edits.peek().is_none_or(|((next, _), _)| *next != row) {
+                writer.write_byte_record(&record)?;
+                continue;
+            }
+            let mut values: Vec<&[u8]> = record.iter().collect();
+            while let Some(((_, field), value)) = edits.next_if(|((next, _), _)| *next == row) {
+                let fields = values.len();
+                *values
+                    .get_mut(*field)
+                    .ok_or(EditorError::FieldOutOfBounds {
+                        field: *field,
+                        fields,
+                    })? = value.as_bytes();
+            }
+            writer.write_byte_record(&ByteRecord::from(values))?;
+        }
+        if let Some(((row, _), _)) = edits.next() {
+            return Err(EditorError::RowOutOfBounds { row: *row, rows });
+        }
+        writer
+            .into_inner()
+            .map_err(|e| e.into_error())?
+            .into_inner()
+            .map_err(|e| e.into_error())?
+            .sync_all()?;
+        Ok(())
+    }
+}
+
+/// Reads the first record of `file_name` only, returning the header, empty if the file has
+/// none, and the number of fields of the record.
+pub fn read_header(
+    file_name: &str,
+    options: &ReadOptions,
+) -> Result<(StringRecord, usize), EditorError> {
+    let mut reader = ReaderBuilder::new()
+        .delimiter(options.delimiter)
+        .has_headers(false)
+        .flexible(true)
+        .from_reader(BufReader::new(File::open(file_name)?));
+    let first = reader.records().next().transpose()?.unwrap_or_default();
+    let fields = first.len();
+    match options.has_headers {
+        true => Ok((first, fields)),
+        false => Ok((StringRecord::new(), fields)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_apply() {
+        let file_name = "test_overlay.csv";
+        let output = "test_overlay_out.csv";
+        std::fs::write(file_name, "\u{feff}id,name\n1,ann\n2,\"bob\nby\"\n3,cy\n")
+            .expect("Failed to write");
+        let mut overlay = Overlay::load(file_name).unwrap();
+        assert!(overlay.is_empty());
+        assert_eq!(overlay.set(2, 1, "cyd"), None);
+        assert_eq!(overlay.set(0, 1, "a, b"), None);
+        assert_eq!(overlay.set(2, 1, "cy"), Some("cyd".to_string()));
+        overlay.save().unwrap();
+
+        let overlay = Overlay::load(file_name).unwrap();
+        assert_eq!(
+            overlay.iter().collect::<Vec<_>>(),
+            vec![(0, 1, "a, b"), (2, 1, "cy")]
+        );
+        let mut csv_data = CSVData::read_from_file(file_name).unwrap();
+        overlay.apply_to(&mut csv_data).unwrap();
+        assert_eq!(csv_data.data[0], vec!["1", "a, b"]);
+        overlay.apply(output, &ReadOptions::default()).unwrap();
+        let written = std::fs::read_to_string(output).unwrap();
+        assert_eq!(
+            written,
+            "\u{feff}id,name\n1,\"a, b\"\n2,\"bob\nby\"\n3,cy\n"
+        );
+
+        let mut outside = overlay.clone();
+        outside.set(3, 0, "4");
+        let error = outside.apply(output, &ReadOptions::default()).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "row index 3 out of bounds, the data has 3 row(s)"
+        );
+        assert_eq!(std::fs::read_to_string(output).unwrap(), written);
+        assert!(!lock::sidecar_path(output, "overlay.tmp").exists());
+        std::fs::remove_file(output).expect("Failed to remove file");
+
+        std::fs::write(file_name, "id,name\n1,ann\n").expect("Failed to write");
+        assert!(Overlay::load(file_name).is_err());
+        assert!(Overlay::discard(file_name).unwrap());
+        assert!(Overlay::load(file_name).unwrap().is_empty());
+        assert_eq!(
+            read_header(file_name, &ReadOptions::default()).unwrap(),
+            (StringRecord::from(vec!["id", "name"]), 2)
+        );
+
+        // Edited cells would be spliced in as UTF-8.
+        let mut overlay = Overlay::load(file_name).unwrap();
+        overlay.set(0, 1, "bob");
+        let utf16 = crate::encoding::encode("id,name\n1,ann\n", encoding_rs::UTF_16LE).unwrap();
+        std::fs::write(file_name, utf16).expect("Failed to write");
+        let error = overlay.apply(output, &ReadOptions::default()).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "encoding error: overlay edits only apply to UTF-8 files, test_overlay.csv is UTF-16LE"
+        );
+        std::fs::write(file_name, b"id,name\n1,Jos\xe9\n").expect("Failed to write");
+        let error = overlay.apply(output, &ReadOptions::default()).unwrap_err();
+        assert!(error
+            .to_string()
+            .ends_with("line 2 of test_overlay.csv is not"));
+        assert!(!Path::new(output).exists());
+        std::fs::remove_file(file_name).expect("Failed to remove file");
+    }
+}
@@ -0,0 +1,215 @@
+use std::{collections::BTreeMap, sync::Arc};
+
+use csv::StringRecord;
+use serde::Serialize;
+
+use crate::{is_na, CSVData, EditorError};
+
+/// What a [`RowProcessor`] does with a row.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Verdict {
+    Keep,
+    Drop,
+}
+
+/// A custom step run on every row, to rewrite values, drop rows or reject the data.
+/// Implement it in another crate and run it with [`CSVData::process_rows`], or add it
+/// to a [`Registry`] to run it by name.
+pub trait RowProcessor {
+    /// Called before the first row with the header, which is empty when the data has none.
+    /// Returns an error to refuse the data, e.g. when a column it needs is missing.
+    fn begin(&mut self, _headers: &StringRecord) -> Result<(), String> {
+        Ok(())
+    }
+
+    /// Rewrites the values of the row at index `row` (0-based) in place, or drops the row.
+    /// Returns an error to reject the row, which stops the run without changing the data.
+    fn process(&mut self, row: usize, values: &mut [String]) -> Result<Verdict, String>;
+}
+
+/// How many values a run of row processors rewrote and how many rows it dropped.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+pub struct Processed {
+    pub changed: usize,
+    pub dropped: usize,
+}
+
+type Factory = Arc<dyn Fn() -> Box<dyn RowProcessor> + Send + Sync>;
+
+/// Row processors by name, as run by `--on-read`, `--on-write` and `apply-plugin`.
+/// Each run gets a new processor from the registered factory.
+#[derive(Clone, Default)]
+pub struct Registry {
+    factories: BTreeMap<String, (String, Factory)>,
+}
+
+impl Registry {
+    /// Returns a registry holding the processors that come with the editor:
+    /// `trim`, `drop-blank` and `no-missing`.
+    pub fn builtin() -> Registry {
+        let mut registry = Registry::default();
+        registry.register("trim", "removes leading and trailing whitespace", || {
+            Box::new(Trim)
+        });
+        registry.register("drop-blank", "drops rows with no value at all", || {
+            Box::new(DropBlank)
+        });
+        registry.register("no-missing", "rejects rows with an empty value", || {
+            Box::new(NoMissing)
+        });
+        registry
+    }
+
+    /// Adds a processor under `name`, replacing any processor with the same name.
+    pub fn register(
+        &mut self,
+        name: &str,
+        description: &str,
+        factory: impl Fn() -> Box<dyn RowProcessor> + Send + Sync + 'static,
+    ) {
+        self.factories.insert(
+            name.to_string(),
+            (description.to_string(), Arc::new(factory)),
+        );
+    }
+
+    /// Returns a new processor registered as `name`.
+    /// Returns an error if there is no such processor.
+    pub fn create(&self, name: &str) -> Result<Box<dyn RowProcessor>, EditorError> {
+        match self.factories.get(name) {
+            Some((_, factory)) => Ok(factory()),
+            None => Err(EditorError::Plugin {
+                name: name.to_string(),
+                row: None,
+                message: format!(
+                    "no such plugin, expected one of {}",
+                    self.iter()
+                        .map(|(name, _)| name)
+                        .collect::<Vec<_>>()
+                        .join(", ")
+                ),
+            }),
+        }
+    }
+
+    /// Returns the names and descriptions of the processors, by name.
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.factories
+            .iter()
+            .map(|(name, (description, _))| (name.as_str(), description.as_str()))
+    }
+
+    /// Runs the processors named `names` over `csv_data`, in order.
+    /// Returns an error, before running any, if a name is unknown.
+    pub fn run(&self, names: &[String], csv_data: &mut CSVData) -> Result<Processed, EditorError> {
+        let processors = names
+            .iter()
+            .map(|name| Ok((name, self.create(name)?)))
+            .collect::<Result<Vec<_>, EditorError>>()?;
+        let mut total = Processed::default();
+        for (name, mut processor) in processors {
+            let processed = csv_data.process_rows(name, processor.as_mut())?;
+            total.changed += processed.changed;
+            total.dropped += processed.dropped;
+        }
+        Ok(total)
+    }
+}
+
+struct Trim;
+
+impl RowProcessor for Trim {
+    fn process(&mut self, _row: usize, values: &mut [String]) -> Result<Verdict, String> {
+        for value in values {
+            if value.trim() != value {
+                *value = value.trim().to_string();
+            }
+        }
+        Ok(Verdict::Keep)
+    }
+}
+
+struct DropBlank;
+
+impl RowProcessor for DropBlank {
+    fn process(&mut self, _row: usize, values: &mut [String]) -> Result<Verdict, String> {
+        if values.iter().all(|value| is_na(value)) {
+            Ok(Verdict::Drop)
+        } else {
+            Ok(Verdict::Keep)
+        }
+    }
+}
+
+struct NoMissing;
+
+impl RowProcessor for NoMissing {
+    fn process(&mut self, _row: usize, values: &mut [String]) -> Result<Verdict, String> {
+        match values.iter().position(|value| is_na(value)) {
+            Some(field) => Err(format!("field index {field} is empty")),
+            None => Ok(Verdict::Keep),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Masks the values of the `email` column, as a downstream scrubber would.
+    struct MaskEmails {
+        column: Option<usize>,
+    }
+
+    impl RowProcessor for MaskEmails {
+        fn begin(&mut self, headers: &StringRecord) -> Result<(), String> {
+            self.column = headers.iter().position(|name| name == "email");
+            self.column.map(|_| ()).ok_or("no email column".to_string())
+        }
+
+        fn process(&mut self, _row: usize, values: &mut [String]) -> Result<Verdict, String> {
+            let value = &mut values[self.column.expect("set by begin")];
+            if let Some((_, domain)) = value.split_once('@') {
+                *value = format!("***@{domain}");
+            }
+            Ok(Verdict::Keep)
+        }
+    }
+
+    #[test]
+    fn test_registry_runs_processors() {
+        let file_name = "test_plugin.csv";
+        std::fs::write(file_name, "name,email\n ann ,ann@example.com\n,\nbob,\n")
+            .expect("Failed to write");
+        let mut csv_data = CSVData::read_from_file(file_name).expect("Failed to read");
+        std::fs::remove_file(file_name).expect("Failed to remove file");
+
+        let mut registry = Registry::builtin();
+        registry.register("mask-emails", "masks email addresses", || {
+            Box::new(MaskEmails { column: None })
+        });
+        let names = ["trim", "drop-blank", "mask-emails"].map(String::from);
+        let processed = registry.run(&names, &mut csv_data).unwrap();
+        assert_eq!(
+            processed,
+            Processed {
+                changed: 2,
+                dropped: 1
+            }
+        );
+        assert_eq!(
+            csv_data.data,
+            vec![
+                StringRecord::from(vec!["ann", "***@example.com"]),
+                StringRecord::from(vec!["bob", ""]),
+            ]
+        );
+
+        let error = registry
+            .run(&["no-missing".to_string()], &mut csv_data)
+            .unwrap_err();
+        assert!(matches!(error, EditorError::Plugin { row: Some(1), .. }));
+        assert_eq!(csv_data.data.len(), 2);
+        assert!(registry.run(&["scrub".to_string()], &mut csv_data).is_err());
+    }
+}
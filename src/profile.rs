@@ -0,0 +1,642 @@
+//! In-depth reports on the values of each column, printed by `profile`: how sure the type
+//! guess is, the shapes the values take, how long they are, which numbers lie far from the
+//! others, and example rows worth a look.
+
+use std::{
+    collections::{HashMap, HashSet},
+    io::{self, Write},
+};
+
+use rayon::prelude::*;
+use serde::Serialize;
+
+use crate::{
+    columns,
+    export::{escape_html, STYLE},
+    is_na,
+    schema::ColumnType,
+    CSVData,
+};
+
+/// Distance from the quartiles, in interquartile ranges, beyond which a number is an
+/// outlier (Tukey's fences).
+const IQR_FACTOR: f64 = 1.5;
+/// Most shapes listed for a column; the values of the others are only counted.
+const PATTERNS: usize = 8;
+/// Most buckets of a length histogram.
+const BUCKETS: usize = 8;
+/// Share of the values, in percent, below which a shape is rare, when the most common one
+/// covers at least half of them.
+const RARE_PERCENT: f64 = 5.0;
+
+/// A report on every column of a file:
+/// `{"file": "sales.csv", "rows": 26, "columns": [{"column": "price", "type": "float", ...}]}`.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Profile {
+    pub file: String,
+    pub rows: usize,
+    pub columns: Vec<ColumnProfile>,
+}
+
+/// A column in a [`Profile`].
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ColumnProfile {
+    pub column: String,
+    /// The type declared with `--types`, or else the one most values have.
+    #[serde(rename = "type")]
+    pub column_type: String,
+    /// Share of the non-empty values of that type, from 0 to 100. For text, the share that
+    /// have no other type.
+    pub confidence: f64,
+    /// Number of non-empty values.
+    pub values: usize,
+    /// Number of empty values (see [`is_na`]).
+    pub empty: usize,
+    /// Number of distinct non-empty values.
+    pub distinct: usize,
+    pub lengths: Lengths,
+    /// The most common shapes, the most common first.
+    pub patterns: Vec<Pattern>,
+    /// Number of values whose shape is not listed.
+    pub other_patterns: usize,
+    /// Numbers beyond the fences of numeric columns with at least four numbers.
+    pub outliers: Option<Outliers>,
+    /// The first anomalous values, in row order.
+    pub anomalies: Vec<Anomaly>,
+    /// Number of anomalous values, listed or not.
+    pub anomaly_count: usize,
+}
+
+/// Lengths in characters of the non-empty values of a column.
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct Lengths {
+    pub min: usize,
+    pub max: usize,
+    pub mean: f64,
+    /// Number of values by range of lengths, shortest first.
+    pub histogram: Vec<Bucket>,
+}
+
+/// A range of lengths, both inclusive, in a [`Lengths`] histogram.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Bucket {
+    pub from: usize,
+    pub to: usize,
+    pub count: usize,
+}
+
+/// Values sharing a shape (see [`shape`]).
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Pattern {
+    /// A regular expression matching the values, e.g. `[A-Z]+\-\d{4}`.
+    pub pattern: String,
+    pub count: usize,
+    /// Share of the non-empty values of that shape, from 0 to 100.
+    pub percent: f64,
+    /// The first value of that shape.
+    pub example: String,
+}
+
+/// Numbers far from the middle half of a numeric column.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Outliers {
+    /// First and third quartiles.
+    pub q1: f64,
+    pub q3: f64,
+    /// Fences, 1.5 interquartile ranges beyond the quartiles.
+    pub low: f64,
+    pub high: f64,
+    /// Number of numbers below `low` or above `high`.
+    pub count: usize,
+}
+
+/// A value that stands out from the rest of its column.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Anomaly {
+    /// Row number, starting at 1.
+    pub row: usize,
+    /// Line of the file the row starts on, when known.
+    pub line: Option<u64>,
+    pub value: String,
+    /// Why the value stands out, e.g. `not an integer` or `outlier above 44.9`.
+    pub reason: String,
+}
+
+/// Profiles every column of `csv_data`, listing at most `examples` anomalous values per
+/// column. Numbers are read in the locale of their column. Values stand out when they are
+/// not of the column's type, are outliers, or have a rare shape.
+pub fn profile(csv_data: &CSVData, examples: usize) -> Profile {
+    let columns = (0..csv_data.fields)
+        .into_par_iter()
+        .map(|field| profile_column(csv_data, field, examples))
+        .collect();
+    Profile {
+        file: csv_data.file_name.clone(),
+        rows: csv_data.data.len(),
+        columns,
+    }
+}
+
+fn profile_column(csv_data: &CSVData, field: usize, examples: usize) -> ColumnProfile {
+    let values: Vec<(usize, &str)> = csv_data
+        .data
+        .iter()
+        .enumerate()
+        .map(|(row, record)| (row, record.get(field).unwrap_or("")))
+        .filter(|(_, value)| !is_na(value))
+        .collect();
+    let locale = csv_data.number_locale(field);
+    let (column_type, confidence) = match csv_data.types.get(field) {
+        Some(&column_type) if column_type != ColumnType::Text => {
+            let accepted = values
+                .iter()
+                .filter(|(_, value)| column_type.accepts_in(value, locale))
+                .count();
+            (column_type, percent(accepted, values.len()))
+        }
+        _ => guess_type(
+            values.iter().map(|(_, value)| *value),
+            |column_type, value| column_type.accepts_in(value, locale),
+        ),
+    };
+
+    let shapes: Vec<String> = values.iter().map(|(_, value)| shape(value)).collect();
+    let mut counts: HashMap<&str, (usize, usize)> = HashMap::new();
+    for (i, shape) in shapes.iter().enumerate() {
+        counts.entry(shape).or_insert((0, i)).0 += 1;
+    }
+    let mut patterns: Vec<Pattern> = counts
+        .iter()
+        .map(|(shape, &(count, first))| Pattern {
+            pattern: shape.to_string(),
+            count,
+            percent: percent(count, values.len()),
+            example: values[first].1.to_string(),
+        })
+        .collect();
+    patterns.sort_by(|a, b| b.count.cmp(&a.count).then(a.pattern.cmp(&b.pattern)));
+    let dominant = patterns
+        .first()
+        .is_some_and(|pattern| pattern.percent >= 50.0);
+    let is_rare = |shape: &str| dominant && percent(counts[shape].0, values.len()) < RARE_PERCENT;
+    let other_patterns = patterns.iter().skip(PATTERNS).map(|p| p.count).sum();
+    patterns.truncate(PATTERNS);
+
+    let numbers: Vec<(usize, f64)> = match column_type {
+        ColumnType::Integer | ColumnType::Float => values
+            .iter()
+            .enumerate()
+            .filter_map(|(i, (_, value))| Some((i, csv_data.parse_number(field, value)?)))
+            .collect(),
+        _ => Vec::new(),
+    };
+    let outliers = outliers(numbers.iter().map(|(_, number)| *number).collect());
+    let mut far = HashMap::new();
+    if let Some(outliers) = &outliers {
+        for &(i, number) in &numbers {
+            if number < outliers.low {
+                far.insert(i, format!("outlier below {}", round(outliers.low)));
+            } else if number > outliers.high {
+                far.insert(i, format!("outlier above {}", round(outliers.high)));
+            }
+        }
+    }
+
+    let mut anomalies = Vec::new();
+    let mut anomaly_count = 0;
+    for (i, &(row, value)) in values.iter().enumerate() {
+        let reason = if !column_type.accepts_in(value, locale) {
+            Some(format!("not {}", article(column_type)))
+        } else if let Some(reason) = far.remove(&i) {
+            Some(reason)
+        } else if is_rare(&shapes[i]) {
+            Some(format!("rare shape {}", shapes[i]))
+        } else {
+            None
+        };
+        let Some(reason) = reason else { continue };
+        anomaly_count += 1;
+        if anomalies.len() < examples {
+            anomalies.push(Anomaly {
+                row: row + 1,
+                line: csv_data.line(row),
+                value: value.to_string(),
+                reason,
+            });
+        }
+    }
+
+    ColumnProfile {
+        column: columns::column_name(&csv_data.headers, field),
+        column_type: column_type.to_string(),
+        confidence,
+        values: values.len(),
+        empty: csv_data.data.len() - values.len(),
+        distinct: values
+            .iter()
+            .map(|(_, value)| *value)
+            .collect::<HashSet<_>>()
+            .len(),
+        lengths: lengths(values.iter().map(|(_, value)| value.chars().count())),
+        patterns,
+        other_patterns,
+        outliers,
+        anomalies,
+        anomaly_count,
+    }
+}
+
+/// Guesses the type of `values`, all non-empty, with the share of them that have it: the
+/// type most of them have, the narrowest one on ties, or text if none covers more than half.
+fn guess_type<'a>(
+    values: impl Iterator<Item = &'a str>,
+    accepts: impl Fn(ColumnType, &str) -> bool,
+) -> (ColumnType, f64) {
+    const CANDIDATES: [ColumnType; 4] = [
+        ColumnType::Integer,
+        ColumnType::Float,
+        ColumnType::Boolean,
+        ColumnType::Date,
+    ];
+    let mut counts = [0; CANDIDATES.len()];
+    let mut text = 0;
+    let mut total = 0;
+    for value in values {
+        total += 1;
+        let mut typed = false;
+        for (count, column_type) in counts.iter_mut().zip(CANDIDATES) {
+            if accepts(column_type, value) {
+                *count += 1;
+                typed = true;
+            }
+        }
+        text += usize::from(!typed);
+    }
+    let (best, count) =
+        counts
+            .iter()
+            .enumerate()
+            .fold((0, 0), |best, (i, &count)| match count > best.1 {
+                true => (i, count),
+                false => best,
+            });
+    match count * 2 > total {
+        true => (CANDIDATES[best], percent(count, total)),
+        false => (ColumnType::Text, percent(text, total)),
+    }
+}
+
+/// Returns the shape of `value` as a regular expression matching it: runs of digits become
+/// `\d{N}`, runs of letters `[A-Z]+` or `[a-z]+` (`\p{Lu}`, `\p{Ll}` or `\p{L}` when they are
+/// not all ASCII), runs of whitespace `\s+`, and other characters stay as they are.
+pub fn shape(value: &str) -> String {
+    #[derive(Clone, Copy, PartialEq)]
+    enum Class {
+        Digit,
+        Upper,
+        Lower,
+        Letter,
+        Space,
+        Other(char),
+    }
+    let class = |c: char| match c {
+        c if c.is_ascii_digit() => Class::Digit,
+        c if c.is_uppercase() => Class::Upper,
+        c if c.is_lowercase() => Class::Lower,
+        c if c.is_alphabetic() => Class::Letter,
+        c if c.is_whitespace() => Class::Space,
+        c => Class::Other(c),
+    };
+    let mut shape = String::new();
+    let mut chars = value.chars().peekable();
+    while let Some(c) = chars.next() {
+        let run = class(c);
+        let (mut length, mut ascii) = (1, c.is_ascii());
+        if !matches!(run, Class::Other(_)) {
+            while let Some(c) = chars.next_if(|&c| class(c) == run) {
+                length += 1;
+                ascii &= c.is_ascii();
+            }
+        }
+        let repeat = if length > 1 { "+" } else { "" };
+        match run {
+            Class::Digit if length > 1 => shape.push_str(&format!("\\d{{{length}}}")),
+            Class::Digit => shape.push_str("\\d"),
+            Class::Upper if ascii => shape.push_str(&format!("[A-Z]{repeat}")),
+            Class::Upper => shape.push_str(&format!("\\p{{Lu}}{repeat}")),
+            Class::Lower if ascii => shape.push_str(&format!("[a-z]{repeat}")),
+            Class::Lower => shape.push_str(&format!("\\p{{Ll}}{repeat}")),
+            Class::Letter => shape.push_str(&format!("\\p{{L}}{repeat}")),
+            Class::Space => shape.push_str(&format!("\\s{repeat}")),
+            Class::Other(c) => shape.push_str(&regex::escape(c.encode_utf8(&mut [0; 4]))),
+        }
+    }
+    shape
+}
+
+fn lengths(lengths: impl Iterator<Item = usize>) -> Lengths {
+    let lengths: Vec<usize> = lengths.collect();
+    let (Some(&min), Some(&max)) = (lengths.iter().min(), lengths.iter().max()) else {
+        return Lengths::default();
+    };
+    let width = (max - min + 1).div_ceil(BUCKETS);
+    let mut histogram: Vec<Bucket> = (min..=max)
+        .step_by(width)
+        .map(|from| Bucket {
+            from,
+            to: (from + width - 1).min(max),
+            count: 0,
+        })
+        .collect();
+    for length in &lengths {
+        histogram[(length - min) / width].count += 1;
+    }
+    Lengths {
+        min,
+        max,
+        mean: lengths.iter().sum::<usize>() as f64 / lengths.len() as f64,
+        histogram,
+    }
+}
+
+/// Returns the quartiles and fences of `numbers`, and how many lie beyond the fences, if
+/// there are at least four numbers.
+fn outliers(mut numbers: Vec<f64>) -> Option<Outliers> {
+    if numbers.len() < 4 {
+        return None;
+    }
+    numbers.sort_by(f64::total_cmp);
+    let quantile = |p: f64| {
+        let position = p * (numbers.len() - 1) as f64;
+        let (below, above) = (position.floor() as usize, position.ceil() as usize);
+        numbers[below] + (numbers[above] - numbers[below]) * position.fract()
+    };
+    let (q1, q3) = (quantile(0.25), quantile(0.75));
+    let (low, high) = (q1 - IQR_FACTOR * (q3 - q1), q3 + IQR_FACTOR * (q3 - q1));
+    let count = numbers
+        .iter()
+        .filter(|&&number| number < low || number > high)
+        .count();
+    Some(Outliers {
+        q1,
+        q3,
+        low,
+        high,
+        count,
+    })
+}
+
+fn percent(count: usize, total: usize) -> f64 {
+    count as f64 * 100.0 / total.max(1) as f64
+}
+
+/// Rounds `number` to 4 decimals, for display.
+fn round(number: f64) -> f64 {
+    (number * 1e4).round() / 1e4
+}
+
+fn article(column_type: ColumnType) -> String {
+    match column_type {
+        ColumnType::Integer => "an integer".to_string(),
+        column_type => format!("a {column_type}"),
+    }
+}
+
+/// Writes `profile` as text for people, one section per column.
+pub fn write_text<W: Write>(profile: &Profile, writer: &mut W) -> io::Result<()> {
+    writeln!(
+        writer,
+        "{}: {} rows, {} columns",
+        profile.file,
+        profile.rows,
+        profile.columns.len()
+    )?;
+    for column in &profile.columns {
+        writeln!(writer)?;
+        writeln!(
+            writer,
+            "{}: {} ({:.1}% of {} values), {} empty, {} distinct",
+            column.column,
+            column.column_type,
+            column.confidence,
+            column.values,
+            column.empty,
+            column.distinct
+        )?;
+        if column.values == 0 {
+            continue;
+        }
+        let lengths = &column.lengths;
+        writeln!(
+            writer,
+            "  lengths: {} to {}, mean {:.1}",
+            lengths.min, lengths.max, lengths.mean
+        )?;
+        let most = lengths.histogram.iter().map(|b| b.count).max().unwrap_or(1);
+        for bucket in &lengths.histogram {
+            let range = match bucket.from == bucket.to {
+                true => bucket.from.to_string(),
+                false => format!("{}-{}", bucket.from, bucket.to),
+            };
+            let bar = "#".repeat((bucket.count * 20).div_ceil(most.max(1)));
+            writeln!(writer, "    {range:>9}  {bar:<20}  {}", bucket.count)?;
+        }
+        writeln!(writer, "  patterns:")?;
+        for pattern in &column.patterns {
+            writeln!(
+                writer,
+                "    {}  {} ({:.1}%), e.g. {}",
+                pattern.pattern, pattern.count, pattern.percent, pattern.example
+            )?;
+        }
+        if column.other_patterns > 0 {
+            writeln!(writer, "    other shapes  {}", column.other_patterns)?;
+        }
+        if let Some(outliers) = &column.outliers {
+            writeln!(
+                writer,
+                "  outliers: {} outside {} to {} (quartiles {} and {})",
+                outliers.count,
+                round(outliers.low),
+                round(outliers.high),
+                round(outliers.q1),
+                round(outliers.q3)
+            )?;
+        }
+        if column.anomaly_count > 0 {
+            writeln!(writer, "  anomalies: {}", column.anomaly_count)?;
+            for anomaly in &column.anomalies {
+                writeln!(
+                    writer,
+                    "    {}: {}, {}",
+                    describe(anomaly),
+                    anomaly.value,
+                    anomaly.reason
+                )?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Writes `profile` as a standalone HTML page, one table per column.
+pub fn write_html<W: Write>(profile: &Profile, writer: &mut W) -> io::Result<()> {
+    let title = escape_html(&format!("Profile of {}", profile.file));
+    writeln!(writer, "<!DOCTYPE html>")?;
+    writeln!(writer, "<html>\n<head>\n<meta charset=\"utf-8\">")?;
+    writeln!(writer, "<title>{title}</title>\n<style>\n{STYLE}</style>")?;
+    writeln!(writer, "</head>\n<body>\n<h1>{title}</h1>")?;
+    writeln!(
+        writer,
+        "<p>{} rows, {} columns</p>",
+        profile.rows,
+        profile.columns.len()
+    )?;
+    for column in &profile.columns {
+        writeln!(writer, "<h2>{}</h2>", escape_html(&column.column))?;
+        writeln!(
+            writer,
+            "<p>{} ({:.1}% of {} values), {} empty, {} distinct</p>",
+            column.column_type, column.confidence, column.values, column.empty, column.distinct
+        )?;
+        if column.values == 0 {
+            continue;
+        }
+        let lengths = &column.lengths;
+        writeln!(
+            writer,
+            "<p>Lengths {} to {}, mean {:.1}</p>",
+            lengths.min, lengths.max, lengths.mean
+        )?;
+        writeln!(writer, "<table>\n<tr><th>Length</th><th>Values</th></tr>")?;
+        for bucket in &lengths.histogram {
+            writeln!(
+                writer,
+                "<tr><td class=\"number\">{}-{}</td><td class=\"number\">{}</td></tr>",
+                bucket.from, bucket.to, bucket.count
+            )?;
+        }
+        writeln!(writer, "</table>")?;
+        writeln!(
+            writer,
+            "<table>\n<tr><th>Pattern</th><th>Values</th><th>%</th><th>Example</th></tr>"
+        )?;
+        for pattern in &column.patterns {
+            writeln!(
+                writer,
+                "<tr><td><code>{}</code></td><td class=\"number\">{}</td><td class=\"number\">{:.1}</td><td>{}</td></tr>",
+                escape_html(&pattern.pattern),
+                pattern.count,
+                pattern.percent,
+                escape_html(&pattern.example)
+            )?;
+        }
+        if column.other_patterns > 0 {
+            writeln!(
+                writer,
+                "<tr><td>other shapes</td><td class=\"number\">{}</td><td></td><td></td></tr>",
+                column.other_patterns
+            )?;
+        }
+        writeln!(writer, "</table>")?;
+        if let Some(outliers) = &column.outliers {
+            writeln!(
+                writer,
+                "<p>{} outliers outside {} to {} (quartiles {} and {})</p>",
+                outliers.count,
+                round(outliers.low),
+                round(outliers.high),
+                round(outliers.q1),
+                round(outliers.q3)
+            )?;
+        }
+        if column.anomaly_count > 0 {
+            writeln!(writer, "<p>{} anomalies</p>", column.anomaly_count)?;
+            writeln!(
+                writer,
+                "<table>\n<tr><th>Row</th><th>Value</th><th>Reason</th></tr>"
+            )?;
+            for anomaly in &column.anomalies {
+                writeln!(
+                    writer,
+                    "<tr><td>{}</td><td>{}</td><td>{}</td></tr>",
+                    describe(anomaly),
+                    escape_html(&anomaly.value),
+                    escape_html(&anomaly.reason)
+                )?;
+            }
+            writeln!(writer, "</table>")?;
+        }
+    }
+    writeln!(writer, "</body>\n</html>")
+}
+
+fn describe(anomaly: &Anomaly) -> String {
+    match anomaly.line {
+        Some(line) => format!("row {} (line {line})", anomaly.row),
+        None => format!("row {}", anomaly.row),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_shape() {
+        assert_eq!(shape("AB-1234"), "[A-Z]+\\-\\d{4}");
+        assert_eq!(shape("Ann Lee"), "[A-Z][a-z]+\\s[A-Z][a-z]+");
+        assert_eq!(shape("12.50"), "\\d{2}\\.\\d{2}");
+        assert_eq!(shape("Émile  x"), "\\p{Lu}[a-z]+\\s+[a-z]");
+    }
+
+    #[test]
+    fn test_profile() {
+        let file_name = "test_profile.csv";
+        let mut contents = "id,price,code\n".to_string();
+        for i in 1..=20 {
+            contents.push_str(&format!("{i},{}.5,AB-{:04}\n", 10 + i % 3, i));
+        }
+        contents.push_str("21,1000,AB-0021\n22,n/a,ab 22\n23,,AB-0023\n");
+        std::fs::write(file_name, contents).expect("Failed to write");
+        let csv_data = CSVData::read_from_file(file_name).expect("Failed to read");
+        std::fs::remove_file(file_name).expect("Failed to remove file");
+
+        let profile = profile(&csv_data, 1);
+        assert_eq!(profile.rows, 23);
+        let id = &profile.columns[0];
+        assert_eq!((id.column_type.as_str(), id.confidence), ("integer", 100.0));
+        assert_eq!(id.distinct, 23);
+        assert_eq!((id.lengths.min, id.lengths.max), (1, 2));
+        assert_eq!(id.anomaly_count, 0);
+
+        let price = &profile.columns[1];
+        assert_eq!(price.column_type, "float");
+        assert_eq!((price.values, price.empty), (22, 1));
+        let outliers = price.outliers.as_ref().unwrap();
+        assert_eq!((outliers.q1, outliers.q3, outliers.count), (10.5, 12.5, 1));
+        assert_eq!(price.anomaly_count, 2);
+        assert_eq!(
+            price.anomalies,
+            vec![Anomaly {
+                row: 21,
+                line: Some(22),
+                value: "1000".to_string(),
+                reason: "outlier above 15.5".to_string(),
+            }]
+        );
+
+        let code = &profile.columns[2];
+        assert_eq!(code.column_type, "text");
+        assert_eq!(code.patterns[0].pattern, "[A-Z]+\\-\\d{4}");
+        assert_eq!(code.patterns[0].count, 22);
+        assert_eq!(code.patterns[0].example, "AB-0001");
+        assert_eq!(code.anomalies[0].reason, "rare shape [a-z]+\\s\\d{2}");
+
+        let mut html = Vec::new();
+        write_html(&profile, &mut html).unwrap();
+        let html = String::from_utf8(html).unwrap();
+        assert!(html.contains("<h2>code</h2>"));
+        assert!(html.contains("<code>[A-Z]+\\-\\d{4}</code>"));
+    }
+}
@@ -0,0 +1,43 @@
+use std::fmt;
+
+/// How often, in rows, long-running operations report progress.
+pub(crate) const REPORT_EVERY: usize = 1024;
+
+/// A long-running operation.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Stage {
+    Read,
+    Sort,
+    Write,
+}
+
+impl fmt::Display for Stage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Stage::Read => "read",
+            Stage::Sort => "sort",
+            Stage::Write => "write",
+        })
+    }
+}
+
+/// An update sent while a stage runs.
+/// Work is counted in bytes for [`Stage::Read`] and in rows otherwise.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ProgressEvent {
+    Started { stage: Stage, total: u64 },
+    Advanced { stage: Stage, done: u64 },
+    Finished { stage: Stage, rows: usize },
+}
+
+/// Receives progress updates from long-running operations.
+/// Any `FnMut(ProgressEvent)` closure is a `Progress`, so `&mut |_| {}` ignores updates.
+pub trait Progress {
+    fn report(&mut self, event: ProgressEvent);
+}
+
+impl<F: FnMut(ProgressEvent)> Progress for F {
+    fn report(&mut self, event: ProgressEvent) {
+        self(event)
+    }
+}
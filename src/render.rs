@@ -0,0 +1,351 @@
+use std::io::{self, Write};
+
+use csv::{StringRecord, WriterBuilder};
+
+use crate::{
+    color::ColorRule,
+    config::{OutputFormat, OutputMode, Settings},
+    report::{self, Records},
+};
+
+const RESET: &str = "\x1b[0m";
+
+/// Writes `records` to `writer` using the output format from `settings`.
+/// The header is only written by formats that have a place for it (table, csv).
+/// With `--output json`, they are written as a [`Records`] report instead.
+pub fn write_records<W: Write>(
+    headers: &StringRecord,
+    records: &[StringRecord],
+    settings: &Settings,
+    writer: &mut W,
+) -> io::Result<()> {
+    if settings.output_mode == OutputMode::Json {
+        return report::write(&Records::new(headers, records), writer);
+    }
+    write_layout(headers, records, &Layout::default(), settings, writer)
+}
+
+/// How a window of columns is laid out in table output.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Layout<'a> {
+    /// Number of leading columns marked off from the others with `||` instead of `|`.
+    pub frozen: usize,
+    /// Position (0-based) in the file of each column, printed as a strip of 1-based
+    /// numbers above the header.
+    pub numbers: Option<&'a [usize]>,
+    /// Minimum width of each column, so that every page lines up the same way.
+    pub widths: Option<&'a [usize]>,
+    /// Number (1-based) of the first row, when rows are numbered in a leading `#` column.
+    pub first_row: Option<usize>,
+}
+
+/// Same as [`write_records`], laying out table output as described by `layout`.
+pub fn write_layout<W: Write>(
+    headers: &StringRecord,
+    records: &[StringRecord],
+    layout: &Layout,
+    settings: &Settings,
+    writer: &mut W,
+) -> io::Result<()> {
+    if let Some(first_row) = layout.first_row {
+        let numbered = |number: String, record: &StringRecord| -> StringRecord {
+            std::iter::once(number.as_str()).chain(record).collect()
+        };
+        let headers = if headers.is_empty() {
+            StringRecord::new()
+        } else {
+            numbered("#".to_string(), headers)
+        };
+        let records: Vec<StringRecord> = records
+            .iter()
+            .enumerate()
+            .map(|(i, record)| numbered((first_row + i).to_string(), record))
+            .collect();
+        let numbers: Option<Vec<String>> = layout.numbers.map(|numbers| {
+            std::iter::once(String::new())
+                .chain(numbers.iter().map(|i| (i + 1).to_string()))
+                .collect()
+        });
+        let widths: Option<Vec<usize>> = layout
+            .widths
+            .map(|widths| std::iter::once(0).chain(widths.iter().copied()).collect());
+        return write_formatted(
+            &headers,
+            &records,
+            numbers.map(StringRecord::from).as_ref(),
+            widths.as_deref(),
+            layout.frozen + 1,
+            settings,
+            writer,
+        );
+    }
+    let numbers: Option<StringRecord> = layout
+        .numbers
+        .map(|numbers| numbers.iter().map(|i| (i + 1).to_string()).collect());
+    write_formatted(
+        headers,
+        records,
+        numbers.as_ref(),
+        layout.widths,
+        layout.frozen,
+        settings,
+        writer,
+    )
+}
+
+/// Writes the records in the output format of `settings`; see [`Layout`] for the rest.
+fn write_formatted<W: Write>(
+    headers: &StringRecord,
+    records: &[StringRecord],
+    numbers: Option<&StringRecord>,
+    widths: Option<&[usize]>,
+    frozen: usize,
+    settings: &Settings,
+    writer: &mut W,
+) -> io::Result<()> {
+    match settings.output_format {
+        OutputFormat::Debug => {
+            for record in records {
+                writeln!(writer, "{record:#?}")?;
+            }
+            Ok(())
+        }
+        OutputFormat::Table => {
+            write_table(headers, records, numbers, widths, frozen, settings, writer)
+        }
+        OutputFormat::Csv => {
+            let mut csv_writer = WriterBuilder::new()
+                .delimiter(settings.delimiter)
+                .from_writer(writer);
+            if !headers.is_empty() {
+                csv_writer.write_record(headers)?;
+            }
+            for record in records {
+                csv_writer.write_record(record)?;
+            }
+            csv_writer.flush()
+        }
+    }
+}
+
+/// Writes the records as left-aligned columns separated by ` | `,
+/// or ` || ` after the last of the frozen columns.
+fn write_table<W: Write>(
+    headers: &StringRecord,
+    records: &[StringRecord],
+    numbers: Option<&StringRecord>,
+    minimum_widths: Option<&[usize]>,
+    frozen: usize,
+    settings: &Settings,
+    writer: &mut W,
+) -> io::Result<()> {
+    let columns = records
+        .iter()
+        .map(|record| record.len())
+        .chain(std::iter::once(headers.len()))
+        .max()
+        .unwrap_or(0);
+    let mut widths = vec![0; columns];
+    if let Some(minimum) = minimum_widths {
+        for (width, minimum) in widths.iter_mut().zip(minimum) {
+            *width = *minimum;
+        }
+    }
+    for record in records
+        .iter()
+        .chain(std::iter::once(headers))
+        .chain(numbers)
+    {
+        for (i, field) in record.iter().enumerate().take(columns) {
+            widths[i] = widths[i].max(field.chars().count());
+        }
+    }
+
+    if let Some(numbers) = numbers {
+        writeln!(writer, "{}", format_row(numbers, &widths, frozen))?;
+    }
+    if !headers.is_empty() {
+        let line = format_row(headers, &widths, frozen);
+        match settings.color_scheme.header_style() {
+            Some(style) if settings.color => writeln!(writer, "{style}{line}{RESET}")?,
+            _ => writeln!(writer, "{line}")?,
+        }
+        let rule: Vec<String> = widths.iter().map(|w| "-".repeat(*w)).collect();
+        writeln!(writer, "{}", join(&rule, frozen, "-+-", "-++-"))?;
+    }
+    let rules = if settings.color {
+        settings.color_rules.as_slice()
+    } else {
+        &[]
+    };
+    for (index, record) in records.iter().enumerate() {
+        if rules.is_empty() {
+            writeln!(writer, "{}", format_row(record, &widths, frozen))?;
+        } else {
+            let line = format_colored_row(record, &widths, frozen, rules, index);
+            writeln!(writer, "{line}")?;
+        }
+    }
+    Ok(())
+}
+
+/// Same as [`format_row`], coloring the cells and the row at `index` on the page as
+/// the first matching rule says.
+fn format_colored_row(
+    record: &StringRecord,
+    widths: &[usize],
+    frozen: usize,
+    rules: &[ColorRule],
+    index: usize,
+) -> String {
+    let row_style = rules
+        .iter()
+        .find_map(|rule| rule.row_style(index))
+        .map_or("", |style| style.code());
+    let cells: Vec<String> = widths
+        .iter()
+        .enumerate()
+        .map(|(i, width)| {
+            let value = record.get(i).unwrap_or("");
+            let cell = format!("{value:<width$}");
+            match rules.iter().find_map(|rule| rule.cell_style(value)) {
+                // Start the row style again after the cell's, which the reset ends.
+                Some(style) => format!("{}{cell}{RESET}{row_style}", style.code()),
+                None => cell,
+            }
+        })
+        .collect();
+    let line = join(&cells, frozen, " | ", " || ");
+    if row_style.is_empty() {
+        line.trim_end().to_string()
+    } else {
+        format!("{row_style}{line}{RESET}")
+    }
+}
+
+fn format_row(record: &StringRecord, widths: &[usize], frozen: usize) -> String {
+    let cells: Vec<String> = widths
+        .iter()
+        .enumerate()
+        .map(|(i, width)| format!("{:<width$}", record.get(i).unwrap_or("")))
+        .collect();
+    join(&cells, frozen, " | ", " || ").trim_end().to_string()
+}
+
+/// Joins `cells` with `separator`, using `frozen_separator` after the first `frozen` cells.
+fn join(cells: &[String], frozen: usize, separator: &str, frozen_separator: &str) -> String {
+    if frozen == 0 || frozen >= cells.len() {
+        return cells.join(separator);
+    }
+    format!(
+        "{}{frozen_separator}{}",
+        cells[..frozen].join(separator),
+        cells[frozen..].join(separator)
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_table_and_csv_output() {
+        let headers = StringRecord::from(vec!["name", "age"]);
+        let records = vec![
+            StringRecord::from(vec!["alice", "7"]),
+            StringRecord::from(vec!["bob", "42"]),
+        ];
+
+        let mut settings = Settings {
+            output_format: OutputFormat::Table,
+            ..Settings::default()
+        };
+        let mut buffer = Vec::new();
+        write_records(&headers, &records, &settings, &mut buffer).expect("Failed to write table");
+        let output = String::from_utf8(buffer).expect("Not UTF-8");
+        assert_eq!(output, "name  | age\n------+----\nalice | 7\nbob   | 42\n");
+
+        settings.output_format = OutputFormat::Csv;
+        settings.delimiter = b';';
+        let mut buffer = Vec::new();
+        write_records(&headers, &records, &settings, &mut buffer).expect("Failed to write csv");
+        let output = String::from_utf8(buffer).expect("Not UTF-8");
+        assert_eq!(output, "name;age\nalice;7\nbob;42\n");
+    }
+
+    #[test]
+    fn test_color_rules() {
+        let headers = StringRecord::from(vec!["item", "amount"]);
+        let records = vec![
+            StringRecord::from(vec!["tea", "-3"]),
+            StringRecord::from(vec!["ERR", "5"]),
+        ];
+        let mut settings = Settings {
+            output_format: OutputFormat::Table,
+            color_rules: vec![
+                "negative".parse().unwrap(),
+                "/^ERR$/=bold".parse().unwrap(),
+                "stripes=dim".parse().unwrap(),
+            ],
+            ..Settings::default()
+        };
+        let mut buffer = Vec::new();
+        write_records(&headers, &records, &settings, &mut buffer).expect("Failed to write");
+        let plain = String::from_utf8(buffer).expect("Not UTF-8");
+        assert_eq!(plain, "item | amount\n-----+-------\ntea  | -3\nERR  | 5\n");
+
+        settings.color = true;
+        let mut buffer = Vec::new();
+        write_records(&headers, &records, &settings, &mut buffer).expect("Failed to write");
+        let colored = String::from_utf8(buffer).expect("Not UTF-8");
+        assert!(colored.contains("tea  | \x1b[31m-3    \x1b[0m\n"));
+        assert!(colored.contains("\x1b[2m\x1b[1mERR \x1b[0m\x1b[2m | 5     \x1b[0m\n"));
+    }
+
+    #[test]
+    fn test_frozen_columns() {
+        let headers = StringRecord::from(vec!["id", "name", "age"]);
+        let records = vec![StringRecord::from(vec!["1", "alice", "7"])];
+        let settings = Settings {
+            output_format: OutputFormat::Table,
+            ..Settings::default()
+        };
+        let mut buffer = Vec::new();
+        let layout = Layout {
+            frozen: 1,
+            ..Layout::default()
+        };
+        write_layout(&headers, &records, &layout, &settings, &mut buffer).expect("Failed to write");
+        let output = String::from_utf8(buffer).expect("Not UTF-8");
+        assert_eq!(
+            output,
+            "id || name  | age\n---++-------+----\n1  || alice | 7\n"
+        );
+
+        let layout = Layout {
+            frozen: 1,
+            numbers: Some(&[0, 11, 12]),
+            widths: Some(&[2, 8, 3]),
+            first_row: None,
+        };
+        let mut buffer = Vec::new();
+        write_layout(&headers, &records, &layout, &settings, &mut buffer).expect("Failed to write");
+        let output = String::from_utf8(buffer).expect("Not UTF-8");
+        assert_eq!(
+            output,
+            "1  || 12       | 13\nid || name     | age\n---++----------+----\n1  || alice    | 7\n"
+        );
+
+        let layout = Layout {
+            first_row: Some(9),
+            ..Layout::default()
+        };
+        let mut buffer = Vec::new();
+        write_layout(&headers, &records, &layout, &settings, &mut buffer).expect("Failed to write");
+        let output = String::from_utf8(buffer).expect("Not UTF-8");
+        assert_eq!(
+            output,
+            "# || id | name  | age\n--++----+-------+----\n9 || 1  | alice | 7\n"
+        );
+    }
+}
@@ -0,0 +1,299 @@
+//! Machine-readable output, printed by the subcommands in place of their usual text with
+//! `--output json`. Each subcommand prints one JSON document on a single line (`view --watch`
+//! prints one per reload), shaped as documented on the types below. Fields may be added to
+//! them, but are not renamed or removed.
+
+use std::io::{self, Write};
+
+use csv::StringRecord;
+use serde::Serialize;
+
+use crate::{merge, rules, RaggedRow};
+
+/// Writes `report` to `writer` as one line of JSON.
+pub fn write<T: Serialize, W: Write>(report: &T, writer: &mut W) -> io::Result<()> {
+    serde_json::to_writer(&mut *writer, report)?;
+    writeln!(writer)
+}
+
+/// Records, printed by `view`, `head`, `tail` and `sample`:
+/// `{"headers": ["id", "name"], "rows": [["1", "ann"], ["2", "bob"]]}`.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Records {
+    /// Names of the columns; empty when the file has no header.
+    pub headers: Vec<String>,
+    /// Values of each record, in column order.
+    pub rows: Vec<Vec<String>>,
+}
+
+impl Records {
+    pub fn new(headers: &StringRecord, records: &[StringRecord]) -> Records {
+        Records {
+            headers: headers.iter().map(str::to_string).collect(),
+            rows: records
+                .iter()
+                .map(|record| record.iter().map(str::to_string).collect())
+                .collect(),
+        }
+    }
+}
+
+/// Where a row is, printed by `goto` with the records of its page:
+/// `{"row": 12, "line": 13, "page": 2, "headers": [...], "rows": [...]}`.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Location {
+    /// Row number, starting at 1.
+    pub row: usize,
+    /// Line of the file the row starts on, when known.
+    pub line: Option<u64>,
+    /// Page number, starting at 1.
+    pub page: usize,
+    #[serde(flatten)]
+    pub records: Records,
+}
+
+/// Rows whose field count differs from the header, printed by `lint`:
+/// `{"file": "data.csv", "ragged_rows": [{"row": 3, "line": 4, "fields": 2, "expected": 3}]}`.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Lint {
+    pub file: String,
+    pub ragged_rows: Vec<Ragged>,
+}
+
+/// A ragged row in a [`Lint`] report.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Ragged {
+    /// Row number, starting at 1.
+    pub row: usize,
+    /// Line of the file the row starts on, when known.
+    pub line: Option<u64>,
+    /// Number of fields of the row.
+    pub fields: usize,
+    /// Number of fields of the header.
+    pub expected: usize,
+}
+
+impl From<&RaggedRow> for Ragged {
+    fn from(ragged: &RaggedRow) -> Ragged {
+        Ragged {
+            row: ragged.row + 1,
+            line: ragged.line,
+            fields: ragged.fields,
+            expected: ragged.expected,
+        }
+    }
+}
+
+/// Values breaking the rules of `check`:
+/// `{"file": "people.csv", "violations": [{"row": 3, "line": 4, "column": "email",
+/// "value": "ann@example.com", "rule": "unique(email)", "message": "..."}]}`.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Check {
+    pub file: String,
+    pub violations: Vec<Violation>,
+}
+
+/// A value breaking a rule in a [`Check`] report.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Violation {
+    /// Row number, starting at 1.
+    pub row: usize,
+    /// Line of the file the row starts on, when known.
+    pub line: Option<u64>,
+    /// Column, as the rule names it.
+    pub column: String,
+    pub value: String,
+    /// The rule broken, e.g. `range(age, 0, 120)`.
+    pub rule: String,
+    pub message: String,
+}
+
+impl From<&rules::Violation> for Violation {
+    fn from(violation: &rules::Violation) -> Violation {
+        Violation {
+            row: violation.row + 1,
+            line: violation.line,
+            column: violation.column.clone(),
+            value: violation.value.clone(),
+            rule: violation.rule.clone(),
+            message: violation.message.clone(),
+        }
+    }
+}
+
+/// A bookmark, printed in a list by `bookmark list`: `[{"name": "todo", "row": 12}]`.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Bookmark {
+    pub name: String,
+    /// Row number, starting at 1.
+    pub row: usize,
+}
+
+/// A comment on a row or cell, printed in a list by `comment list` and written as a row of
+/// the CSV report of `comment export`:
+/// `[{"row": 4, "line": 5, "column": "price", "value": "9999", "text": "too high"}]`.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Comment {
+    /// Row number, starting at 1.
+    pub row: usize,
+    /// Line of the file the row starts on, when known.
+    pub line: Option<u64>,
+    /// Column of the commented cell, `null` for a comment on the whole row.
+    pub column: Option<String>,
+    /// Value of the commented cell, when the data has it.
+    pub value: Option<String>,
+    pub text: String,
+}
+
+/// A cell edit waiting in the overlay of a file, printed in a list by `overlay list`:
+/// `[{"row": 18204, "column": "price", "value": "12.50"}]`.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct OverlayEdit {
+    /// Row number, starting at 1.
+    pub row: usize,
+    pub column: String,
+    /// The new value of the cell.
+    pub value: String,
+}
+
+/// A row processor, printed in a list by `apply-plugin --list`:
+/// `[{"name": "trim", "description": "..."}]`.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Plugin {
+    pub name: String,
+    pub description: String,
+}
+
+/// Distinct values of a column, printed by `value-counts`:
+/// `{"column": "country", "rows": 6, "values": [{"value": "France", "count": 3, "percent": 50.0}]}`.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ValueCounts {
+    pub column: String,
+    /// Number of rows counted.
+    pub rows: usize,
+    pub values: Vec<ValueCount>,
+}
+
+/// A distinct value in a [`ValueCounts`] report.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ValueCount {
+    pub value: String,
+    /// Number of rows holding the value.
+    pub count: usize,
+    /// Share of the rows holding the value, from 0 to 100.
+    pub percent: f64,
+}
+
+/// What `merge` did, with the conflicts left between markers:
+/// `{"changes": 3, "conflicts": [{"row": 5, "key": "4", "column": "name", "base": "dee",
+/// "ours": "dea", "theirs": "dei"}]}`.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Merged {
+    /// Number of their changes taken: cells set, rows added and rows removed.
+    pub changes: usize,
+    pub conflicts: Vec<MergeConflict>,
+}
+
+/// A conflict in a [`Merged`] report. A side missing the row has no value.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct MergeConflict {
+    /// Row number in the merged file, starting at 1.
+    pub row: usize,
+    /// Values of the key columns, separated by commas.
+    pub key: String,
+    pub column: String,
+    pub base: Option<String>,
+    pub ours: Option<String>,
+    pub theirs: Option<String>,
+}
+
+impl From<&merge::Conflict> for MergeConflict {
+    fn from(conflict: &merge::Conflict) -> MergeConflict {
+        MergeConflict {
+            row: conflict.row + 1,
+            key: conflict.key.clone(),
+            column: conflict.column.clone(),
+            base: conflict.base.clone(),
+            ours: conflict.ours.clone(),
+            theirs: conflict.theirs.clone(),
+        }
+    }
+}
+
+/// A file written by `split`, printed in a list: `[{"file": "sales_Lyon.csv", "rows": 120}]`.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Part {
+    pub file: String,
+    pub rows: usize,
+}
+
+/// What `normalize-dates` did:
+/// `{"columns": [{"column": "sold", "changed": 4}], "unparsed": [{"row": 3, "line": 4,
+/// "column": "sold", "value": "soon"}]}`.
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct NormalizedDates {
+    /// Columns rewritten, in order.
+    pub columns: Vec<NormalizedColumn>,
+    /// Values of those columns that are not dates, left as they are.
+    pub unparsed: Vec<Unparsed>,
+}
+
+/// A column in a [`NormalizedDates`] report.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct NormalizedColumn {
+    pub column: String,
+    /// Number of values rewritten.
+    pub changed: usize,
+}
+
+/// A value that is not a date in a [`NormalizedDates`] report.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Unparsed {
+    /// Row number, starting at 1.
+    pub row: usize,
+    /// Line of the file the row starts on, when known.
+    pub line: Option<u64>,
+    pub column: String,
+    pub value: String,
+}
+
+// `apply-plugin` and `script` print a `crate::plugin::Processed`: `{"changed": 4, "dropped": 1}`,
+// `copy-range` the cells as a list of rows: `[["1", "ann"], ["2", "bob"]]`, and `profile` a
+// `crate::profile::Profile`.
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_write_reports() {
+        let headers = StringRecord::from(vec!["id", "name"]);
+        let records = [StringRecord::from(vec!["1", "ann"])];
+        let location = Location {
+            row: 1,
+            line: Some(2),
+            page: 1,
+            records: Records::new(&headers, &records),
+        };
+        let mut output = Vec::new();
+        write(&location, &mut output).unwrap();
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            "{\"row\":1,\"line\":2,\"page\":1,\"headers\":[\"id\",\"name\"],\"rows\":[[\"1\",\"ann\"]]}\n"
+        );
+
+        let ragged = RaggedRow {
+            row: 2,
+            line: None,
+            fields: 1,
+            expected: 2,
+        };
+        let lint = Lint {
+            file: "data.csv".to_string(),
+            ragged_rows: vec![Ragged::from(&ragged)],
+        };
+        let value = serde_json::to_value(&lint).unwrap();
+        assert_eq!(value["ragged_rows"][0]["row"], 3);
+        assert!(value["ragged_rows"][0]["line"].is_null());
+    }
+}
@@ -0,0 +1,76 @@
+use std::time::Instant;
+
+use bootle_geditor_3000::progress::{Progress, ProgressEvent, Stage};
+use indicatif::{ProgressBar, ProgressStyle};
+
+/// Shows progress bars on stderr and, with `--timing`, prints how long each stage took.
+/// Bars are hidden automatically when stderr is not a terminal, and with `--quiet`.
+pub struct Reporter {
+    timing: bool,
+    quiet: bool,
+    bar: Option<ProgressBar>,
+    started: Instant,
+    total: u64,
+}
+
+impl Reporter {
+    pub fn new(timing: bool, quiet: bool) -> Reporter {
+        Reporter {
+            timing,
+            quiet,
+            bar: None,
+            started: Instant::now(),
+            total: 0,
+        }
+    }
+}
+
+impl Progress for Reporter {
+    fn report(&mut self, event: ProgressEvent) {
+        match event {
+            ProgressEvent::Started { stage, total } => {
+                let template = match stage {
+                    Stage::Read => "{msg:>5} [{bar:40}] {bytes}/{total_bytes} ({eta})",
+                    Stage::Sort | Stage::Write => "{msg:>5} [{bar:40}] {pos}/{len} rows ({eta})",
+                };
+                let bar = match self.quiet {
+                    true => ProgressBar::hidden(),
+                    false => ProgressBar::new(total),
+                }
+                .with_message(stage.to_string());
+                if let Ok(style) = ProgressStyle::with_template(template) {
+                    bar.set_style(style.progress_chars("=> "));
+                }
+                self.bar = Some(bar);
+                self.started = Instant::now();
+                self.total = total;
+            }
+            ProgressEvent::Advanced { done, .. } => {
+                if let Some(bar) = &self.bar {
+                    bar.set_position(done);
+                }
+            }
+            ProgressEvent::Finished { stage, rows } => {
+                if let Some(bar) = self.bar.take() {
+                    bar.finish_and_clear();
+                }
+                if self.timing {
+                    let elapsed = self.started.elapsed().as_secs_f64();
+                    let per_second = |amount: f64| amount / elapsed.max(f64::EPSILON);
+                    let mut line = format!(
+                        "{stage:>5}: {elapsed:.3}s, {rows} rows ({:.0} rows/s)",
+                        per_second(rows as f64)
+                    );
+                    if stage == Stage::Read {
+                        let megabytes = self.total as f64 / 1_000_000.0;
+                        line.push_str(&format!(
+                            ", {megabytes:.2} MB ({:.2} MB/s)",
+                            per_second(megabytes)
+                        ));
+                    }
+                    eprintln!("{line}");
+                }
+            }
+        }
+    }
+}
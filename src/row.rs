@@ -0,0 +1,250 @@
+//! Read-only views of the rows of [`CSVData`], for library code that wants values by
+//! column name, read as their column's type, or whole rows as its own structs:
+//!
+//! ```no_run
+//! # use bootle_geditor_3000::CSVData;
+//! #[derive(serde::Deserialize)]
+//! struct Order {
+//!     id: u32,
+//!     price: Option<f64>,
+//! }
+//!
+//! let mut csv_data = CSVData::read_from_file("orders.csv")?;
+//! csv_data.set_types("auto")?;
+//! for row in csv_data.iter_rows() {
+//!     let order: Order = row.deserialize()?;
+//!     println!("{} {:?} {:?}", order.id, order.price, row.cell_by_name("status"));
+//! }
+//! # Ok::<(), bootle_geditor_3000::EditorError>(())
+//! ```
+
+use std::fmt;
+
+use chrono::NaiveDate;
+use csv::StringRecord;
+use serde::de::DeserializeOwned;
+
+use crate::{
+    is_na,
+    schema::{self, ColumnType, DATE_FORMAT},
+    CSVData, EditorError,
+};
+
+/// A row of the data, with the header and the column types at hand (see
+/// [`CSVData::iter_rows`]).
+#[derive(Clone, Copy)]
+pub struct Row<'a> {
+    csv_data: &'a CSVData,
+    index: usize,
+}
+
+/// A value read as the declared type of its column (see [`CSVData::set_types`]).
+#[derive(Debug, Clone, PartialEq)]
+pub enum Cell<'a> {
+    /// A missing value (see [`is_na`]).
+    Empty,
+    /// A value of a text column, or one that does not match its column's type.
+    Text(&'a str),
+    /// A whole number, read in the column's number locale.
+    Integer(i64),
+    /// A number, read in the column's number locale.
+    Float(f64),
+    /// `true`/`yes` or `false`/`no`, in any case.
+    Boolean(bool),
+    Date(NaiveDate),
+}
+
+impl fmt::Display for Cell<'_> {
+    /// Writes the value as a plain cell: numbers without thousands separators and with a
+    /// `.` for decimals, booleans as `true` or `false`, dates as `YYYY-MM-DD`.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Cell::Empty => Ok(()),
+            Cell::Text(text) => f.write_str(text),
+            Cell::Integer(number) => write!(f, "{number}"),
+            Cell::Float(number) => write!(f, "{number}"),
+            Cell::Boolean(value) => write!(f, "{value}"),
+            Cell::Date(date) => write!(f, "{}", date.format(DATE_FORMAT)),
+        }
+    }
+}
+
+impl<'a> Row<'a> {
+    pub(crate) fn new(csv_data: &'a CSVData, index: usize) -> Row<'a> {
+        Row { csv_data, index }
+    }
+
+    /// Returns the index of the row in the data (0-based).
+    pub fn index(&self) -> usize {
+        self.index
+    }
+
+    /// Returns the line of the file the row starts on, when known.
+    pub fn line(&self) -> Option<u64> {
+        self.csv_data.line(self.index)
+    }
+
+    /// Returns the values of the row as they are stored.
+    pub fn record(&self) -> &'a StringRecord {
+        &self.csv_data.data[self.index]
+    }
+
+    /// Returns the value of the row in column `field` (0-based), as it is stored.
+    pub fn get(&self, field: usize) -> Option<&'a str> {
+        self.record().get(field)
+    }
+
+    /// Returns the value of the row in the column named `name` in the header, as it is
+    /// stored. The first column of that name is used.
+    pub fn get_by_name(&self, name: &str) -> Option<&'a str> {
+        self.get(self.field(name)?)
+    }
+
+    /// Returns the value of the row in column `field` (0-based), read as the column's type.
+    /// Values that do not match it, and every value of untyped data, are text.
+    pub fn cell(&self, field: usize) -> Option<Cell<'a>> {
+        let value = self.get(field)?;
+        if is_na(value) {
+            return Some(Cell::Empty);
+        }
+        let csv_data = self.csv_data;
+        let number = || match csv_data.number_locale(field) {
+            Some(locale) => locale.plain_number(value),
+            None => Some(value.trim().to_string()),
+        };
+        let column_type = csv_data.types.get(field).copied().unwrap_or_default();
+        let cell = match column_type {
+            ColumnType::Text => None,
+            ColumnType::Integer => number()
+                .and_then(|number| number.parse().ok())
+                .map(Cell::Integer),
+            ColumnType::Float => csv_data.parse_number(field, value).map(Cell::Float),
+            ColumnType::Boolean => schema::parse_bool(value.trim()).map(Cell::Boolean),
+            ColumnType::Date => NaiveDate::parse_from_str(value.trim(), DATE_FORMAT)
+                .ok()
+                .map(Cell::Date),
+        };
+        Some(cell.unwrap_or(Cell::Text(value)))
+    }
+
+    /// Same as [`Row::cell`], for the column named `name` in the header.
+    pub fn cell_by_name(&self, name: &str) -> Option<Cell<'a>> {
+        self.cell(self.field(name)?)
+    }
+
+    /// Maps the row into `T` with serde, matching the header to the field names of `T`, or
+    /// the columns to its fields in order when the data has no header. Values are read as
+    /// by [`Row::cell`] first, so missing values deserialize as `None`, numbers in any locale
+    /// as numbers, and `yes`/`no` as booleans in boolean columns.
+    /// Returns an error if a value does not fit its field.
+    pub fn deserialize<T: DeserializeOwned>(&self) -> Result<T, EditorError> {
+        let record: StringRecord = (0..self.record().len())
+            .filter_map(|field| self.cell(field))
+            .map(|cell| cell.to_string())
+            .collect();
+        let headers = &self.csv_data.headers;
+        let has_headers = !headers.is_empty();
+        record
+            .deserialize(has_headers.then_some(headers))
+            .map_err(|source| EditorError::Parse {
+                row: Some((self.index + usize::from(has_headers)) as u64),
+                source,
+            })
+    }
+
+    fn field(&self, name: &str) -> Option<usize> {
+        self.csv_data
+            .headers
+            .iter()
+            .position(|header| header == name)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cells() {
+        let file_name = "test_row_cells.csv";
+        std::fs::write(
+            file_name,
+            "id,price,paid,due,note\n1,\"1.234,5\",yes,2024-02-29,ok\n2, ,no,soon,\n",
+        )
+        .expect("Failed to write");
+        let mut csv_data = CSVData::read_from_file(file_name).expect("Failed to read");
+        std::fs::remove_file(file_name).expect("Failed to remove file");
+        let rows: Vec<Row> = csv_data.iter_rows().collect();
+        assert_eq!(rows[0].cell_by_name("id"), Some(Cell::Text("1")));
+
+        csv_data.set_number_locales("price:de").unwrap();
+        csv_data
+            .set_types("id:int,price:float,paid:bool,due:date")
+            .unwrap();
+        let rows: Vec<Row> = csv_data.iter_rows().collect();
+        assert_eq!(rows.len(), 2);
+        assert_eq!(rows[1].index(), 1);
+        assert_eq!(rows[1].line(), Some(3));
+        assert_eq!(rows[0].get_by_name("price"), Some("1.234,5"));
+        assert_eq!(rows[0].cell(0), Some(Cell::Integer(1)));
+        assert_eq!(rows[0].cell_by_name("price"), Some(Cell::Float(1234.5)));
+        assert_eq!(rows[0].cell(2), Some(Cell::Boolean(true)));
+        assert_eq!(
+            rows[0].cell(3),
+            Some(Cell::Date(NaiveDate::from_ymd_opt(2024, 2, 29).unwrap()))
+        );
+        assert_eq!(rows[1].cell(1), Some(Cell::Empty));
+        assert_eq!(rows[1].cell(3), Some(Cell::Text("soon")));
+        assert_eq!(rows[1].cell(5), None);
+        assert_eq!(rows[1].cell_by_name("total"), None);
+        assert_eq!(Cell::Float(1234.5).to_string(), "1234.5");
+    }
+
+    #[test]
+    fn test_serde() {
+        #[derive(Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+        struct Order {
+            id: u32,
+            price: Option<f64>,
+            paid: bool,
+        }
+
+        let orders = vec![
+            Order {
+                id: 1,
+                price: Some(1234.5),
+                paid: true,
+            },
+            Order {
+                id: 2,
+                price: None,
+                paid: false,
+            },
+        ];
+        let mut csv_data = CSVData::serialize_from("test_serde.csv", &orders).unwrap();
+        assert_eq!(csv_data.headers, vec!["id", "price", "paid"]);
+        assert_eq!(csv_data.data[1], vec!["2", "", "false"]);
+        assert_eq!(csv_data.deserialize_into::<Order>().unwrap(), orders);
+
+        csv_data.data[1] = StringRecord::from(vec!["2", " ", "no"]);
+        csv_data.set_types("paid:bool").unwrap();
+        assert_eq!(csv_data.deserialize_into::<Order>().unwrap(), orders);
+        csv_data.data[0] = StringRecord::from(vec!["one", "", "yes"]);
+        let error = csv_data.deserialize_into::<Order>().unwrap_err();
+        assert!(error.to_string().starts_with("parse error in row 1:"));
+
+        let pairs = CSVData::serialize_from("test_serde.csv", [("a", 1), ("b", 2)]).unwrap();
+        assert!(pairs.headers.is_empty());
+        assert_eq!(pairs.fields, 2);
+        assert_eq!(
+            pairs.deserialize_into::<(String, u8)>().unwrap()[1],
+            ("b".to_string(), 2)
+        );
+        assert_eq!(
+            CSVData::serialize_from("test_serde.csv", Vec::<Order>::new())
+                .unwrap()
+                .fields,
+            0
+        );
+    }
+}
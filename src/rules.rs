@@ -0,0 +1,344 @@
+//! Constraints on the values of columns, beyond their types, checked by `check`. A rules file
+//! has one rule per line, and `#` starts a comment:
+//!
+//! ```text
+//! not_empty(id)
+//! unique(email)
+//! range(age, 0, 120)
+//! matches(zip, '^\d{5}$')
+//! ```
+//!
+//! Columns are named as in `--columns`, by name or 1-based number, and may be quoted.
+
+use std::{collections::HashMap, fmt, str::FromStr};
+
+use regex::Regex;
+
+use crate::{columns, is_na, CSVData, EditorError};
+
+/// A constraint on the values of a column.
+#[derive(Debug, Clone)]
+pub enum Rule {
+    /// Every row has a value in the column.
+    NotEmpty(String),
+    /// No two rows have the same value in the column. Empty values are left out.
+    Unique(String),
+    /// Values are numbers from `min` to `max`, both included, read in the column's number
+    /// locale. Empty values are left out.
+    Range { column: String, min: f64, max: f64 },
+    /// Values match a regular expression, which needs `^` and `$` to match whole values.
+    /// Empty values are left out.
+    Matches { column: String, regex: Regex },
+}
+
+impl Rule {
+    /// Returns the column the rule constrains, as written.
+    pub fn column(&self) -> &str {
+        match self {
+            Rule::NotEmpty(column) | Rule::Unique(column) => column,
+            Rule::Range { column, .. } | Rule::Matches { column, .. } => column,
+        }
+    }
+}
+
+impl PartialEq for Rule {
+    fn eq(&self, other: &Rule) -> bool {
+        self.to_string() == other.to_string()
+    }
+}
+
+impl fmt::Display for Rule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Rule::NotEmpty(column) => write!(f, "not_empty({column})"),
+            Rule::Unique(column) => write!(f, "unique({column})"),
+            Rule::Range { column, min, max } => write!(f, "range({column}, {min}, {max})"),
+            Rule::Matches { column, regex } => {
+                write!(
+                    f,
+                    "matches({column}, '{}')",
+                    regex.as_str().replace('\'', "''")
+                )
+            }
+        }
+    }
+}
+
+impl FromStr for Rule {
+    type Err = EditorError;
+
+    /// Parses a rule written `name(column, arguments...)`.
+    fn from_str(text: &str) -> Result<Rule, EditorError> {
+        let invalid = |message: String| EditorError::InvalidFormat(format!("rule: {message}"));
+        let text = text.trim();
+        let (name, rest) = text
+            .split_once('(')
+            .ok_or_else(|| invalid(format!("expected name(column, ...) in '{text}'")))?;
+        let arguments = rest
+            .trim_end()
+            .strip_suffix(')')
+            .ok_or_else(|| invalid(format!("missing ')' in '{text}'")))?;
+        let arguments =
+            split_arguments(arguments).map_err(|e| invalid(format!("{e} in '{text}'")))?;
+        let number = |value: &str| {
+            value
+                .parse::<f64>()
+                .map_err(|_| invalid(format!("'{value}' is not a number in '{text}'")))
+        };
+        let rule = match (name.trim(), arguments.as_slice()) {
+            ("not_empty", [column]) => Rule::NotEmpty(column.clone()),
+            ("unique", [column]) => Rule::Unique(column.clone()),
+            ("range", [column, min, max]) => Rule::Range {
+                column: column.clone(),
+                min: number(min)?,
+                max: number(max)?,
+            },
+            ("matches", [column, pattern]) => Rule::Matches {
+                column: column.clone(),
+                regex: Regex::new(pattern).map_err(|e| invalid(e.to_string()))?,
+            },
+            ("not_empty" | "unique", _) => {
+                return Err(invalid(format!("{name} takes a column in '{text}'")))
+            }
+            ("range", _) => {
+                return Err(invalid(format!(
+                    "range takes a column, a minimum and a maximum in '{text}'"
+                )))
+            }
+            ("matches", _) => {
+                return Err(invalid(format!(
+                    "matches takes a column and a pattern in '{text}'"
+                )))
+            }
+            (name, _) => return Err(invalid(format!("unknown rule '{name}'"))),
+        };
+        Ok(rule)
+    }
+}
+
+/// Splits the arguments of a rule at the commas that are not quoted. Quotes, `'` or `"`,
+/// are removed, and a doubled quote stands for the quote itself.
+fn split_arguments(text: &str) -> Result<Vec<String>, String> {
+    let mut arguments = Vec::new();
+    let mut argument = String::new();
+    let mut quoted = false;
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '\'' | '"' => {
+                // Spaces before the quotes, and after them, are not part of the argument.
+                if argument.trim().is_empty() {
+                    argument.clear();
+                }
+                quoted = true;
+                loop {
+                    match chars.next() {
+                        Some(q) if q == c && chars.peek() == Some(&c) => {
+                            chars.next();
+                            argument.push(c);
+                        }
+                        Some(q) if q == c => break,
+                        Some(q) => argument.push(q),
+                        None => return Err(format!("unclosed {c}")),
+                    }
+                }
+            }
+            ',' => {
+                arguments.push(finish(&mut argument, quoted));
+                quoted = false;
+            }
+            c if quoted && c.is_whitespace() => {}
+            c => argument.push(c),
+        }
+    }
+    arguments.push(finish(&mut argument, quoted));
+    if arguments.iter().any(String::is_empty) {
+        return Err("empty argument".to_string());
+    }
+    Ok(arguments)
+}
+
+/// Returns the argument read so far, trimmed unless it was quoted, and starts the next one.
+fn finish(argument: &mut String, quoted: bool) -> String {
+    let argument = std::mem::take(argument);
+    match quoted {
+        true => argument,
+        false => argument.trim().to_string(),
+    }
+}
+
+/// Rules read from a rules file, or given one by one.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Rules {
+    pub rules: Vec<Rule>,
+}
+
+impl FromStr for Rules {
+    type Err = EditorError;
+
+    /// Parses a rules file: one rule per line, blank lines and `#` comments ignored.
+    fn from_str(text: &str) -> Result<Rules, EditorError> {
+        let mut rules = Vec::new();
+        for (number, line) in text.lines().enumerate() {
+            let line = match line.trim_start().starts_with('#') {
+                true => "",
+                false => line.trim(),
+            };
+            if line.is_empty() {
+                continue;
+            }
+            let rule = line.parse().map_err(|e| match e {
+                EditorError::InvalidFormat(message) => {
+                    EditorError::InvalidFormat(format!("line {}: {message}", number + 1))
+                }
+                e => e,
+            })?;
+            rules.push(rule);
+        }
+        Ok(Rules { rules })
+    }
+}
+
+impl Rules {
+    /// Reads the rules file `file_name`.
+    pub fn load(file_name: &str) -> Result<Rules, EditorError> {
+        std::fs::read_to_string(file_name)?.parse()
+    }
+
+    /// Returns the values of `csv_data` breaking the rules, by row then in the order of the
+    /// rules. Returns an error if a rule names a column the data does not have.
+    pub fn check(&self, csv_data: &CSVData) -> Result<Vec<Violation>, EditorError> {
+        let mut violations = Vec::new();
+        for rule in &self.rules {
+            let field = columns::parse_column(rule.column(), &csv_data.headers, csv_data.fields)?;
+            let violation = |row: usize, value: &str, message: String| Violation {
+                row,
+                line: csv_data.line(row),
+                field,
+                column: rule.column().to_string(),
+                value: value.to_string(),
+                rule: rule.to_string(),
+                message,
+            };
+            let mut seen = HashMap::new();
+            for (row, record) in csv_data.data.iter().enumerate() {
+                let value = record.get(field).unwrap_or("");
+                let message = match rule {
+                    Rule::NotEmpty(_) if is_na(value) => "is empty".to_string(),
+                    _ if is_na(value) => continue,
+                    Rule::Unique(_) => match seen.get(value) {
+                        Some(first) => format!("'{value}' is already in row {}", first + 1),
+                        None => {
+                            seen.insert(value, row);
+                            continue;
+                        }
+                    },
+                    Rule::Range { min, max, .. } => match csv_data.parse_number(field, value) {
+                        Some(number) if (*min..=*max).contains(&number) => continue,
+                        Some(_) => format!("'{value}' is not between {min} and {max}"),
+                        None => format!("'{value}' is not a number"),
+                    },
+                    Rule::Matches { regex, .. } if !regex.is_match(value) => {
+                        format!("'{value}' does not match '{}'", regex.as_str())
+                    }
+                    Rule::NotEmpty(_) | Rule::Matches { .. } => continue,
+                };
+                violations.push(violation(row, value, message));
+            }
+        }
+        // Sorting is stable, so the violations of a row stay in the order of the rules.
+        violations.sort_by_key(|violation| violation.row);
+        Ok(violations)
+    }
+}
+
+/// A value breaking a rule.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Violation {
+    /// Index of the row in the data (0-based).
+    pub row: usize,
+    /// Line of the file the row starts on, when known.
+    pub line: Option<u64>,
+    /// Index of the column (0-based).
+    pub field: usize,
+    /// Column, as the rule names it.
+    pub column: String,
+    pub value: String,
+    /// The rule broken, as written by [`Rule`]'s `Display`.
+    pub rule: String,
+    pub message: String,
+}
+
+impl fmt::Display for Violation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "row {}", self.row + 1)?;
+        if let Some(line) = self.line {
+            write!(f, " (line {line})")?;
+        }
+        write!(f, ", {}: {} [{}]", self.column, self.message, self.rule)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_rules() {
+        let rules: Rules = "# people\nnot_empty(id)\n\n  unique(\"e-mail\")\nrange(age, 0, 120)\nmatches(zip, '^\\d{5}$')\nmatches(\"name\", 'it''s, ok')"
+            .parse()
+            .unwrap();
+        let written: Vec<String> = rules.rules.iter().map(Rule::to_string).collect();
+        assert_eq!(
+            written,
+            vec![
+                "not_empty(id)",
+                "unique(e-mail)",
+                "range(age, 0, 120)",
+                "matches(zip, '^\\d{5}$')",
+                "matches(name, 'it''s, ok')",
+            ]
+        );
+        let error = |text: &str| text.parse::<Rules>().unwrap_err().to_string();
+        assert_eq!(
+            error("unique(id)\nrange(age, 0)"),
+            "invalid format: line 2: rule: range takes a column, a minimum and a maximum in 'range(age, 0)'"
+        );
+        assert!(error("between(age, 1, 2)").contains("unknown rule 'between'"));
+        assert!(error("matches(zip, '[')").contains("regex parse error"));
+        assert!(error("range(age, 0, old)").contains("'old' is not a number"));
+        assert!(error("unique(id").contains("missing ')'"));
+        assert!(error("unique()").contains("empty argument"));
+    }
+
+    #[test]
+    fn test_check() {
+        let file_name = "test_rules.csv";
+        std::fs::write(
+            file_name,
+            "id,email,age,zip\n1,ann@example.com,34,12345\n,bob@example.com,130,1234\n3,ann@example.com,,12345\n4,,x,\n",
+        )
+        .expect("Failed to write");
+        let csv_data = CSVData::read_from_file(file_name).expect("Failed to read");
+        std::fs::remove_file(file_name).expect("Failed to remove file");
+        let rules: Rules =
+            "not_empty(id)\nunique(email)\nrange(3, 0, 120)\nmatches(zip, '^\\d{5}$')"
+                .parse()
+                .unwrap();
+        let violations = rules.check(&csv_data).unwrap();
+        let lines: Vec<String> = violations.iter().map(Violation::to_string).collect();
+        assert_eq!(
+            lines,
+            vec![
+                "row 2 (line 3), id: is empty [not_empty(id)]",
+                "row 2 (line 3), 3: '130' is not between 0 and 120 [range(3, 0, 120)]",
+                "row 2 (line 3), zip: '1234' does not match '^\\d{5}$' [matches(zip, '^\\d{5}$')]",
+                "row 3 (line 4), email: 'ann@example.com' is already in row 1 [unique(email)]",
+                "row 4 (line 5), 3: 'x' is not a number [range(3, 0, 120)]",
+            ]
+        );
+        assert_eq!(violations[1].field, 2);
+        let unknown: Rules = "unique(phone)".parse().unwrap();
+        assert!(unknown.check(&csv_data).is_err());
+    }
+}
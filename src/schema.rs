@@ -0,0 +1,419 @@
+use std::fmt;
+
+use chrono::NaiveDate;
+use clap::ValueEnum;
+use csv::StringRecord;
+use rayon::prelude::*;
+use serde::Deserialize;
+
+use crate::{columns, EditorError};
+
+/// Format in which dates are stored in the data.
+pub(crate) const DATE_FORMAT: &str = "%Y-%m-%d";
+
+/// The kind of values a column holds.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ColumnType {
+    #[default]
+    Text,
+    Integer,
+    Float,
+    Boolean,
+    /// A calendar date written as `YYYY-MM-DD`
+    Date,
+}
+
+impl ColumnType {
+    /// Parses a type name as used in `--types`, e.g. `int` or `date`.
+    pub fn from_name(name: &str) -> Option<ColumnType> {
+        match name.to_ascii_lowercase().as_str() {
+            "text" | "str" | "string" => Some(ColumnType::Text),
+            "int" | "integer" => Some(ColumnType::Integer),
+            "float" | "number" | "decimal" => Some(ColumnType::Float),
+            "bool" | "boolean" => Some(ColumnType::Boolean),
+            "date" => Some(ColumnType::Date),
+            _ => None,
+        }
+    }
+
+    /// Returns whether `value` is valid for this type. Empty values are always accepted.
+    pub fn accepts(self, value: &str) -> bool {
+        self.accepts_in(value, None)
+    }
+
+    /// Same as [`ColumnType::accepts`], reading numbers written with the separators of
+    /// `locale` when given.
+    pub fn accepts_in(self, value: &str, locale: Option<Locale>) -> bool {
+        let value = value.trim();
+        if value.is_empty() {
+            return true;
+        }
+        let number = || match locale {
+            Some(locale) => locale.plain_number(value),
+            None => Some(value.to_string()),
+        };
+        match self {
+            ColumnType::Text => true,
+            ColumnType::Integer => number().is_some_and(|number| number.parse::<i64>().is_ok()),
+            ColumnType::Float => number()
+                .and_then(|number| number.parse::<f64>().ok())
+                .is_some_and(f64::is_finite),
+            ColumnType::Boolean => parse_bool(value).is_some(),
+            ColumnType::Date => NaiveDate::parse_from_str(value, DATE_FORMAT).is_ok(),
+        }
+    }
+}
+
+impl fmt::Display for ColumnType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            ColumnType::Text => "text",
+            ColumnType::Integer => "integer",
+            ColumnType::Float => "float",
+            ColumnType::Boolean => "boolean",
+            ColumnType::Date => "date",
+        };
+        f.write_str(name)
+    }
+}
+
+pub(crate) fn parse_bool(value: &str) -> Option<bool> {
+    match value.to_ascii_lowercase().as_str() {
+        "true" | "yes" => Some(true),
+        "false" | "no" => Some(false),
+        _ => None,
+    }
+}
+
+/// Resolves a type spec such as `age:int,price:float,2-3:date` into one type per column.
+/// Columns are named as in `--columns`; unmentioned columns are text.
+/// `auto` infers every column's type from the data instead, reading numbers in the
+/// `locales` of their columns (see [`parse_locales`]).
+pub fn parse_types(
+    spec: &str,
+    headers: &StringRecord,
+    data: &[StringRecord],
+    fields: usize,
+    locales: &[Option<Locale>],
+) -> Result<Vec<ColumnType>, EditorError> {
+    if spec.trim() == "auto" {
+        return Ok(infer_types(data, fields, locales));
+    }
+    let mut types = vec![ColumnType::Text; fields];
+    for item in spec.split(',') {
+        let (column, name) = item.rsplit_once(':').ok_or_else(|| {
+            EditorError::InvalidColumns(format!("expected column:type, got '{item}'"))
+        })?;
+        let column_type = ColumnType::from_name(name.trim()).ok_or_else(|| {
+            EditorError::InvalidColumns(format!("unknown type '{name}' for column '{column}'"))
+        })?;
+        for index in columns::parse_columns(column, headers, fields)? {
+            types[index] = column_type;
+        }
+    }
+    Ok(types)
+}
+
+/// Guesses each column's type: the narrowest type accepting all of its non-empty values,
+/// numbers being read in the locale of their column in `locales`, if any.
+/// Columns with no values at all are text.
+pub fn infer_types(
+    data: &[StringRecord],
+    fields: usize,
+    locales: &[Option<Locale>],
+) -> Vec<ColumnType> {
+    const CANDIDATES: [ColumnType; 4] = [
+        ColumnType::Integer,
+        ColumnType::Float,
+        ColumnType::Boolean,
+        ColumnType::Date,
+    ];
+    (0..fields)
+        .into_par_iter()
+        .map(|field| {
+            let mut values = data
+                .iter()
+                .filter_map(|record| record.get(field))
+                .filter(|value| !value.trim().is_empty())
+                .peekable();
+            if values.peek().is_none() {
+                return ColumnType::Text;
+            }
+            let values: Vec<&str> = values.collect();
+            let locale = locales.get(field).copied().flatten();
+            CANDIDATES
+                .into_iter()
+                .find(|candidate| {
+                    values
+                        .iter()
+                        .all(|value| candidate.accepts_in(value, locale))
+                })
+                .unwrap_or(ColumnType::Text)
+        })
+        .collect()
+}
+
+/// Resolves a spec such as `price:de,qty:en` into the locale numbers of each column are
+/// written in. Columns are named as in `--columns`; unmentioned columns hold plain numbers,
+/// like `1234.5`. A locale alone, such as `de`, applies to every column.
+pub fn parse_locales(
+    spec: &str,
+    headers: &StringRecord,
+    fields: usize,
+) -> Result<Vec<Option<Locale>>, EditorError> {
+    let locale = |name: &str, column: &str| {
+        Locale::from_str(name.trim(), true).map_err(|_| {
+            EditorError::InvalidColumns(format!("unknown locale '{name}' for column '{column}'"))
+        })
+    };
+    if !spec.contains(':') {
+        return Ok(vec![Some(locale(spec, "*")?); fields]);
+    }
+    let mut locales = vec![None; fields];
+    for item in spec.split(',') {
+        let (column, name) = item.rsplit_once(':').ok_or_else(|| {
+            EditorError::InvalidColumns(format!("expected column:locale, got '{item}'"))
+        })?;
+        let locale = locale(name, column)?;
+        for index in columns::parse_columns(column, headers, fields)? {
+            locales[index] = Some(locale);
+        }
+    }
+    Ok(locales)
+}
+
+/// Conventions for showing numbers and dates.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, ValueEnum)]
+#[serde(rename_all = "kebab-case")]
+pub enum Locale {
+    /// 1,234.56 and 12/31/2024
+    En,
+    /// 1.234,56 and 31.12.2024
+    De,
+    /// 1 234,56 and 31/12/2024
+    Fr,
+    /// 1234.56 and 2024-12-31
+    Iso,
+}
+
+impl Locale {
+    /// Returns `value`, a number written with the separators of the locale, as a plain
+    /// number such as `-1234.5`, or `None` if it is not one. Thousands separators are
+    /// optional, but must group the digits by three.
+    pub fn plain_number(self, value: &str) -> Option<String> {
+        // French text often separates thousands with a (narrow) no-break space.
+        let value = match self {
+            Locale::Fr => value.trim().replace(['\u{a0}', '\u{202f}'], " "),
+            _ => value.trim().to_string(),
+        };
+        let (integer, fraction) = match value.split_once(self.decimal_separator()) {
+            Some((integer, fraction)) => (integer, Some(fraction)),
+            None => (value.as_str(), None),
+        };
+        let (sign, digits) = match integer.strip_prefix(['-', '+']) {
+            Some(digits) => (&integer[..1], digits),
+            None => ("", integer),
+        };
+        let mut plain = sign.to_string();
+        match self.thousands_separator() {
+            Some(separator) if digits.contains(separator) => {
+                let mut groups = digits.split(separator);
+                let first = groups.next().unwrap_or("");
+                let grouped = (1..=3).contains(&first.len())
+                    && groups.all(|group| group.len() == 3)
+                    && digits.chars().all(|c| c == separator || c.is_ascii_digit());
+                if !grouped {
+                    return None;
+                }
+                plain.extend(digits.split(separator));
+            }
+            _ => plain.push_str(digits),
+        }
+        if let Some(fraction) = fraction {
+            plain.push('.');
+            plain.push_str(fraction);
+        }
+        plain
+            .parse::<f64>()
+            .is_ok_and(f64::is_finite)
+            .then_some(plain)
+    }
+
+    /// Reads `value`, a number written with the separators of the locale.
+    pub fn parse_number(self, value: &str) -> Option<f64> {
+        self.plain_number(value)?.parse().ok()
+    }
+
+    fn thousands_separator(self) -> Option<char> {
+        match self {
+            Locale::En => Some(','),
+            Locale::De => Some('.'),
+            Locale::Fr => Some(' '),
+            Locale::Iso => None,
+        }
+    }
+
+    fn decimal_separator(self) -> char {
+        match self {
+            Locale::En | Locale::Iso => '.',
+            Locale::De | Locale::Fr => ',',
+        }
+    }
+
+    fn date_format(self) -> &'static str {
+        match self {
+            Locale::En => "%m/%d/%Y",
+            Locale::De => "%d.%m.%Y",
+            Locale::Fr => "%d/%m/%Y",
+            Locale::Iso => DATE_FORMAT,
+        }
+    }
+}
+
+/// Formats `value` for display according to its column type.
+/// Numbers get the locale's separators and, for floats, `decimals` fixed decimals;
+/// dates are rewritten in the locale's order. Values that do not parse are left as they are.
+pub fn format_value(
+    value: &str,
+    column_type: ColumnType,
+    locale: Option<Locale>,
+    decimals: Option<usize>,
+) -> String {
+    let locale = locale.unwrap_or(Locale::Iso);
+    let trimmed = value.trim();
+    match column_type {
+        ColumnType::Integer if trimmed.parse::<i64>().is_ok() => {
+            group_number(trimmed, None, locale)
+        }
+        ColumnType::Float => match trimmed.parse::<f64>() {
+            Ok(number) if number.is_finite() => {
+                let text = match decimals {
+                    Some(decimals) => format!("{number:.decimals$}"),
+                    None => trimmed.to_string(),
+                };
+                let (integer, fraction) = match text.split_once('.') {
+                    Some((integer, fraction)) => (integer, Some(fraction)),
+                    None => (text.as_str(), None),
+                };
+                group_number(integer, fraction, locale)
+            }
+            _ => value.to_string(),
+        },
+        ColumnType::Date => match NaiveDate::parse_from_str(trimmed, DATE_FORMAT) {
+            Ok(date) => date.format(locale.date_format()).to_string(),
+            Err(_) => value.to_string(),
+        },
+        _ => value.to_string(),
+    }
+}
+
+/// Inserts thousands separators into `integer` and appends `fraction` with the locale's decimal separator.
+fn group_number(integer: &str, fraction: Option<&str>, locale: Locale) -> String {
+    let (sign, digits) = match integer.strip_prefix(['-', '+']) {
+        Some(digits) => (&integer[..1], digits),
+        None => ("", integer),
+    };
+    let mut grouped = String::with_capacity(integer.len() + digits.len() / 3 + 8);
+    grouped.push_str(sign);
+    for (i, digit) in digits.chars().enumerate() {
+        if i > 0 && (digits.len() - i) % 3 == 0 {
+            if let Some(separator) = locale.thousands_separator() {
+                grouped.push(separator);
+            }
+        }
+        grouped.push(digit);
+    }
+    if let Some(fraction) = fraction {
+        grouped.push(locale.decimal_separator());
+        grouped.push_str(fraction);
+    }
+    grouped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_and_infer_types() {
+        let headers = StringRecord::from(vec!["name", "age", "price", "born", "member"]);
+        let data = vec![
+            StringRecord::from(vec!["ann", "34", "9.5", "1990-01-02", "yes"]),
+            StringRecord::from(vec!["bob", "", "12", "1985-12-31", "no"]),
+        ];
+        assert_eq!(
+            infer_types(&data, 5, &[]),
+            vec![
+                ColumnType::Text,
+                ColumnType::Integer,
+                ColumnType::Float,
+                ColumnType::Date,
+                ColumnType::Boolean
+            ]
+        );
+        let types = parse_types("age:int,3:float", &headers, &data, 5, &[]).unwrap();
+        assert_eq!(types[1], ColumnType::Integer);
+        assert_eq!(types[2], ColumnType::Float);
+        assert_eq!(types[3], ColumnType::Text);
+        assert!(parse_types("age:color", &headers, &data, 5, &[]).is_err());
+        assert!(parse_types("age", &headers, &data, 5, &[]).is_err());
+    }
+
+    #[test]
+    fn test_number_locales() {
+        let de = Locale::De;
+        assert_eq!(de.plain_number("1.234,56").as_deref(), Some("1234.56"));
+        assert_eq!(de.plain_number("-1234,5").as_deref(), Some("-1234.5"));
+        assert_eq!(de.plain_number("1.234.567").as_deref(), Some("1234567"));
+        assert_eq!(de.plain_number("1,234.56"), None);
+        assert_eq!(de.plain_number("12.34"), None);
+        assert_eq!(Locale::En.parse_number("1,234.5"), Some(1234.5));
+        assert_eq!(Locale::En.parse_number("1,5"), None);
+        assert_eq!(Locale::Fr.parse_number("1\u{202f}234,5"), Some(1234.5));
+        assert_eq!(Locale::Iso.parse_number("1,234"), None);
+
+        let headers = StringRecord::from(vec!["price", "qty"]);
+        let data = vec![
+            StringRecord::from(vec!["1.234,50", "1,000"]),
+            StringRecord::from(vec!["3", "12"]),
+        ];
+        let locales = parse_locales("price:de,qty:en", &headers, 2).unwrap();
+        assert_eq!(locales, vec![Some(Locale::De), Some(Locale::En)]);
+        assert_eq!(
+            infer_types(&data, 2, &locales),
+            vec![ColumnType::Float, ColumnType::Integer]
+        );
+        assert_eq!(
+            infer_types(&data, 2, &[]),
+            vec![ColumnType::Text, ColumnType::Text]
+        );
+        assert_eq!(
+            parse_locales("fr", &headers, 2).unwrap(),
+            vec![Some(Locale::Fr); 2]
+        );
+        assert!(parse_locales("price:xx", &headers, 2).is_err());
+    }
+
+    #[test]
+    fn test_format_value() {
+        let de = Some(Locale::De);
+        assert_eq!(
+            format_value("1234567", ColumnType::Integer, de, None),
+            "1.234.567"
+        );
+        assert_eq!(
+            format_value("-1234.5", ColumnType::Float, de, Some(2)),
+            "-1.234,50"
+        );
+        assert_eq!(
+            format_value("1234.5", ColumnType::Float, Some(Locale::En), None),
+            "1,234.5"
+        );
+        assert_eq!(
+            format_value("2024-12-31", ColumnType::Date, Some(Locale::Fr), None),
+            "31/12/2024"
+        );
+        assert_eq!(format_value("n/a", ColumnType::Integer, de, None), "n/a");
+        assert_eq!(format_value("1234", ColumnType::Text, de, None), "1234");
+    }
+}
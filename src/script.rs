@@ -0,0 +1,143 @@
+use std::path::Path;
+
+use csv::StringRecord;
+use rhai::{Dynamic, Engine, Map, Scope, AST};
+
+use crate::{
+    plugin::{RowProcessor, Verdict},
+    EditorError,
+};
+
+/// A [rhai](https://rhai.rs) script run on every row, as a [`RowProcessor`].
+///
+/// The script gets the row as a map named `row`, from column name (or 1-based number
+/// when the data has no header) to text, and its 1-based number as `row_number`.
+/// It either changes `row` in place or returns a map of the values to change.
+/// Returning `false` drops the row; throwing an error rejects it.
+pub struct Script {
+    name: String,
+    engine: Engine,
+    ast: AST,
+    /// Names of the columns, as keys of the row map.
+    columns: Vec<String>,
+}
+
+impl Script {
+    /// Compiles `source`; `name` identifies the script in errors.
+    /// Returns an error if the script does not parse.
+    pub fn compile(name: &str, source: &str) -> Result<Script, EditorError> {
+        let engine = Engine::new();
+        let ast = engine.compile(source).map_err(|e| EditorError::Plugin {
+            name: name.to_string(),
+            row: None,
+            message: e.to_string(),
+        })?;
+        Ok(Script {
+            name: name.to_string(),
+            engine,
+            ast,
+            columns: Vec::new(),
+        })
+    }
+
+    /// Reads and compiles the script in `path`.
+    /// Returns an error if the file cannot be read or the script does not parse.
+    pub fn read_from_file(path: &Path) -> Result<Script, EditorError> {
+        let source = std::fs::read_to_string(path)?;
+        Script::compile(&path.display().to_string(), &source)
+    }
+
+    /// Returns the name identifying the script, e.g. the path it was read from.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+}
+
+impl RowProcessor for Script {
+    fn begin(&mut self, headers: &StringRecord) -> Result<(), String> {
+        self.columns = headers.iter().map(str::to_string).collect();
+        Ok(())
+    }
+
+    fn process(&mut self, row: usize, values: &mut [String]) -> Result<Verdict, String> {
+        if self.columns.len() < values.len() {
+            self.columns = (1..=values.len()).map(|i| i.to_string()).collect();
+        }
+        let map: Map = self
+            .columns
+            .iter()
+            .zip(values.iter())
+            .map(|(name, value)| (name.into(), Dynamic::from(value.clone())))
+            .collect();
+        let mut scope = Scope::new();
+        scope.push("row", map);
+        scope.push("row_number", row as i64 + 1);
+        let result: Dynamic = self
+            .engine
+            .eval_ast_with_scope(&mut scope, &self.ast)
+            .map_err(|e| e.to_string())?;
+        let changes = if result.is_unit() {
+            scope
+                .get_value::<Map>("row")
+                .ok_or("`row` is no longer a map")?
+        } else if result.as_bool() == Ok(false) {
+            return Ok(Verdict::Drop);
+        } else {
+            result.try_cast::<Map>().ok_or(
+                "the script must return the row, a map of values to change, or false to drop it",
+            )?
+        };
+        for (name, value) in changes {
+            let field = self
+                .columns
+                .iter()
+                .position(|column| column == name.as_str())
+                .ok_or_else(|| format!("no column named '{name}'"))?;
+            if let Some(old) = values.get_mut(field) {
+                *old = value.to_string();
+            }
+        }
+        Ok(Verdict::Keep)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::CSVData;
+
+    #[test]
+    fn test_script() {
+        let file_name = "test_script.csv";
+        std::fs::write(file_name, "name,price\n ann ,2\nbob,x\nsample,1\n")
+            .expect("Failed to write");
+        let mut csv_data = CSVData::read_from_file(file_name).expect("Failed to read");
+        std::fs::remove_file(file_name).expect("Failed to remove file");
+
+        let mut script = Script::compile(
+            "clean",
+            r#"
+            if row.name == "sample" { return false; }
+            row.name.trim();
+            row.name.make_upper();
+            if row.price == "x" { return; }
+            #{ name: row.name, price: row.price.parse_float() * 1.5 }
+            "#,
+        )
+        .unwrap();
+        let processed = csv_data.process_rows("clean", &mut script).unwrap();
+        assert_eq!(processed.dropped, 1);
+        assert_eq!(
+            csv_data.data,
+            vec![
+                StringRecord::from(vec!["ANN", "3.0"]),
+                StringRecord::from(vec!["BOB", "x"]),
+            ]
+        );
+
+        let mut script = Script::compile("bad", "row.nope = 1; row").unwrap();
+        let error = csv_data.process_rows("bad", &mut script).unwrap_err();
+        assert!(error.to_string().contains("no column named 'nope'"));
+        assert!(Script::compile("broken", "row.name = (").is_err());
+    }
+}
@@ -0,0 +1,185 @@
+use rayon::prelude::*;
+use regex::{Regex, RegexBuilder};
+
+use crate::{
+    color::{ColorRule, Style},
+    CSVData, EditorError,
+};
+
+/// Points for each character of the query found in the text.
+const MATCHED: i64 = 16;
+/// Extra points for a character right after the previous one.
+const CONSECUTIVE: i64 = 8;
+/// Extra points for a character starting the text or a word in it.
+const WORD_START: i64 = 10;
+/// Points lost for each character skipped between two matched ones.
+const GAP: i64 = 1;
+
+/// Scores how well `query` matches `text`, the way fuzzy finders like skim do: the characters
+/// of the query must appear in the text in order, ignoring case, and score more when they follow
+/// each other or start words. Returns `None` when they do not all appear.
+pub fn fuzzy_score(query: &str, text: &str) -> Option<i64> {
+    let query: Vec<char> = query
+        .chars()
+        .filter(|c| !c.is_whitespace())
+        .flat_map(char::to_lowercase)
+        .collect();
+    let text: Vec<char> = text.chars().flat_map(char::to_lowercase).collect();
+    let first = *query.first()?;
+    // Try every place the query could start, keeping the best greedy match.
+    (0..text.len())
+        .filter(|&start| text[start] == first)
+        .filter_map(|start| {
+            let mut score = 0;
+            let mut position = start;
+            let mut previous: Option<usize> = None;
+            for &c in &query {
+                position += text[position..].iter().position(|&t| t == c)?;
+                score += MATCHED;
+                if position == 0 || !text[position - 1].is_alphanumeric() {
+                    score += WORD_START;
+                }
+                match previous {
+                    Some(previous) if position == previous + 1 => score += CONSECUTIVE,
+                    Some(previous) => score -= GAP * (position - previous - 1) as i64,
+                    None => {}
+                }
+                previous = Some(position);
+                position += 1;
+            }
+            Some(score)
+        })
+        .max()
+}
+
+/// A row found by a [`Search`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Match {
+    /// Index of the row (0-based).
+    pub row: usize,
+    /// Index of the best matching cell (0-based).
+    pub field: usize,
+    /// How well the cell matches; higher is better, and 0 for regular expressions.
+    pub score: i64,
+}
+
+/// A search over the cells of the data, fuzzy or by regular expression.
+#[derive(Debug, Clone)]
+pub enum Search {
+    /// Cells holding the characters of the text in order, ignoring case (see [`fuzzy_score`]).
+    Fuzzy(String),
+    /// Cells matching a regular expression.
+    Regex(Regex),
+}
+
+impl Search {
+    /// Returns a fuzzy search for `text`.
+    /// Returns an error if it has nothing but whitespace.
+    pub fn fuzzy(text: &str) -> Result<Search, EditorError> {
+        if text.trim().is_empty() {
+            return Err(EditorError::InvalidFormat("empty search".to_string()));
+        }
+        Ok(Search::Fuzzy(text.trim().to_string()))
+    }
+
+    /// Returns a search for cells matching `pattern`.
+    /// Returns an error if it is not a valid regular expression.
+    pub fn regex(pattern: &str) -> Result<Search, EditorError> {
+        Regex::new(pattern)
+            .map(Search::Regex)
+            .map_err(|e| EditorError::InvalidFormat(format!("search '{pattern}': {e}")))
+    }
+
+    /// Returns the rows with a matching cell, searched on every core: the best matches first
+    /// for a fuzzy search, in file order for a regular expression.
+    pub fn find(&self, csv_data: &CSVData) -> Vec<Match> {
+        let mut matches: Vec<Match> = csv_data
+            .data
+            .par_iter()
+            .enumerate()
+            .filter_map(|(row, record)| {
+                record
+                    .iter()
+                    .enumerate()
+                    .filter_map(|(field, value)| {
+                        let score = match self {
+                            Search::Fuzzy(text) => fuzzy_score(text, value)?,
+                            Search::Regex(pattern) => pattern.is_match(value).then_some(0)?,
+                        };
+                        Some(Match { row, field, score })
+                    })
+                    // The first of the best cells.
+                    .max_by_key(|found| (found.score, std::cmp::Reverse(found.field)))
+            })
+            .collect();
+        matches.sort_by_key(|found| (std::cmp::Reverse(found.score), found.row));
+        matches
+    }
+
+    /// Returns a rule highlighting the matching cells in table output.
+    pub fn highlight(&self) -> ColorRule {
+        let pattern = match self {
+            Search::Regex(pattern) => pattern.clone(),
+            Search::Fuzzy(text) => {
+                let characters: Vec<String> = text
+                    .chars()
+                    .filter(|c| !c.is_whitespace())
+                    .map(|c| regex::escape(&c.to_string()))
+                    .collect();
+                RegexBuilder::new(&characters.join(".*"))
+                    .case_insensitive(true)
+                    .build()
+                    .expect("escaped characters form a valid pattern")
+            }
+        };
+        ColorRule::Match {
+            pattern,
+            style: Style::Reverse,
+        }
+    }
+}
+
+impl PartialEq for Search {
+    fn eq(&self, other: &Search) -> bool {
+        match (self, other) {
+            (Search::Fuzzy(text), Search::Fuzzy(other)) => text == other,
+            (Search::Regex(pattern), Search::Regex(other)) => pattern.as_str() == other.as_str(),
+            _ => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fuzzy_score() {
+        let exact = fuzzy_score("nguyen", "Nguyen Van An").unwrap();
+        let spread = fuzzy_score("nguyen", "Nancy Gu Yen").unwrap();
+        assert!(exact > spread);
+        assert!(fuzzy_score("jhn smth", "John Smith").is_some());
+        assert_eq!(fuzzy_score("xyz", "John Smith"), None);
+        assert_eq!(fuzzy_score("", "John Smith"), None);
+    }
+
+    #[test]
+    fn test_find() {
+        let csv_data = CSVData::read_from_file("testdata.csv").expect("Failed to read CSV");
+        let first = csv_data.data[0].get(1).unwrap().to_string();
+        let matches = Search::fuzzy(&first).unwrap().find(&csv_data);
+        assert_eq!(matches[0].row, 0);
+        assert!(matches
+            .windows(2)
+            .all(|pair| pair[0].score >= pair[1].score));
+
+        let matches = Search::regex("^p").unwrap().find(&csv_data);
+        assert!(matches.windows(2).all(|pair| pair[0].row < pair[1].row));
+        assert!(Search::regex("(").is_err());
+        assert!(Search::fuzzy(" ").is_err());
+        assert!(matches!(
+            Search::fuzzy("jhn").unwrap().highlight(),
+            ColorRule::Match { pattern, .. } if pattern.is_match("JOHN")
+        ));
+    }
+}
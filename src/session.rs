@@ -0,0 +1,138 @@
+//! Snapshots of interactive sessions. `interactive --session FILE` keeps one up to date after
+//! every command, and `--resume FILE` opens the same files in the same state, unsaved
+//! changes included.
+
+use std::{collections::BTreeMap, path::Path};
+
+use serde::{Deserialize, Serialize};
+
+use crate::EditorError;
+
+/// State of an interactive session, written as JSON. Rows and pages are 1-based.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Snapshot {
+    /// `--columns` the files were opened with.
+    #[serde(default)]
+    pub columns: Option<String>,
+    /// `--filter` the files were opened with.
+    #[serde(default)]
+    pub filter: Option<String>,
+    /// Index (0-based) of the file shown.
+    #[serde(default)]
+    pub current: usize,
+    /// Number of columns kept in view when scrolling.
+    #[serde(default)]
+    pub freeze_cols: usize,
+    /// Cells copied or cut with `y` and `x`, one list per row.
+    #[serde(default)]
+    pub clipboard: Vec<Vec<String>>,
+    pub files: Vec<FileState>,
+}
+
+/// State of one file open in a [`Snapshot`].
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct FileState {
+    pub file: String,
+    /// Page shown.
+    #[serde(default = "first")]
+    pub page: usize,
+    /// Number of columns after the frozen ones scrolled out of view.
+    #[serde(default)]
+    pub scroll: usize,
+    #[serde(default)]
+    pub search: Option<SavedSearch>,
+    /// Row of each bookmark.
+    #[serde(default)]
+    pub bookmarks: BTreeMap<String, usize>,
+    /// The data with the changes not saved to the file yet, if any.
+    #[serde(default)]
+    pub unsaved: Option<Unsaved>,
+}
+
+/// The last search of a [`FileState`].
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct SavedSearch {
+    /// Text of a fuzzy search, or the regular expression when `regex` is set.
+    pub pattern: String,
+    #[serde(default)]
+    pub regex: bool,
+    /// Match shown.
+    #[serde(default = "first")]
+    pub current: usize,
+}
+
+/// Data of a [`FileState`] edited since it was last saved.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Unsaved {
+    pub headers: Vec<String>,
+    pub rows: Vec<Vec<String>>,
+}
+
+fn first() -> usize {
+    1
+}
+
+impl Snapshot {
+    /// Reads a snapshot written by [`Snapshot::save`].
+    pub fn load(path: &Path) -> Result<Snapshot, EditorError> {
+        let contents = std::fs::read_to_string(path)?;
+        let snapshot: Snapshot = serde_json::from_str(&contents)
+            .map_err(|e| EditorError::Session(format!("{}: {e}", path.display())))?;
+        if snapshot.files.is_empty() {
+            return Err(EditorError::Session(format!(
+                "{}: no files to open",
+                path.display()
+            )));
+        }
+        Ok(snapshot)
+    }
+
+    /// Writes the snapshot to `path`. It is written next to it first and then renamed,
+    /// so that a session killed while writing leaves the previous snapshot whole.
+    pub fn save(&self, path: &Path) -> Result<(), EditorError> {
+        let contents =
+            serde_json::to_string(self).map_err(|e| EditorError::Session(e.to_string()))?;
+        let mut partial = path.as_os_str().to_owned();
+        partial.push(".part");
+        std::fs::write(&partial, contents)?;
+        std::fs::rename(&partial, path)?;
+        log::debug!(file:? = path, files = self.files.len(); "saved session");
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_save_and_load() {
+        let dir = std::env::temp_dir().join(format!("session-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("session.json");
+        let snapshot = Snapshot {
+            columns: Some("name,age".to_string()),
+            current: 0,
+            files: vec![FileState {
+                file: "people.csv".to_string(),
+                page: 3,
+                bookmarks: BTreeMap::from([("todo".to_string(), 12)]),
+                unsaved: Some(Unsaved {
+                    headers: vec!["name".to_string()],
+                    rows: vec![vec!["ann".to_string()]],
+                }),
+                ..FileState::default()
+            }],
+            ..Snapshot::default()
+        };
+        snapshot.save(&path).unwrap();
+        assert_eq!(Snapshot::load(&path).unwrap(), snapshot);
+
+        // Missing fields take their defaults.
+        std::fs::write(&path, r#"{"files": [{"file": "people.csv"}]}"#).unwrap();
+        assert_eq!(Snapshot::load(&path).unwrap().files[0].page, 1);
+        std::fs::write(&path, r#"{"files": []}"#).unwrap();
+        assert!(Snapshot::load(&path).is_err());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,323 @@
+//! Writing data as a table of an SQLite database, and reading a table back, so that other
+//! tools can query edited files (`convert --to sqlite`, `convert --from sqlite`). Columns
+//! keep their types (see [`CSVData::set_types`]) as declared SQL types, which reading the
+//! table turns back into column types. Needs the `sqlite` feature, on by default.
+
+use clap::ValueEnum;
+
+use crate::{schema::ColumnType, CSVData, EditorError};
+
+/// Formats `convert` reads and writes.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum Format {
+    #[default]
+    Csv,
+    /// A table of an SQLite database
+    Sqlite,
+}
+
+/// What writing a table does when the database already has one of that name.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum IfExists {
+    /// Fail, leaving the table as it is
+    #[default]
+    Fail,
+    /// Drop the table and write a new one
+    Replace,
+    /// Add the rows to the table, whose columns must have the same names
+    Append,
+}
+
+/// Returns the SQL type columns of `column_type` are declared with. Booleans are stored as
+/// 0 and 1, and dates as `YYYY-MM-DD` text.
+pub fn sql_type(column_type: ColumnType) -> &'static str {
+    match column_type {
+        ColumnType::Text => "TEXT",
+        ColumnType::Integer => "INTEGER",
+        ColumnType::Float => "REAL",
+        ColumnType::Boolean => "BOOLEAN",
+        ColumnType::Date => "DATE",
+    }
+}
+
+/// Returns the column type of columns declared with `sql_type`, following SQLite's rules
+/// for type names, e.g. `VARCHAR(20)` is text and `BIGINT` an integer.
+pub fn column_type(sql_type: &str) -> ColumnType {
+    let name = sql_type.to_ascii_uppercase();
+    if name.contains("INT") {
+        ColumnType::Integer
+    } else if ["REAL", "FLOA", "DOUB"]
+        .iter()
+        .any(|part| name.contains(part))
+    {
+        ColumnType::Float
+    } else if name.starts_with("BOOL") {
+        ColumnType::Boolean
+    } else if name == "DATE" {
+        ColumnType::Date
+    } else {
+        ColumnType::Text
+    }
+}
+
+/// Writes the rows of `csv_data` to `table` of the database at `path`, created if needed,
+/// and returns how many were written. Columns are named after the header (see
+/// [`crate::columns::column_name`]) and values stored as their column's type, missing
+/// values as `NULL`; untyped data is all text. Nothing is written if a row fails.
+#[cfg(feature = "sqlite")]
+pub fn export(
+    csv_data: &CSVData,
+    path: &str,
+    table: &str,
+    if_exists: IfExists,
+) -> Result<usize, EditorError> {
+    use rusqlite::types::Value;
+
+    use crate::{columns, row::Cell};
+
+    let mut connection = rusqlite::Connection::open(path).map_err(sqlite_error)?;
+    let transaction = connection.transaction().map_err(sqlite_error)?;
+    let exists = transaction
+        .query_row(
+            "SELECT count(*) FROM sqlite_master WHERE type = 'table' AND name = ?1",
+            [table],
+            |row| row.get::<_, i64>(0),
+        )
+        .map_err(sqlite_error)?
+        > 0;
+    let names: Vec<String> = (0..csv_data.fields)
+        .map(|field| quote(&columns::column_name(&csv_data.headers, field)))
+        .collect();
+    match (exists, if_exists) {
+        (true, IfExists::Fail) => {
+            return Err(EditorError::Sqlite(format!(
+                "{path} already has a table named {table}"
+            )))
+        }
+        (true, IfExists::Append) => {}
+        (exists, _) => {
+            if exists {
+                transaction
+                    .execute(&format!("DROP TABLE {}", quote(table)), [])
+                    .map_err(sqlite_error)?;
+            }
+            let columns: Vec<String> = names
+                .iter()
+                .enumerate()
+                .map(|(field, name)| {
+                    let column_type = csv_data.types.get(field).copied().unwrap_or_default();
+                    format!("{name} {}", sql_type(column_type))
+                })
+                .collect();
+            transaction
+                .execute(
+                    &format!("CREATE TABLE {} ({})", quote(table), columns.join(", ")),
+                    [],
+                )
+                .map_err(sqlite_error)?;
+        }
+    }
+    let placeholders: Vec<String> = (1..=names.len()).map(|i| format!("?{i}")).collect();
+    let insert = format!(
+        "INSERT INTO {} ({}) VALUES ({})",
+        quote(table),
+        names.join(", "),
+        placeholders.join(", ")
+    );
+    {
+        let mut statement = transaction.prepare(&insert).map_err(sqlite_error)?;
+        for row in csv_data.iter_rows() {
+            let values = (0..csv_data.fields).map(|field| match row.cell(field) {
+                None | Some(Cell::Empty) => Value::Null,
+                Some(Cell::Integer(number)) => Value::Integer(number),
+                Some(Cell::Float(number)) => Value::Real(number),
+                Some(Cell::Boolean(value)) => Value::Integer(value.into()),
+                Some(cell) => Value::Text(cell.to_string()),
+            });
+            statement
+                .execute(rusqlite::params_from_iter(values))
+                .map_err(|e| {
+                    EditorError::Sqlite(format!("{}: {e}", csv_data.describe_row(row.index())))
+                })?;
+        }
+    }
+    transaction.commit().map_err(sqlite_error)?;
+    log::info!(path, table, rows = csv_data.data.len(); "wrote table");
+    Ok(csv_data.data.len())
+}
+
+/// Reads `table` of the database at `path`, or its only table, as data to be written to
+/// `file_name`. The header is made of the column names, and column types come from their
+/// declared SQL types (see [`column_type`]). `NULL` reads as an empty value, and booleans
+/// as `true` or `false`.
+/// Returns an error if `table` is not given and the database has several, or if a value
+/// is binary data.
+#[cfg(feature = "sqlite")]
+pub fn import(path: &str, table: Option<&str>, file_name: &str) -> Result<CSVData, EditorError> {
+    use csv::StringRecord;
+    use rusqlite::{types::ValueRef, OpenFlags};
+
+    let connection = rusqlite::Connection::open_with_flags(path, OpenFlags::SQLITE_OPEN_READ_ONLY)
+        .map_err(sqlite_error)?;
+    let table = match table {
+        Some(table) => table.to_string(),
+        None => {
+            let mut statement = connection
+                .prepare(
+                    "SELECT name FROM sqlite_master WHERE type = 'table' \
+                     AND name NOT LIKE 'sqlite_%' ORDER BY name",
+                )
+                .map_err(sqlite_error)?;
+            let tables = statement
+                .query_map([], |row| row.get::<_, String>(0))
+                .map_err(sqlite_error)?
+                .collect::<Result<Vec<_>, _>>()
+                .map_err(sqlite_error)?;
+            match tables.as_slice() {
+                [table] => table.clone(),
+                [] => return Err(EditorError::Sqlite(format!("{path} has no tables"))),
+                tables => {
+                    return Err(EditorError::Sqlite(format!(
+                        "{path} has several tables, name the one to read: {}",
+                        tables.join(", ")
+                    )))
+                }
+            }
+        }
+    };
+    let mut statement = connection
+        .prepare(&format!("SELECT * FROM {}", quote(&table)))
+        .map_err(sqlite_error)?;
+    let headers: StringRecord = statement.column_names().into_iter().collect();
+    let types: Vec<ColumnType> = statement
+        .columns()
+        .iter()
+        .map(|column| column.decl_type().map_or(ColumnType::Text, column_type))
+        .collect();
+    let mut rows = statement.query([]).map_err(sqlite_error)?;
+    let mut data = Vec::new();
+    while let Some(row) = rows.next().map_err(sqlite_error)? {
+        let mut record = StringRecord::with_capacity(0, types.len());
+        for (field, column_type) in types.iter().enumerate() {
+            let value = match row.get_ref(field).map_err(sqlite_error)? {
+                ValueRef::Null => String::new(),
+                ValueRef::Integer(number) if *column_type == ColumnType::Boolean => {
+                    (number != 0).to_string()
+                }
+                ValueRef::Integer(number) => number.to_string(),
+                ValueRef::Real(number) => number.to_string(),
+                ValueRef::Text(text) => String::from_utf8_lossy(text).into_owned(),
+                ValueRef::Blob(_) => {
+                    return Err(EditorError::Sqlite(format!(
+                        "row {} of {table} holds binary data in column {}",
+                        data.len() + 1,
+                        &headers[field]
+                    )))
+                }
+            };
+            record.push_field(&value);
+        }
+        data.push(record);
+    }
+    log::info!(path, table = table.as_str(), rows = data.len(); "read table");
+    let mut csv_data = CSVData::new(file_name, headers, data);
+    csv_data.types = types;
+    Ok(csv_data)
+}
+
+#[cfg(not(feature = "sqlite"))]
+pub fn export(
+    _csv_data: &CSVData,
+    _path: &str,
+    _table: &str,
+    _if_exists: IfExists,
+) -> Result<usize, EditorError> {
+    Err(not_built())
+}
+
+#[cfg(not(feature = "sqlite"))]
+pub fn import(_path: &str, _table: Option<&str>, _file_name: &str) -> Result<CSVData, EditorError> {
+    Err(not_built())
+}
+
+#[cfg(not(feature = "sqlite"))]
+fn not_built() -> EditorError {
+    EditorError::Sqlite("SQLite needs the editor to be built with the sqlite feature".to_string())
+}
+
+/// Quotes a table or column name for SQL.
+#[cfg(feature = "sqlite")]
+fn quote(name: &str) -> String {
+    format!("\"{}\"", name.replace('"', "\"\""))
+}
+
+#[cfg(feature = "sqlite")]
+fn sqlite_error(e: rusqlite::Error) -> EditorError {
+    EditorError::Sqlite(e.to_string())
+}
+
+#[cfg(all(test, feature = "sqlite"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_export_and_import() {
+        let file_name = "test_sqlite.csv";
+        let database = "test_sqlite.db";
+        std::fs::write(
+            file_name,
+            "id,\"the \"\"name\"\"\",price,paid,due\n1,ann,3.5,yes,2024-02-29\n2,bob,,no,\n",
+        )
+        .expect("Failed to write");
+        let mut csv_data = CSVData::read_from_file(file_name).expect("Failed to read");
+        std::fs::remove_file(file_name).expect("Failed to remove file");
+        csv_data.set_types("auto").unwrap();
+        let _ = std::fs::remove_file(database);
+        assert_eq!(
+            export(&csv_data, database, "orders", IfExists::Fail).unwrap(),
+            2
+        );
+        assert!(export(&csv_data, database, "orders", IfExists::Fail)
+            .unwrap_err()
+            .to_string()
+            .contains("already has a table named orders"));
+        assert_eq!(
+            export(&csv_data, database, "orders", IfExists::Append).unwrap(),
+            2
+        );
+
+        let read = import(database, None, "out.csv").unwrap();
+        assert_eq!(read.headers, csv_data.headers);
+        assert_eq!(read.types, csv_data.types);
+        assert_eq!(read.data.len(), 4);
+        assert_eq!(read.data[0], vec!["1", "ann", "3.5", "true", "2024-02-29"]);
+        assert_eq!(read.data[3], vec!["2", "bob", "", "false", ""]);
+
+        export(&read, database, "copy", IfExists::Replace).unwrap();
+        let error = import(database, None, "out.csv").err().unwrap();
+        assert!(error
+            .to_string()
+            .contains("several tables, name the one to read: copy, orders"));
+        assert_eq!(
+            import(database, Some("copy"), "out.csv")
+                .unwrap()
+                .data
+                .len(),
+            4
+        );
+        std::fs::remove_file(database).expect("Failed to remove file");
+    }
+
+    #[test]
+    fn test_column_type() {
+        assert_eq!(column_type("VARCHAR(20)"), ColumnType::Text);
+        assert_eq!(column_type("bigint"), ColumnType::Integer);
+        assert_eq!(column_type("DOUBLE PRECISION"), ColumnType::Float);
+        assert_eq!(
+            column_type(sql_type(ColumnType::Boolean)),
+            ColumnType::Boolean
+        );
+        assert_eq!(column_type(sql_type(ColumnType::Date)), ColumnType::Date);
+        assert_eq!(column_type("DATETIME"), ColumnType::Text);
+    }
+}
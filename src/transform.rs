@@ -0,0 +1,208 @@
+use std::str::FromStr;
+
+use csv::StringRecord;
+use regex::Regex;
+
+use crate::{columns, EditorError};
+
+/// Symbols removed by [`TextOp::StripCurrency`].
+const CURRENCY_SYMBOLS: &[char] = &[
+    '$', '€', '£', '¥', '₹', '₽', '₩', '¢', '₺', '₪', '₫', '฿', '₴', '₦',
+];
+
+/// An operation rewriting every value of a column, as used by `transform --op`.
+#[derive(Debug, Clone)]
+pub enum TextOp {
+    /// Removes leading and trailing whitespace.
+    Trim,
+    Upper,
+    Lower,
+    /// Pads integers with leading zeros to the given width, after any sign.
+    ZeroPad(usize),
+    /// Removes currency symbols and the whitespace around them, e.g. `$ 1,200` becomes `1,200`.
+    StripCurrency,
+    /// Replaces every match of a regular expression; `$1` or `${name}` insert groups.
+    Replace {
+        pattern: Regex,
+        replacement: String,
+    },
+}
+
+impl FromStr for TextOp {
+    type Err = EditorError;
+
+    /// Parses `trim`, `upper`, `lower`, `zero-pad:N`, `strip-currency`, or a sed-style
+    /// substitution `s/PATTERN/REPLACEMENT/`, where any character can replace `/`.
+    fn from_str(spec: &str) -> Result<TextOp, EditorError> {
+        let op = match spec {
+            "trim" => TextOp::Trim,
+            "upper" => TextOp::Upper,
+            "lower" => TextOp::Lower,
+            "strip-currency" => TextOp::StripCurrency,
+            _ => {
+                if let Some(width) = spec.strip_prefix("zero-pad:") {
+                    let width = width
+                        .parse()
+                        .map_err(|_| invalid(format!("invalid width in '{spec}'")))?;
+                    TextOp::ZeroPad(width)
+                } else if let Some(rest) = spec.strip_prefix('s') {
+                    let separator = rest.chars().next().filter(|c| !c.is_alphanumeric());
+                    let parts: Vec<&str> = match separator {
+                        Some(separator) => rest[separator.len_utf8()..].split(separator).collect(),
+                        None => Vec::new(),
+                    };
+                    let [pattern, replacement, ""] = parts[..] else {
+                        return Err(invalid(format!("unknown operation '{spec}'")));
+                    };
+                    TextOp::Replace {
+                        pattern: Regex::new(pattern).map_err(|e| invalid(e.to_string()))?,
+                        replacement: replacement.to_string(),
+                    }
+                } else {
+                    return Err(invalid(format!("unknown operation '{spec}'")));
+                }
+            }
+        };
+        Ok(op)
+    }
+}
+
+impl TextOp {
+    /// Returns `value` rewritten by this operation.
+    pub fn apply(&self, value: &str) -> String {
+        match self {
+            TextOp::Trim => value.trim().to_string(),
+            TextOp::Upper => value.to_uppercase(),
+            TextOp::Lower => value.to_lowercase(),
+            TextOp::ZeroPad(width) => {
+                let (sign, digits) = match value.strip_prefix(['-', '+']) {
+                    Some(digits) => (&value[..1], digits),
+                    None => ("", value),
+                };
+                if digits.is_empty() || !digits.bytes().all(|b| b.is_ascii_digit()) {
+                    return value.to_string();
+                }
+                let width = width.saturating_sub(sign.len());
+                format!("{sign}{digits:0>width$}")
+            }
+            TextOp::StripCurrency => {
+                let stripped: String = value
+                    .chars()
+                    .filter(|c| !CURRENCY_SYMBOLS.contains(c))
+                    .collect();
+                if stripped.len() == value.len() {
+                    return value.to_string();
+                }
+                stripped.split_whitespace().collect::<Vec<_>>().join(" ")
+            }
+            TextOp::Replace {
+                pattern,
+                replacement,
+            } => pattern
+                .replace_all(value, replacement.as_str())
+                .into_owned(),
+        }
+    }
+}
+
+/// A piece of a merge format string.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Piece {
+    Text(String),
+    /// The value of a column (0-based).
+    Column(usize),
+}
+
+/// Parses a format such as `{last}, {first}` into pieces. Columns are named between
+/// braces as in `--columns`; `{{` and `}}` stand for literal braces.
+pub fn parse_format(
+    format: &str,
+    headers: &StringRecord,
+    fields: usize,
+) -> Result<Vec<Piece>, EditorError> {
+    let mut pieces = Vec::new();
+    let mut text = String::new();
+    let mut chars = format.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '{' if chars.peek() == Some(&'{') => {
+                chars.next();
+                text.push('{');
+            }
+            '}' if chars.peek() == Some(&'}') => {
+                chars.next();
+                text.push('}');
+            }
+            '{' => {
+                let mut name = String::new();
+                loop {
+                    match chars.next() {
+                        Some('}') => break,
+                        Some(c) => name.push(c),
+                        None => return Err(invalid(format!("unclosed '{{' in '{format}'"))),
+                    }
+                }
+                if !text.is_empty() {
+                    pieces.push(Piece::Text(std::mem::take(&mut text)));
+                }
+                pieces.push(Piece::Column(columns::parse_column(
+                    &name, headers, fields,
+                )?));
+            }
+            '}' => return Err(invalid(format!("unmatched '}}' in '{format}'"))),
+            c => text.push(c),
+        }
+    }
+    if !text.is_empty() {
+        pieces.push(Piece::Text(text));
+    }
+    Ok(pieces)
+}
+
+/// Fills the pieces with the values of `record`. Missing fields are empty.
+pub fn format_record(pieces: &[Piece], record: &StringRecord) -> String {
+    pieces
+        .iter()
+        .map(|piece| match piece {
+            Piece::Text(text) => text.as_str(),
+            Piece::Column(column) => record.get(*column).unwrap_or(""),
+        })
+        .collect()
+}
+
+fn invalid(message: String) -> EditorError {
+    EditorError::InvalidFormat(message)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_text_ops() {
+        let apply = |spec: &str, value: &str| spec.parse::<TextOp>().unwrap().apply(value);
+        assert_eq!(apply("trim", "  a b "), "a b");
+        assert_eq!(apply("upper", "straße"), "STRASSE");
+        assert_eq!(apply("zero-pad:5", "-42"), "-0042");
+        assert_eq!(apply("zero-pad:5", "4a"), "4a");
+        assert_eq!(apply("strip-currency", "$ 1,200"), "1,200");
+        assert_eq!(apply("strip-currency", "  12 "), "  12 ");
+        assert_eq!(apply(r"s|(\w+)@(\w+)|$2:$1|", "ann@home"), "home:ann");
+        assert!("s/(/x/".parse::<TextOp>().is_err());
+        assert!("s/a/b".parse::<TextOp>().is_err());
+        assert!("shout".parse::<TextOp>().is_err());
+    }
+
+    #[test]
+    fn test_parse_format() {
+        let headers = StringRecord::from(vec!["first", "last"]);
+        let pieces = parse_format("{last}, {1} {{x}}", &headers, 2).unwrap();
+        assert_eq!(
+            format_record(&pieces, &StringRecord::from(vec!["Ada", "Lovelace"])),
+            "Lovelace, Ada {x}"
+        );
+        assert!(parse_format("{last", &headers, 2).is_err());
+        assert!(parse_format("last}", &headers, 2).is_err());
+        assert!(parse_format("{middle}", &headers, 2).is_err());
+    }
+}
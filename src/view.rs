@@ -0,0 +1,350 @@
+//! How the interactive viewer lays out the columns of a file: which ones are hidden, which
+//! are pinned to the left, and in what order the others come. The layout only changes what
+//! is shown, never the data, and is kept in a hidden sidecar next to the file
+//! (`.name.csv.view`) so that the file opens the same way the next time.
+
+use std::path::PathBuf;
+
+use csv::StringRecord;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    columns,
+    config::Settings,
+    csv_data::{column_window, describe_window},
+    lock, CSVData, EditorError,
+};
+
+/// The layout of the columns of a file in the viewer.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ColumnView {
+    path: PathBuf,
+    /// Names of the columns (see [`columns::column_name`]), which the sidecar refers to.
+    names: Vec<String>,
+    /// Every column (0-based), in the order they are shown, the pinned ones first.
+    order: Vec<usize>,
+    /// Number of pinned columns at the start of `order`.
+    pinned: usize,
+    /// Whether each column (0-based) is hidden.
+    hidden: Vec<bool>,
+}
+
+/// The sidecar's contents, naming columns as [`columns::column_name`] does.
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct Sidecar {
+    #[serde(default)]
+    pinned: Vec<String>,
+    /// Order of the columns that are not pinned.
+    #[serde(default)]
+    order: Vec<String>,
+    #[serde(default)]
+    hidden: Vec<String>,
+}
+
+impl ColumnView {
+    /// Returns the layout showing every column of `file_name` in file order. `headers` and
+    /// `fields` describe its data.
+    pub fn new(file_name: &str, headers: &StringRecord, fields: usize) -> ColumnView {
+        ColumnView {
+            path: lock::sidecar_path(file_name, "view"),
+            names: (0..fields)
+                .map(|field| columns::column_name(headers, field))
+                .collect(),
+            order: (0..fields).collect(),
+            pinned: 0,
+            hidden: vec![false; fields],
+        }
+    }
+
+    /// Returns the layout saved for `file_name`, or the file order if it has none. Columns
+    /// the data no longer has are left out, and new ones are shown after the others.
+    pub fn load(
+        file_name: &str,
+        headers: &StringRecord,
+        fields: usize,
+    ) -> Result<ColumnView, EditorError> {
+        let mut view = ColumnView::new(file_name, headers, fields);
+        let sidecar: Sidecar = match std::fs::read_to_string(&view.path) {
+            Ok(contents) => toml::from_str(&contents).map_err(|e| {
+                EditorError::InvalidFormat(format!("{}: {}", view.path.display(), e.message()))
+            })?,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(view),
+            Err(e) => return Err(e.into()),
+        };
+        let find = |name: &String| columns::parse_column(name, headers, fields).ok();
+        let mut placed = vec![false; fields];
+        let mut order = Vec::with_capacity(fields);
+        for (i, names) in [&sidecar.pinned, &sidecar.order].into_iter().enumerate() {
+            for field in names.iter().filter_map(find) {
+                if !std::mem::replace(&mut placed[field], true) {
+                    order.push(field);
+                }
+            }
+            if i == 0 {
+                view.pinned = order.len();
+            }
+        }
+        order.extend((0..fields).filter(|&field| !placed[field]));
+        view.order = order;
+        for field in sidecar.hidden.iter().filter_map(find) {
+            view.hidden[field] = true;
+        }
+        Ok(view)
+    }
+
+    /// Writes the layout to the sidecar, removing it when the layout is the file order.
+    pub fn save(&self) -> Result<(), EditorError> {
+        if self.is_file_order() {
+            return match std::fs::remove_file(&self.path) {
+                Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e.into()),
+                _ => Ok(()),
+            };
+        }
+        let names = |fields: &[usize]| -> Vec<String> {
+            fields
+                .iter()
+                .map(|&field| self.names[field].clone())
+                .collect()
+        };
+        let sidecar = Sidecar {
+            pinned: names(self.pinned()),
+            order: names(&self.order[self.pinned..]),
+            hidden: names(&self.hidden()),
+        };
+        let contents =
+            toml::to_string(&sidecar).map_err(|e| EditorError::InvalidFormat(e.to_string()))?;
+        std::fs::write(&self.path, contents)?;
+        log::debug!(file:? = self.path; "saved column layout");
+        Ok(())
+    }
+
+    /// Tells whether every column is shown, in file order.
+    fn is_file_order(&self) -> bool {
+        let reordered = self.order.iter().enumerate().any(|(i, &field)| i != field);
+        !reordered && self.pinned == 0 && !self.hidden.contains(&true)
+    }
+
+    /// Returns the columns (0-based) shown, in order.
+    pub fn shown(&self) -> Vec<usize> {
+        self.order
+            .iter()
+            .copied()
+            .filter(|&field| !self.hidden[field])
+            .collect()
+    }
+
+    /// Returns the pinned columns (0-based), in order, hidden ones included.
+    pub fn pinned(&self) -> &[usize] {
+        &self.order[..self.pinned]
+    }
+
+    /// Returns the hidden columns (0-based), in the order they would be shown.
+    pub fn hidden(&self) -> Vec<usize> {
+        self.order
+            .iter()
+            .copied()
+            .filter(|&field| self.hidden[field])
+            .collect()
+    }
+
+    /// Returns the number of shown columns kept in view when scrolling: the pinned ones,
+    /// then the `settings.freeze_cols` first of the others.
+    pub fn frozen(&self, settings: &Settings) -> usize {
+        let pinned = self.pinned().iter().filter(|&&f| !self.hidden[f]).count();
+        (pinned + settings.freeze_cols).min(self.shown().len())
+    }
+
+    /// Hides `fields` (0-based). Returns an error if no column would be left to show.
+    pub fn hide(&mut self, fields: &[usize]) -> Result<(), EditorError> {
+        let mut hidden = self.hidden.clone();
+        for &field in fields {
+            hidden[field] = true;
+        }
+        if !hidden.contains(&false) {
+            return Err(EditorError::InvalidColumns(
+                "cannot hide every column".to_string(),
+            ));
+        }
+        self.hidden = hidden;
+        Ok(())
+    }
+
+    /// Shows `fields` (0-based) again, or every hidden column with `None`.
+    pub fn unhide(&mut self, fields: Option<&[usize]>) {
+        match fields {
+            Some(fields) => fields.iter().for_each(|&field| self.hidden[field] = false),
+            None => self.hidden.fill(false),
+        }
+    }
+
+    /// Pins `fields` (0-based) after the columns already pinned, showing them if hidden.
+    pub fn pin(&mut self, fields: &[usize]) {
+        for &field in fields {
+            self.hidden[field] = false;
+            let position = self.position(field);
+            if position >= self.pinned {
+                self.order.remove(position);
+                self.order.insert(self.pinned, field);
+                self.pinned += 1;
+            }
+        }
+    }
+
+    /// Unpins `fields` (0-based), or every pinned column with `None`. Unpinned columns come
+    /// first after the pinned ones.
+    pub fn unpin(&mut self, fields: Option<&[usize]>) {
+        let fields = match fields {
+            Some(fields) => fields.to_vec(),
+            None => self.pinned().to_vec(),
+        };
+        // Unpinned in reverse, so that several keep their order.
+        for &field in fields.iter().rev() {
+            let position = self.position(field);
+            if position < self.pinned {
+                self.order.remove(position);
+                self.pinned -= 1;
+                self.order.insert(self.pinned, field);
+            }
+        }
+    }
+
+    /// Moves `field` (0-based) to be the `to`th (1-based) column shown, as far as it can go
+    /// without leaving the pinned columns, or joining them.
+    pub fn move_to(&mut self, field: usize, to: usize) -> Result<(), EditorError> {
+        let shown = self.shown();
+        if self.hidden[field] {
+            return Err(EditorError::InvalidColumns(format!(
+                "column '{}' is hidden",
+                self.names[field]
+            )));
+        }
+        let target = shown[to.clamp(1, shown.len()) - 1];
+        let from = self.position(field);
+        // Once the column is taken out, inserting it where the target was puts it after
+        // a target on its right and before one on its left: in the target's place.
+        let position = self.position(target);
+        self.order.remove(from);
+        let (first, last) = match from < self.pinned {
+            true => (0, self.pinned - 1),
+            false => (self.pinned, self.order.len()),
+        };
+        self.order.insert(position.clamp(first, last), field);
+        Ok(())
+    }
+
+    /// Shows every column again, in file order.
+    pub fn reset(&mut self) {
+        self.order = (0..self.names.len()).collect();
+        self.pinned = 0;
+        self.hidden.fill(false);
+    }
+
+    /// Describes the columns shown when scrolled `scroll` columns to the right, like
+    /// [`CSVData::column_label`] but counting shown columns only, and how many are hidden.
+    /// Returns `None` when every column is shown.
+    pub fn label(&self, csv_data: &CSVData, scroll: usize, settings: &Settings) -> Option<String> {
+        if self.is_file_order() {
+            return csv_data.column_label(scroll, settings);
+        }
+        let shown = self.shown().len();
+        let hidden = self.names.len() - shown;
+        let frozen = self.frozen(settings);
+        let window = column_window(frozen, scroll, settings, shown);
+        let label = match window.start == frozen && window.end == shown {
+            true => None,
+            false => Some(describe_window(frozen, window, shown)),
+        };
+        match (label, hidden) {
+            (label, 0) => label,
+            (Some(label), hidden) => Some(format!("{label}, {hidden} hidden")),
+            (None, hidden) => Some(format!("{hidden} column(s) hidden")),
+        }
+    }
+
+    /// Renders rows `start..end` of `csv_data` laid out by the view, scrolled `scroll`
+    /// columns to the right of the frozen ones, as [`CSVData::render_scrolled`] does
+    /// without a layout.
+    pub fn render<W: std::io::Write>(
+        &self,
+        csv_data: &CSVData,
+        start: usize,
+        end: usize,
+        scroll: usize,
+        settings: &Settings,
+        writer: &mut W,
+    ) -> Result<(), EditorError> {
+        if self.is_file_order() {
+            return csv_data.render_scrolled(start, end, scroll, settings, writer);
+        }
+        let shown = self.shown();
+        let frozen = self.frozen(settings);
+        let window = column_window(frozen, scroll, settings, shown.len());
+        let columns: Vec<usize> = shown[..frozen]
+            .iter()
+            .chain(&shown[window])
+            .copied()
+            .collect();
+        csv_data.render_columns(start, end, &columns, frozen, settings, writer)
+    }
+
+    /// Returns where `field` is in `order`.
+    fn position(&self, field: usize) -> usize {
+        self.order
+            .iter()
+            .position(|&f| f == field)
+            .expect("every column is in the order")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_layout() {
+        let file_name = "test_view.csv";
+        std::fs::write(file_name, "id,name,city,price,notes\n1,ann,paris,5,\n").unwrap();
+        let csv_data = CSVData::read_from_file(file_name).unwrap();
+        std::fs::remove_file(file_name).unwrap();
+        let headers = csv_data.headers.clone();
+        let mut view = ColumnView::load(file_name, &headers, 5).unwrap();
+        assert_eq!(view.shown(), vec![0, 1, 2, 3, 4]);
+        view.hide(&[4]).unwrap();
+        view.pin(&[3]);
+        assert_eq!(view.shown(), vec![3, 0, 1, 2]);
+        view.move_to(2, 2).unwrap();
+        assert_eq!(view.shown(), vec![3, 2, 0, 1]);
+        view.move_to(2, 4).unwrap();
+        assert_eq!(view.shown(), vec![3, 0, 1, 2]);
+        // Pinned columns stay pinned, and others cannot join them.
+        view.move_to(0, 1).unwrap();
+        assert_eq!(view.shown(), vec![3, 0, 1, 2]);
+        assert!(view.hide(&[0, 1, 2, 3]).is_err());
+        let settings = Settings {
+            cols_per_page: Some(2),
+            ..Settings::default()
+        };
+        assert_eq!(view.frozen(&settings), 1);
+        assert_eq!(
+            view.label(&csv_data, 1, &settings).as_deref(),
+            Some("columns 1, 3-4 of 4, 1 hidden")
+        );
+        view.save().unwrap();
+        let sidecar = std::fs::read_to_string(".test_view.csv.view").unwrap();
+        assert!(sidecar.contains("pinned = [\"price\"]"));
+
+        // The layout follows the columns by name, and shows new ones last.
+        let moved = StringRecord::from(vec!["name", "extra", "price", "id", "city", "notes"]);
+        let view = ColumnView::load(file_name, &moved, 6).unwrap();
+        assert_eq!(view.shown(), vec![2, 3, 0, 4, 1]);
+        assert_eq!(view.hidden(), vec![5]);
+
+        let mut view = ColumnView::load(file_name, &headers, 5).unwrap();
+        view.unpin(None);
+        view.unhide(None);
+        assert_eq!(view.shown(), vec![3, 0, 1, 2, 4]);
+        view.reset();
+        view.save().unwrap();
+        assert!(!std::path::Path::new(".test_view.csv.view").exists());
+    }
+}
@@ -0,0 +1,49 @@
+use std::{
+    path::{Path, PathBuf},
+    sync::mpsc,
+    time::Duration,
+};
+
+use notify::{EventKind, RecommendedWatcher, RecursiveMode, Watcher};
+
+/// How long to wait after a change before reporting it, so that a file written
+/// in several chunks is reloaded once rather than once per chunk.
+const SETTLE: Duration = Duration::from_millis(200);
+
+/// Watches `file` and calls `on_change`, at most once per [`SETTLE`], while it changes.
+/// `on_change` returns `false` to stop watching. Watching also stops when the
+/// returned watcher is dropped.
+///
+/// The parent directory is watched rather than the file itself, so that files
+/// replaced by a rename (as most editors and many jobs do) keep being followed.
+pub fn watch<F>(file: &str, mut on_change: F) -> notify::Result<RecommendedWatcher>
+where
+    F: FnMut() -> bool + Send + 'static,
+{
+    let path = std::path::absolute(file)?;
+    let directory = path
+        .parent()
+        .map_or_else(|| PathBuf::from("."), Path::to_path_buf);
+    let (sender, receiver) = mpsc::channel();
+    let mut watcher = notify::recommended_watcher(move |event: notify::Result<notify::Event>| {
+        let relevant = event.is_ok_and(|event| {
+            !matches!(event.kind, EventKind::Access(_)) && event.paths.contains(&path)
+        });
+        if relevant {
+            let _ = sender.send(());
+        }
+    })?;
+    watcher.watch(&directory, RecursiveMode::NonRecursive)?;
+    std::thread::spawn(move || {
+        while receiver.recv().is_ok() {
+            // Changes made while settling are covered by this call.
+            std::thread::sleep(SETTLE);
+            while receiver.try_recv().is_ok() {}
+            log::debug!("watched file changed");
+            if !on_change() {
+                break;
+            }
+        }
+    });
+    Ok(watcher)
+}